@@ -1,16 +1,261 @@
 use anyhow::Context;
-use autobase::base::BaseTable;
-use skrifa::{raw::TableProvider, FontRef};
-use std::process::ExitCode;
+use autobase::{
+    base::BaseTable,
+    base_coords::{coord_details, Adjustment},
+    font_metrics::{font_minmax, MetricSource},
+    names::{describe_language, describe_script},
+    render::parse_location,
+    utils::{iso15924_to_opentype, KNOWN_ISO_SCRIPTS},
+    woff2,
+};
+use clap::Parser;
+use skrifa::{raw::TableProvider, FontRef, Tag};
+use std::{path::PathBuf, process::ExitCode, str::FromStr};
+
+/// Print a font's BASE table.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The font whose table to print: an OpenType font or collection, or a WOFF2 file
+    font_path: PathBuf,
+
+    /// Which font of a collection to read
+    #[arg(long, default_value_t = 0)]
+    index: u32,
+
+    /// The output format: FEA with comments naming each record (fea), or the table as
+    /// structured data (json), which can't be combined with --location or --with-metrics
+    #[arg(long, default_value = "fea")]
+    format: Format,
+
+    /// Only print the records of these scripts, as OpenType script tags or ISO 15924
+    /// codes (e.g. hani,Latn)
+    #[arg(long = "script", value_delimiter = ',')]
+    scripts: Vec<String>,
+
+    /// Only print the records of one axis (horizontal or vertical)
+    #[arg(long)]
+    axis: Option<Axis>,
+
+    /// Also print the values of varying coordinates at this location, given as user-space
+    /// axis values (e.g. "wght=700 wdth=75"); may be repeated
+    #[arg(long = "location")]
+    locations: Vec<String>,
+
+    /// Also print the font's hhea, typo and win metrics, and flag each horizontal MinMax
+    /// record which is clipped on Windows or which lies within the default metrics
+    #[arg(long)]
+    with_metrics: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Fea,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fea" => Ok(Format::Fea),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format {} (expected fea or json)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl FromStr for Axis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "horizontal" => Ok(Axis::Horizontal),
+            "vertical" => Ok(Axis::Vertical),
+            _ => Err(format!(
+                "unknown axis {} (expected horizontal or vertical)",
+                s
+            )),
+        }
+    }
+}
+
+/// The OpenType tag of a script given as either a tag or an ISO 15924 code.
+fn script_tag(script: &str) -> anyhow::Result<Tag> {
+    if KNOWN_ISO_SCRIPTS.contains(&script) {
+        iso15924_to_opentype(script)
+            .with_context(|| format!("script {} has no OpenType tag", script))
+    } else {
+        Tag::new_checked(script.as_bytes())
+            .map_err(|_| anyhow::anyhow!("invalid script tag: {}", script))
+    }
+}
+
+/// Print the font's metrics as FEA comments, then each horizontal MinMax record
+/// with what is wrong with it: values beyond the win metrics, where Windows
+/// clips, and records which lie within the default metrics and so change nothing.
+fn print_metrics(font: &FontRef, base: &BaseTable) -> anyhow::Result<()> {
+    println!("# Font metrics:");
+    for source in [MetricSource::Hhea, MetricSource::Typo, MetricSource::Win] {
+        match source.extents(font) {
+            Ok((descender, ascender)) => println!("#   {}: {}, {}", source, ascender, descender),
+            Err(e) => println!("#   {}: missing ({})", source, e),
+        }
+    }
+    let default = font_minmax(font, MetricSource::default())?;
+    let win = MetricSource::Win.extents(font).ok();
+    for script in base.horizontal.iter() {
+        let records = script
+            .default_minmax
+            .iter()
+            .map(|mm| (Tag::new(b"dflt"), mm))
+            .chain(script.languages.iter().map(|(lang, mm)| (*lang, mm)));
+        for (language, mm) in records {
+            let mut flags = vec![];
+            if let Some((win_descender, win_ascender)) = win {
+                if let Some(max) = mm.highest.filter(|max| *max > win_ascender) {
+                    flags.push(format!(
+                        "max is {} above usWinAscent, so is clipped on Windows",
+                        max as i32 - win_ascender as i32
+                    ));
+                }
+                if let Some(min) = mm.lowest.filter(|min| *min < win_descender) {
+                    flags.push(format!(
+                        "min is {} below usWinDescent, so is clipped on Windows",
+                        win_descender as i32 - min as i32
+                    ));
+                }
+            }
+            let within = |value: Option<i16>, inside: fn(i16, i16) -> bool, limit: Option<i16>| {
+                value.is_none_or(|value| limit.is_some_and(|limit| inside(value, limit)))
+            };
+            if within(mm.highest, |max, asc| max <= asc, default.highest)
+                && within(mm.lowest, |min, desc| min >= desc, default.lowest)
+            {
+                flags.push(format!(
+                    "redundant, as it lies within the {}",
+                    MetricSource::default()
+                ));
+            }
+            let side = |value: Option<i16>| value.map_or("NULL".to_string(), |v| v.to_string());
+            println!(
+                "# HorizAxis {} {} min {}, max {}{}",
+                script.script,
+                language,
+                side(mm.lowest),
+                side(mm.highest),
+                if flags.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", flags.join("; "))
+                }
+            );
+        }
+    }
+    Ok(())
+}
 
 fn main() -> anyhow::Result<ExitCode> {
-    let font = std::env::args()
-        .nth(1)
-        .expect("Please provide a font file path");
-    let font_data = std::fs::read(font).context("Failed to load font file")?;
-    let font = FontRef::new(&font_data).context("Failed to parse font file")?;
+    let args = Args::parse();
+    if args.format == Format::Json && (!args.locations.is_empty() || args.with_metrics) {
+        anyhow::bail!("--location and --with-metrics only apply to the fea format");
+    }
+    let mut font_data = std::fs::read(&args.font_path).context("Failed to load font file")?;
+    let mut index = args.index;
+    if woff2::is_woff2(&font_data) {
+        font_data = woff2::decode(&font_data, index).context("Failed to decode WOFF2 font")?;
+        index = 0;
+    }
+    let font = FontRef::from_index(&font_data, index).context("Failed to parse font file")?;
     let base = font.base().context("Failed to read BASE table")?;
-    let our_base = BaseTable::from_skrifa(&base)?;
+    let mut our_base = BaseTable::from_skrifa(&base)?;
+    if !args.scripts.is_empty() {
+        let tags = args
+            .scripts
+            .iter()
+            .map(|script| script_tag(script))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        our_base
+            .horizontal
+            .retain(|record| tags.contains(&record.script));
+        our_base
+            .vertical
+            .retain(|record| tags.contains(&record.script));
+    }
+    match args.axis {
+        Some(Axis::Horizontal) => our_base.vertical.clear(),
+        Some(Axis::Vertical) => our_base.horizontal.clear(),
+        None => {}
+    }
+    if args.format == Format::Json {
+        println!("{}", our_base.to_json()?);
+        return Ok(ExitCode::SUCCESS);
+    }
+    // List the records with their human-readable names, as FEA comments
+    for (axis, scripts) in [
+        ("HorizAxis", &our_base.horizontal),
+        ("VertAxis", &our_base.vertical),
+    ] {
+        for script in scripts.iter() {
+            let languages = script
+                .languages
+                .keys()
+                .map(|lang| describe_language(*lang))
+                .collect::<Vec<_>>();
+            if languages.is_empty() {
+                println!("# {} {}", axis, describe_script(script.script));
+            } else {
+                println!(
+                    "# {} {}: {}",
+                    axis,
+                    describe_script(script.script),
+                    languages.join(", ")
+                );
+            }
+        }
+    }
     println!("{}", our_base.to_fea());
+    if args.with_metrics {
+        print_metrics(&font, &our_base)?;
+    }
+
+    // Device tables, glyph points and variations don't survive the conversion
+    // to FEA, so they are listed afterwards
+    let details = coord_details(&base)
+        .context("Failed to read BASE coordinates")?
+        .into_iter()
+        .filter(|detail| {
+            let records = if detail.axis == "HorizAxis" {
+                &our_base.horizontal
+            } else {
+                &our_base.vertical
+            };
+            records.iter().any(|record| record.script == detail.script)
+        })
+        .collect::<Vec<_>>();
+    if !details.is_empty() {
+        println!("# Coordinates with adjustments which FEA doesn't show:");
+    }
+    for detail in details {
+        println!("# {}", detail);
+        if !matches!(detail.adjustment, Adjustment::Variation { .. }) {
+            continue;
+        }
+        for location in args.locations.iter() {
+            let coords = parse_location(&font, location)?;
+            println!(
+                "#   at {}: {}",
+                location,
+                detail.resolve(&base, coords.coords())?
+            );
+        }
+    }
     Ok(ExitCode::SUCCESS)
 }
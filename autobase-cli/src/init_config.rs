@@ -0,0 +1,182 @@
+//! Write a commented starter configuration for a font.
+use std::{collections::BTreeMap, fmt::Write as _, fs, path::Path};
+
+use anyhow::Context;
+use autobase::{
+    cjk::is_cjk_script, measurement::wordlist_script_and_language, names::script_name,
+    utils::supported_scripts,
+};
+
+/// Inspect the font and build a starter configuration, as TOML text.
+pub fn starter_config(font_path: &Path) -> anyhow::Result<String> {
+    let font_bytes = fs::read(font_path).context("failed to read font file")?;
+    let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+    let mut scripts = supported_scripts(&font).into_iter().collect::<Vec<_>>();
+    scripts.sort();
+    let is_cjk = scripts.iter().any(|script| is_cjk_script(script));
+
+    // Languages with their own bundled word lists, by script
+    let mut languages: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for word_list in static_lang_word_lists::ALL_WORD_LISTS.iter() {
+        let source = wordlist_script_and_language(word_list);
+        if let (Some(script), Some(language)) = (
+            scripts.iter().find(|s| **s == source.script),
+            source.language,
+        ) {
+            languages.entry(script).or_default().push(language);
+        }
+    }
+    for list in languages.values_mut() {
+        list.sort();
+        list.dedup();
+    }
+
+    let describe = |script: &str| match script_name(script) {
+        Some(name) => format!("{} ({})", script, name),
+        None => script.to_string(),
+    };
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# autobase configuration for {}",
+        font_path.file_name().unwrap_or_default().to_string_lossy()
+    )?;
+    writeln!(
+        out,
+        "# Generated by `autobase init-config`; see the README for every key."
+    )?;
+    writeln!(out)?;
+    writeln!(out, "# Scripts supported by the font:")?;
+    for script in scripts.iter() {
+        writeln!(out, "#   {}", describe(script))?;
+    }
+    writeln!(
+        out,
+        "# To ignore scripts the font only covers incidentally, list them here:"
+    )?;
+    writeln!(out, "# exclude_scripts = []")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "# Font units within which MinMax values are treated as equal to the default"
+    )?;
+    writeln!(out, "tolerance = 10")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "# Languages which get their own MinMax records. These languages have word lists;"
+    )?;
+    writeln!(
+        out,
+        "# uncomment those whose extremes may differ from the rest of their script."
+    )?;
+    writeln!(out, "languages = [")?;
+    for (script, list) in languages.iter() {
+        for language in list {
+            writeln!(out, "    # \"{}_{}\",", language, script)?;
+        }
+    }
+    writeln!(out, "]")?;
+    writeln!(out)?;
+    writeln!(out, "# Words (or fragments) never to measure")?;
+    writeln!(out, "exclusions = []")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "# Split out languages which differ from their script by more than this many units"
+    )?;
+    writeln!(out, "# auto_split_threshold = 50")?;
+    if is_cjk {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "# The font has CJK glyphs, so ideographic baselines will be generated."
+        )?;
+        writeln!(
+            out,
+            "# How far the average ideographic advance may be from the em, as a fraction"
+        )?;
+        writeln!(out, "# of the em, for the font to be treated as square")?;
+        writeln!(out, "# cjk_square_tolerance = 0.01")?;
+        writeln!(
+            out,
+            "# Write idtp baselines \"always\", \"never\", or only for non-square fonts (\"auto\")"
+        )?;
+        writeln!(out, "# cjk_idtp = \"auto\"")?;
+        writeln!(out, "# Seed a DFLT script record from these scripts")?;
+        writeln!(out, "# dflt_from = [\"hani\"]")?;
+    }
+    writeln!(out)?;
+    writeln!(
+        out,
+        "# How the extremes of each script are chosen: \"absolute\", \"percentile\" or"
+    )?;
+    writeln!(out, "# \"trimmed-mean\"")?;
+    writeln!(out, "# strategy = \"absolute\"")?;
+    for script in scripts.iter().filter(|script| !is_cjk_script(script)) {
+        writeln!(out)?;
+        writeln!(out, "# {}", describe(script))?;
+        writeln!(out, "# [script_strategies.{}]", script)?;
+        writeln!(out, "# strategy = \"percentile\"")?;
+        writeln!(out, "# percentile = 99.5")?;
+    }
+    writeln!(out)?;
+    writeln!(
+        out,
+        "# Manually set min and/or max values for a script or language, e.g."
+    )?;
+    writeln!(out, "# Latn = {{ max = 1100 }}")?;
+    writeln!(out, "[override]")?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use autobase::config::{parse_config, ConfigFormat};
+    use write_fonts::{tables::cmap::Cmap, types::GlyphId, FontBuilder};
+
+    use super::*;
+
+    /// The starter config for a font mapping `chars`.
+    fn starter_config_for(name: &str, chars: &[char]) -> String {
+        let mut builder = FontBuilder::new();
+        builder
+            .add_table(
+                &Cmap::from_mappings(
+                    chars
+                        .iter()
+                        .enumerate()
+                        .map(|(gid, c)| (*c, GlyphId::new(gid as u32 + 1))),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let path =
+            std::env::temp_dir().join(format!("autobase-{}-{}.ttf", name, std::process::id()));
+        fs::write(&path, builder.build()).unwrap();
+        let text = starter_config(&path);
+        fs::remove_file(&path).unwrap();
+        text.unwrap()
+    }
+
+    #[test]
+    fn the_starter_config_parses() {
+        let text = starter_config_for("init-latn", &['a', 'b', 'c']);
+        let config = parse_config(&text, ConfigFormat::Toml, Path::new("autobase.toml")).unwrap();
+        assert_eq!(config.tolerance, Some(10));
+        assert!(config.languages.is_empty());
+        assert!(text.contains("#   Latn (Latin)\n"));
+        assert!(text.contains("# [script_strategies.Latn]\n"));
+        assert!(!text.contains("cjk_idtp"));
+    }
+
+    #[test]
+    fn cjk_fonts_get_the_cjk_settings() {
+        let text = starter_config_for("init-hani", &['a', '水']);
+        assert!(text.contains("# cjk_idtp = \"auto\"\n"));
+        // CJK scripts are measured from the em-box, not with a strategy
+        assert!(text.contains("# [script_strategies.Latn]\n"));
+        assert!(!text.contains("# [script_strategies.Hani]\n"));
+        parse_config(&text, ConfigFormat::Toml, Path::new("autobase.toml")).unwrap();
+    }
+}
@@ -1,22 +1,48 @@
 use autobase::{
-    base::{BaseTable, MinMax},
-    base_script,
-    cjk::{self, compute_bounds},
-    config, utils,
+    base::{BaseTable, Tolerance},
+    changelog, cjk,
+    cjk::compute_bounds,
+    config, config_check, explain, family, fea,
+    fingerprint::Fingerprint,
+    font_metrics::{FontDefaults, MetricSource},
+    html_report,
+    pipeline::{self, FontResult, Generator, GeneratorOptions},
+    profile::Profile,
+    report::{FontReport, Report},
+    ufo, utils, visualize,
+    word_csv::WordCsv,
+    wordlist::WordListSource,
 };
 
 use anyhow::Context;
-use clap::Parser;
-use fontheight::{Report, Reporter};
-use rayon::{iter::ParallelIterator, prelude::*};
+use clap::{Parser, Subcommand};
+use rayon::prelude::*;
 use skrifa::raw::TableProvider;
-use std::{collections::BTreeMap, fs, iter, path::PathBuf, process::ExitCode};
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
 use write_fonts::FontBuilder;
 
-use crate::utils::supported_scripts;
+use crate::{progress::Progress, utils::supported_scripts};
+
+mod init_config;
+mod progress;
+
 #[derive(Debug, Parser)]
-#[command(version, about)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Output TTF
     #[arg(short = 'o', long, requires = "binary")]
     output: Option<PathBuf>,
@@ -25,201 +51,1478 @@ struct Args {
     #[arg(required = true)]
     font_path: Vec<PathBuf>,
 
+    /// Where to take the font's supported scripts from: the scripts of the characters in
+    /// the cmap (cmap), or the GSUB/GPOS script lists (layout)
+    #[arg(long = "scripts-from")]
+    scripts_from: Option<config::ScriptSource>,
+
+    /// How to use the languages declared in the font's meta table: ignore them (ignore),
+    /// always keep declared scripts (prioritize), or only add records for them (restrict)
+    #[arg(long = "meta")]
+    meta: Option<autobase::meta::MetaPolicy>,
+
+    /// Ignore scripts with less coverage than this, as a codepoint count (e.g. 12) or a
+    /// percentage of the script's exemplar characters (e.g. 5%)
+    #[arg(long = "min-script-coverage")]
+    min_script_coverage: Option<config::CoverageThreshold>,
+
     /// Add min-max records for experimental Android multiscript vertical metrics
     #[arg(short = 'm', long = "min-max")]
     min_max: bool,
 
+    /// Only compute baselines (CJK, hang, math and romn), skipping the word list analysis entirely
+    #[arg(long = "baseline-only", conflicts_with = "min_max")]
+    baseline_only: bool,
+
     /// Use hhea ascent/descent as font default min/max; otherwise use OS/2 sTypoAscender/sTypoDescender
     #[arg(short = 'u', long = "use-hhea", requires = "min_max")]
     use_hhea: bool,
 
+    /// Where the font default min/max comes from: typo (OS/2 sTypoAscender/sTypoDescender),
+    /// hhea, win (OS/2 usWinAscent/usWinDescent) or bbox (the head bounding box)
+    #[arg(long = "metric-source", conflicts_with = "use_hhea")]
+    metric_source: Option<MetricSource>,
+
+    /// Clamp generated min/max values to usWinDescent/usWinAscent, warning about the words which exceed them
+    #[arg(long = "clamp-to-win")]
+    clamp_to_win: bool,
+
+    /// Clamp generated max values to this ceiling
+    #[arg(long = "clamp-max", allow_negative_numbers = true)]
+    clamp_max: Option<i16>,
+
+    /// Clamp generated min values to this floor
+    #[arg(long = "clamp-min", allow_negative_numbers = true)]
+    clamp_min: Option<i16>,
+
     /// The number of words from each list to test
     #[arg(short = 'k', long = "words", default_value_t = 1000)]
     words_per_list: usize,
 
+    /// The number of threads to measure with (defaults to the number of cores)
+    #[arg(short = 'j', long = "jobs")]
+    jobs: Option<usize>,
+
+    /// Stop starting new measurements after this long (e.g. 90, 30s, 10m or 1h),
+    /// and emit results from what has been measured
+    #[arg(long = "max-duration", value_parser = parse_duration)]
+    max_duration: Option<Duration>,
+
+    /// Write a JSON report of the words which produced each extreme, for each script,
+    /// language and location, to this file
+    #[arg(long = "report")]
+    report: Option<PathBuf>,
+
+    /// Write a standalone HTML page drawing the words which produced each extreme
+    /// against the baselines and the font's hhea and typo lines to this file
+    #[arg(long = "html-report")]
+    html_report: Option<PathBuf>,
+
+    /// Explain how each value of the generated records was arrived at: the word lists and
+    /// words behind it, and any override, tolerance, clamping or FEA replacement applied
+    #[arg(long = "explain")]
+    explain: bool,
+
+    /// Write every measured word, with its script, language, word list, location and
+    /// lowest and highest points, to this CSV file
+    #[arg(long = "words-csv")]
+    words_csv: Option<PathBuf>,
+
+    /// Write PNG proofs of the extreme words of each script at each location into this
+    /// directory (requires the png feature)
+    #[arg(long = "png-proofs")]
+    png_proofs: Option<PathBuf>,
+
+    /// Write the BASE table into these UFO sources' features.fea and lib.plist rather
+    /// than printing the FEA, so that fontmake builds pick it up
+    #[arg(long = "ufo", conflicts_with_all = ["binary", "check"])]
+    ufo: Vec<PathBuf>,
+
+    /// When collating several fonts, the one (e.g. the Regular) whose baselines win and
+    /// whose MinMax values are kept unless another font's lie beyond the tolerance
+    #[arg(long = "primary")]
+    primary: Option<PathBuf>,
+
+    /// How the tables of several fonts are combined: "union" (the default) keeps every
+    /// script record and the furthest extents, "intersection" keeps only the scripts every
+    /// font has, and "average" and "median" combine each value across the fonts
+    /// (overrides the config file)
+    #[arg(long = "collation")]
+    collation: Option<config::Collation>,
+
     /// Write new BASE table into font binary
     #[arg(short = 'b', long = "binary")]
     binary: bool,
 
+    /// Replace values in fonts' existing BASE tables when writing the binary; without this,
+    /// fonts whose existing table would lose anything are left alone
+    #[arg(long = "force")]
+    force: bool,
+
+    /// Also write the changelog of modifications to each font's BASE table to this file
+    #[arg(long = "changelog", requires = "binary")]
+    changelog: Option<PathBuf>,
+
+    /// Font units within which MinMax values are considered close enough to be omitted
+    /// (overrides the config file)
+    #[arg(long = "tolerance")]
+    tolerance: Option<u16>,
+
+    /// Tolerance for maxima, in place of --tolerance (overrides the config file)
+    #[arg(long = "max-tolerance")]
+    max_tolerance: Option<u16>,
+
+    /// Tolerance for minima, in place of --tolerance (overrides the config file)
+    #[arg(long = "min-tolerance")]
+    min_tolerance: Option<u16>,
+
+    /// Directory in which to cache tokenized corpora between runs
+    #[arg(long = "cache-dir")]
+    cache_dir: Option<PathBuf>,
+
+    /// Directory in which to cache word list measurements between runs
+    #[arg(long = "measurement-cache-dir")]
+    measurement_cache_dir: Option<PathBuf>,
+
+    /// Only add records for these scripts, as comma-separated ISO 15924 codes (e.g. Latn,Cyrl)
+    #[arg(long = "scripts", value_delimiter = ',')]
+    scripts: Vec<String>,
+
+    /// Don't add records for these scripts, as comma-separated ISO 15924 codes
+    #[arg(long = "exclude-scripts", value_delimiter = ',')]
+    exclude_scripts: Vec<String>,
+
+    /// Languages to split out into their own records, as comma-separated
+    /// language_Script pairs (e.g. vi_Latn,fi_Latn); replaces the config file's list
+    #[arg(long = "languages", value_delimiter = ',')]
+    languages: Vec<config::ScriptLanguage>,
+
+    /// Don't measure or add records for these languages, as comma-separated ISO 639 codes
+    #[arg(long = "exclude-languages", value_delimiter = ',')]
+    exclude_languages: Vec<String>,
+
     /// Configuration file
     #[arg(short = 'c', long = "config")]
     config: Option<PathBuf>,
 
+    /// Don't search upward from the font for an autobase.toml or a [tool.autobase]
+    /// section in pyproject.toml when no configuration file is given
+    #[arg(long = "no-config-discovery")]
+    no_config_discovery: bool,
+
+    /// Format of the configuration file (toml or json); by default, guessed from its extension
+    #[arg(long = "config-format", requires = "config")]
+    config_format: Option<config::ConfigFormat>,
+
+    /// Apply a bundle of settings for a common workflow (gf-cjk, android-multiscript or
+    /// web-latin-ext) on top of the config file
+    #[arg(long = "recipe")]
+    recipe: Option<config::Recipe>,
+
+    /// Enforce a specification (gf-cjk): pin the baselines it fixes and fail, with a
+    /// report, if the font or its table doesn't comply
+    #[arg(long = "profile")]
+    profile: Option<Profile>,
+
+    /// Additional word list to measure, as path:script[:language] (may be repeated)
+    #[arg(long = "wordlist")]
+    wordlists: Vec<WordListSource>,
+
+    /// Plain-text corpus to split into words and measure, as path:script[:language] (may be repeated)
+    #[arg(long = "corpus")]
+    corpora: Vec<WordListSource>,
+
+    /// Word frequency table (tab-separated word and count per line) used to weight extremes
+    #[arg(long = "frequencies")]
+    frequencies: Option<PathBuf>,
+
+    /// Share of word occurrences allowed to lie beyond the chosen extremes (e.g. 0.001)
+    #[arg(long = "frequency-cutoff")]
+    frequency_cutoff: Option<f64>,
+
+    /// Only measure word lists and corpora given on the command line or in the config file
+    #[arg(long = "no-bundled-wordlists")]
+    no_bundled_wordlists: bool,
+
+    /// Only analyze scripts which have no record (or, with -m, no MinMax) in the font's
+    /// existing BASE table, and add the results to that table
+    #[arg(long = "only-missing")]
+    only_missing: bool,
+
+    /// Bottom edge of the ideographic em-box, in place of the measured one; the top
+    /// edge is one em above it
+    #[arg(short = 'd', long = "descender", allow_negative_numbers = true)]
+    descender: Option<i16>,
+
+    /// Fraction of the em within which the average ideographic advance must lie for a
+    /// CJK font to be treated as square (default 0.01)
+    #[arg(long = "cjk-square-tolerance")]
+    cjk_square_tolerance: Option<f32>,
+
+    /// Always write the CJK idtp baselines, even if the font is square
+    #[arg(long = "force-idtp", conflicts_with = "no_idtp")]
+    force_idtp: bool,
+
+    /// Never write the CJK idtp baselines, even if the font is not square
+    #[arg(long = "no-idtp")]
+    no_idtp: bool,
+
+    /// Measure every CJK glyph when computing the CJK baselines, even if the
+    /// configuration sets cjk_sample_size
+    #[arg(long = "cjk-exact")]
+    cjk_exact: bool,
+
+    /// Stop at the first font which fails, rather than continuing with the rest.
+    /// Fonts are then processed one at a time, in the order given
+    #[arg(long = "fail-fast")]
+    fail_fast: bool,
+
+    /// Compare the generated table with each font's existing BASE table rather than
+    /// writing anything, exiting with 1 if values differ by more than the tolerance and 2 if
+    /// records differ
+    #[arg(long = "check", conflicts_with_all = ["binary", "output", "changelog"])]
+    check: bool,
+
+    /// Compare the tables generated for the fonts of a family with each other rather than
+    /// writing anything, exiting with 1 if values differ between them by more than the tolerance
+    #[arg(
+        long = "family-check",
+        conflicts_with_all = ["binary", "output", "changelog", "check", "ufo"]
+    )]
+    family_check: bool,
+
+    /// Compile the FEA output and warn if it differs from the binary output (requires the fea-check feature)
+    #[arg(long = "check-fea")]
+    check_fea: bool,
+
     #[command(flatten)]
     verbosity: clap_verbosity::Verbosity<clap_verbosity::InfoLevel>,
 }
 
-fn main() -> anyhow::Result<ExitCode> {
-    let args = Args::parse();
-    env_logger::Builder::new()
-        .filter_level(args.verbosity.log_level_filter())
-        .init();
+impl Args {
+    /// Whether any of the reports which need each script's measurements was asked for.
+    fn wants_report(&self) -> bool {
+        self.report.is_some()
+            || self.html_report.is_some()
+            || self.png_proofs.is_some()
+            || self.explain
+    }
+}
 
-    let config = if let Some(config_path) = args.config.as_deref() {
-        config::load_config(config_path).context("failed to load config")?
-    } else {
-        config::Config::default()
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Inspect a font and write a commented starter configuration file for it
+    InitConfig {
+        /// The font to inspect
+        font_path: PathBuf,
+
+        /// Where to write the configuration; by default, it is printed
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the distribution of a font's CJK glyph bounds alongside the CJK baselines chosen from them
+    CjkReport {
+        /// The font to report on
+        font_path: PathBuf,
+
+        /// Path to the configuration file; by default, one is searched for as usual
+        #[arg(short = 'c', long = "config")]
+        config: Option<PathBuf>,
+    },
+    /// Draw the baselines of a font's BASE table as rules through sample text, as an SVG
+    Visualize {
+        /// The font to draw
+        font_path: PathBuf,
+
+        /// Where to write the SVG; by default, it is printed
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+
+        /// Draw the font's existing BASE table, rather than the baselines autobase computes
+        #[arg(long)]
+        existing: bool,
+
+        /// Text to draw for every script, in place of words from the script's word lists
+        #[arg(long)]
+        text: Option<String>,
+
+        /// Path to the configuration file; by default, one is searched for as usual
+        #[arg(short = 'c', long = "config", conflicts_with = "existing")]
+        config: Option<PathBuf>,
+    },
+    /// Regenerate the BASE table of a font, measuring only the scripts whose glyphs have
+    /// changed since the last update and keeping the existing records of the rest
+    Update {
+        /// The font to update
+        font_path: PathBuf,
+
+        /// Where to write the updated font; by default, the font is updated in place
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+
+        /// Path to the configuration file; by default, one is searched for as usual
+        #[arg(short = 'c', long = "config")]
+        config: Option<PathBuf>,
+
+        /// Where the fingerprint of the font's glyphs is kept between runs; by default,
+        /// beside the font as <font file>.autobase.json
+        #[arg(long)]
+        fingerprint: Option<PathBuf>,
+
+        /// Measure every script, whatever the fingerprint says
+        #[arg(long)]
+        all: bool,
+    },
+    /// Write the BASE table already in a font as feature code, or into a UFO source
+    Extract {
+        /// The font whose table to extract
+        font_path: PathBuf,
+
+        /// Where to write the feature code; by default, it is printed
+        #[arg(short = 'o', long)]
+        output: Option<PathBuf>,
+
+        /// Write the table into this UFO source's features.fea and lib.plist instead
+        #[arg(long = "into-ufo", conflicts_with = "output")]
+        into_ufo: Option<PathBuf>,
+    },
+    /// Validate a configuration file, reporting every problem found
+    ConfigCheck {
+        /// The configuration file to check
+        config: PathBuf,
+
+        /// Format of the configuration file (toml or json); by default, guessed from its extension
+        #[arg(long = "config-format")]
+        config_format: Option<config::ConfigFormat>,
+    },
+}
+
+/// How a run ended, as its exit status, so that CI can tell a font which has
+/// drifted from what autobase would generate apart from a run which failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    /// Success, or with --check, the font matches
+    Match = 0,
+    /// With --check, values differ by more than the tolerance
+    ValuesDiffer = 1,
+    /// With --check, records, baselines or MinMax sides differ
+    Structural = 2,
+    /// Something went wrong
+    Error = 3,
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            Status::Error.into()
+        }
+    }
+}
+
+fn run() -> anyhow::Result<ExitCode> {
+    // clap exits with 2 on a usage error, which --check uses for a structural
+    // difference, so usage errors are reported as failures
+    let mut args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            return Ok(if e.use_stderr() {
+                Status::Error.into()
+            } else {
+                Status::Match.into()
+            });
+        }
     };
+    let progress = Progress::new(args.command.is_none() && io::stderr().is_terminal());
+    let mut logger = env_logger::Builder::new();
+    logger.filter_level(args.verbosity.log_level_filter());
+    if let Some(target) = progress.log_target() {
+        logger.target(env_logger::Target::Pipe(target));
+    }
+    logger.init();
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .context("failed to configure the thread pool")?;
+    }
+
+    if let Some(command) = &args.command {
+        return run_command(command);
+    }
 
+    // Each font uses the configuration discovered from its own directory, unless
+    // one is given; fonts with the same configuration share a generator
+    let explicit_config = match args.config.as_deref() {
+        Some(config_path) => {
+            let format = args
+                .config_format
+                .unwrap_or_else(|| config::ConfigFormat::from_path(config_path));
+            Some(config::load_config_as(config_path, format).context("failed to load config")?)
+        }
+        None => None,
+    };
+    let mut generators: Vec<(Option<PathBuf>, Generator)> = vec![];
+    let mut font_generators = vec![];
+    for font_path in args.font_path.iter() {
+        let (source, config) = match &explicit_config {
+            Some(config) => (args.config.clone(), config.clone()),
+            None if args.no_config_discovery => (None, config::Config::default()),
+            None => discover_config(font_path)?,
+        };
+        let index = match generators.iter().position(|(s, _)| *s == source) {
+            Some(index) => index,
+            None => {
+                generators.push((source, configure(&args, config)?));
+                generators.len() - 1
+            }
+        };
+        font_generators.push(index);
+    }
+    // The family's table is collated with the primary font's configuration
+    let primary_index = args
+        .primary
+        .as_deref()
+        .and_then(|primary| args.font_path.iter().position(|path| path == primary))
+        .unwrap_or(0);
+    let generator = &generators[font_generators[primary_index]].1;
+    if generators.len() > 1 {
+        log::info!(
+            "The fonts use {} different configurations; the family's table is collated with {}",
+            generators.len(),
+            generators[font_generators[primary_index]]
+                .0
+                .as_deref()
+                .map_or("the defaults".to_string(), |path| path
+                    .display()
+                    .to_string())
+        );
+    }
+    args.min_max = generator.options().min_max;
+    if generator.config().binary && !args.ufo.is_empty() {
+        anyhow::bail!("--ufo cannot be used when the configuration sets binary = true");
+    }
+    args.binary |= generator.config().binary;
+    if args.explain && !args.min_max {
+        log::warn!("--explain describes the MinMax records, which are only written with -m");
+    }
     if args.binary && args.font_path.len() > 1 && args.output.is_some() {
         anyhow::bail!("The -o option only makes sense with a single input font");
     }
+    if let Some(primary) = args.primary.as_deref() {
+        if !args.font_path.iter().any(|path| path == primary) {
+            anyhow::bail!(
+                "The primary font {} is not one of the input fonts",
+                primary.display()
+            );
+        }
+    }
 
-    let bases = args
+    let config = generator.config();
+    let words_csv = args
+        .words_csv
+        .as_deref()
+        .map(WordCsv::create)
+        .transpose()
+        .context("failed to create word CSV")?;
+    let mut failures: Vec<(PathBuf, anyhow::Error)> = vec![];
+    let mut generated = vec![];
+    let mut generated_paths = vec![];
+    let process = |(path, generator): (&PathBuf, &Generator)| {
+        let font_progress = progress.font(&path.display().to_string());
+        let font_csv = words_csv
+            .as_ref()
+            .map(|csv| csv.font(&path.display().to_string()));
+        let result = fs::read(path)
+            .context("failed to read font file")
+            .and_then(|font_bytes| {
+                generator.generate_font(&font_bytes, &font_progress, font_csv.as_ref())
+            });
+        font_progress.finish();
+        result
+    };
+    // Fonts are processed in parallel (sharing the thread pool limited by --jobs),
+    // but their results are handled in the order they were given. With
+    // --fail-fast they are processed one at a time, so that nothing is measured
+    // after the first failure and each font's log is kept together.
+    let jobs = args
         .font_path
         .iter()
-        .map(|path| {
-            let font_bytes = fs::read(path).context("failed to read font file")?;
-            generate_base_for_font(&args, config.clone(), font_bytes)
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+        .zip(font_generators.iter().map(|index| &generators[*index].1))
+        .collect::<Vec<_>>();
+    let results = if args.fail_fast {
+        let mut results = vec![];
+        for job in jobs.iter().copied() {
+            let result = process(job);
+            let failed = result.is_err();
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        results
+    } else {
+        jobs.par_iter().copied().map(process).collect::<Vec<_>>()
+    };
+    if let Some(csv) = &words_csv {
+        csv.flush().context("failed to write word CSV")?;
+    }
+    for (path, result) in args.font_path.iter().zip(results) {
+        match result {
+            Ok(g) => {
+                generated.push(g);
+                generated_paths.push(path.clone());
+            }
+            Err(e) if args.fail_fast => {
+                return Err(e.context(format!("failed to process {}", path.display())))
+            }
+            Err(e) => {
+                log::error!("Failed to process {}: {:#}", path.display(), e);
+                failures.push((path.clone(), e));
+            }
+        }
+    }
+    if generated.is_empty() {
+        report_failures(&failures, args.font_path.len());
+        return Ok(Status::Error.into());
+    }
+    if let Some(primary) = args.primary.as_deref() {
+        let Some(index) = generated_paths.iter().position(|path| path == primary) else {
+            report_failures(&failures, args.font_path.len());
+            anyhow::bail!(
+                "The primary font {} could not be processed",
+                primary.display()
+            );
+        };
+        generated[index].primary = true;
+    }
+    for (path, result) in generated_paths.iter().zip(generated.iter_mut()) {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        (result.weight, result.min_max_weight) = config.font_weight(&file_name);
+    }
+
+    if args.wants_report() {
+        let report = Report {
+            fonts: generated_paths
+                .iter()
+                .zip(generated.iter())
+                .map(|(path, result)| FontReport {
+                    font: path.display().to_string(),
+                    scripts: result.scripts.clone(),
+                })
+                .collect(),
+        };
+        write_reports(&args, &report, &generated_paths)?;
+    }
+
+    let base = generator.collate(&generated);
+    let fea = generator.collate_for_fea(&generated);
+    for ((result, downgrades), path) in generated
+        .iter()
+        .zip(fea.downgrades.iter())
+        .zip(generated_paths.iter())
+    {
+        if args.explain {
+            let downgrades = if args.binary { &[][..] } else { downgrades };
+            eprintln!("How the values for {} were chosen:", path.display());
+            for script in result.scripts.iter() {
+                eprint!(
+                    "{}",
+                    explain::explain_script(
+                        script,
+                        config,
+                        &result.font_defaults.horizontal,
+                        downgrades
+                    )
+                );
+            }
+        }
+        if !args.binary {
+            for downgrade in downgrades {
+                log::warn!("{}: FEA cannot express {}", path.display(), downgrade);
+            }
+        }
+    }
+    let fea_base = fea.base;
+    // The comparison only reports; the binary table is the one collated above
+    if args.check_fea {
+        check_fea_consistency(&base, &fea_base);
+    }
+
+    // The summary goes to stderr, leaving stdout to the FEA or changelog
+    let written = if args.binary { &base } else { &fea_base };
+    if !written.horizontal.is_empty() || !written.vertical.is_empty() {
+        eprint!("{}", written.summary());
+    }
 
-    let base = collate_bases(bases, config.tolerance);
+    if let Some(profile) = config.profile {
+        if !check_profile(profile, &base, &generated_paths)? {
+            return Ok(Status::Error.into());
+        }
+    }
+
+    if args.family_check {
+        let status = check_family(&generated, &generated_paths, config.min_max_tolerance());
+        if !failures.is_empty() {
+            report_failures(&failures, args.font_path.len());
+            return Ok(Status::Error.into());
+        }
+        return Ok(status.into());
+    }
+
+    if args.check {
+        let status = check_fonts(&base, &generated_paths, config.min_max_tolerance())?;
+        if !failures.is_empty() {
+            report_failures(&failures, args.font_path.len());
+            return Ok(Status::Error.into());
+        }
+        return Ok(status.into());
+    }
 
     if args.binary {
-        for font_path in args.font_path {
-            let font_bytes = fs::read(&font_path).context("failed to read font file")?;
-            let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
-            let mut new_font = FontBuilder::new();
-            new_font.add_table(&base.to_skrifa()?)?;
-            new_font.copy_missing_tables(font.clone());
-            let binary = new_font.build();
-            let output_path = args.output.clone().unwrap_or(font_path);
-            fs::write(&output_path, binary).context("failed to write font file")?;
-            log::info!("Wrote font to {:?}", output_path);
+        let mut changelog_text = String::new();
+        for font_path in generated_paths {
+            let result = write_font(
+                &base,
+                &font_path,
+                args.output.as_deref(),
+                args.force || args.only_missing,
+            );
+            match result {
+                Ok(changes) => {
+                    changelog_text.push_str(&format!("{}:\n", font_path.display()));
+                    if changes.is_empty() {
+                        changelog_text.push_str("  No changes to BASE table\n");
+                    }
+                    for change in changes {
+                        changelog_text.push_str(&format!("  {}\n", change));
+                    }
+                }
+                Err(e) if args.fail_fast => {
+                    return Err(e.context(format!("failed to write {}", font_path.display())))
+                }
+                Err(e) => {
+                    log::error!("Failed to write {}: {:#}", font_path.display(), e);
+                    failures.push((font_path, e));
+                }
+            }
+        }
+        print!("{}", changelog_text);
+        if let Some(changelog_path) = args.changelog.as_deref() {
+            fs::write(changelog_path, changelog_text).context("failed to write changelog")?;
+        }
+    } else if !args.ufo.is_empty() {
+        for ufo_path in args.ufo.iter() {
+            ufo::write_to_ufo(&base, &fea_base, ufo_path)
+                .with_context(|| format!("failed to write {}", ufo_path.display()))?;
+            log::info!("Wrote BASE table to {}", ufo_path.display());
         }
     } else {
-        println!("{}", base.to_fea());
-        return Ok(ExitCode::SUCCESS);
-    }
-    Ok(ExitCode::SUCCESS)
-}
-
-fn generate_base_for_font(
-    args: &Args,
-    config: config::Config,
-    font_bytes: Vec<u8>,
-) -> Result<BaseTable, anyhow::Error> {
-    let reporter = Reporter::new(&font_bytes)?;
-    let font = reporter.fontref();
-    let locations = reporter.interesting_locations();
-    let instances = locations
-        .par_iter()
-        .map(|location| reporter.instance(location))
-        .collect::<Result<Vec<_>, _>>()
-        .context("failed to initialise instances for testing")?;
-    let supported = supported_scripts(font);
-    log::info!(
-        "Supported scripts: {}",
-        supported.iter().cloned().collect::<Vec<_>>().join(", ")
-    );
-    let wordlists = static_lang_word_lists::ALL_WORD_LISTS
-        .iter()
-        .filter(|word_list| {
-            // Filter out word lists that don't have a script in the font
-            word_list
-                .script()
-                .map(|x| supported.contains(x))
-                .unwrap_or(false)
-        });
-    // We want to filter out any words which are in the exclusions. But:
-    // - We can't clone or modify a wordlist
-    // - We can create a wordlist from an iterator but we then lose the metadata
-    // - We can't create new metadata objects or change the metadata on an existing wordlist
-    // - We can't add a filter function into par_check after par_iter because the function can't go across threads
-    // - We can't add a filter function into par_check before par_iter because we need Wordlist.par_iter to produce a ParWordListIter
-    // So there's not much we can do except get a large number of exemplars and hope for the best.
-    let reports = wordlists
-        // Cartesian product relevant word lists with instances
-        .flat_map(|word_list| instances.iter().zip(iter::repeat(word_list)))
-        .par_bridge()
-        .map(|(reporter, word_list)| {
-            reporter.par_check(word_list, Some(args.words_per_list), 10000)
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    let mut reports_by_script: BTreeMap<String, Vec<Report>> = BTreeMap::new();
-    for report in reports.into_iter() {
-        if let Some(script) = report.word_list.script() {
-            reports_by_script
-                .entry(script.to_string())
-                .or_default()
-                .push(report);
-        }
-    }
-    let font_minmax = get_font_minmax(font, args.use_hhea);
-    log::info!(
-        "Font default min {} max {}",
-        font_minmax.lowest.unwrap_or_default(),
-        font_minmax.highest.unwrap_or_default(),
-    );
-    let mut base_script_records = if args.min_max {
-        reports_by_script
+        println!("{}", fea_base.to_fea());
+    }
+    if !failures.is_empty() {
+        report_failures(&failures, args.font_path.len());
+        return Ok(Status::Error.into());
+    }
+    Ok(Status::Match.into())
+}
+
+/// Compare the generated table with each font's existing BASE table, printing the
+/// differences, and return the most severe.
+fn check_fonts(
+    base: &BaseTable,
+    font_paths: &[PathBuf],
+    tolerance: Tolerance,
+) -> anyhow::Result<Status> {
+    let mut status = Status::Match;
+    for font_path in font_paths {
+        let font_bytes = fs::read(font_path).context("failed to read font file")?;
+        let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+        let existing = BaseTable::from_font(&font)
+            .context("failed to read existing BASE table")?
+            .unwrap_or_default();
+        let changes = existing.approx_eq(base, tolerance);
+        println!("{}:", font_path.display());
+        if changes.is_empty() {
+            println!("  BASE table matches");
+        }
+        for change in changes {
+            println!("  {}", change);
+            status = status.max(match change.severity(tolerance) {
+                changelog::Severity::Structural => Status::Structural,
+                _ => Status::ValuesDiffer,
+            });
+        }
+    }
+    Ok(status)
+}
+
+/// Check each font, with the table to be written into it, against a profile,
+/// reporting every violation; returns whether they all comply.
+fn check_profile(
+    profile: Profile,
+    base: &BaseTable,
+    font_paths: &[PathBuf],
+) -> anyhow::Result<bool> {
+    let mut complies = true;
+    for font_path in font_paths {
+        let font_bytes = fs::read(font_path).context("failed to read font file")?;
+        let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+        let violations = profile.check(&font, base)?;
+        if violations.is_empty() {
+            log::info!(
+                "{} complies with the {} profile",
+                font_path.display(),
+                profile
+            );
+            continue;
+        }
+        complies = false;
+        eprintln!(
+            "{} does not comply with the {} profile:",
+            font_path.display(),
+            profile
+        );
+        for violation in violations {
+            eprintln!("  {}", violation);
+        }
+    }
+    Ok(complies)
+}
+
+/// Compare the tables generated for each font of a family, printing the values
+/// which differ between them by more than the tolerance.
+fn check_family(fonts: &[FontResult], font_paths: &[PathBuf], tolerance: Tolerance) -> Status {
+    let divergences = family::divergences(fonts, tolerance);
+    if divergences.is_empty() {
+        println!("All {} fonts agree within {} units", fonts.len(), tolerance);
+        return Status::Match;
+    }
+    for divergence in divergences {
+        println!("{}:", divergence);
+        for (path, value) in font_paths.iter().zip(divergence.values.iter()) {
+            let value = value.map_or("-".to_string(), |v| v.to_string());
+            println!("  {:>6}  {}", value, path.display());
+        }
+    }
+    Status::ValuesDiffer
+}
+
+/// Write the JSON and HTML reports, if they were asked for.
+fn write_reports(args: &Args, report: &Report, font_paths: &[PathBuf]) -> anyhow::Result<()> {
+    if let Some(path) = args.report.as_deref() {
+        fs::write(path, report.to_json()?).context("failed to write report")?;
+        log::info!("Wrote report to {}", path.display());
+    }
+    if let Some(path) = args.html_report.as_deref() {
+        let font_bytes = font_paths
             .iter()
-            .flat_map(|(script, reports)| {
-                base_script::base_script_record(script, reports, &config, &font_minmax)
-            })
-            .collect::<Vec<_>>()
-    } else {
-        vec![]
+            .map(fs::read)
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to read font file")?;
+        let fonts = font_bytes
+            .iter()
+            .map(|bytes| skrifa::FontRef::new(bytes))
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to parse font file")?;
+        fs::write(path, html_report::to_html(report, &fonts))
+            .context("failed to write HTML report")?;
+        log::info!("Wrote HTML report to {}", path.display());
+    }
+    if let Some(dir) = args.png_proofs.as_deref() {
+        write_png_proofs(dir, report, font_paths)?;
+        log::info!("Wrote PNG proofs to {}", dir.display());
+    }
+    Ok(())
+}
+
+/// Write a PNG of the highest and lowest words of each script at each location,
+/// against the font's hhea lines and the script's chosen min and max.
+#[cfg(feature = "png")]
+fn write_png_proofs(dir: &Path, report: &Report, font_paths: &[PathBuf]) -> anyhow::Result<()> {
+    use autobase::{
+        proof::{proof_png, Rule},
+        render::parse_location,
+        report::Extreme,
     };
+    use std::collections::BTreeMap;
 
-    // If we are not writing into the binary (ie. just outputting FEA), we
-    // can't use NULL MinMax values, because FEA doesn't support them. So we
-    // need to replace them with the font's default min/max values.
-    if !args.binary {
-        for script in base_script_records.iter_mut() {
-            if let Some(script_minmax) = &script.default_minmax {
-                if script_minmax.is_empty() {
-                    continue;
+    const GREY: [u8; 3] = [160, 160, 160];
+    const BLUE: [u8; 3] = [0, 102, 204];
+    const RED: [u8; 3] = [221, 0, 0];
+
+    fs::create_dir_all(dir).context("failed to create proof directory")?;
+    for (font_report, path) in report.fonts.iter().zip(font_paths) {
+        let font_bytes = fs::read(path).context("failed to read font file")?;
+        let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+        let stem = path
+            .file_stem()
+            .map_or("font".into(), |stem| stem.to_string_lossy());
+        let mut font_rules = vec![Rule {
+            value: 0,
+            color: GREY,
+        }];
+        if let Ok(hhea) = font.hhea() {
+            for value in [hhea.ascender().to_i16(), hhea.descender().to_i16()] {
+                font_rules.push(Rule { value, color: BLUE });
+            }
+        }
+        for script in font_report.scripts.iter() {
+            let record = script.record.as_ref().and_then(|r| r.default.as_ref());
+            // The highest and lowest words of each location, across its word lists
+            let mut by_location: BTreeMap<&str, (Option<&Extreme>, Option<&Extreme>)> =
+                BTreeMap::new();
+            for measurement in script.measurements.iter() {
+                let (max, min) = by_location
+                    .entry(measurement.location.as_str())
+                    .or_default();
+                if let Some(candidate) = &measurement.extremes.max {
+                    if max.is_none_or(|max| candidate.value > max.value) {
+                        *max = Some(candidate);
+                    }
+                }
+                if let Some(candidate) = &measurement.extremes.min {
+                    if min.is_none_or(|min| candidate.value < min.value) {
+                        *min = Some(candidate);
+                    }
                 }
-                // Replace any nulls in the script default min/max
-                script.default_minmax =
-                    Some(script_minmax.clone().with_nulls_replaced(&font_minmax));
             }
-            for (_baseline, lang) in script.languages.iter_mut() {
-                *lang = lang.clone().with_nulls_replaced(&font_minmax);
+            for (location, (max, min)) in by_location {
+                let skrifa_location = parse_location(&font, location)?;
+                let words = max
+                    .iter()
+                    .chain(min.iter())
+                    .map(|extreme| (extreme.word.as_str(), &skrifa_location))
+                    .collect::<Vec<_>>();
+                let chosen = record.map_or((max, min), |r| (r.max.as_ref(), r.min.as_ref()));
+                let mut rules = font_rules.clone();
+                rules.extend(
+                    [chosen.0, chosen.1]
+                        .into_iter()
+                        .flatten()
+                        .map(|extreme| Rule {
+                            value: extreme.value,
+                            color: RED,
+                        }),
+                );
+                let png = proof_png(&font, &words, &rules, 300)?;
+                let location = location.replace('=', "").replace(' ', "_");
+                let name = format!("{}-{}-{}.png", stem, script.script, location);
+                fs::write(dir.join(name), png).context("failed to write proof")?;
             }
         }
     }
+    Ok(())
+}
+
+#[cfg(not(feature = "png"))]
+fn write_png_proofs(_dir: &Path, _report: &Report, _font_paths: &[PathBuf]) -> anyhow::Result<()> {
+    anyhow::bail!("--png-proofs requires autobase-cli to be built with the png feature")
+}
 
-    let mut base = BaseTable::new(
-        base_script_records,
-        vec![], // No vertical today
-    );
-    let needs_cjk = supported.iter().any(|s| cjk::is_cjk_script(s));
-    if needs_cjk {
-        log::info!("CJK scripts detected, adding CJK BASE records");
-        let cjk_bounds = compute_bounds(font)?;
-        let upem = font.head()?.units_per_em() as f32;
-        cjk_bounds.insert_into_base(upem, &supported, &mut base);
+/// Write the BASE table into the font, returning the changes made to its existing BASE table.
+///
+/// Unless `force` is given, a font whose existing BASE table would lose records or
+/// values isn't written, and the error lists what would be lost.
+fn write_font(
+    base: &BaseTable,
+    font_path: &Path,
+    output: Option<&Path>,
+    force: bool,
+) -> anyhow::Result<Vec<changelog::Change>> {
+    let font_bytes = fs::read(font_path).context("failed to read font file")?;
+    let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+    let existing = BaseTable::from_font(&font)
+        .context("failed to read existing BASE table")?
+        .unwrap_or_default();
+    if !force {
+        let lost = changelog::losses(&existing, base)
+            .into_iter()
+            .map(|change| format!("  {}", change))
+            .collect::<Vec<_>>();
+        if !lost.is_empty() {
+            anyhow::bail!(
+                "the font already has a BASE table, which would lose:\n{}\nUse --force to replace it, or --only-missing to add to it",
+                lost.join("\n")
+            );
+        }
     }
-    if !needs_cjk && !args.min_max {
-        log::info!("No CJK BASE table needed, -m was not given");
+    let mut new_font = FontBuilder::new();
+    new_font.add_table(&base.to_skrifa()?)?;
+    new_font.copy_missing_tables(font.clone());
+    let binary = new_font.build();
+    let output_path = output.unwrap_or(font_path);
+    fs::write(output_path, binary).context("failed to write font file")?;
+    log::info!("Wrote font to {:?}", output_path);
+    Ok(changelog::changes(&existing, base))
+}
+
+/// Write a font's existing BASE table as FEA to `output` (or standard output),
+/// or into the UFO at `into_ufo`.
+///
+/// FEA can't express NULL MinMax values, so they are replaced with the font's
+/// defaults, as when generating; the UFO's lib.plist keeps the table as it is.
+fn extract_base(
+    font_path: &Path,
+    output: Option<&Path>,
+    into_ufo: Option<&Path>,
+) -> anyhow::Result<()> {
+    let font_bytes = fs::read(font_path).context("failed to read font file")?;
+    let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+    let base = font.base().context("font has no BASE table")?;
+    let base = BaseTable::from_skrifa(&base).context("failed to read existing BASE table")?;
+    let defaults = FontDefaults::new(&font, MetricSource::default())?;
+    let (fea_base, downgrades) = fea::downgrade_for_fea(&base, &defaults);
+    for downgrade in downgrades {
+        log::info!("{}", downgrade);
+    }
+    match (output, into_ufo) {
+        (_, Some(ufo_path)) => {
+            ufo::write_to_ufo(&base, &fea_base, ufo_path)
+                .with_context(|| format!("failed to write {}", ufo_path.display()))?;
+            log::info!("Wrote BASE table to {}", ufo_path.display());
+        }
+        (Some(output), None) => {
+            fs::write(output, fea_base.to_fea()).context("failed to write feature file")?;
+            log::info!("Wrote BASE table to {}", output.display());
+        }
+        (None, None) => println!("{}", fea_base.to_fea()),
     }
-    Ok(base)
+    Ok(())
 }
 
-fn collate_bases(bases: Vec<BaseTable>, tolerance: Option<u16>) -> BaseTable {
-    let base_iter = bases.into_iter();
-    let mut first = match base_iter.clone().next() {
-        Some(b) => b,
-        None => return BaseTable::new(vec![], vec![]),
-    };
-    for b in base_iter {
-        first.merge(&b, tolerance);
+fn run_command(command: &Command) -> anyhow::Result<ExitCode> {
+    match command {
+        Command::InitConfig {
+            font_path,
+            output,
+            force,
+        } => {
+            let text = init_config::starter_config(font_path)?;
+            match output {
+                Some(output) => {
+                    if output.exists() && !force {
+                        anyhow::bail!(
+                            "{} already exists; use --force to overwrite it",
+                            output.display()
+                        );
+                    }
+                    fs::write(output, text).context("failed to write config")?;
+                    log::info!("Wrote starter config to {}", output.display());
+                }
+                None => print!("{}", text),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::CjkReport { font_path, config } => {
+            let config = match config {
+                Some(config) => config::load_config(config).context("failed to load config")?,
+                None => discover_config(font_path)?.1,
+            };
+            let font_bytes = fs::read(font_path).context("failed to read font file")?;
+            let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+            let options = config.cjk_options();
+            let metrics = compute_bounds(&font, &options)?.with_em_box(&config.cjk_em_box);
+            print!("{}", cjk::CjkReport::new(&font, &options, metrics)?);
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Visualize {
+            font_path,
+            output,
+            existing,
+            text,
+            config,
+        } => {
+            let font_bytes = fs::read(font_path).context("failed to read font file")?;
+            let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+            let base = if *existing {
+                let base = font.base().context("font has no BASE table")?;
+                BaseTable::from_skrifa(&base).context("failed to read existing BASE table")?
+            } else {
+                let config = match config {
+                    Some(config) => config::load_config(config).context("failed to load config")?,
+                    None => discover_config(font_path)?.1,
+                };
+                let mut supported = supported_scripts(&font);
+                supported.retain(|script| config.script_allowed(script));
+                let mut base = BaseTable::default();
+                pipeline::add_cjk_baselines(&font, &config, &supported, &mut base)?;
+                base
+            };
+            if base.horizontal.is_empty() {
+                anyhow::bail!(
+                    "There are no horizontal baselines to draw for {}",
+                    font_path.display()
+                );
+            }
+            let svg = visualize::baselines_svg(&font, &base, |script| {
+                text.clone().or_else(|| sample_text(script))
+            });
+            match output {
+                Some(output) => {
+                    fs::write(output, svg).context("failed to write SVG")?;
+                    log::info!("Wrote baselines to {}", output.display());
+                }
+                None => print!("{}", svg),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Update {
+            font_path,
+            output,
+            config,
+            fingerprint,
+            all,
+        } => {
+            update_font(
+                font_path,
+                output.as_deref(),
+                config.as_deref(),
+                fingerprint.as_deref(),
+                *all,
+            )?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Extract {
+            font_path,
+            output,
+            into_ufo,
+        } => {
+            extract_base(font_path, output.as_deref(), into_ufo.as_deref())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ConfigCheck {
+            config,
+            config_format,
+        } => {
+            let text = fs::read_to_string(config).context("failed to read config")?;
+            let format = config_format.unwrap_or_else(|| config::ConfigFormat::from_path(config));
+            let base_dir = config.parent().unwrap_or(Path::new("."));
+            let problems = config_check::check_config(&text, format, base_dir);
+            for problem in problems.iter() {
+                match problem.line_column(&text) {
+                    Some((line, column)) => println!(
+                        "{}:{}:{}: {}",
+                        config.display(),
+                        line,
+                        column,
+                        problem.message
+                    ),
+                    None => println!("{}: {}", config.display(), problem.message),
+                }
+            }
+            if problems.is_empty() {
+                println!("{}: no problems found", config.display());
+                Ok(ExitCode::SUCCESS)
+            } else {
+                println!("{} problem(s) found", problems.len());
+                Ok(ExitCode::FAILURE)
+            }
+        }
+    }
+}
+
+/// A few words from the first bundled word list of an OpenType script, to draw
+/// its baselines through.
+fn sample_text(script: skrifa::Tag) -> Option<String> {
+    let scripts = utils::opentype_to_iso15924(script);
+    let word_list = static_lang_word_lists::ALL_WORD_LISTS
+        .iter()
+        .find(|word_list| {
+            word_list
+                .script()
+                .is_some_and(|script| scripts.contains(&script))
+        })?;
+    Some(word_list.iter().take(4).collect::<Vec<_>>().join(" "))
+}
+
+/// Apply the command line's settings to a font's configuration, and make the
+/// generator for the fonts which use it.
+fn configure(args: &Args, mut config: config::Config) -> anyhow::Result<Generator> {
+    if let Some(recipe) = args.recipe {
+        recipe.apply(&mut config);
     }
-    // Simplify the BASE table to remove redundant entries
-    first.simplify(tolerance); // 5 units tolerance
-    first
+    if args.profile.is_some() {
+        config.profile = args.profile;
+    }
+    let min_max = args.min_max || (config.min_max && !args.baseline_only);
+    // --use-hhea overrides the config's metric_source, but its use_hhea doesn't
+    if args.metric_source.is_some() {
+        config.metric_source = args.metric_source;
+    } else if args.use_hhea {
+        config.metric_source = Some(MetricSource::Hhea);
+    }
+    config.resolve_metric_source(min_max);
+    config.wordlists.extend(args.wordlists.iter().cloned());
+    config.corpora.extend(args.corpora.iter().cloned());
+    config.skip_bundled_wordlists |= args.no_bundled_wordlists;
+    config.cjk_exact |= args.cjk_exact;
+    config.clamp_to_win |= args.clamp_to_win;
+    if args.clamp_max.is_some() {
+        config.clamp_max = args.clamp_max;
+    }
+    if args.clamp_min.is_some() {
+        config.clamp_min = args.clamp_min;
+    }
+    if args.descender.is_some() {
+        config.cjk_descender = args.descender;
+    }
+    if args.cjk_square_tolerance.is_some() {
+        config.cjk_square_tolerance = args.cjk_square_tolerance;
+    }
+    if args.force_idtp {
+        config.cjk_idtp = cjk::IdtpPolicy::Always;
+    } else if args.no_idtp {
+        config.cjk_idtp = cjk::IdtpPolicy::Never;
+    }
+    if args.frequencies.is_some() {
+        config.frequencies = args.frequencies.clone();
+    }
+    if args.frequency_cutoff.is_some() {
+        config.frequency_cutoff = args.frequency_cutoff;
+    }
+    if args.tolerance.is_some() {
+        config.tolerance = args.tolerance;
+    }
+    if args.max_tolerance.is_some() {
+        config.max_tolerance = args.max_tolerance;
+    }
+    if args.min_tolerance.is_some() {
+        config.min_tolerance = args.min_tolerance;
+    }
+    if let Some(collation) = args.collation {
+        config.collation = collation;
+    }
+    if let Some(scripts_from) = args.scripts_from {
+        config.scripts_from = scripts_from;
+    }
+    if let Some(meta) = args.meta {
+        config.meta = meta;
+    }
+    if args.min_script_coverage.is_some() {
+        config.min_script_coverage = args.min_script_coverage;
+    }
+    if args.cache_dir.is_some() {
+        config.cache_dir = args.cache_dir.clone();
+    }
+    if args.measurement_cache_dir.is_some() {
+        config.measurement_cache_dir = args.measurement_cache_dir.clone();
+    }
+    if !args.scripts.is_empty() {
+        config.scripts = args.scripts.clone();
+    }
+    config
+        .exclude_scripts
+        .extend(args.exclude_scripts.iter().cloned());
+    if !args.languages.is_empty() {
+        config.languages = args.languages.clone();
+    }
+    config
+        .exclude_languages
+        .extend(args.exclude_languages.iter().cloned());
+    Generator::new(
+        config,
+        GeneratorOptions {
+            min_max,
+            baseline_only: args.baseline_only,
+            only_missing: args.only_missing,
+            words_per_list: args.words_per_list,
+            max_duration: args.max_duration,
+            reports: args.wants_report(),
+        },
+    )
 }
 
-fn get_font_minmax(font: &skrifa::FontRef, use_hhea: bool) -> MinMax {
-    let (ascender, descender) = if use_hhea {
-        let hhea = font.hhea().unwrap();
-        (hhea.ascender().to_i16(), hhea.descender().to_i16())
+/// Find the configuration for a font which applies when none is given explicitly,
+/// and the file it came from.
+fn discover_config(font_path: &Path) -> anyhow::Result<(Option<PathBuf>, config::Config)> {
+    let font_path = fs::canonicalize(font_path).unwrap_or_else(|_| font_path.to_path_buf());
+    let start = font_path.parent().unwrap_or(Path::new("."));
+    match config::discover_config(start).with_context(|| {
+        format!(
+            "failed to load the configuration discovered for {}",
+            font_path.display()
+        )
+    })? {
+        Some((path, config)) => {
+            log::info!(
+                "Using configuration from {} for {}",
+                path.display(),
+                font_path.display()
+            );
+            Ok((Some(path), config))
+        }
+        None => Ok((None, config::Config::default())),
+    }
+}
+
+/// Regenerate the records of the scripts whose glyphs have changed since the
+/// fingerprint was stored, and write them into the font with the others unchanged.
+fn update_font(
+    font_path: &Path,
+    output: Option<&Path>,
+    config_path: Option<&Path>,
+    fingerprint_path: Option<&Path>,
+    all: bool,
+) -> anyhow::Result<()> {
+    let (config_path, mut config) = match config_path {
+        Some(path) => (
+            Some(path.to_path_buf()),
+            config::load_config(path).context("failed to load config")?,
+        ),
+        None => discover_config(font_path)?,
+    };
+    // The table depends on the settings as well as the glyphs, so a changed
+    // configuration, or a changed file it refers to, makes every script stale
+    let mut settings = match &config_path {
+        Some(path) => fs::read(path).context("failed to read config")?,
+        None => vec![],
+    };
+    for file in config.referenced_files() {
+        let contents =
+            fs::read(file).with_context(|| format!("failed to read {}", file.display()))?;
+        // Prefix each with its length, so that bytes can't move between them
+        for part in [file.as_os_str().as_encoded_bytes(), &contents] {
+            settings.extend((part.len() as u64).to_be_bytes());
+            settings.extend(part);
+        }
+    }
+    let fingerprint_path = fingerprint_path
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| Fingerprint::sidecar_path(font_path));
+
+    let font_bytes = fs::read(font_path).context("failed to read font file")?;
+    let font = skrifa::FontRef::new(&font_bytes).context("failed to parse font file")?;
+    let fingerprint = Fingerprint::of_font(&font, &settings)?;
+    let existing = BaseTable::from_font(&font).context("failed to read existing BASE table")?;
+    let previous = match (all, &existing) {
+        (false, Some(_)) => Fingerprint::load(&fingerprint_path)?,
+        _ => None,
+    };
+    let stale = fingerprint.stale_scripts(previous.as_ref(), config.cjk_per_script);
+    let removed = previous
+        .as_ref()
+        .map(|previous| fingerprint.removed_scripts(previous))
+        .unwrap_or_default();
+    if previous.is_some() && stale.is_empty() && removed.is_empty() {
+        log::info!("No script of {} has changed", font_path.display());
+        return Ok(());
+    }
+    log::info!("Measuring {}", stale.join(", "));
+
+    let regenerate = stale
+        .iter()
+        .filter(|script| config.script_allowed(script))
+        .cloned()
+        .collect::<Vec<_>>();
+    let regenerated = if regenerate.is_empty() {
+        BaseTable::default()
     } else {
-        let os2 = font.os2().unwrap();
-        (os2.s_typo_ascender(), os2.s_typo_descender())
+        config.scripts = regenerate;
+        config.resolve_metric_source(config.min_max);
+        let options = GeneratorOptions {
+            min_max: config.min_max,
+            ..Default::default()
+        };
+        Generator::new(config.clone(), options)?.generate(&[&font_bytes])?
+    };
+    let base = match existing {
+        Some(mut base) if previous.is_some() => {
+            let tags = stale
+                .iter()
+                .chain(removed.iter())
+                .filter_map(|script| config.opentype_script(script))
+                .collect::<Vec<_>>();
+            base.replace_scripts(&tags, &regenerated);
+            base
+        }
+        _ => regenerated,
+    };
+
+    let mut new_font = FontBuilder::new();
+    new_font.add_table(&base.to_skrifa()?)?;
+    new_font.copy_missing_tables(font.clone());
+    let output_path = output.unwrap_or(font_path);
+    fs::write(output_path, new_font.build()).context("failed to write font file")?;
+    log::info!("Wrote font to {:?}", output_path);
+    // The fingerprint describes the glyphs as measured, which are those of the
+    // font written
+    fingerprint.save(&match output {
+        Some(output) if fingerprint_path == Fingerprint::sidecar_path(font_path) => {
+            Fingerprint::sidecar_path(output)
+        }
+        _ => fingerprint_path,
+    })?;
+    Ok(())
+}
+
+/// Print an end-of-run summary of the fonts which could not be processed.
+fn report_failures(failures: &[(PathBuf, anyhow::Error)], total: usize) {
+    eprintln!("{} of {} fonts failed:", failures.len(), total);
+    for (path, error) in failures {
+        eprintln!("  {}: {:#}", path.display(), error);
+    }
+}
+
+/// Parse a duration given in seconds, optionally with an `s`, `m` or `h` suffix.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let (number, unit) = match text.strip_suffix(['s', 'm', 'h']) {
+        Some(number) => (number, &text[number.len()..]),
+        None => (text, "s"),
+    };
+    let value: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid duration: {}", text))?;
+    let seconds = match unit {
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => value,
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration {}: {}", text, e))
+}
+
+/// Log how the compiled FEA output differs from the binary output.
+///
+/// This never changes either output, nor fails the run.
+#[cfg(feature = "fea-check")]
+fn check_fea_consistency(base: &BaseTable, fea_base: &BaseTable) {
+    let divergences = match autobase::consistency::fea_divergences(base, fea_base) {
+        Ok(divergences) => divergences,
+        Err(e) => {
+            log::error!("Failed to compare FEA and binary output: {}", e);
+            return;
+        }
+    };
+    if divergences.is_empty() {
+        log::info!("FEA and binary output compile to identical BASE tables");
+    }
+    for divergence in divergences {
+        log::error!("FEA and binary output diverge: {}", divergence);
+    }
+}
+
+#[cfg(not(feature = "fea-check"))]
+fn check_fea_consistency(_base: &BaseTable, _fea_base: &BaseTable) {
+    log::error!("--check-fea requires autobase-cli to be built with the fea-check feature");
+}
+
+#[cfg(test)]
+mod tests {
+    use autobase::base::{BaseScript, MinMax};
+    use skrifa::Tag;
+    use write_fonts::{
+        tables::{
+            head::{Flags, Head, MacStyle},
+            os2::Os2,
+        },
+        types::{Fixed, LongDateTime},
     };
-    MinMax::new_min_max(descender, ascender)
+
+    use super::*;
+
+    /// A font whose typo metrics run from -250 to 900, and whose Latin record
+    /// has a max of 950 and a NULL min.
+    fn font_with_base() -> Vec<u8> {
+        let mut builder = FontBuilder::new();
+        builder
+            .add_table(&Head::new(
+                Fixed::ONE,
+                0,
+                Flags::empty(),
+                1000,
+                LongDateTime::new(0),
+                LongDateTime::new(0),
+                0,
+                -250,
+                1000,
+                900,
+                MacStyle::empty(),
+                0,
+                0,
+            ))
+            .unwrap();
+        builder
+            .add_table(&Os2 {
+                s_typo_ascender: 900,
+                s_typo_descender: -250,
+                ..Default::default()
+            })
+            .unwrap();
+        let font = builder.build();
+        let mut latn = BaseScript::new(Tag::new(b"latn"));
+        latn.default_minmax = Some(MinMax {
+            lowest: None,
+            ..MinMax::new_min_max(0, 950)
+        });
+        BaseTable::new(vec![latn], vec![])
+            .add_to_binary(&skrifa::FontRef::new(&font).unwrap())
+            .unwrap()
+    }
+
+    /// A fresh directory for one test's files.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("autobase-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn extracted_fea_fills_null_minmax_with_the_font_default() {
+        let dir = scratch("extract-fea");
+        let font_path = dir.join("font.ttf");
+        fs::write(&font_path, font_with_base()).unwrap();
+        let output = dir.join("base.fea");
+        let result = extract_base(&font_path, Some(&output), None);
+        let fea = fs::read_to_string(&output);
+        fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        let fea = fea.unwrap();
+        assert!(fea.contains("table BASE"));
+        assert!(fea.contains("-250, 950"), "{}", fea);
+    }
+
+    #[test]
+    fn extracting_into_a_ufo_keeps_null_minmax_in_the_lib() {
+        let dir = scratch("extract-ufo");
+        let font_path = dir.join("font.ttf");
+        fs::write(&font_path, font_with_base()).unwrap();
+        let ufo = dir.join("font.ufo");
+        fs::create_dir_all(&ufo).unwrap();
+        fs::write(ufo.join("metainfo.plist"), "").unwrap();
+        let result = extract_base(&font_path, None, Some(&ufo));
+        let features = fs::read_to_string(ufo.join("features.fea"));
+        let lib = fs::read_to_string(ufo.join("lib.plist"));
+        fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        assert!(features.unwrap().contains("-250, 950"));
+        let lib = lib.unwrap();
+        assert!(lib.contains(ufo::LIB_KEY));
+        assert!(lib.contains("<integer>950</integer>"), "{}", lib);
+        assert!(!lib.contains("<integer>-250</integer>"), "{}", lib);
+    }
+
+    #[test]
+    fn fonts_without_a_base_table_cannot_be_extracted() {
+        let dir = scratch("extract-none");
+        let font_path = dir.join("font.ttf");
+        let mut builder = FontBuilder::new();
+        builder.add_table(&Os2::default()).unwrap();
+        fs::write(&font_path, builder.build()).unwrap();
+        let result = extract_base(&font_path, None, None);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result.unwrap_err().to_string(), "font has no BASE table");
+    }
 }
@@ -0,0 +1,85 @@
+//! Progress bars for interactive runs.
+//!
+//! Measuring a large family can take minutes, so when autobase is run in a
+//! terminal it shows a bar for each font, with an estimate of the time left and
+//! the script and word list being measured. Log lines are printed above the
+//! bars. When stderr isn't a terminal nothing is drawn, and the log is left
+//! as plain lines for machines to read.
+use std::io::{self, Write};
+
+use autobase::{config::ScriptLanguage, pipeline::MeasureProgress};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+const TEMPLATE: &str = "{prefix} [{bar:30}] {pos}/{len} (eta {eta}) {wide_msg}";
+
+/// The progress bars of a run, if they are shown at all.
+pub struct Progress {
+    bars: Option<MultiProgress>,
+}
+
+impl Progress {
+    pub fn new(enabled: bool) -> Self {
+        Progress {
+            bars: enabled.then(MultiProgress::new),
+        }
+    }
+
+    /// Where log lines should be written so that they don't disturb the bars,
+    /// if any are shown.
+    pub fn log_target(&self) -> Option<Box<dyn Write + Send>> {
+        self.bars
+            .clone()
+            .map(|bars| Box::new(LogWriter(bars)) as Box<dyn Write + Send>)
+    }
+
+    /// Start the bar of one font.
+    pub fn font(&self, name: &str) -> FontProgress {
+        FontProgress(self.bars.as_ref().map(|bars| {
+            let bar = bars.add(ProgressBar::new(0));
+            if let Ok(style) = ProgressStyle::with_template(TEMPLATE) {
+                bar.set_style(style.progress_chars("=> "));
+            }
+            bar.set_prefix(name.to_string());
+            bar
+        }))
+    }
+}
+
+/// The progress of measuring one font's word lists at each of its locations.
+pub struct FontProgress(Option<ProgressBar>);
+
+impl FontProgress {
+    pub fn finish(&self) {
+        if let Some(bar) = &self.0 {
+            bar.finish_with_message("done");
+        }
+    }
+}
+
+impl MeasureProgress for FontProgress {
+    fn add_measurements(&self, count: usize) {
+        if let Some(bar) = &self.0 {
+            bar.inc_length(count as u64);
+        }
+    }
+
+    fn measuring(&self, source: &ScriptLanguage, word_list: &str) {
+        if let Some(bar) = &self.0 {
+            bar.set_message(format!("{}: {}", source.script, word_list));
+            bar.inc(1);
+        }
+    }
+}
+
+/// Writes log lines while the bars are hidden.
+struct LogWriter(MultiProgress);
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
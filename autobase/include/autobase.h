@@ -0,0 +1,49 @@
+/* The C interface to autobase, available when it is built with the capi feature:
+ *
+ *     cargo rustc -p autobase --release --features capi --crate-type cdylib
+ *
+ * (or --crate-type staticlib for a static library).
+ */
+#ifndef AUTOBASE_H
+#define AUTOBASE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct {
+    /* Add MinMax records, as --min-max does */
+    bool min_max;
+    /* The number of words from each list to measure, or 0 for the default */
+    size_t words_per_list;
+    /* A configuration in TOML, as it would be written in autobase.toml, or NULL.
+       Paths in it are relative to the working directory. */
+    const char *config;
+} AutobaseOptions;
+
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} AutobaseBytes;
+
+/* Generate a BASE table for the font in `font`, returning the font with the
+ * table added. `options` may be NULL for the defaults. On failure, the returned
+ * data is NULL and autobase_last_error() describes what went wrong. */
+AutobaseBytes autobase_generate(const uint8_t *font, size_t len, const AutobaseOptions *options);
+
+/* Release a buffer returned by autobase_generate. */
+void autobase_free(AutobaseBytes bytes);
+
+/* The message describing the last failure on this thread, or NULL. It remains
+ * valid until the next failure on the same thread. */
+const char *autobase_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
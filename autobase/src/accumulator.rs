@@ -0,0 +1,138 @@
+//! Fold measurements into what is needed to write each script's records.
+//!
+//! Measuring every word list at every location of a variable font produces a
+//! measurement per pair, each holding thousands of exemplar words. Rather than
+//! collecting them all before choosing extremes, each measurement is folded into
+//! its script's accumulator as soon as it is produced: its MinMax is chosen
+//! straight away, and of its exemplars only the highest and lowest points reached
+//! by each distinct word are kept, for statistics and clipping warnings. Peak
+//! memory then depends on the vocabulary measured, rather than on the number of
+//! word lists and locations.
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    base::MinMax,
+    config::{Config, ScriptLanguage},
+    error::AutobaseError,
+    measurement::Measurement,
+};
+
+/// The MinMax chosen from one measurement.
+#[derive(Debug, Clone)]
+pub struct MeasuredMinMax {
+    /// The name of the word list which was measured
+    pub name: String,
+    /// The script and language the results are attributed to
+    pub source: ScriptLanguage,
+    /// The location the words were measured at, as user-space axis values
+    pub location: String,
+    /// The optical size band the location falls in, if any
+    pub band: Option<String>,
+    pub minmax: MinMax,
+}
+
+/// What is kept of the measurements of one script.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptAccumulator {
+    /// The MinMax of each measurement folded in
+    pub minmaxes: Vec<MeasuredMinMax>,
+    /// The highest and lowest points reached by each distinct word measured
+    pub words: HashMap<String, (f64, f64)>,
+}
+
+impl ScriptAccumulator {
+    pub fn add(
+        &mut self,
+        measurement: &Measurement,
+        location: &str,
+        band: Option<&str>,
+        config: &Config,
+    ) -> Result<(), AutobaseError> {
+        if let Some(minmax) = MinMax::from_measurement(measurement, config)? {
+            self.minmaxes.push(MeasuredMinMax {
+                name: measurement.name.clone(),
+                source: measurement.source.clone(),
+                location: location.to_string(),
+                band: band.map(str::to_string),
+                minmax,
+            });
+        }
+        for word in measurement.highest.iter().chain(measurement.lowest.iter()) {
+            if let Some((highest, lowest)) = self.words.get_mut(&word.word) {
+                *highest = highest.max(word.highest);
+                *lowest = lowest.min(word.lowest);
+            } else {
+                self.words
+                    .insert(word.word.clone(), (word.highest, word.lowest));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn merge(&mut self, other: ScriptAccumulator) {
+        self.minmaxes.extend(other.minmaxes);
+        for (word, (highest, lowest)) in other.words {
+            self.words
+                .entry(word)
+                .and_modify(|(h, l)| {
+                    *h = h.max(highest);
+                    *l = l.min(lowest);
+                })
+                .or_insert((highest, lowest));
+        }
+    }
+}
+
+/// The accumulators of each script, for each optical size band measured in.
+#[derive(Debug, Default)]
+pub struct Accumulators {
+    scripts: BTreeMap<(Option<String>, String), ScriptAccumulator>,
+}
+
+impl Accumulators {
+    /// Fold in a measurement made at the given location, in the given optical
+    /// size band.
+    pub fn add(
+        &mut self,
+        location: &str,
+        band: Option<&str>,
+        measurement: &Measurement,
+        config: &Config,
+    ) -> Result<(), AutobaseError> {
+        self.scripts
+            .entry((band.map(str::to_string), measurement.source.script.clone()))
+            .or_default()
+            .add(measurement, location, band, config)
+    }
+
+    pub fn merge(mut self, other: Accumulators) -> Self {
+        for (key, accumulator) in other.scripts {
+            self.scripts.entry(key).or_default().merge(accumulator);
+        }
+        self
+    }
+
+    /// Each script's accumulator within an optical size band.
+    pub fn in_band<'a>(
+        &'a self,
+        band: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a ScriptAccumulator)> + 'a {
+        self.scripts
+            .iter()
+            .filter(move |((b, _), _)| b.as_deref() == Some(band))
+            .map(|((_, script), accumulator)| (script.as_str(), accumulator))
+    }
+
+    /// Combine the bands into one accumulator per script, keeping only those
+    /// measured in `band` if one is given.
+    pub fn into_scripts(self, band: Option<&str>) -> BTreeMap<String, ScriptAccumulator> {
+        let mut scripts: BTreeMap<String, ScriptAccumulator> = BTreeMap::new();
+        for ((b, script), accumulator) in self.scripts {
+            if band.is_some_and(|band| b.as_deref() != Some(band)) {
+                continue;
+            }
+            scripts.entry(script).or_default().merge(accumulator);
+        }
+        scripts
+    }
+}
@@ -1,23 +1,85 @@
 //! Abstract the BASE table into a manageable structure.
 //!
 //! Handles both reading and writing binary BASE table data, and exporting to AFDKO feature syntax.
+//! The types also serialize with serde, with tags written as strings, so that
+//! computed tables can be stored, sent elsewhere and compared.
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 use itertools::Itertools;
-use skrifa::{FontRef, Tag};
+use serde::{Deserialize, Serialize};
+use skrifa::{
+    raw::{ReadError, TableProvider},
+    FontRef, Tag,
+};
 use write_fonts::{tables::base as write_base, FontBuilder};
 
-use crate::error::AutobaseError;
+use crate::{
+    changelog::{self, Change, Severity},
+    cjk::{is_cjk_script, CjkMetrics, DEFAULT_SQUARE_TOLERANCE},
+    error::AutobaseError,
+    font_metrics::{font_minmax, MetricSource},
+    utils::iso15924_to_opentype,
+};
+
+/// The baseline tags registered in the OpenType specification.
+pub const REGISTERED_BASELINES: [Tag; 7] = [
+    Tag::new(b"hang"),
+    Tag::new(b"icfb"),
+    Tag::new(b"icft"),
+    Tag::new(b"ideo"),
+    Tag::new(b"idtp"),
+    Tag::new(b"math"),
+    Tag::new(b"romn"),
+];
+
+/// How far, in font units, a MinMax value may lie beyond another and still be
+/// considered close enough to it.
+///
+/// Clipping at the top and at the bottom of a line doesn't cost the same, so
+/// maxima and minima each have their own tolerance.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tolerance {
+    pub max: u16,
+    pub min: u16,
+}
+
+impl Tolerance {
+    /// The same tolerance for maxima and minima.
+    pub fn uniform(tolerance: u16) -> Self {
+        Self {
+            max: tolerance,
+            min: tolerance,
+        }
+    }
+
+    /// The larger of the two, for values such as baselines which are neither a
+    /// maximum nor a minimum.
+    pub fn loosest(&self) -> u16 {
+        self.max.max(self.min)
+    }
+}
+
+impl std::fmt::Display for Tolerance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.max == self.min {
+            write!(f, "{}", self.max)
+        } else {
+            write!(f, "{} (max) / {} (min)", self.max, self.min)
+        }
+    }
+}
 
 /// A MinMax represents the highest and lowest points of a set of glyphs, along with
 /// the word that produced each extreme. This is useful for debugging and for
 /// understanding why a particular BASE table was generated.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MinMax {
     pub highest: Option<i16>,
+    #[serde(default)]
     pub highest_word: String,
     pub lowest: Option<i16>,
+    #[serde(default)]
     pub lowest_word: String,
 }
 
@@ -50,16 +112,19 @@ impl MinMax {
         })
     }
 
-    pub fn merge(&mut self, other: &MinMax, tolerance: Option<u16>) {
-        let tolerance = tolerance.unwrap_or(0);
+    pub fn merge(&mut self, other: &MinMax, tolerance: Tolerance) {
         if let Some(other_high) = other.highest {
-            if self.highest.is_none() || self.highest.unwrap() < other_high - tolerance as i16 {
+            if self.highest.is_none()
+                || self.highest.unwrap() < other_high.saturating_sub_unsigned(tolerance.max)
+            {
                 self.highest = Some(other_high);
                 self.highest_word = other.highest_word.clone();
             }
         }
         if let Some(other_low) = other.lowest {
-            if self.lowest.is_none() || self.lowest.unwrap() > other_low + tolerance as i16 {
+            if self.lowest.is_none()
+                || self.lowest.unwrap() > other_low.saturating_add_unsigned(tolerance.min)
+            {
                 self.lowest = Some(other_low);
                 self.lowest_word = other.lowest_word.clone();
             }
@@ -94,6 +159,24 @@ impl MinMax {
         new
     }
 
+    /// Clamp the values to within the given limits; sides without a limit are left alone.
+    pub fn clamped(&self, limits: &MinMax) -> MinMax {
+        let mut new = self.clone();
+        if let (Some(high), Some(limit_high)) = (new.highest, limits.highest) {
+            if high > limit_high {
+                new.highest = Some(limit_high);
+                new.highest_word = format!("<clamped from {}>", self.highest_word);
+            }
+        }
+        if let (Some(low), Some(limit_low)) = (new.lowest, limits.lowest) {
+            if low < limit_low {
+                new.lowest = Some(limit_low);
+                new.lowest_word = format!("<clamped from {}>", self.lowest_word);
+            }
+        }
+        new
+    }
+
     pub fn with_nulls_replaced(&self, defaults: &MinMax) -> MinMax {
         let mut new = self.clone();
         if new.highest.is_none() {
@@ -107,13 +190,13 @@ impl MinMax {
         new
     }
 
-    pub fn extend(&self, extend_by: u16) -> MinMax {
+    pub fn extend(&self, extend_by: Tolerance) -> MinMax {
         let mut new = self.clone();
         if let Some(high) = self.highest {
-            new.highest = Some(high + extend_by as i16);
+            new.highest = Some(high.saturating_add_unsigned(extend_by.max));
         }
         if let Some(low) = self.lowest {
-            new.lowest = Some(low - extend_by as i16);
+            new.lowest = Some(low.saturating_sub_unsigned(extend_by.min));
         }
         new
     }
@@ -138,21 +221,26 @@ impl std::fmt::Display for MinMax {
 /// A BaseScript represents the BASE table data for a particular script, including
 /// its default baseline, any other baselines, and MinMax data for the script as a
 /// whole and for any languages within the script.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BaseScript {
     /// The script tag, e.g. 'hani'
     ///
     /// Note that this is an OpenType script tag, not a ISO 15924 code.
+    #[serde(with = "tags::tag")]
     pub script: Tag,
     /// The default baseline tag, e.g. 'romn'
+    #[serde(default, with = "tags::option")]
     pub default_baseline: Option<Tag>,
     /// A map of baseline tags to their y-coordinates
+    #[serde(default, with = "tags::map")]
     pub baselines: BTreeMap<Tag, i16>,
     /// The default MinMax for the script
+    #[serde(default)]
     pub default_minmax: Option<MinMax>,
     /// A map of language tags to their MinMax values
     ///
     /// The language tag is a 4-character OpenType language tag.
+    #[serde(default, with = "tags::map")]
     pub languages: BTreeMap<Tag, MinMax>,
 }
 
@@ -167,6 +255,20 @@ impl BaseScript {
         }
     }
 
+    /// The MinMax which applies to a language of the script: the language's own
+    /// record if it has one, and otherwise the script's default MinMax. A
+    /// language of `None` asks for the default MinMax.
+    pub fn min_max(&self, language: Option<Tag>) -> Option<&MinMax> {
+        language
+            .and_then(|language| self.languages.get(&language))
+            .or(self.default_minmax.as_ref())
+    }
+
+    /// The coordinate of a baseline, if the script has one.
+    pub fn baseline(&self, baseline: Tag) -> Option<i16> {
+        self.baselines.get(&baseline).copied()
+    }
+
     /// Convert to a Skrifa BaseScriptRecord representation for writing to a font.
     pub fn to_skrifa(
         &self,
@@ -210,8 +312,19 @@ impl BaseScript {
         ))
     }
 
-    pub fn simplify(&mut self, tolerance: Option<u16>) {
-        let tolerance = tolerance.unwrap_or(0);
+    /// The baselines in the table's tag list for which this script has no value.
+    ///
+    /// When written, these are filled with a coordinate of 0, which cannot be
+    /// distinguished from a real measurement at 0.
+    pub fn filled_baselines(&self, baseline_tags: &[Tag]) -> Vec<Tag> {
+        baseline_tags
+            .iter()
+            .filter(|tag| !self.baselines.contains_key(*tag))
+            .copied()
+            .collect()
+    }
+
+    pub fn simplify(&mut self, tolerance: Tolerance) {
         if let Some(script_default) = &self.default_minmax {
             // First, remove entries that are close to the script default
             for (lang, v) in self.languages.iter_mut() {
@@ -233,8 +346,19 @@ impl BaseScript {
         }
     }
 
-    pub fn merge(&self, other: &BaseScript, tolerance: Option<u16>) -> Self {
+    /// Merge another font's record for the same script into this one.
+    ///
+    /// This record's baselines win; the other's only fill in those missing here.
+    /// MinMax values are widened to the other's where they lie further out by
+    /// more than the tolerance.
+    pub fn merge(&self, other: &BaseScript, tolerance: Tolerance) -> Self {
         let mut merged = self.clone();
+        if merged.default_baseline.is_none() {
+            merged.default_baseline = other.default_baseline;
+        }
+        for (tag, value) in &other.baselines {
+            merged.baselines.entry(*tag).or_insert(*value);
+        }
         if let Some(other_def) = &other.default_minmax {
             if let Some(merged_def) = &mut merged.default_minmax {
                 merged_def.merge(other_def, tolerance);
@@ -254,41 +378,100 @@ impl BaseScript {
 }
 
 /// A BaseTable represents the entire BASE table, with horizontal and vertical axes.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct BaseTable {
     /// The horizontal axis BaseScript records
+    #[serde(default)]
     pub horizontal: Vec<BaseScript>,
     /// The vertical axis BaseScript records
+    #[serde(default)]
     pub vertical: Vec<BaseScript>,
 }
 
 impl BaseTable {
-    /// Convert to a Skrifa Base representation for writing to a font.
-    pub fn to_skrifa(&self) -> Result<write_base::Base, AutobaseError> {
-        let mut baseline_tags: BTreeMap<Tag, ()> = BTreeMap::new();
-        for script in self.horizontal.iter().chain(self.vertical.iter()) {
+    /// The baseline tags written to an axis's tag list: every baseline of each of
+    /// its scripts which has baseline values.
+    ///
+    /// Each axis has its own list, so that the horizontal `hang` and `math`
+    /// baselines aren't written as 0 into every vertical record.
+    fn baseline_tags(scripts: &[BaseScript]) -> Vec<Tag> {
+        let mut baseline_tags: BTreeSet<Tag> = BTreeSet::new();
+        for script in scripts {
             if let Some(def) = script.default_baseline {
-                baseline_tags.insert(def, ());
+                baseline_tags.insert(def);
+                baseline_tags.extend(script.baselines.keys().copied());
+            }
+        }
+        baseline_tags.into_iter().collect()
+    }
+
+    /// The table as it reads back once written: each script with baseline values
+    /// has a value for every baseline in its axis's tag list, 0 where it had none.
+    pub fn as_written(&self) -> BaseTable {
+        let mut table = self.clone();
+        for axis in [&mut table.horizontal, &mut table.vertical] {
+            let baseline_tags = Self::baseline_tags(axis);
+            for script in axis.iter_mut() {
+                if script.default_baseline.is_none() {
+                    continue;
+                }
+                for tag in script.filled_baselines(&baseline_tags) {
+                    script.baselines.insert(tag, 0);
+                }
+            }
+        }
+        table
+    }
+
+    /// Warn about each baseline which a script with baseline values has no value
+    /// for, and which will be written as 0.
+    pub fn warn_filled_baselines(&self) {
+        for axis in [&self.horizontal, &self.vertical] {
+            let baseline_tags = Self::baseline_tags(axis);
+            for script in axis.iter() {
+                if script.default_baseline.is_none() {
+                    continue;
+                }
+                for tag in script.filled_baselines(&baseline_tags) {
+                    if REGISTERED_BASELINES.contains(&tag) {
+                        log::warn!(
+                            "Script {} has no value for baseline {}; writing 0",
+                            script.script,
+                            tag
+                        );
+                    } else {
+                        log::debug!(
+                            "Script {} has no value for baseline {}; writing 0",
+                            script.script,
+                            tag
+                        );
+                    }
+                }
             }
         }
-        let baseline_tags: Vec<Tag> = baseline_tags.into_keys().collect();
+    }
+
+    /// Convert to a Skrifa Base representation for writing to a font.
+    pub fn to_skrifa(&self) -> Result<write_base::Base, AutobaseError> {
+        let horizontal_tags = Self::baseline_tags(&self.horizontal);
+        let vertical_tags = Self::baseline_tags(&self.vertical);
 
         let mut horizontal_scripts: Vec<write_base::BaseScriptRecord> = self
             .horizontal
             .iter()
-            .map(|s| s.to_skrifa(&baseline_tags))
+            .map(|s| s.to_skrifa(&horizontal_tags))
             .collect::<Result<Vec<_>, _>>()?;
         let mut vertical_scripts: Vec<write_base::BaseScriptRecord> = self
             .vertical
             .iter()
-            .map(|s| s.to_skrifa(&baseline_tags))
+            .map(|s| s.to_skrifa(&vertical_tags))
             .collect::<Result<Vec<_>, _>>()?;
         horizontal_scripts.sort_by_key(|r| r.base_script_tag);
         vertical_scripts.sort_by_key(|r| r.base_script_tag);
 
         let horizontal_axis = if !horizontal_scripts.is_empty() {
             Some(write_base::Axis::new(
-                Some(write_base::BaseTagList::new(baseline_tags.clone())),
+                Some(write_base::BaseTagList::new(horizontal_tags)),
                 write_base::BaseScriptList::new(horizontal_scripts),
             ))
         } else {
@@ -296,7 +479,7 @@ impl BaseTable {
         };
         let vertical_axis = if !vertical_scripts.is_empty() {
             Some(write_base::Axis::new(
-                Some(write_base::BaseTagList::new(baseline_tags)),
+                Some(write_base::BaseTagList::new(vertical_tags)),
                 write_base::BaseScriptList::new(vertical_scripts),
             ))
         } else {
@@ -431,7 +614,7 @@ impl BaseTable {
                     .iter()
                     .flatten()
                     .enumerate()
-                    .map(|(i, coord)| (base_tag_list[i], coord.coordinate()))
+                    .filter_map(|(i, coord)| Some((*base_tag_list.get(i)?, coord.coordinate())))
                     .collect();
                 default_baseline_index = base_values.default_baseline_index() as usize;
             }
@@ -446,6 +629,67 @@ impl BaseTable {
         Ok(base_scripts)
     }
 
+    /// A table of every script and language record, with its MinMax values and
+    /// baselines, for people to read. The default baseline is marked with `*`.
+    pub fn summary(&self) -> String {
+        let value = |v: Option<i16>| v.map_or("-".to_string(), |v| v.to_string());
+        let mut rows = vec![["script", "lang", "min", "max", "baselines"].map(String::from)];
+        for (axis, scripts) in [("", &self.horizontal), (" (vert)", &self.vertical)] {
+            for script in scripts.iter() {
+                let baselines = script
+                    .baselines
+                    .iter()
+                    .map(|(tag, y)| {
+                        let default = if script.default_baseline == Some(*tag) {
+                            "*"
+                        } else {
+                            ""
+                        };
+                        format!("{}{} {}", tag, default, y)
+                    })
+                    .join(", ");
+                let default = script.default_minmax.as_ref();
+                rows.push([
+                    format!("{}{}", script.script, axis),
+                    "dflt".to_string(),
+                    value(default.and_then(|m| m.lowest)),
+                    value(default.and_then(|m| m.highest)),
+                    baselines,
+                ]);
+                for (language, minmax) in script.languages.iter() {
+                    rows.push([
+                        String::new(),
+                        language.to_string().trim_end().to_string(),
+                        value(minmax.lowest),
+                        value(minmax.highest),
+                        String::new(),
+                    ]);
+                }
+            }
+        }
+        let mut widths = [0; 5];
+        for row in rows.iter() {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut summary = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            let line = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .join(" | ");
+            summary.push_str(line.trim_end());
+            summary.push('\n');
+            if i == 0 {
+                summary.push_str(&widths.iter().map(|width| "-".repeat(*width)).join("-+-"));
+                summary.push('\n');
+            }
+        }
+        summary
+    }
+
     /// Create a BaseTable from a Skrifa Base representation read from a font.
     pub fn from_skrifa(base: &skrifa::raw::tables::base::Base) -> Result<Self, AutobaseError> {
         Ok(Self {
@@ -460,6 +704,21 @@ impl BaseTable {
         })
     }
 
+    /// Read a font's BASE table, or `None` if it has none. A BASE table which
+    /// can't be read is an error, rather than being treated as missing.
+    pub fn from_font(font: &FontRef) -> Result<Option<Self>, AutobaseError> {
+        match font.base() {
+            Ok(base) => Self::from_skrifa(&base).map(Some),
+            Err(ReadError::TableIsMissing(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The table as JSON, in the same form as it is stored in UFO sources.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
     /// Create a new BASE table
     pub fn new(horizontal: Vec<BaseScript>, vertical: Vec<BaseScript>) -> Self {
         Self {
@@ -468,6 +727,47 @@ impl BaseTable {
         }
     }
 
+    /// Build a minimal, conservative BASE table from the font's vertical metrics alone.
+    ///
+    /// Each of the given scripts (as ISO 15924 codes) gets a default MinMax of the
+    /// OS/2 typo ascender and descender (or, if the font has no OS/2 table, the
+    /// next best metrics), and if any of them are CJK scripts, an ideographic
+    /// em-box is added with its bottom edge at the descender. No
+    /// word lists are measured, so this is fast, and makes a useful baseline to
+    /// compare the full analysis against.
+    pub fn from_font_metrics(
+        font: &FontRef,
+        scripts: &HashSet<&str>,
+    ) -> Result<Self, AutobaseError> {
+        let upem = font.head()?.units_per_em() as f32;
+        let font_minmax = font_minmax(font, MetricSource::Typo)?;
+        let mut base = BaseTable::new(
+            scripts
+                .iter()
+                .flat_map(|script| iso15924_to_opentype(script))
+                .map(|ot_script| BaseScript {
+                    default_minmax: Some(font_minmax.clone()),
+                    ..BaseScript::new(ot_script)
+                })
+                .collect(),
+            vec![],
+        );
+        if scripts.iter().any(|s| is_cjk_script(s)) {
+            let ot_scripts = scripts
+                .iter()
+                .flat_map(|script| iso15924_to_opentype(script))
+                .collect::<Vec<_>>();
+            let descender = font_minmax.lowest.unwrap_or_default() as f32;
+            CjkMetrics::from_em_box(descender, upem).insert_into_base(
+                upem,
+                DEFAULT_SQUARE_TOLERANCE,
+                &ot_scripts,
+                &mut base,
+            )?;
+        }
+        Ok(base)
+    }
+
     /// Add the BASE table to a binary font, returning the new binary data.
     pub fn add_to_binary(&self, font: &FontRef) -> Result<Vec<u8>, AutobaseError> {
         let mut new_font = FontBuilder::new();
@@ -477,7 +777,7 @@ impl BaseTable {
         Ok(binary)
     }
 
-    pub fn merge(&mut self, other: &BaseTable, tolerance: Option<u16>) {
+    pub fn merge(&mut self, other: &BaseTable, tolerance: Tolerance) {
         for (my_axis, their_axis) in [
             (&mut self.horizontal, &other.horizontal),
             (&mut self.vertical, &other.vertical),
@@ -485,8 +785,8 @@ impl BaseTable {
             // For each script in other, see if we have it already
             for script in their_axis.iter() {
                 // Find a matching script in self
-                if let Some(my_script) = my_axis.iter().find(|s| s.script == script.script) {
-                    my_script.merge(script, tolerance);
+                if let Some(my_script) = my_axis.iter_mut().find(|s| s.script == script.script) {
+                    *my_script = my_script.merge(script, tolerance);
                 } else {
                     my_axis.push(script.clone());
                 }
@@ -494,9 +794,413 @@ impl BaseTable {
         }
     }
 
-    pub fn simplify(&mut self, tolerance: Option<u16>) {
+    /// Whether the horizontal axis has a record for the given OpenType script tag.
+    ///
+    /// If `with_minmax` is true, the record must also have a default MinMax.
+    pub fn has_script(&self, script: Tag, with_minmax: bool) -> bool {
+        self.script(script)
+            .is_some_and(|s| !with_minmax || s.default_minmax.is_some())
+    }
+
+    /// The horizontal record of a script.
+    pub fn script(&self, script: Tag) -> Option<&BaseScript> {
+        self.horizontal.iter().find(|s| s.script == script)
+    }
+
+    /// The vertical record of a script.
+    pub fn vertical_script(&self, script: Tag) -> Option<&BaseScript> {
+        self.vertical.iter().find(|s| s.script == script)
+    }
+
+    /// The scripts with horizontal records, in the order they are stored.
+    pub fn scripts(&self) -> impl Iterator<Item = Tag> + '_ {
+        self.horizontal.iter().map(|s| s.script)
+    }
+
+    /// The horizontal MinMax which applies to a language of a script, as
+    /// [`BaseScript::min_max`].
+    pub fn min_max(&self, script: Tag, language: Option<Tag>) -> Option<&MinMax> {
+        self.script(script)?.min_max(language)
+    }
+
+    /// The horizontal coordinate of one of a script's baselines.
+    pub fn baseline(&self, script: Tag, baseline: Tag) -> Option<i16> {
+        self.script(script)?.baseline(baseline)
+    }
+
+    /// The differences between this table and another which go beyond `tolerance`
+    /// font units, as changes from this table to the other.
+    ///
+    /// The tables are approximately equal if the list is empty. Values which moved
+    /// by no more than the tolerance aren't listed (maxima are compared with
+    /// `tolerance.max`, minima with `tolerance.min` and baselines with the larger
+    /// of the two), but records, baselines and MinMax sides present in only one of
+    /// the tables always are, as is a change of default baseline. The words the
+    /// values came from are ignored.
+    pub fn approx_eq(&self, other: &BaseTable, tolerance: Tolerance) -> Vec<Change> {
+        changelog::changes(self, other)
+            .into_iter()
+            .filter(|change| change.severity(tolerance) != Severity::WithinTolerance)
+            .collect()
+    }
+
+    /// Fill in anything missing from this table with the values from another table.
+    ///
+    /// Unlike [`BaseTable::merge`], existing values are never changed: scripts
+    /// which are not present are added, scripts without baselines gain the other
+    /// table's baselines, and scripts without a default MinMax gain the other
+    /// table's MinMax and language records.
+    pub fn fill_missing(&mut self, other: &BaseTable) {
+        for (my_axis, their_axis) in [
+            (&mut self.horizontal, &other.horizontal),
+            (&mut self.vertical, &other.vertical),
+        ] {
+            for script in their_axis.iter() {
+                let Some(my_script) = my_axis.iter_mut().find(|s| s.script == script.script) else {
+                    log::info!("Adding BASE record for script {}", script.script);
+                    my_axis.push(script.clone());
+                    continue;
+                };
+                if my_script.default_baseline.is_none() && script.default_baseline.is_some() {
+                    log::info!("Adding baselines for script {}", script.script);
+                    my_script.default_baseline = script.default_baseline;
+                    my_script.baselines = script.baselines.clone();
+                }
+                if my_script.default_minmax.is_none() && script.default_minmax.is_some() {
+                    log::info!("Adding MinMax records for script {}", script.script);
+                    my_script.default_minmax = script.default_minmax.clone();
+                    for (lang, mm) in script.languages.iter() {
+                        my_script.languages.entry(*lang).or_insert(mm.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replace the records of the given scripts with those in another table.
+    ///
+    /// Records for these scripts which the other table lacks are removed, and
+    /// the other table's `DFLT` records, which may have been derived from them,
+    /// replace this table's.
+    pub fn replace_scripts(&mut self, scripts: &[Tag], other: &BaseTable) {
+        let dflt = Tag::new(b"DFLT");
+        let replaced = |axis: &[BaseScript], script: Tag| {
+            scripts.contains(&script) || (script == dflt && axis.iter().any(|r| r.script == dflt))
+        };
+        for (my_axis, their_axis) in [
+            (&mut self.horizontal, &other.horizontal),
+            (&mut self.vertical, &other.vertical),
+        ] {
+            my_axis.retain(|record| !replaced(their_axis, record.script));
+            my_axis.extend(
+                their_axis
+                    .iter()
+                    .filter(|record| scripts.contains(&record.script) || record.script == dflt)
+                    .cloned(),
+            );
+            my_axis.sort_by_key(|record| record.script);
+        }
+    }
+
+    /// Add a DFLT script record to each axis, seeded from the first script in the
+    /// priority list which the axis has a record for.
+    ///
+    /// The DFLT record takes that script's baselines and default MinMax, but not its
+    /// language records. Axes which already have a DFLT record are left alone.
+    pub fn add_dflt(&mut self, priority: &[Tag]) {
+        let dflt = Tag::new(b"DFLT");
+        for (axis, scripts) in [
+            ("HorizAxis", &mut self.horizontal),
+            ("VertAxis", &mut self.vertical),
+        ] {
+            if scripts.is_empty() || scripts.iter().any(|s| s.script == dflt) {
+                continue;
+            }
+            let Some(source) = priority
+                .iter()
+                .find_map(|tag| scripts.iter().find(|s| s.script == *tag))
+            else {
+                log::warn!(
+                    "Not adding a DFLT record to {}: none of the scripts {} are present",
+                    axis,
+                    priority.iter().map(|t| t.to_string()).join(", ")
+                );
+                continue;
+            };
+            log::info!("Seeding {} DFLT record from script {}", axis, source.script);
+            let record = BaseScript {
+                script: dflt,
+                languages: BTreeMap::new(),
+                ..source.clone()
+            };
+            scripts.push(record);
+        }
+    }
+
+    /// Stop emitting BaseValues for scripts which have a default baseline but no
+    /// measured baselines at all, rather than writing a set of zeros for them.
+    pub fn omit_unmeasured_baselines(&mut self) {
+        for script in self.horizontal.iter_mut().chain(self.vertical.iter_mut()) {
+            if script.default_baseline.is_some() && script.baselines.is_empty() {
+                log::info!(
+                    "Not emitting baselines for script {}, which has no measured baselines",
+                    script.script
+                );
+                script.default_baseline = None;
+            }
+        }
+    }
+
+    pub fn simplify(&mut self, tolerance: Tolerance) {
         for script in self.horizontal.iter_mut().chain(self.vertical.iter_mut()) {
             script.simplify(tolerance);
         }
     }
 }
+
+/// Serialize tags as strings, without the spaces which pad short tags.
+mod tags {
+    use std::collections::BTreeMap;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+    use skrifa::Tag;
+
+    fn to_string(tag: &Tag) -> String {
+        tag.to_string().trim_end().to_string()
+    }
+
+    fn from_string<E: Error>(text: &str) -> Result<Tag, E> {
+        Tag::new_checked(text.as_bytes())
+            .map_err(|_| E::custom(format!("invalid OpenType tag {:?}", text)))
+    }
+
+    pub mod tag {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(tag: &Tag, serializer: S) -> Result<S::Ok, S::Error> {
+            to_string(tag).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Tag, D::Error> {
+            from_string(&String::deserialize(deserializer)?)
+        }
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            tag: &Option<Tag>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            tag.as_ref().map(to_string).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Tag>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|text| from_string(&text))
+                .transpose()
+        }
+    }
+
+    pub mod map {
+        use super::*;
+
+        pub fn serialize<S: Serializer, V: Serialize>(
+            map: &BTreeMap<Tag, V>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(map.iter().map(|(tag, value)| (to_string(tag), value)))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<BTreeMap<Tag, V>, D::Error> {
+            BTreeMap::<String, V>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(text, value)| Ok((from_string(&text)?, value)))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font_with_base(base: Option<&[u8]>) -> Vec<u8> {
+        let mut builder = FontBuilder::new();
+        builder.add_raw(Tag::new(b"maxp"), vec![0, 0, 0x50, 0, 0, 1]);
+        if let Some(base) = base {
+            builder.add_raw(Tag::new(b"BASE"), base.to_vec());
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn malformed_base_is_not_missing() {
+        let data = font_with_base(None);
+        let font = FontRef::new(&data).unwrap();
+        assert!(BaseTable::from_font(&font).unwrap().is_none());
+
+        let empty = write_fonts::dump_table(&write_base::Base::default()).unwrap();
+        let data = font_with_base(Some(&empty));
+        let font = FontRef::new(&data).unwrap();
+        assert!(BaseTable::from_font(&font).unwrap().is_some());
+
+        // A horizontal axis offset pointing beyond the end of the table
+        let data = font_with_base(Some(&[0, 1, 0, 0, 0, 0x40, 0, 0]));
+        let font = FontRef::new(&data).unwrap();
+        assert!(BaseTable::from_font(&font).is_err());
+    }
+
+    fn record(script: &[u8; 4], romn: Option<i16>, min_max: Option<(i16, i16)>) -> BaseScript {
+        let mut record = BaseScript::new(Tag::new(script));
+        if let Some(romn) = romn {
+            record.default_baseline = Some(Tag::new(b"romn"));
+            record.baselines.insert(Tag::new(b"romn"), romn);
+        }
+        record.default_minmax = min_max.map(|(low, high)| MinMax::new_min_max(low, high));
+        record
+    }
+
+    fn written(base: &BaseTable) -> BaseTable {
+        use skrifa::raw::{FontData, FontRead};
+        let data = write_fonts::dump_table(&base.to_skrifa().unwrap()).unwrap();
+        let base = skrifa::raw::tables::base::Base::read(FontData::new(&data)).unwrap();
+        BaseTable::from_skrifa(&base).unwrap()
+    }
+
+    #[test]
+    fn each_axis_has_its_own_tag_list() {
+        let tag = |tag: &[u8; 4]| Tag::new(tag);
+        let with_baselines = |script, default, baselines: &[(&[u8; 4], i16)]| {
+            let mut record = BaseScript::new(tag(script));
+            record.default_baseline = Some(tag(default));
+            record.baselines = baselines.iter().map(|(t, v)| (tag(t), *v)).collect();
+            record
+        };
+        let base = BaseTable::new(
+            vec![
+                with_baselines(
+                    b"hani",
+                    b"ideo",
+                    &[(b"ideo", -120), (b"idtp", 880), (b"romn", 0)],
+                ),
+                with_baselines(
+                    b"latn",
+                    b"romn",
+                    &[(b"hang", 700), (b"math", 270), (b"romn", 0)],
+                ),
+            ],
+            vec![with_baselines(
+                b"hani",
+                b"ideo",
+                &[(b"ideo", 0), (b"idtp", 1000), (b"romn", 120)],
+            )],
+        );
+        let read = written(&base);
+        assert_eq!(read.baseline(tag(b"latn"), tag(b"hang")), Some(700));
+        let vertical = read.vertical_script(tag(b"hani")).unwrap();
+        assert_eq!(
+            vertical.baselines.keys().collect::<Vec<_>>(),
+            [&tag(b"ideo"), &tag(b"idtp"), &tag(b"romn")]
+        );
+        // The FEA output lists the same tags on each axis
+        assert!(base
+            .to_fea()
+            .contains(" VertAxis.BaseTagList      ideo idtp romn;"));
+    }
+
+    #[test]
+    fn rewriting_the_same_table_loses_nothing() {
+        let mut latn = record(b"latn", Some(0), None);
+        latn.baselines.insert(Tag::new(b"hang"), 700);
+        let base = BaseTable::new(vec![latn, record(b"grek", Some(0), None)], vec![]);
+        // The Greek record's hang baseline is filled with 0 when written
+        let first = written(&base);
+        assert_eq!(
+            first.baseline(Tag::new(b"grek"), Tag::new(b"hang")),
+            Some(0)
+        );
+        assert!(changelog::losses(&BaseTable::default(), &base).is_empty());
+        assert!(changelog::losses(&first, &base).is_empty());
+        assert!(changelog::losses(&written(&base), &base).is_empty());
+    }
+
+    #[test]
+    fn approx_eq_tolerance_is_inclusive_per_side() {
+        let old = BaseTable::new(vec![record(b"latn", Some(0), Some((-200, 800)))], vec![]);
+        let new = BaseTable::new(vec![record(b"latn", Some(7), Some((-210, 805)))], vec![]);
+        let tolerance = |max, min| Tolerance { max, min };
+        // The maximum moved by 5, the minimum by 10 and the baseline, which is
+        // compared with the larger tolerance, by 7
+        assert!(old.approx_eq(&new, tolerance(5, 10)).is_empty());
+        let changes = old.approx_eq(&new, tolerance(5, 9));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].severity(tolerance(5, 9)), Severity::ValuesDiffer);
+        assert_eq!(old.approx_eq(&new, tolerance(4, 10)).len(), 1);
+        assert_eq!(old.approx_eq(&new, tolerance(6, 6)).len(), 2);
+
+        // A side which is gone is structural, however large the tolerance
+        let mut lost = new.clone();
+        lost.horizontal[0].default_minmax.as_mut().unwrap().lowest = None;
+        let changes = old.approx_eq(&lost, Tolerance::uniform(1000));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].severity(Tolerance::uniform(1000)),
+            Severity::Structural
+        );
+    }
+
+    #[test]
+    fn replace_scripts_removes_scripts_no_longer_measured() {
+        let mut table = BaseTable::new(
+            vec![
+                record(b"DFLT", Some(0), None),
+                record(b"cyrl", None, Some((-250, 850))),
+                record(b"grek", None, Some((-250, 850))),
+                record(b"latn", None, Some((-200, 800))),
+            ],
+            vec![],
+        );
+        let remeasured = BaseTable::new(
+            vec![
+                record(b"DFLT", Some(5), None),
+                record(b"cyrl", None, Some((-400, 1000))),
+                record(b"latn", None, Some((-220, 820))),
+            ],
+            vec![],
+        );
+        let scripts = [Tag::new(b"latn"), Tag::new(b"grek")];
+        table.replace_scripts(&scripts, &remeasured);
+        let tags = table.scripts().collect::<Vec<_>>();
+        assert_eq!(
+            tags,
+            [Tag::new(b"DFLT"), Tag::new(b"cyrl"), Tag::new(b"latn")]
+        );
+        assert_eq!(
+            table.min_max(Tag::new(b"latn"), None).unwrap().highest,
+            Some(820)
+        );
+        // Scripts which weren't stale keep their records
+        assert_eq!(
+            table.min_max(Tag::new(b"cyrl"), None).unwrap().highest,
+            Some(850)
+        );
+        assert_eq!(
+            table.baseline(Tag::new(b"DFLT"), Tag::new(b"romn")),
+            Some(5)
+        );
+
+        // Without a new DFLT record, the old one stays
+        let mut table = BaseTable::new(vec![record(b"DFLT", Some(0), None)], vec![]);
+        table.replace_scripts(&scripts, &BaseTable::default());
+        assert_eq!(
+            table.baseline(Tag::new(b"DFLT"), Tag::new(b"romn")),
+            Some(0)
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! The parts of a BASE table's coordinates which [`BaseTable`](crate::base::BaseTable) leaves out.
+//!
+//! autobase only writes plain design-unit coordinates (format 1), and reads
+//! every coordinate as one. Shipped fonts can also use format 2, whose value
+//! follows a point of a glyph's outline, and format 3, whose value is adjusted
+//! by a device table for hinted sizes or, in variable fonts, by a variation
+//! index into the table's item variation store. These are listed here so that
+//! they can be inspected, and variations resolved at a location.
+use skrifa::{
+    instance::NormalizedCoord,
+    raw::{
+        tables::{
+            base::{Axis, Base, BaseCoord},
+            layout::DeviceOrVariationIndex,
+            variations::DeltaSetIndex,
+        },
+        ReadError,
+    },
+    Tag,
+};
+
+/// How a coordinate is adjusted beyond its design-unit value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Adjustment {
+    /// The value follows a point of a glyph's outline (format 2)
+    GlyphPoint { glyph: u16, point: u16 },
+    /// The value is corrected at hinted sizes by a device table (format 3)
+    Device {
+        start_size: u16,
+        end_size: u16,
+        /// The correction at each size from `start_size` to `end_size`, in pixels
+        deltas: Vec<i8>,
+    },
+    /// The value varies, by the deltas of an item variation store entry (format 3)
+    Variation { outer: u16, inner: u16 },
+}
+
+impl std::fmt::Display for Adjustment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Adjustment::GlyphPoint { glyph, point } => {
+                write!(f, "follows point {} of glyph {}", point, glyph)
+            }
+            Adjustment::Device {
+                start_size,
+                end_size,
+                deltas,
+            } => write!(
+                f,
+                "device table for {}-{} ppem: {}",
+                start_size,
+                end_size,
+                deltas
+                    .iter()
+                    .map(|delta| delta.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Adjustment::Variation { outer, inner } => {
+                write!(f, "variation index {}/{}", outer, inner)
+            }
+        }
+    }
+}
+
+/// A coordinate which is more than a design-unit value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoordDetail {
+    /// `HorizAxis` or `VertAxis`, as in FEA
+    pub axis: &'static str,
+    pub script: Tag,
+    /// The language of a MinMax record, or `None` for the script's default
+    pub language: Option<Tag>,
+    /// What the coordinate is: `min`, `max`, or a baseline tag
+    pub value: String,
+    /// The value in design units, before any adjustment
+    pub coordinate: i16,
+    pub adjustment: Adjustment,
+}
+
+impl CoordDetail {
+    /// The coordinate at a location, given as normalized coordinates: its
+    /// design-unit value plus any variation deltas.
+    pub fn resolve(&self, base: &Base, coords: &[NormalizedCoord]) -> Result<i32, ReadError> {
+        let Adjustment::Variation { outer, inner } = self.adjustment else {
+            return Ok(self.coordinate as i32);
+        };
+        let Some(store) = base.item_var_store().transpose()? else {
+            return Ok(self.coordinate as i32);
+        };
+        let delta = store.compute_delta(DeltaSetIndex { outer, inner }, coords)?;
+        Ok(self.coordinate as i32 + delta)
+    }
+}
+
+impl std::fmt::Display for CoordDetail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}: {}",
+            self.axis,
+            self.script,
+            self.language.unwrap_or(Tag::new(b"dflt")),
+            self.value,
+            self.coordinate,
+            self.adjustment
+        )
+    }
+}
+
+/// Every coordinate in a BASE table which is more than a design-unit value.
+pub fn coord_details(base: &Base) -> Result<Vec<CoordDetail>, ReadError> {
+    let mut details = vec![];
+    for (name, axis) in [
+        ("HorizAxis", base.horiz_axis()),
+        ("VertAxis", base.vert_axis()),
+    ] {
+        if let Some(axis) = axis.transpose()? {
+            axis_details(name, &axis, &mut details)?;
+        }
+    }
+    Ok(details)
+}
+
+fn axis_details(
+    name: &'static str,
+    axis: &Axis,
+    details: &mut Vec<CoordDetail>,
+) -> Result<(), ReadError> {
+    let base_tag_list: Vec<Tag> = axis
+        .base_tag_list()
+        .transpose()?
+        .map(|b| b.baseline_tags().iter().map(|x| x.get()).collect())
+        .unwrap_or_default();
+    let script_list = axis.base_script_list()?;
+    for script_record in script_list.base_script_records() {
+        let script = script_record.base_script_tag();
+        let base_script = script_record.base_script(script_list.offset_data())?;
+        let mut add = |language: Option<Tag>, value: String, coord: BaseCoord| {
+            if let Some(adjustment) = adjustment(&coord)? {
+                details.push(CoordDetail {
+                    axis: name,
+                    script,
+                    language,
+                    value,
+                    coordinate: coord.coordinate(),
+                    adjustment,
+                });
+            }
+            Ok::<_, ReadError>(())
+        };
+        if let Some(base_values) = base_script.base_values().transpose()? {
+            for (i, coord) in base_values.base_coords().iter().enumerate() {
+                let tag = base_tag_list
+                    .get(i)
+                    .map_or(format!("baseline {}", i), |tag| tag.to_string());
+                add(None, tag, coord?)?;
+            }
+        }
+        let mut min_maxes = vec![];
+        if let Some(min_max) = base_script.default_min_max().transpose()? {
+            min_maxes.push((None, min_max));
+        }
+        for langsys in base_script.base_lang_sys_records() {
+            min_maxes.push((
+                Some(langsys.base_lang_sys_tag()),
+                langsys.min_max(base_script.offset_data())?,
+            ));
+        }
+        for (language, min_max) in min_maxes {
+            if let Some(coord) = min_max.min_coord().transpose()? {
+                add(language, "min".to_string(), coord)?;
+            }
+            if let Some(coord) = min_max.max_coord().transpose()? {
+                add(language, "max".to_string(), coord)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn adjustment(coord: &BaseCoord) -> Result<Option<Adjustment>, ReadError> {
+    Ok(match coord {
+        BaseCoord::Format1(_) => None,
+        BaseCoord::Format2(coord) => Some(Adjustment::GlyphPoint {
+            glyph: coord.reference_glyph(),
+            point: coord.base_coord_point(),
+        }),
+        BaseCoord::Format3(coord) => match coord.device().transpose()? {
+            None => None,
+            Some(DeviceOrVariationIndex::Device(device)) => Some(Adjustment::Device {
+                start_size: device.start_size(),
+                end_size: device.end_size(),
+                deltas: device.iter().collect(),
+            }),
+            Some(DeviceOrVariationIndex::VariationIndex(index)) => Some(Adjustment::Variation {
+                outer: index.delta_set_outer_index(),
+                inner: index.delta_set_inner_index(),
+            }),
+        },
+    })
+}
@@ -1,79 +1,88 @@
 //! This module determines base table BaseScriptRecords; that is, script-specific vertical metrics.
 //!
-//! It does this by examining the reports generated by fontheight, and finding the highest
-//! and lowest glyphs in each script. If the user has specified particular languages to
+//! It does this by examining the measurements generated by fontheight, and finding the highest
+//! and lowest glyphs in each script. Each measurement's MinMax is chosen as it is folded into
+//! its script's accumulator (see `accumulator`), and the records are built from those. If the user has specified particular languages to
 //! separate out, we do so. We also respect any manual overrides specified in the config file.
 use crate::{
-    base::{BaseScript, MinMax},
-    config::{Config, ScriptLanguage},
-    utils::{iso15924_to_opentype, iso639_to_opentype},
+    accumulator::MeasuredMinMax,
+    base::{BaseScript, MinMax, Tolerance},
+    config::{Aggregation, Config, Override},
+    error::AutobaseError,
+    frequency::weighted_extreme,
+    measurement::{Measurement, WordExtremes},
+    stats::mean_and_standard_deviation,
+    utils::to_font_unit,
 };
-use fontheight::{Report, WordList};
+use skrifa::Tag;
 use std::collections::{BTreeMap, HashMap};
 
 impl MinMax {
-    fn from_report(r: Report, config: &Config) -> Option<Self> {
-        let script_and_language = wordlist_script_and_language(r.word_list);
-        let override_ = config.r#override.get(&script_and_language);
+    pub(crate) fn from_measurement(
+        m: &Measurement,
+        config: &Config,
+    ) -> Result<Option<Self>, AutobaseError> {
+        // Overrides for a particular language system (e.g. `zh-Hant`) are applied
+        // to its record instead, as the measurement is shared by all of them
+        let override_ = config.r#override.get(&m.source);
         // If there are no exemplars and no overrides, we can't produce a MinMax
-        if r.exemplars.is_empty() && override_.is_none() {
-            return None;
+        if m.is_empty() && override_.is_none() {
+            return Ok(None);
         }
 
-        let (mut highest, mut highest_word) = if r.exemplars.is_empty() {
-            (None, "<none>".to_string())
+        let (highest, lowest) = if m.exact {
+            (
+                m.highest.first().map(|w| (w.highest, w.word.clone())),
+                m.lowest.first().map(|w| (w.lowest, w.word.clone())),
+            )
         } else {
-            let h = r
-                .exemplars
-                .highest()
-                .iter()
-                .find(|w| {
-                    !config
-                        .exclusions
-                        .iter()
-                        .any(|excluded_pattern| w.word.contains(excluded_pattern))
-                })
-                .unwrap();
-            (Some(h.extremes.highest() as i16), h.word.to_string())
+            let aggregation = config.aggregation_for(&m.source.script);
+            (
+                choose_extreme(&m.highest, |w| w.highest, aggregation, config),
+                choose_extreme(&m.lowest, |w| w.lowest, aggregation, config),
+            )
         };
-        let (mut lowest, mut lowest_word) = if r.exemplars.is_empty() {
-            (None, "<none>".to_string())
-        } else {
-            let l = r
-                .exemplars
-                .lowest()
-                .iter()
-                .find(|w| {
-                    !config
-                        .exclusions
-                        .iter()
-                        .any(|excluded_pattern| w.word.contains(excluded_pattern))
-                })
-                .unwrap();
-            (Some(l.extremes.lowest() as i16), l.word.to_string())
+        let (highest, highest_word) = match highest {
+            Some((h, word)) => (
+                Some(to_font_unit(h, format_args!("Highest point of {}", word))?),
+                word,
+            ),
+            None => (None, "<none>".to_string()),
         };
-        if let Some(ov) = override_ {
-            if let Some(max) = ov.max {
-                highest = Some(max);
-                highest_word = "<override>".to_string();
-            }
-            if let Some(min) = ov.min {
-                lowest = Some(min);
-                lowest_word = "<override>".to_string();
-            }
-        }
-        if highest.is_none() && lowest.is_none() {
-            return None;
-        }
-
-        Some(MinMax {
+        let (lowest, lowest_word) = match lowest {
+            Some((l, word)) => (
+                Some(to_font_unit(l, format_args!("Lowest point of {}", word))?),
+                word,
+            ),
+            None => (None, "<none>".to_string()),
+        };
+        let mut minmax = MinMax {
             highest,
             highest_word,
             lowest,
             lowest_word,
-        })
+        };
+        if let Some(ov) = override_ {
+            minmax.apply_override(ov);
+        }
+        if minmax.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(minmax))
     }
-    fn aggregate(minmaxes: &[MinMax], tolerance: Option<u16>) -> Option<Self> {
+
+    /// Replace the values given by an override.
+    fn apply_override(&mut self, ov: &Override) {
+        if let Some(max) = ov.max {
+            self.highest = Some(max);
+            self.highest_word = "<override>".to_string();
+        }
+        if let Some(min) = ov.min {
+            self.lowest = Some(min);
+            self.lowest_word = "<override>".to_string();
+        }
+    }
+    fn aggregate(minmaxes: &[MinMax], tolerance: Tolerance) -> Option<Self> {
         if minmaxes.is_empty() {
             return None;
         }
@@ -85,100 +94,287 @@ impl MinMax {
     }
 }
 
-fn wordlist_script_and_language(w: &WordList) -> ScriptLanguage {
-    if let Some(lang) = w.language() {
-        ScriptLanguage {
-            script: w.script().unwrap_or("Zzzz").to_string(),
-            language: Some(lang.to_string()),
+/// Choose the value of an extreme, and the word it came from, from exemplars
+/// ordered most extreme first.
+///
+/// `value` selects the extreme (highest or lowest) being chosen.
+fn choose_extreme(
+    exemplars: &[WordExtremes],
+    value: fn(&WordExtremes) -> f64,
+    aggregation: Aggregation,
+    config: &Config,
+) -> Option<(f64, String)> {
+    let mut candidates: Vec<&WordExtremes> = exemplars
+        .iter()
+        .filter(|w| {
+            !config
+                .exclusions
+                .iter()
+                .any(|excluded_pattern| w.word.contains(excluded_pattern))
+        })
+        .collect();
+    if let Some(stddevs) = config.outlier_stddevs {
+        candidates = without_outliers(candidates, value, stddevs);
+    }
+    let chosen = match (aggregation, config.frequency_cutoff) {
+        (Aggregation::Absolute, Some(cutoff)) => weighted_extreme(candidates.into_iter(), cutoff),
+        (Aggregation::Absolute, None) => candidates.first().copied(),
+        (Aggregation::Percentile(percentile), cutoff) => {
+            percentile_extreme(&candidates, percentile, cutoff)
+        }
+        (Aggregation::TrimmedMean(words), _) => return trimmed_mean(&candidates, value, words),
+    };
+    chosen.map(|w| (value(w), w.word.to_string()))
+}
+
+/// The mean value of the most extreme `words` exemplars.
+fn trimmed_mean(
+    exemplars: &[&WordExtremes],
+    value: fn(&WordExtremes) -> f64,
+    words: usize,
+) -> Option<(f64, String)> {
+    let top = &exemplars[..words.min(exemplars.len())];
+    let first = top.first()?;
+    let mean = top.iter().map(|w| value(w)).sum::<f64>() / top.len() as f64;
+    Some((
+        mean.round(),
+        format!("<mean of {} words from {}>", top.len(), first.word),
+    ))
+}
+
+/// Discard exemplars whose value lies more than `stddevs` standard deviations from the mean.
+fn without_outliers(
+    exemplars: Vec<&WordExtremes>,
+    value: fn(&WordExtremes) -> f64,
+    stddevs: f64,
+) -> Vec<&WordExtremes> {
+    let values = exemplars.iter().map(|w| value(w)).collect::<Vec<_>>();
+    let Some((mean, stddev)) = mean_and_standard_deviation(&values) else {
+        return exemplars;
+    };
+    let (kept, outliers): (Vec<_>, Vec<_>) = exemplars
+        .into_iter()
+        .partition(|w| (value(w) - mean).abs() <= stddevs * stddev);
+    for outlier in outliers.iter() {
+        log::debug!(
+            "  Discarding outlier {} ({:.0}, mean {:.0}, standard deviation {:.0})",
+            outlier.word,
+            value(outlier),
+            mean,
+            stddev
+        );
+    }
+    kept
+}
+
+/// Choose the exemplar at the given percentile of extremeness.
+///
+/// At the 99.5th percentile, the most extreme 0.5% of words are passed over. With
+/// a frequency cutoff, the words passed over must also make up no more than the
+/// cutoff's share of word occurrences, so common words are never passed over
+/// just because they are few. The exemplars must be ordered from most to least
+/// extreme.
+fn percentile_extreme<'a>(
+    exemplars: &[&'a WordExtremes],
+    percentile: f64,
+    cutoff: Option<f64>,
+) -> Option<&'a WordExtremes> {
+    if exemplars.is_empty() {
+        return None;
+    }
+    let skip = ((100.0 - percentile.clamp(0.0, 100.0)) / 100.0 * exemplars.len() as f64).floor();
+    let mut index = (skip as usize).min(exemplars.len() - 1);
+    if let Some(weighted) =
+        cutoff.and_then(|cutoff| weighted_extreme(exemplars.iter().copied(), cutoff))
+    {
+        if let Some(position) = exemplars.iter().position(|w| std::ptr::eq(*w, weighted)) {
+            index = index.min(position);
+        }
+    }
+    Some(exemplars[index])
+}
+
+/// Remove languages whose MinMax differs from that of the rest of the script by more
+/// than `threshold` units, returning them so that they can get their own records.
+fn auto_split_languages(
+    remaining: &mut Vec<(Option<String>, MinMax)>,
+    threshold: u16,
+    tolerance: Tolerance,
+) -> Vec<(String, MinMax)> {
+    let mut by_language: BTreeMap<&String, MinMax> = BTreeMap::new();
+    for (lang, minmax) in remaining.iter() {
+        if let Some(lang) = lang {
+            by_language
+                .entry(lang)
+                .and_modify(|existing| existing.merge(minmax, tolerance))
+                .or_insert(minmax.clone());
         }
-    } else {
-        ScriptLanguage {
-            script: w.script().unwrap_or("Zzzz").to_string(),
-            language: None,
+    }
+    let differs = |ours: Option<i16>, theirs: Option<i16>| match (ours, theirs) {
+        (Some(ours), Some(theirs)) => {
+            (ours as i32 - theirs as i32).unsigned_abs() > threshold as u32
+        }
+        _ => false,
+    };
+    let mut promoted = vec![];
+    for (lang, minmax) in by_language.into_iter() {
+        let others = remaining
+            .iter()
+            .filter(|(other_lang, _)| other_lang.as_ref() != Some(lang))
+            .map(|(_, mm)| mm.clone())
+            .collect::<Vec<_>>();
+        let Some(rest) = MinMax::aggregate(&others, tolerance) else {
+            continue;
+        };
+        if differs(minmax.highest, rest.highest) || differs(minmax.lowest, rest.lowest) {
+            log::info!(
+                " Automatically splitting out language {}: {} differs from the rest of the script ({}) by more than {}",
+                lang,
+                minmax,
+                rest,
+                threshold
+            );
+            promoted.push((lang.clone(), minmax));
         }
     }
+    remaining.retain(|(lang, _)| {
+        !promoted
+            .iter()
+            .any(|(promoted_lang, _)| lang.as_ref() == Some(promoted_lang))
+    });
+    promoted
 }
+
+/// The extremes of all of a script's measurements, without splitting out any languages.
+pub fn script_extremes(measurements: &[MeasuredMinMax], config: &Config) -> Option<MinMax> {
+    let minmaxes = measurements
+        .iter()
+        .map(|m| m.minmax.clone())
+        .collect::<Vec<_>>();
+    MinMax::aggregate(&minmaxes, config.min_max_tolerance())
+}
+
 pub fn base_script_record(
     script: &str,
-    reports: &[Report],
+    measurements: &[MeasuredMinMax],
     config: &Config,
     font_default: &MinMax,
 ) -> Option<BaseScript> {
-    let Some(ot_script) = iso15924_to_opentype(script) else {
+    let Some(ot_script) = config.opentype_script(script) else {
         log::warn!("Script {} does not have an OpenType tag, skipping", script);
         return None;
     };
     log::info!("Writing min-max BASE script records for script {}", script);
-    log::debug!("Got {} reports", reports.len());
+    log::debug!("Got {} measurements", measurements.len());
     log::debug!(
-        "Reports: {:#?}",
-        reports
+        "Measurements: {:#?}",
+        measurements
             .iter()
-            .map(|r| r.word_list.name().to_string())
+            .map(|m| m.name.to_string())
             .collect::<Vec<_>>()
     );
 
-    // We've received multiple reports for the script, which may be distinguished by language.
+    // We've received multiple measurements for the script, which may be distinguished by language.
     // If the config specifies particular languages, we break them out of our computations.
-    // (In the future, we might also automatically break out outliers.)
+    // If automatic splitting is enabled, we also break out languages which differ from the rest.
+    let tolerance = config.min_max_tolerance();
     let mut remaining_langs = vec![];
-    let mut lang_specific_minmax: HashMap<String, MinMax> = HashMap::new();
-    let mut split_languages: Vec<&String> = config
+    let mut lang_specific_minmax: BTreeMap<Tag, MinMax> = BTreeMap::new();
+    // Each language split out gets a record for each of its language systems, so
+    // zh-Hans and zh-Hant are both made from the Chinese measurements. An override
+    // for a particular language system applies to its record alone.
+    let mut split_languages: BTreeMap<(&String, Tag), Option<&Override>> = BTreeMap::new();
+    for sl in config
         .languages
         .iter()
+        .chain(config.r#override.keys())
         .filter(|sl| sl.script == script)
-        .flat_map(|sl| sl.language.as_ref())
-        .collect::<Vec<_>>();
-    // Also split out anything manually overridden
-    split_languages.extend(
-        config
-            .r#override
-            .keys()
-            .filter(|sl| sl.script == script)
-            .flat_map(|sl| sl.language.as_ref()),
-    );
-    split_languages.sort();
-    split_languages.dedup();
-    log::debug!(" Splitting out languages: {:?}", split_languages);
-    for report in reports.iter() {
-        let Some(minmax) = MinMax::from_report(report.clone(), config) else {
+    {
+        let Some(lang) = sl.language.as_ref() else {
             continue;
         };
-        let minmax =
-            minmax.with_inliers_removed(&font_default.extend(config.tolerance.unwrap_or(0)));
+        if !config.language_code_allowed(lang) {
+            continue;
+        }
+        let ov = sl.opentype_language.and_then(|_| config.r#override.get(sl));
+        for tag in sl
+            .opentype_language
+            .map(|tag| vec![tag])
+            .unwrap_or_else(|| config.opentype_languages(script, lang))
+        {
+            let entry = split_languages.entry((lang, tag)).or_default();
+            *entry = entry.or(ov);
+        }
+    }
+    log::debug!(" Splitting out languages: {:?}", split_languages.keys());
+    for measurement in measurements.iter() {
+        let minmax = measurement
+            .minmax
+            .clone()
+            .with_inliers_removed(&font_default.extend(tolerance));
         if minmax.is_empty() {
             log::debug!(
-                "  Skipping report for {} ({}) as within {} of font default {:?}",
-                report.word_list.name(),
-                report.word_list.language().unwrap_or("<none>"),
-                config.tolerance.unwrap_or(0),
+                "  Skipping measurement for {} ({}) as within {} of font default {:?}",
+                measurement.name,
+                measurement.source.language.as_deref().unwrap_or("<none>"),
+                tolerance,
                 font_default
             );
             continue;
         }
-        if let Some(lang) = report.word_list.language() {
-            if split_languages.contains(&&lang.to_string()) {
+        if let Some(lang) = &measurement.source.language {
+            let tags = split_languages
+                .keys()
+                .filter(|(split, _)| *split == lang)
+                .map(|(_, tag)| *tag)
+                .collect::<Vec<_>>();
+            if tags.is_empty() {
+                remaining_langs.push((Some(lang.to_string()), minmax));
+                continue;
+            }
+            for tag in tags {
                 lang_specific_minmax
-                    .entry(lang.to_string())
-                    .and_modify(|existing| existing.merge(&minmax, config.tolerance))
-                    .or_insert(minmax);
-            } else {
-                remaining_langs.push(minmax);
+                    .entry(tag)
+                    .and_modify(|existing| existing.merge(&minmax, tolerance))
+                    .or_insert_with(|| minmax.clone());
             }
         } else {
-            remaining_langs.push(minmax);
+            remaining_langs.push((None, minmax));
+        }
+    }
+    for ((lang, tag), ov) in split_languages.iter() {
+        if let Some(ov) = ov {
+            log::debug!(" Overriding language {} ({})", lang, tag);
+            lang_specific_minmax
+                .entry(*tag)
+                .or_insert_with(|| MinMax {
+                    highest: None,
+                    highest_word: String::new(),
+                    lowest: None,
+                    lowest_word: String::new(),
+                })
+                .apply_override(ov);
         }
     }
+    if let Some(threshold) = config.auto_split_threshold {
+        for (lang, minmax) in auto_split_languages(&mut remaining_langs, threshold, tolerance) {
+            lang_specific_minmax
+                .entry(config.opentype_language(script, &lang))
+                .and_modify(|existing| existing.merge(&minmax, tolerance))
+                .or_insert(minmax);
+        }
+    }
+    let remaining_langs = remaining_langs
+        .into_iter()
+        .map(|(_, minmax)| minmax)
+        .collect::<Vec<_>>();
 
     let language_minmax = lang_specific_minmax
         .into_iter()
-        .map(|(lang, mm)| {
-            log::info!(" Language {}: {:?}", lang, mm);
-            (iso639_to_opentype(&lang), mm)
-        })
+        .inspect(|(tag, mm)| log::info!(" Language {}: {:?}", tag, mm))
         .collect::<BTreeMap<_, _>>();
 
-    let mut script_minmax = MinMax::aggregate(&remaining_langs, config.tolerance);
+    let mut script_minmax = MinMax::aggregate(&remaining_langs, tolerance);
     if let Some(ref script_mm) = script_minmax {
         script_minmax = Some(script_mm.clone().with_inliers_removed(font_default));
     }
@@ -195,3 +391,144 @@ pub fn base_script_record(
         languages: language_minmax,
     })
 }
+
+/// The number of words listed in each clipping warning.
+const CLIPPED_WORDS_SHOWN: usize = 10;
+
+/// Clamp a script record's MinMax values to the given limits.
+///
+/// Extremes beyond the clipping box only document that the words will be
+/// clipped, so some vendors prefer the clamped values. A warning lists the
+/// measured words (with their highest and lowest points) which exceed each limit.
+pub fn clamp_record(record: &mut BaseScript, words: &HashMap<String, (f64, f64)>, limits: &MinMax) {
+    let words_beyond = |exceeds: &dyn Fn(f64, f64) -> bool| {
+        let mut words = words
+            .iter()
+            .filter(|(_, (highest, lowest))| exceeds(*highest, *lowest))
+            .map(|(word, _)| word.as_str())
+            .collect::<Vec<_>>();
+        words.sort();
+        words
+    };
+    if let Some(max) = limits.highest {
+        let words = words_beyond(&|highest, _| highest > max as f64);
+        warn_clipped(record, "above the maximum", max, &words);
+    }
+    if let Some(min) = limits.lowest {
+        let words = words_beyond(&|_, lowest| lowest < min as f64);
+        warn_clipped(record, "below the minimum", min, &words);
+    }
+    if let Some(minmax) = &record.default_minmax {
+        record.default_minmax = Some(minmax.clamped(limits));
+    }
+    for minmax in record.languages.values_mut() {
+        *minmax = minmax.clamped(limits);
+    }
+}
+
+fn warn_clipped(record: &BaseScript, side: &str, limit: i16, words: &[&str]) {
+    if words.is_empty() {
+        return;
+    }
+    let mut listed = words
+        .iter()
+        .take(CLIPPED_WORDS_SHOWN)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if words.len() > CLIPPED_WORDS_SHOWN {
+        listed.push_str(&format!(" and {} more", words.len() - CLIPPED_WORDS_SHOWN));
+    }
+    log::warn!(
+        "Script {}: {} words reach {} of {} and will be clipped: {}",
+        record.script,
+        words.len(),
+        side,
+        limit,
+        listed
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScriptLanguage;
+
+    fn measured(language: &str, highest: i16, lowest: i16) -> MeasuredMinMax {
+        MeasuredMinMax {
+            name: format!("{}_Hani", language),
+            source: ScriptLanguage::new("Hani", Some(language)).unwrap(),
+            location: String::new(),
+            band: None,
+            minmax: MinMax::new_min_max(lowest, highest),
+        }
+    }
+
+    #[test]
+    fn language_systems_get_their_own_records() {
+        let config = Config {
+            languages: vec!["zh-Hans".parse().unwrap()],
+            r#override: HashMap::from([(
+                "zh-Hant".parse().unwrap(),
+                Override {
+                    max: Some(1000),
+                    min: None,
+                },
+            )]),
+            ..Default::default()
+        };
+        let record = base_script_record(
+            "Hani",
+            &[measured("zh", 900, -150), measured("ja", 880, -120)],
+            &config,
+            &MinMax::new_min_max(-100, 800),
+        )
+        .unwrap();
+        assert_eq!(
+            record.languages.keys().copied().collect::<Vec<_>>(),
+            vec![Tag::new(b"ZHS "), Tag::new(b"ZHT ")]
+        );
+        let zhs = &record.languages[&Tag::new(b"ZHS ")];
+        assert_eq!((zhs.highest, zhs.lowest), (Some(900), Some(-150)));
+        let zht = &record.languages[&Tag::new(b"ZHT ")];
+        assert_eq!((zht.highest, zht.lowest), (Some(1000), Some(-150)));
+        let dflt = record.default_minmax.unwrap();
+        assert_eq!((dflt.highest, dflt.lowest), (Some(880), Some(-120)));
+    }
+
+    #[test]
+    fn percentiles_honour_the_frequency_cutoff() {
+        let word = |word: &str, highest: f64, frequency: f64| WordExtremes {
+            word: word.to_string(),
+            highest,
+            lowest: 0.0,
+            frequency: Some(frequency),
+        };
+        // The most extreme of 200 words is common; the next is rare
+        let mut exemplars = vec![word("common", 1000.0, 500.0), word("rare", 990.0, 1.0)];
+        exemplars.extend((0..198).map(|i| word(&format!("w{}", i), 900.0 - i as f64, 1.0)));
+        let highest = |config: &Config| {
+            choose_extreme(
+                &exemplars,
+                |w| w.highest,
+                Aggregation::Percentile(99.0),
+                config,
+            )
+            .unwrap()
+        };
+        // The 99th percentile passes over the two most extreme words
+        assert_eq!(highest(&Config::default()).1, "w0");
+        // But the common word is more than 10% of occurrences, so it can't be
+        let config = Config {
+            frequency_cutoff: Some(0.1),
+            ..Default::default()
+        };
+        assert_eq!(highest(&config), (1000.0, "common".to_string()));
+        // A cutoff which would pass over more words than the percentile doesn't
+        let config = Config {
+            frequency_cutoff: Some(0.9),
+            ..Default::default()
+        };
+        assert_eq!(highest(&config).1, "w0");
+    }
+}
@@ -0,0 +1,281 @@
+//! Compute the registered baselines which don't come from the ideographic em-box.
+//!
+//! `romn` is the alphabetic baseline, at zero. `hang` is the headline from which
+//! scripts such as Devanagari hang, measured from a letter of the script, or the
+//! cap height for scripts without one. `math` is the axis on which mathematical
+//! operators are centred, measured from the minus sign. Only the horizontal axis
+//! gets these baselines; the vertical ones come from the CJK em-box.
+use skrifa::{instance::LocationRef, raw::TableProvider, FontRef, MetadataProvider, Tag};
+
+use crate::{
+    base::{BaseScript, BaseTable},
+    cjk::outline_bounds,
+    error::AutobaseError,
+    utils::to_font_unit,
+};
+
+/// A letter of each hanging script whose top lies on the headline.
+const HEADLINE_CHARACTERS: [(&str, char); 4] =
+    [("Deva", 'क'), ("Beng", 'ক'), ("Guru", 'ਕ'), ("Tibt", 'ཀ')];
+
+/// Characters centred on the math axis, in order of preference.
+const MATH_AXIS_CHARACTERS: [char; 3] = ['\u{2212}', '+', '='];
+
+/// The bounds of a character's glyph at the default location, as (y_min, y_max).
+fn vertical_extent(font: &FontRef, c: char) -> Option<(f32, f32)> {
+    let gid = font.charmap().map(c)?;
+    let bounds = outline_bounds(&font.outline_glyphs(), LocationRef::default(), gid)?;
+    Some((bounds.y_min, bounds.y_max))
+}
+
+/// The cap height from the OS/2 table, or else the height of "H".
+fn cap_height(font: &FontRef) -> Option<f32> {
+    font.os2()
+        .ok()
+        .and_then(|os2| os2.s_cap_height())
+        .filter(|height| *height > 0)
+        .map(|height| height as f32)
+        .or_else(|| vertical_extent(font, 'H').map(|(_, top)| top))
+}
+
+/// Whether an ISO 15924 script hangs from a headline.
+fn is_hanging_script(script: &str) -> bool {
+    HEADLINE_CHARACTERS
+        .iter()
+        .any(|(hanging, _)| *hanging == script)
+}
+
+/// The hanging baseline of an ISO 15924 script.
+pub fn hanging_baseline(font: &FontRef, script: &str) -> Option<f32> {
+    HEADLINE_CHARACTERS
+        .iter()
+        .find(|(hanging, _)| *hanging == script)
+        .and_then(|(_, c)| vertical_extent(font, *c))
+        .map(|(_, top)| top)
+        .or_else(|| cap_height(font))
+}
+
+/// The math axis of the font.
+pub fn math_baseline(font: &FontRef) -> Option<f32> {
+    MATH_AXIS_CHARACTERS
+        .iter()
+        .find_map(|c| vertical_extent(font, *c))
+        .map(|(bottom, top)| (bottom + top) / 2.0)
+}
+
+/// Add the `hang`, `math` and `romn` baselines of each script to the table.
+///
+/// `scripts` pairs ISO 15924 scripts with their OpenType tags. Baselines already
+/// in the table, such as the CJK `romn`, are kept. A baseline is only added if
+/// every script has a value for it, so that no script has one written as 0.
+/// Scripts without a default baseline get `hang` if they hang from a headline,
+/// and `romn` otherwise.
+pub fn insert_into_base(
+    font: &FontRef,
+    scripts: &[(&str, Tag)],
+    base: &mut BaseTable,
+) -> Result<(), AutobaseError> {
+    let hanging = scripts
+        .iter()
+        .map(|(script, _)| hanging_baseline(font, script))
+        .collect::<Option<Vec<_>>>();
+    if hanging.is_none() {
+        log::info!("Not adding hang baselines, as the font has no cap height");
+    }
+    let math = math_baseline(font);
+    if math.is_none() {
+        log::info!("Not adding math baselines, as the font has no minus, plus or equals sign");
+    }
+    for (index, &(script, ot_script)) in scripts.iter().enumerate() {
+        let mut values = vec![(Tag::new(b"romn"), 0.0)];
+        if let Some(hanging) = &hanging {
+            values.push((Tag::new(b"hang"), hanging[index]));
+        }
+        if let Some(math) = math {
+            values.push((Tag::new(b"math"), math));
+        }
+        let record = match base.horizontal.iter().position(|bs| bs.script == ot_script) {
+            Some(position) => &mut base.horizontal[position],
+            None => {
+                base.horizontal.push(BaseScript::new(ot_script));
+                base.horizontal.last_mut().unwrap()
+            }
+        };
+        let default_baseline = if hanging.is_some() && is_hanging_script(script) {
+            Tag::new(b"hang")
+        } else {
+            Tag::new(b"romn")
+        };
+        record.default_baseline.get_or_insert(default_baseline);
+        for (tag, value) in values {
+            let value = to_font_unit(
+                value as f64,
+                format_args!("horizontal {} baseline of {}", tag, ot_script),
+            )?;
+            record.baselines.entry(tag).or_insert(value);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use skrifa::{
+        raw::{tables::glyf::CurvePoint, FontData, FontRead},
+        GlyphId,
+    };
+    use write_fonts::{
+        tables::{
+            cmap::Cmap,
+            glyf::{Bbox, Contour, GlyfLocaBuilder, SimpleGlyph},
+            head::{Flags, Head, MacStyle},
+            hhea::Hhea,
+            hmtx::{Hmtx, LongMetric},
+            loca::LocaFormat,
+            maxp::Maxp,
+        },
+        types::{Fixed, LongDateTime},
+        FontBuilder,
+    };
+
+    use super::*;
+
+    fn rectangle(bottom: i16, top: i16) -> SimpleGlyph {
+        let points = [(50, bottom), (50, top), (450, top), (450, bottom)];
+        SimpleGlyph {
+            bbox: Bbox {
+                x_min: 50,
+                y_min: bottom,
+                x_max: 450,
+                y_max: top,
+            },
+            contours: vec![Contour::from(
+                points
+                    .iter()
+                    .map(|&(x, y)| CurvePoint::on_curve(x, y))
+                    .collect::<Vec<_>>(),
+            )],
+            instructions: vec![],
+        }
+    }
+
+    /// A font whose glyphs are rectangles spanning the given heights.
+    fn font_with_glyphs(glyphs: &[(char, i16, i16)]) -> Vec<u8> {
+        font_with_advances(
+            &glyphs
+                .iter()
+                .map(|&(c, bottom, top)| (c, bottom, top, 500))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// [`font_with_glyphs`] with an advance for each glyph.
+    pub(crate) fn font_with_advances(glyphs: &[(char, i16, i16, u16)]) -> Vec<u8> {
+        let mut glyf = GlyfLocaBuilder::new();
+        glyf.add_glyph(&rectangle(0, 700)).unwrap();
+        for &(_, bottom, top, _) in glyphs {
+            glyf.add_glyph(&rectangle(bottom, top)).unwrap();
+        }
+        let (glyf, loca, loca_format) = glyf.build();
+        let count = glyphs.len() as u16 + 1;
+        let mut builder = FontBuilder::new();
+        builder
+            .add_table(&Head::new(
+                Fixed::ONE,
+                0,
+                Flags::empty(),
+                1000,
+                LongDateTime::new(0),
+                LongDateTime::new(0),
+                50,
+                -200,
+                450,
+                900,
+                MacStyle::empty(),
+                0,
+                (loca_format == LocaFormat::Long) as i16,
+            ))
+            .unwrap();
+        builder
+            .add_table(&Hhea {
+                ascender: 900.into(),
+                descender: (-200).into(),
+                number_of_h_metrics: count,
+                ..Default::default()
+            })
+            .unwrap();
+        builder
+            .add_table(&Hmtx::new(
+                std::iter::once(LongMetric::new(500, 50))
+                    .chain(
+                        glyphs
+                            .iter()
+                            .map(|&(_, _, _, advance)| LongMetric::new(advance, 50)),
+                    )
+                    .collect(),
+                vec![],
+            ))
+            .unwrap();
+        builder.add_table(&Maxp::new(count)).unwrap();
+        builder
+            .add_table(
+                &Cmap::from_mappings(
+                    glyphs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &(c, _, _, _))| (c, GlyphId::new(i as u32 + 1))),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        builder.add_table(&glyf).unwrap();
+        builder.add_table(&loca).unwrap();
+        builder.build()
+    }
+
+    fn written(base: &BaseTable) -> BaseTable {
+        let data = write_fonts::dump_table(&base.to_skrifa().unwrap()).unwrap();
+        let base = skrifa::raw::tables::base::Base::read(FontData::new(&data)).unwrap();
+        BaseTable::from_skrifa(&base).unwrap()
+    }
+
+    #[test]
+    fn baselines_come_from_headlines_cap_height_and_minus() {
+        let data = font_with_glyphs(&[('H', 0, 700), ('क', -10, 750), ('\u{2212}', 240, 300)]);
+        let font = FontRef::new(&data).unwrap();
+        let (deva, latn) = (Tag::new(b"dev2"), Tag::new(b"latn"));
+        let mut base = BaseTable::default();
+        insert_into_base(&font, &[("Deva", deva), ("Latn", latn)], &mut base).unwrap();
+        // They survive being written, rather than being left out of the tag list
+        let base = written(&base);
+        let tag = |tag: &[u8; 4]| Tag::new(tag);
+        assert_eq!(
+            base.script(deva).unwrap().default_baseline,
+            Some(tag(b"hang"))
+        );
+        assert_eq!(
+            base.script(latn).unwrap().default_baseline,
+            Some(tag(b"romn"))
+        );
+        assert_eq!(base.baseline(deva, tag(b"hang")), Some(750));
+        assert_eq!(base.baseline(latn, tag(b"hang")), Some(700));
+        for script in [deva, latn] {
+            assert_eq!(base.baseline(script, tag(b"math")), Some(270));
+            assert_eq!(base.baseline(script, tag(b"romn")), Some(0));
+        }
+    }
+
+    #[test]
+    fn unmeasurable_baselines_are_left_out() {
+        let data = font_with_glyphs(&[('a', 0, 500)]);
+        let font = FontRef::new(&data).unwrap();
+        let latn = Tag::new(b"latn");
+        let mut base = BaseTable::default();
+        insert_into_base(&font, &[("Latn", latn)], &mut base).unwrap();
+        let record = base.script(latn).unwrap();
+        assert_eq!(
+            record.baselines.keys().collect::<Vec<_>>(),
+            [&Tag::new(b"romn")]
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! A C interface, so that font build tools written in C or C++ can link autobase
+//! directly rather than running the command line tool.
+//!
+//! The interface is deliberately small: a font goes in, the same font with a
+//! generated BASE table comes out, and anything which goes wrong is reported as
+//! a message. `include/autobase.h` declares it for C callers.
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr,
+};
+
+use anyhow::Context;
+use skrifa::FontRef;
+
+use crate::{
+    config::{self, Config, ConfigFormat},
+    pipeline::{Generator, GeneratorOptions},
+};
+
+/// Options for [`autobase_generate`].
+#[repr(C)]
+pub struct AutobaseOptions {
+    /// Add MinMax records, as `--min-max` does
+    pub min_max: bool,
+    /// The number of words from each list to measure, or 0 for the default
+    pub words_per_list: usize,
+    /// A configuration in TOML, as it would be written in `autobase.toml`, or NULL.
+    /// Paths in it are relative to the working directory
+    pub config: *const c_char,
+}
+
+/// A buffer of bytes allocated by autobase, to be released with [`autobase_free`].
+#[repr(C)]
+pub struct AutobaseBytes {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl AutobaseBytes {
+    fn null() -> Self {
+        AutobaseBytes {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Read the options given by a C caller.
+///
+/// # Safety
+///
+/// `options`, if not null, must point to a valid [`AutobaseOptions`], whose
+/// `config`, if not null, is a NUL-terminated string.
+unsafe fn read_options(
+    options: *const AutobaseOptions,
+) -> anyhow::Result<(Config, GeneratorOptions)> {
+    let mut generator_options = GeneratorOptions::default();
+    let Some(options) = options.as_ref() else {
+        return Ok((Config::default(), generator_options));
+    };
+    let mut config = if options.config.is_null() {
+        Config::default()
+    } else {
+        let text = CStr::from_ptr(options.config)
+            .to_str()
+            .context("the configuration is not UTF-8")?;
+        // Paths in the configuration are relative to the working directory
+        config::parse_config(text, ConfigFormat::Toml, Path::new("autobase.toml"))
+            .context("failed to parse the configuration")?
+    };
+    generator_options.min_max = options.min_max || config.min_max;
+    config.resolve_metric_source(generator_options.min_max);
+    if options.words_per_list > 0 {
+        generator_options.words_per_list = options.words_per_list;
+    }
+    Ok((config, generator_options))
+}
+
+fn generate(
+    font_bytes: &[u8],
+    config: Config,
+    options: GeneratorOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let generator = Generator::new(config, options)?;
+    let base = generator.generate(&[font_bytes])?;
+    let font = FontRef::new(font_bytes).context("failed to parse font file")?;
+    Ok(base.add_to_binary(&font)?)
+}
+
+/// Generate a BASE table for a font, returning the font with the table added.
+///
+/// On failure, the returned buffer's `data` is NULL, and
+/// [`autobase_last_error`] describes what went wrong.
+///
+/// # Safety
+///
+/// `font` must point to `len` readable bytes. `options` may be NULL for the
+/// defaults; otherwise it must point to a valid [`AutobaseOptions`], whose
+/// `config`, if not NULL, is a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn autobase_generate(
+    font: *const u8,
+    len: usize,
+    options: *const AutobaseOptions,
+) -> AutobaseBytes {
+    if font.is_null() {
+        set_last_error("no font was given".to_string());
+        return AutobaseBytes::null();
+    }
+    let font_bytes = std::slice::from_raw_parts(font, len);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let (config, options) = read_options(options)?;
+        generate(font_bytes, config, options)
+    }));
+    match result {
+        Ok(Ok(bytes)) => {
+            let bytes = bytes.into_boxed_slice();
+            let len = bytes.len();
+            AutobaseBytes {
+                data: Box::into_raw(bytes) as *mut u8,
+                len,
+            }
+        }
+        Ok(Err(e)) => {
+            set_last_error(format!("{:#}", e));
+            AutobaseBytes::null()
+        }
+        Err(_) => {
+            set_last_error("autobase panicked".to_string());
+            AutobaseBytes::null()
+        }
+    }
+}
+
+/// Release a buffer returned by [`autobase_generate`].
+///
+/// # Safety
+///
+/// `bytes` must have been returned by [`autobase_generate`] and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn autobase_free(bytes: AutobaseBytes) {
+    if !bytes.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            bytes.data, bytes.len,
+        )));
+    }
+}
+
+/// The message describing the last failure on this thread, or NULL if there
+/// hasn't been one. The string remains valid until the next failure on the
+/// same thread.
+#[no_mangle]
+pub extern "C" fn autobase_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font_metrics::MetricSource;
+
+    fn options(config: &CStr) -> AutobaseOptions {
+        AutobaseOptions {
+            min_max: false,
+            words_per_list: 0,
+            config: config.as_ptr(),
+        }
+    }
+
+    #[test]
+    fn config_is_resolved_as_the_cli_does() {
+        let tags = std::env::temp_dir().join(format!("autobase-capi-{}.txt", std::process::id()));
+        std::fs::write(&tags, "xx ABC\n").unwrap();
+        let text = CString::new(format!(
+            "min_max = true\nuse_hhea = true\nlanguage_tag_file = {:?}\nlanguages = []\n[override]\n",
+            tags.display().to_string()
+        ))
+        .unwrap();
+        let (config, generator_options) = unsafe { read_options(&options(&text)) }.unwrap();
+        std::fs::remove_file(&tags).unwrap();
+        assert!(generator_options.min_max);
+        assert_eq!(config.metric_source, Some(MetricSource::Hhea));
+        assert_eq!(config.language_tags["xx"], "ABC");
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let text = CString::new("tolerance = \"wide\"\nlanguages = []\n[override]\n").unwrap();
+        assert!(unsafe { read_options(&options(&text)) }.is_err());
+        let (config, _) = unsafe { read_options(ptr::null()) }.unwrap();
+        assert!(config.language_tags.is_empty());
+
+        let font = [0u8; 4];
+        let result = unsafe { autobase_generate(font.as_ptr(), font.len(), ptr::null()) };
+        assert!(result.data.is_null());
+        let message = unsafe { CStr::from_ptr(autobase_last_error()) };
+        assert!(!message.to_bytes().is_empty());
+    }
+}
@@ -0,0 +1,316 @@
+//! Describe the differences between two BASE tables.
+//!
+//! When autobase modifies a font which already has a BASE table, reviewers want
+//! to know what changed: which records were added or removed, and which values
+//! moved from what to what. This module compares an old and a new table and
+//! produces a list of changes which can be displayed as a concise changelog.
+use std::collections::BTreeSet;
+
+use skrifa::Tag;
+
+use crate::{
+    base::{BaseScript, BaseTable, MinMax, Tolerance},
+    names::{describe_language, describe_script},
+};
+
+/// A single difference between two BASE tables.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// A script record exists only in the new table
+    ScriptAdded { axis: &'static str, script: Tag },
+    /// A script record exists only in the old table
+    ScriptRemoved { axis: &'static str, script: Tag },
+    /// The default baseline of a script changed
+    DefaultBaselineChanged {
+        axis: &'static str,
+        script: Tag,
+        old: Option<Tag>,
+        new: Option<Tag>,
+    },
+    /// The coordinate of a baseline changed, or the baseline was added or removed
+    BaselineChanged {
+        axis: &'static str,
+        script: Tag,
+        baseline: Tag,
+        old: Option<i16>,
+        new: Option<i16>,
+    },
+    /// A MinMax record changed, or was added or removed
+    ///
+    /// A language of `None` refers to the script's default MinMax.
+    MinMaxChanged {
+        axis: &'static str,
+        script: Tag,
+        language: Option<Tag>,
+        old: Option<MinMax>,
+        new: Option<MinMax>,
+    },
+}
+
+fn describe_coord(coord: Option<i16>) -> String {
+    coord
+        .map(|c| c.to_string())
+        .unwrap_or_else(|| "NULL".to_string())
+}
+
+fn describe_minmax(minmax: &Option<MinMax>) -> String {
+    match minmax {
+        Some(mm) => format!(
+            "min {}, max {}",
+            describe_coord(mm.lowest),
+            describe_coord(mm.highest)
+        ),
+        None => "none".to_string(),
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::ScriptAdded { axis, script } => {
+                write!(
+                    f,
+                    "+ {} {}: script record added",
+                    axis,
+                    describe_script(*script)
+                )
+            }
+            Change::ScriptRemoved { axis, script } => {
+                write!(
+                    f,
+                    "- {} {}: script record removed",
+                    axis,
+                    describe_script(*script)
+                )
+            }
+            Change::DefaultBaselineChanged {
+                axis,
+                script,
+                old,
+                new,
+            } => write!(
+                f,
+                "~ {} {} default baseline: {} → {}",
+                axis,
+                describe_script(*script),
+                old.map(|t| t.to_string())
+                    .unwrap_or_else(|| "none".to_string()),
+                new.map(|t| t.to_string())
+                    .unwrap_or_else(|| "none".to_string())
+            ),
+            Change::BaselineChanged {
+                axis,
+                script,
+                baseline,
+                old,
+                new,
+            } => write!(
+                f,
+                "{} {} {} {} baseline: {} → {}",
+                marker(old.is_some(), new.is_some()),
+                axis,
+                describe_script(*script),
+                baseline,
+                old.map(|c| c.to_string())
+                    .unwrap_or_else(|| "none".to_string()),
+                new.map(|c| c.to_string())
+                    .unwrap_or_else(|| "none".to_string())
+            ),
+            Change::MinMaxChanged {
+                axis,
+                script,
+                language,
+                old,
+                new,
+            } => write!(
+                f,
+                "{} {} {} {} MinMax: {} → {}",
+                marker(old.is_some(), new.is_some()),
+                axis,
+                describe_script(*script),
+                language
+                    .map(describe_language)
+                    .unwrap_or_else(|| "dflt".to_string()),
+                describe_minmax(old),
+                describe_minmax(new)
+            ),
+        }
+    }
+}
+
+impl Change {
+    /// Whether the change only adds to the old table, losing nothing from it.
+    pub fn is_addition(&self) -> bool {
+        match self {
+            Change::ScriptAdded { .. } => true,
+            Change::ScriptRemoved { .. } => false,
+            Change::DefaultBaselineChanged { old, .. } => old.is_none(),
+            Change::BaselineChanged { old, .. } => old.is_none(),
+            Change::MinMaxChanged { old, .. } => old.is_none(),
+        }
+    }
+}
+
+fn marker(had_old: bool, has_new: bool) -> &'static str {
+    match (had_old, has_new) {
+        (false, true) => "+",
+        (true, false) => "-",
+        _ => "~",
+    }
+}
+
+/// Do two MinMax records have the same coordinates? (The words they came from are ignored.)
+fn same_values(old: &Option<MinMax>, new: &Option<MinMax>) -> bool {
+    match (old, new) {
+        (Some(old), Some(new)) => old.lowest == new.lowest && old.highest == new.highest,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn script_changes(axis: &'static str, old: &BaseScript, new: &BaseScript) -> Vec<Change> {
+    let script = new.script;
+    let mut changes = vec![];
+    if old.default_baseline != new.default_baseline {
+        changes.push(Change::DefaultBaselineChanged {
+            axis,
+            script,
+            old: old.default_baseline,
+            new: new.default_baseline,
+        });
+    }
+    let baselines: BTreeSet<&Tag> = old.baselines.keys().chain(new.baselines.keys()).collect();
+    for baseline in baselines {
+        let (old_coord, new_coord) = (
+            old.baselines.get(baseline).copied(),
+            new.baselines.get(baseline).copied(),
+        );
+        if old_coord != new_coord {
+            changes.push(Change::BaselineChanged {
+                axis,
+                script,
+                baseline: *baseline,
+                old: old_coord,
+                new: new_coord,
+            });
+        }
+    }
+    if !same_values(&old.default_minmax, &new.default_minmax) {
+        changes.push(Change::MinMaxChanged {
+            axis,
+            script,
+            language: None,
+            old: old.default_minmax.clone(),
+            new: new.default_minmax.clone(),
+        });
+    }
+    let languages: BTreeSet<&Tag> = old.languages.keys().chain(new.languages.keys()).collect();
+    for language in languages {
+        let (old_mm, new_mm) = (
+            old.languages.get(language).cloned(),
+            new.languages.get(language).cloned(),
+        );
+        if !same_values(&old_mm, &new_mm) {
+            changes.push(Change::MinMaxChanged {
+                axis,
+                script,
+                language: Some(*language),
+                old: old_mm,
+                new: new_mm,
+            });
+        }
+    }
+    changes
+}
+
+/// List the changes needed to turn the old table into the new one.
+///
+/// The tables are compared as written, so a baseline filled with 0 in one
+/// matches one missing from the other, as a table read from a font can't tell
+/// the two apart.
+pub fn changes(old: &BaseTable, new: &BaseTable) -> Vec<Change> {
+    let (old, new) = (old.as_written(), new.as_written());
+    let mut changes = vec![];
+    for (axis, old_scripts, new_scripts) in [
+        ("HorizAxis", &old.horizontal, &new.horizontal),
+        ("VertAxis", &old.vertical, &new.vertical),
+    ] {
+        let scripts: BTreeSet<Tag> = old_scripts
+            .iter()
+            .chain(new_scripts.iter())
+            .map(|s| s.script)
+            .collect();
+        for script in scripts {
+            let old_script = old_scripts.iter().find(|s| s.script == script);
+            let new_script = new_scripts.iter().find(|s| s.script == script);
+            match (old_script, new_script) {
+                (Some(old_script), Some(new_script)) => {
+                    changes.extend(script_changes(axis, old_script, new_script))
+                }
+                (None, Some(new_script)) => {
+                    changes.push(Change::ScriptAdded { axis, script });
+                    changes.extend(script_changes(axis, &BaseScript::new(script), new_script));
+                }
+                (Some(_), None) => changes.push(Change::ScriptRemoved { axis, script }),
+                (None, None) => {}
+            }
+        }
+    }
+    changes
+}
+
+/// The changes from the old table to the new one which lose records or values,
+/// and which writing the new table over the old one needs `--force` for.
+pub fn losses(old: &BaseTable, new: &BaseTable) -> Vec<Change> {
+    changes(old, new)
+        .into_iter()
+        .filter(|change| !change.is_addition())
+        .collect()
+}
+
+/// How much a change matters when checking that a font's BASE table is still
+/// what autobase would generate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Values moved by no more than the tolerance
+    WithinTolerance,
+    /// Values moved by more than the tolerance
+    ValuesDiffer,
+    /// Records, baselines or MinMax sides were added or removed, or a default
+    /// baseline changed
+    Structural,
+}
+
+impl Change {
+    /// How much this change matters, given a tolerance in font units.
+    ///
+    /// Maxima are compared with `tolerance.max`, minima with `tolerance.min`, and
+    /// baselines with the larger of the two.
+    pub fn severity(&self, tolerance: Tolerance) -> Severity {
+        let coord = |old: Option<i16>, new: Option<i16>, tolerance: u16| match (old, new) {
+            (Some(old), Some(new))
+                if (old as i32 - new as i32).unsigned_abs() > tolerance as u32 =>
+            {
+                Severity::ValuesDiffer
+            }
+            (Some(_), Some(_)) | (None, None) => Severity::WithinTolerance,
+            _ => Severity::Structural,
+        };
+        match self {
+            Change::ScriptAdded { .. }
+            | Change::ScriptRemoved { .. }
+            | Change::DefaultBaselineChanged { .. } => Severity::Structural,
+            Change::BaselineChanged { old, new, .. } => coord(*old, *new, tolerance.loosest()),
+            Change::MinMaxChanged {
+                old: Some(old),
+                new: Some(new),
+                ..
+            } => coord(old.lowest, new.lowest, tolerance.min).max(coord(
+                old.highest,
+                new.highest,
+                tolerance.max,
+            )),
+            Change::MinMaxChanged { .. } => Severity::Structural,
+        }
+    }
+}
@@ -1,16 +1,19 @@
-use std::collections::HashSet;
+use serde::Deserialize;
 
 use crate::{
     base::{BaseScript, BaseTable},
     error::AutobaseError,
-    utils::iso15924_to_opentype,
+    stats::mean_and_standard_deviation,
+    utils::to_font_unit,
 };
 use skrifa::{
     metrics::BoundingBox,
+    outline::{DrawSettings, OutlineGlyphFormat, OutlinePen},
     prelude::{LocationRef, Size},
-    raw::TableProvider,
-    GlyphId, MetadataProvider, Tag,
+    raw::{tables::hmtx::Hmtx, TableProvider},
+    GlyphId, GlyphNames, MetadataProvider, OutlineGlyphCollection, Tag,
 };
+use std::collections::{HashMap, HashSet};
 
 // To let the function work with both ISO and OpenType script tags, we include both
 pub const CJK_SCRIPTS: [&str; 10] = [
@@ -46,10 +49,47 @@ pub struct CjkMetrics {
     v_idtp: Option<f32>,
     /// Vertical roman baseline
     v_romn: Option<f32>,
+
+    /// Whether the idtp baselines are written
+    idtp: IdtpPolicy,
+    /// The average horizontal advance, which decides whether the font is square
+    average_width: f32,
+}
+
+/// Fixed values for the CJK baselines, e.g. from a foundry's published em-box.
+///
+/// Any value given here replaces the measured one; the rest are still computed.
+#[derive(Debug, Default, Clone, Deserialize, PartialEq)]
+pub struct CjkEmBox {
+    #[serde(default)]
+    pub h_icfb: Option<i16>,
+    #[serde(default)]
+    pub h_icft: Option<i16>,
+    #[serde(default)]
+    pub h_ideo: Option<i16>,
+    #[serde(default)]
+    pub h_idtp: Option<i16>,
+    #[serde(default)]
+    pub v_icfb: Option<i16>,
+    #[serde(default)]
+    pub v_icft: Option<i16>,
+    #[serde(default)]
+    pub v_ideo: Option<i16>,
+    #[serde(default)]
+    pub v_idtp: Option<i16>,
 }
 
 impl CjkMetrics {
-    fn from_bounds(bounds: &[BoundingBox], upem: f32, average_width: f32) -> Self {
+    /// Compute the metrics from glyph bounds.
+    ///
+    /// `trim_percent` is the percentage of glyphs at each end of each edge's
+    /// distribution which are left out of that edge's average.
+    fn from_bounds(
+        bounds: &[BoundingBox],
+        upem: f32,
+        average_width: f32,
+        trim_percent: f32,
+    ) -> Self {
         let bbox_y_average = bounds
             .iter()
             .map(|b| (b.y_max + b.y_min) / 2.0)
@@ -57,10 +97,21 @@ impl CjkMetrics {
             / bounds.len() as f32;
         let h_idtp = bbox_y_average + upem / 2.0;
         let h_ideo = bbox_y_average - upem / 2.0;
-        let average_top = bounds.iter().map(|b| b.y_max).sum::<f32>() / bounds.len() as f32;
-        let average_bottom = bounds.iter().map(|b| b.y_min).sum::<f32>() / bounds.len() as f32;
-        let average_left = bounds.iter().map(|b| b.x_min).sum::<f32>() / bounds.len() as f32;
-        let average_right = bounds.iter().map(|b| b.x_max).sum::<f32>() / bounds.len() as f32;
+        let edge = |value: fn(&BoundingBox) -> f32| {
+            trimmed_mean(bounds.iter().map(value).collect(), trim_percent)
+        };
+        let (average_top, trimmed) = edge(|b| b.y_max);
+        let (average_bottom, _) = edge(|b| b.y_min);
+        let (average_left, _) = edge(|b| b.x_min);
+        let (average_right, _) = edge(|b| b.x_max);
+        if trimmed > 0 {
+            log::info!(
+                "Trimmed the {} highest and {} lowest of {} glyphs from each edge of the character face",
+                trimmed,
+                trimmed,
+                bounds.len()
+            );
+        }
 
         CjkMetrics {
             h_icfb: Some(average_bottom),
@@ -73,139 +124,1430 @@ impl CjkMetrics {
             v_ideo: Some(0.0),
             v_idtp: Some(average_width),
             v_romn: Some(-h_ideo),
+            idtp: IdtpPolicy::Auto,
+            average_width,
+        }
+    }
+
+    /// An ideographic em-box derived from a descender alone, without measuring any glyphs.
+    ///
+    /// The em-box is assumed to be square, with its bottom edge at the descender;
+    /// the character face baselines are left unset.
+    pub fn from_em_box(descender: f32, upem: f32) -> Self {
+        CjkMetrics {
+            h_icfb: None,
+            h_icft: None,
+            h_ideo: Some(descender),
+            h_idtp: Some(descender + upem),
+            h_romn: Some(0.0),
+            v_icfb: None,
+            v_icft: None,
+            v_ideo: Some(0.0),
+            v_idtp: Some(upem),
+            v_romn: Some(-descender),
+            idtp: IdtpPolicy::Auto,
+            average_width: upem,
         }
     }
 
+    /// Pin the bottom edge of the ideographic em-box to the given descender.
+    ///
+    /// The em-box keeps its height of one em, so its top edge moves with it, as
+    /// does the vertical roman baseline.
+    fn with_descender(mut self, descender: f32, upem: f32) -> Self {
+        self.h_ideo = Some(descender);
+        self.h_idtp = Some(descender + upem);
+        self.v_romn = Some(-descender);
+        self
+    }
+
+    /// Replace the computed baselines with any fixed values given in the em-box.
+    ///
+    /// The roman baselines follow an overridden horizontal `ideo`, so that the
+    /// em-box stays consistent between the two axes.
+    pub fn with_em_box(mut self, em_box: &CjkEmBox) -> Self {
+        let replace = |value: &mut Option<f32>, fixed: Option<i16>| {
+            if let Some(fixed) = fixed {
+                *value = Some(fixed as f32);
+            }
+        };
+        replace(&mut self.h_icfb, em_box.h_icfb);
+        replace(&mut self.h_icft, em_box.h_icft);
+        replace(&mut self.h_ideo, em_box.h_ideo);
+        replace(&mut self.h_idtp, em_box.h_idtp);
+        replace(&mut self.v_icfb, em_box.v_icfb);
+        replace(&mut self.v_icft, em_box.v_icft);
+        replace(&mut self.v_ideo, em_box.v_ideo);
+        replace(&mut self.v_idtp, em_box.v_idtp);
+        if let Some(ideo) = em_box.h_ideo {
+            self.v_romn = Some(-(ideo as f32));
+        }
+        if em_box.h_idtp.is_some() || em_box.v_idtp.is_some() {
+            // A fixed idtp must be written, even if the font is square
+            self.idtp = IdtpPolicy::Always;
+        }
+        self
+    }
+
+    /// The average horizontal advance of the ideographs, which decides whether
+    /// the em-box is square.
+    pub fn average_width(&self) -> f32 {
+        self.average_width
+    }
+
+    /// Decide whether the em-box is square from another average advance, such as
+    /// that of the whole font, rather than from these glyphs' own.
+    ///
+    /// The vertical `idtp` is still these glyphs' average advance.
+    pub fn with_average_width(mut self, average_width: f32) -> Self {
+        self.average_width = average_width;
+        self
+    }
+
+    /// Override the automatic decision whether to write the `idtp` baselines.
+    ///
+    /// [`IdtpPolicy::Auto`] leaves the decision (or a fixed `idtp` from the em-box)
+    /// as it is.
+    pub fn with_idtp_policy(mut self, policy: IdtpPolicy) -> Self {
+        if policy != IdtpPolicy::Auto {
+            self.idtp = policy;
+        }
+        self
+    }
+
+    /// Add the CJK baselines to the BASE table for each of the supported scripts.
+    ///
+    /// `square_tolerance` is the fraction of the em within which the average
+    /// ideographic advance must lie for the font to be treated as square, in which
+    /// case the `idtp` baselines are omitted. Baselines which don't fit in a font
+    /// unit are an error.
     pub fn insert_into_base(
         &self,
         upem: f32,
-        supported_scripts: &HashSet<&str>,
+        square_tolerance: f32,
+        supported_scripts: &[Tag],
         base: &mut BaseTable,
-    ) {
-        let average_width = self.v_idtp.unwrap();
-        let font_is_square = (average_width - upem).abs() / upem < 0.01;
+    ) -> Result<(), AutobaseError> {
+        let write_idtp = match self.idtp {
+            IdtpPolicy::Auto => !font_is_square(self.average_width, upem, square_tolerance),
+            IdtpPolicy::Always => true,
+            IdtpPolicy::Never => false,
+        };
         // get all the supported scripts; if they're not already in the base table, add them
         // for each script, the default baseline should be ideo if it's a CJK script, romn otherwise
         // we want to add the following baseline: icfb, icft, ideo, romn; idtp only if the font is not square
+        let baselines = |axis: &str, values: [(&[u8; 4], Option<f32>); 5]| {
+            values
+                .into_iter()
+                .filter(|(tag, _)| write_idtp || *tag != b"idtp")
+                .filter_map(|(tag, value)| {
+                    let value = value?;
+                    let tag = Tag::new(tag);
+                    Some(
+                        to_font_unit(value as f64, format_args!("{} {} baseline", axis, tag))
+                            .map(|value| (tag, value)),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let horizontal = baselines(
+            "horizontal",
+            [
+                (b"icfb", self.h_icfb),
+                (b"icft", self.h_icft),
+                (b"ideo", self.h_ideo),
+                (b"romn", self.h_romn),
+                (b"idtp", self.h_idtp),
+            ],
+        )?;
+        let vertical = baselines(
+            "vertical",
+            [
+                (b"icfb", self.v_icfb),
+                (b"icft", self.v_icft),
+                (b"ideo", self.v_ideo),
+                (b"romn", self.v_romn),
+                (b"idtp", self.v_idtp),
+            ],
+        )?;
 
-        // supported_scripts is expected to be ISO scripts, convert them to OT
-        for ot_script in supported_scripts
-            .iter()
-            .flat_map(|s| iso15924_to_opentype(s))
-        {
+        // supported_scripts are OpenType script tags
+        for &ot_script in supported_scripts {
             let default_baseline = if is_cjk_script(&ot_script.to_string()) {
                 Tag::new(b"ideo")
             } else {
                 Tag::new(b"romn")
             };
-            // Find a horizontal basescript record for this script, or create one
-            let h_basescript =
-                if let Some(bs) = base.horizontal.iter_mut().find(|bs| bs.script == ot_script) {
-                    bs
-                } else {
-                    base.horizontal.push(BaseScript::new(ot_script));
-                    base.horizontal.last_mut().unwrap()
-                };
-            h_basescript.default_baseline = Some(default_baseline);
-            let hbaselines = &mut h_basescript.baselines;
-            if let Some(icfb) = self.h_icfb {
-                hbaselines.insert(Tag::new(b"icfb"), icfb as i16);
-            }
-            if let Some(icft) = self.h_icft {
-                hbaselines.insert(Tag::new(b"icft"), icft as i16);
-            }
-            if let Some(ideo) = self.h_ideo {
-                hbaselines.insert(Tag::new(b"ideo"), ideo as i16);
+            for (scripts, values) in [
+                (&mut base.horizontal, &horizontal),
+                (&mut base.vertical, &vertical),
+            ] {
+                // Find a basescript record for this script, or create one
+                let basescript =
+                    if let Some(bs) = scripts.iter_mut().find(|bs| bs.script == ot_script) {
+                        bs
+                    } else {
+                        scripts.push(BaseScript::new(ot_script));
+                        scripts.last_mut().unwrap()
+                    };
+                basescript.default_baseline = Some(default_baseline);
+                basescript.baselines.extend(values.iter().copied());
             }
-            if let Some(romn) = self.h_romn {
-                hbaselines.insert(Tag::new(b"romn"), romn as i16);
-            }
-            if !font_is_square {
-                if let Some(idtp) = self.h_idtp {
-                    hbaselines.insert(Tag::new(b"idtp"), idtp as i16);
-                }
-            }
-            // Find a vertical basescript record for this script, or create one
-            let v_basescript =
-                if let Some(bs) = base.vertical.iter_mut().find(|bs| bs.script == ot_script) {
-                    bs
-                } else {
-                    base.vertical.push(BaseScript::new(ot_script));
-                    base.vertical.last_mut().unwrap()
-                };
-            v_basescript.default_baseline = Some(default_baseline);
-            let vbaselines = &mut v_basescript.baselines;
-            if let Some(icfb) = self.v_icfb {
-                vbaselines.insert(Tag::new(b"icfb"), icfb as i16);
-            }
-            if let Some(icft) = self.v_icft {
-                vbaselines.insert(Tag::new(b"icft"), icft as i16);
-            }
-            if let Some(ideo) = self.v_ideo {
-                vbaselines.insert(Tag::new(b"ideo"), ideo as i16);
+        }
+        Ok(())
+    }
+}
+
+/// The mean of the values, leaving out `trim_percent`% of them at each end.
+///
+/// Returns the mean and the number of values trimmed from each end.
+fn trimmed_mean(mut values: Vec<f32>, trim_percent: f32) -> (f32, usize) {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let trim = (values.len() as f32 * trim_percent.clamp(0.0, 50.0) / 100.0).floor() as usize;
+    // Always keep at least one value
+    let trim = trim.min(values.len().saturating_sub(1) / 2);
+    let kept = &values[trim..values.len() - trim];
+    (kept.iter().sum::<f32>() / kept.len() as f32, trim)
+}
+
+/// Whether the `idtp` baselines are written.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdtpPolicy {
+    /// Only if the font is not square (or a fixed `idtp` is given)
+    #[default]
+    Auto,
+    /// Always, even if the font is square
+    Always,
+    /// Never, even if the font is not square
+    Never,
+}
+
+impl std::str::FromStr for IdtpPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(IdtpPolicy::Auto),
+            "always" => Ok(IdtpPolicy::Always),
+            "never" => Ok(IdtpPolicy::Never),
+            _ => Err(format!(
+                "unknown idtp policy {} (expected auto, always or never)",
+                s
+            )),
+        }
+    }
+}
+
+/// The default tolerance for [`font_is_square`], as a fraction of the em.
+pub const DEFAULT_SQUARE_TOLERANCE: f32 = 0.01;
+
+/// Whether an average ideographic advance is close enough to the em to call the
+/// em-box square.
+///
+/// The tolerance is a fraction of the em, so that the decision is the same for a
+/// font and its rescaled copy: with the default of 1%, a 2000-unit advance is
+/// square at 2000 UPM but not at 2048 UPM.
+pub fn font_is_square(average_advance: f32, upem: f32, tolerance: f32) -> bool {
+    if upem <= 0.0 {
+        return false;
+    }
+    (average_advance - upem).abs() <= tolerance.max(0.0) * upem
+}
+
+/// A group of CJK scripts whose glyphs share a character face.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CjkGroup {
+    /// Han ideographs (also used for Bopomofo)
+    Han,
+    /// Hiragana and Katakana
+    Kana,
+    /// Hangul syllables
+    Hangul,
+}
+
+impl CjkGroup {
+    pub const ALL: [CjkGroup; 3] = [CjkGroup::Han, CjkGroup::Kana, CjkGroup::Hangul];
+
+    /// Whether the codepoint is one of the characters measured for this group.
+    ///
+    /// Only the core letters of each script are included: in some designs,
+    /// enclosed characters, symbols and the like may be larger than the
+    /// character face, so they are excluded.
+    pub fn contains(self, cp: u32) -> bool {
+        match self {
+            CjkGroup::Han => {
+                (0x4E00..0x9FFF).contains(&cp)
+                    || (0x3400..0x4DBF).contains(&cp) // CJK Unified Ideographs Extension A
+                    || (0x20000..0x2A6DF).contains(&cp) // CJK Unified Ideographs Extension B
             }
-            if let Some(romn) = self.v_romn {
-                vbaselines.insert(Tag::new(b"romn"), romn as i16);
+            CjkGroup::Kana => {
+                (0x3040..=0x30FF).contains(&cp) // Hiragana and Katakana
+                    || (0xFF00..=0xFFEF).contains(&cp) // Full-width and half-width forms
             }
-            if !font_is_square {
-                if let Some(idtp) = self.v_idtp {
-                    vbaselines.insert(Tag::new(b"idtp"), idtp as i16);
+            CjkGroup::Hangul => (0xAC00..=0xD7AF).contains(&cp),
+        }
+    }
+
+    /// The group whose glyphs determine the baselines of an OpenType script.
+    pub fn for_script(script: Tag) -> Option<Self> {
+        match &script.to_be_bytes() {
+            b"hani" | b"bopo" => Some(CjkGroup::Han),
+            b"kana" | b"hira" => Some(CjkGroup::Kana),
+            b"hang" => Some(CjkGroup::Hangul),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for CjkGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CjkGroup::Han => "Han",
+            CjkGroup::Kana => "Kana",
+            CjkGroup::Hangul => "Hangul",
+        })
+    }
+}
+
+/// A codepoint range or glyph left out of the CJK bounds computation.
+///
+/// Written in the config as `"U+3007"`, `"U+2E80-U+2EFF"`, a single character
+/// such as `"〇"`, or otherwise a glyph name.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum CjkExclusion {
+    /// An inclusive range of codepoints
+    Codepoints(u32, u32),
+    /// A glyph, by name
+    Glyph(String),
+}
+
+impl CjkExclusion {
+    fn excludes_codepoint(&self, cp: u32) -> bool {
+        match self {
+            CjkExclusion::Codepoints(first, last) => (*first..=*last).contains(&cp),
+            CjkExclusion::Glyph(_) => false,
+        }
+    }
+}
+
+fn parse_codepoint(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix("U+").or_else(|| s.strip_prefix("u+"))?;
+    u32::from_str_radix(hex, 16).ok()
+}
+
+impl std::str::FromStr for CjkExclusion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(CjkExclusion::Codepoints(c as u32, c as u32));
+        }
+        if s.starts_with("U+") || s.starts_with("u+") {
+            let (first, last) = s.split_once('-').unwrap_or((s, s));
+            return match (parse_codepoint(first), parse_codepoint(last)) {
+                (Some(first), Some(last)) if first <= last => {
+                    Ok(CjkExclusion::Codepoints(first, last))
                 }
+                _ => Err(format!("invalid codepoint range: {}", s)),
+            };
+        }
+        if s.is_empty() {
+            return Err("empty CJK exclusion".to_string());
+        }
+        Ok(CjkExclusion::Glyph(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for CjkExclusion {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The glyphs named in the exclusions; names not in the font are skipped with a warning.
+fn excluded_glyph_ids(f: &skrifa::FontRef, exclude: &[CjkExclusion]) -> HashSet<GlyphId> {
+    let names = exclude
+        .iter()
+        .filter_map(|exclusion| match exclusion {
+            CjkExclusion::Glyph(name) => Some(name.as_str()),
+            CjkExclusion::Codepoints(..) => None,
+        })
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        return HashSet::new();
+    }
+    let glyph_names = GlyphNames::new(f);
+    let mut found = HashSet::new();
+    for name in names {
+        match glyph_names.iter().find(|(_, n)| n.as_str() == name) {
+            Some((gid, _)) => {
+                found.insert(gid);
             }
+            None => log::warn!("Excluded CJK glyph {} is not in the font", name),
         }
     }
+    found
 }
 
-fn cjk_glyphs(f: &skrifa::FontRef) -> Vec<GlyphId> {
-    let mut cjk_glyphs = f
+fn group_glyphs(f: &skrifa::FontRef, groups: &[CjkGroup], options: &CjkOptions) -> Vec<GlyphId> {
+    let excluded_glyphs = excluded_glyph_ids(f, &options.exclude);
+    let glyphs = f
         .charmap()
         .mappings()
-        .filter(|(cp, _gid)| {
-            // We're going to be using this to find the ideographic bounding
-            // box, so we're only interesting in Han/Kanji. In some designs,
-            // kana, enclosed characters, etc. may be taller than the
-            // ideographic bounding box, so we exclude them.
-            (0x4E00..0x9FFF).contains(cp)
-            || (0x3400..0x4DBF).contains(cp) // CJK Unified Ideographs Extension A
-            || (0x20000..0x2A6DF).contains(cp) // CJK Unified Ideographs Extension B
+        .filter(|(cp, _gid)| groups.iter().any(|group| group.contains(*cp)))
+        .filter(|(cp, gid)| {
+            !excluded_glyphs.contains(gid)
+                && !options
+                    .exclude
+                    .iter()
+                    .any(|exclusion| exclusion.excludes_codepoint(*cp))
         })
         .map(|(_cp, gid)| gid)
+        .collect();
+    match (options.advance_tolerance, f.hmtx()) {
+        (Some(tolerance), Ok(hmtx)) => with_modal_advance(glyphs, &hmtx, tolerance),
+        _ => glyphs,
+    }
+}
+
+/// Keep only the glyphs whose advance is within `tolerance` (a fraction) of the
+/// most common advance among them.
+///
+/// Proportional and half-width forms would otherwise pull the averaged em-box
+/// away from that of the full-width design.
+fn with_modal_advance(glyphs: Vec<GlyphId>, hmtx: &Hmtx, tolerance: f32) -> Vec<GlyphId> {
+    let mut counts: HashMap<u16, usize> = HashMap::new();
+    for &gid in glyphs.iter() {
+        if let Some(advance) = hmtx.advance(gid) {
+            *counts.entry(advance).or_default() += 1;
+        }
+    }
+    // Break ties towards the wider advance, which is more likely to be full-width
+    let Some(modal) = counts
+        .into_iter()
+        .max_by_key(|&(advance, count)| (count, advance))
+        .map(|(advance, _)| advance as f32)
+    else {
+        return glyphs;
+    };
+    let before = glyphs.len();
+    let kept = glyphs
+        .into_iter()
+        .filter(|&gid| {
+            hmtx.advance(gid)
+                .is_some_and(|advance| (advance as f32 - modal).abs() <= tolerance * modal)
+        })
         .collect::<Vec<_>>();
-    if cjk_glyphs.is_empty() {
-        // Maybe just a Korean or Kana font?
-        cjk_glyphs = f
-            .charmap()
-            .mappings()
-            .filter(
-                |(cp, _gid)|
-                // Korean Hangul syllables
-                (0xAC00..=0xD7AF).contains(cp)
-                || // Kana characters
-                (0x3040..=0x30FF).contains(cp)
-                || (0xFF00..=0xFFEF).contains(cp), // Full-width Kana
-            )
-            .map(|(_cp, gid)| gid)
-            .collect();
+    if kept.len() < before {
+        log::info!(
+            "Excluded {} CJK glyphs whose advance is not within {}% of the common advance {}",
+            before - kept.len(),
+            tolerance * 100.0,
+            modal
+        );
     }
-    cjk_glyphs
+    kept
 }
 
-pub fn compute_bounds(f: &skrifa::FontRef) -> Result<CjkMetrics, AutobaseError> {
+/// The glyphs from which the CJK baselines are measured, with the options for measuring them.
+fn cjk_glyphs(f: &skrifa::FontRef, options: &CjkOptions) -> (Vec<GlyphId>, CjkOptions) {
+    // We're going to be using this to find the ideographic bounding
+    // box, so we're only interesting in Han/Kanji. In some designs,
+    // kana may be taller than the ideographic bounding box, so we exclude them.
+    let han = group_glyphs(f, &[CjkGroup::Han], options);
+    if !han.is_empty() {
+        return (han, options.clone());
+    }
+    // Maybe just a Korean font? Hangul has its own strategy, and Korean fonts
+    // often include kana, which shouldn't affect it.
+    let hangul = group_glyphs(f, &[CjkGroup::Hangul], options);
+    if !hangul.is_empty() {
+        return (hangul, options.for_group(CjkGroup::Hangul));
+    }
+    // Or a Kana font?
+    (group_glyphs(f, &[CjkGroup::Kana], options), options.clone())
+}
+
+/// A pen which computes the tight bounding box of an outline, taking the
+/// extrema of curve segments rather than their control points.
+#[derive(Default)]
+struct TightBoundsPen {
+    bounds: Option<BoundingBox>,
+    current: (f32, f32),
+}
+
+impl TightBoundsPen {
+    fn add_point(&mut self, x: f32, y: f32) {
+        let bounds = self.bounds.get_or_insert(BoundingBox {
+            x_min: x,
+            y_min: y,
+            x_max: x,
+            y_max: y,
+        });
+        bounds.x_min = bounds.x_min.min(x);
+        bounds.y_min = bounds.y_min.min(y);
+        bounds.x_max = bounds.x_max.max(x);
+        bounds.y_max = bounds.y_max.max(y);
+    }
+}
+
+/// Parameter values in (0, 1) at which a cubic bezier's derivative is zero
+/// along one axis.
+fn cubic_extrema(p0: f32, p1: f32, p2: f32, p3: f32) -> Vec<f32> {
+    let (d0, d1, d2) = (p1 - p0, p2 - p1, p3 - p2);
+    let a = d0 - 2.0 * d1 + d2;
+    let b = 2.0 * (d1 - d0);
+    let c = d0;
+    let roots = if a.abs() < f32::EPSILON {
+        if b.abs() < f32::EPSILON {
+            vec![]
+        } else {
+            vec![-c / b]
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            vec![]
+        } else {
+            let sqrt = discriminant.sqrt();
+            vec![(-b + sqrt) / (2.0 * a), (-b - sqrt) / (2.0 * a)]
+        }
+    };
+    roots.into_iter().filter(|t| *t > 0.0 && *t < 1.0).collect()
+}
+
+fn cubic_at(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let mt = 1.0 - t;
+    mt * mt * mt * p0 + 3.0 * mt * mt * t * p1 + 3.0 * mt * t * t * p2 + t * t * t * p3
+}
+
+impl OutlinePen for TightBoundsPen {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.add_point(x, y);
+        self.current = (x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.add_point(x, y);
+        self.current = (x, y);
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        // Elevate to a cubic so we only need one extremum solver
+        let (x0, y0) = self.current;
+        let (c1x, c1y) = (x0 + 2.0 / 3.0 * (cx0 - x0), y0 + 2.0 / 3.0 * (cy0 - y0));
+        let (c2x, c2y) = (x + 2.0 / 3.0 * (cx0 - x), y + 2.0 / 3.0 * (cy0 - y));
+        self.curve_to(c1x, c1y, c2x, c2y, x, y);
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        let (x0, y0) = self.current;
+        for t in cubic_extrema(x0, cx0, cx1, x)
+            .into_iter()
+            .chain(cubic_extrema(y0, cy0, cy1, y))
+        {
+            self.add_point(cubic_at(x0, cx0, cx1, x, t), cubic_at(y0, cy0, cy1, y, t));
+        }
+        self.add_point(x, y);
+        self.current = (x, y);
+    }
+
+    fn close(&mut self) {}
+}
+
+/// Compute the bounds of a glyph from its outline.
+///
+/// CFF and CFF2 charstrings don't carry a stored bounding box the way `glyf`
+/// glyphs do, so we draw the outline and find the true extrema of its curves.
+pub(crate) fn outline_bounds(
+    outlines: &OutlineGlyphCollection,
+    location: LocationRef,
+    gid: GlyphId,
+) -> Option<BoundingBox> {
+    let outline = outlines.get(gid)?;
+    let mut pen = TightBoundsPen::default();
+    outline
+        .draw(DrawSettings::unhinted(Size::unscaled(), location), &mut pen)
+        .ok()?;
+    pen.bounds
+}
+
+/// How the ideographic character face is determined.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CjkStrategy {
+    /// Average the bounds of every ideograph in the font
+    #[default]
+    Average,
+    /// Average the bounds of a small set of reference ideographs, as several CJK
+    /// foundries document for their own fonts
+    ReferenceGlyphs,
+}
+
+/// The reference ideographs used by [`CjkStrategy::ReferenceGlyphs`] unless others are given.
+pub const DEFAULT_REFERENCE_CHARACTERS: &str = "水東国";
+
+/// The reference syllables used to measure Hangul by [`CjkStrategy::ReferenceGlyphs`]
+/// unless others are given.
+///
+/// Hangul character faces are conventionally wider than Han ones, and are set by
+/// syllables which fill the em-box, including final consonants.
+pub const DEFAULT_HANGUL_REFERENCE_CHARACTERS: &str = "한글흙";
+
+/// Options controlling how the CJK baselines are computed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CjkOptions {
+    pub strategy: CjkStrategy,
+    /// The ideographs whose bounds give the character face under [`CjkStrategy::ReferenceGlyphs`]
+    pub reference_characters: Vec<char>,
+    /// Measure Han, Kana and Hangul separately, each for its own script records
+    pub per_script: bool,
+    /// Codepoints and glyphs left out of the measurement
+    pub exclude: Vec<CjkExclusion>,
+    /// The number of glyphs sampled from the pool, or `None` to measure every glyph
+    pub sample_size: Option<usize>,
+    /// The seed for choosing the sample, so that runs are reproducible
+    pub sample_seed: u64,
+    /// The percentage of glyphs at each end of each character face edge left out of its average
+    pub trim_percent: f32,
+    /// Glyphs whose advance is further than this fraction from the most common
+    /// advance are left out, or `None` to keep every glyph
+    pub advance_tolerance: Option<f32>,
+    /// A fixed bottom edge for the ideographic em-box, in place of the measured one
+    pub descender: Option<i16>,
+    /// The strategy for the character face of Hangul, when it is measured on its own
+    pub hangul_strategy: CjkStrategy,
+    /// The syllables whose bounds give the Hangul character face under [`CjkStrategy::ReferenceGlyphs`]
+    pub hangul_reference_characters: Vec<char>,
+}
+
+impl CjkOptions {
+    /// The options for measuring one group's glyphs on their own.
+    ///
+    /// Hangul has its own strategy and reference characters; for the other groups,
+    /// only the reference characters belonging to the group are kept.
+    fn for_group(&self, group: CjkGroup) -> CjkOptions {
+        let mut options = self.clone();
+        if group == CjkGroup::Hangul {
+            options.strategy = self.hangul_strategy;
+            options.reference_characters = self.hangul_reference_characters.clone();
+        }
+        options
+            .reference_characters
+            .retain(|&c| group.contains(c as u32));
+        options
+    }
+}
+
+impl Default for CjkOptions {
+    fn default() -> Self {
+        CjkOptions {
+            strategy: CjkStrategy::default(),
+            reference_characters: DEFAULT_REFERENCE_CHARACTERS.chars().collect(),
+            per_script: false,
+            exclude: vec![],
+            sample_size: None,
+            sample_seed: 0,
+            trim_percent: 0.0,
+            advance_tolerance: None,
+            descender: None,
+            hangul_strategy: CjkStrategy::default(),
+            hangul_reference_characters: DEFAULT_HANGUL_REFERENCE_CHARACTERS.chars().collect(),
+        }
+    }
+}
+
+/// Measures the bounds of glyphs at the font's default location.
+struct BoundsMeasurer<'a> {
+    glyph_metrics: skrifa::metrics::GlyphMetrics<'a>,
+    outlines: OutlineGlyphCollection<'a>,
+    is_cff: bool,
+}
+
+impl<'a> BoundsMeasurer<'a> {
+    fn new(f: &skrifa::FontRef<'a>) -> Self {
+        let outlines = f.outline_glyphs();
+        let is_cff = matches!(
+            outlines.format(),
+            Some(OutlineGlyphFormat::Cff | OutlineGlyphFormat::Cff2)
+        );
+        BoundsMeasurer {
+            glyph_metrics: f.glyph_metrics(Size::unscaled(), LocationRef::default()),
+            outlines,
+            is_cff,
+        }
+    }
+
+    /// The bounds of the non-empty glyphs among those given.
+    fn bounds(&self, glyphs: &[GlyphId]) -> Vec<BoundingBox> {
+        glyphs
+            .iter()
+            .filter_map(|&gid| {
+                if self.is_cff {
+                    outline_bounds(&self.outlines, LocationRef::default(), gid)
+                } else {
+                    self.glyph_metrics.bounds(gid)
+                }
+            })
+            // Empty glyphs have no character face; don't let them drag the averages towards zero
+            .filter(|b| b.x_max > b.x_min && b.y_max > b.y_min)
+            .collect()
+    }
+}
+
+/// Compute the CJK baselines from the font's ideographs (or, failing those, its
+/// Hangul and Kana).
+///
+/// Fails with `AutobaseError::NoCjkGlyphs` if the font has none of them, or
+/// they are all empty.
+pub fn compute_bounds(
+    f: &skrifa::FontRef,
+    options: &CjkOptions,
+) -> Result<CjkMetrics, AutobaseError> {
+    let (glyphs, options) = cjk_glyphs(f, options);
+    if glyphs.is_empty() {
+        return Err(AutobaseError::NoCjkGlyphs);
+    }
+    metrics_for_glyphs(f, &glyphs, &options)
+}
+
+/// Compute the CJK baselines from the glyphs of one group of scripts alone.
+///
+/// Returns `None` if the font has no glyphs in the group, or they are all empty.
+pub fn compute_group_bounds(
+    f: &skrifa::FontRef,
+    group: CjkGroup,
+    options: &CjkOptions,
+) -> Result<Option<CjkMetrics>, AutobaseError> {
+    let glyphs = group_glyphs(f, &[group], options);
+    if glyphs.is_empty() {
+        return Ok(None);
+    }
+    match metrics_for_glyphs(f, &glyphs, &options.for_group(group)) {
+        Err(AutobaseError::NoCjkGlyphs) => Ok(None),
+        result => result.map(Some),
+    }
+}
+
+fn metrics_for_glyphs(
+    f: &skrifa::FontRef,
+    relevant_glyphs: &[GlyphId],
+    options: &CjkOptions,
+) -> Result<CjkMetrics, AutobaseError> {
     let upem = f.head()?.units_per_em() as f32;
-    let glyph_metrics = f.glyph_metrics(Size::unscaled(), LocationRef::default());
     let hmtx = f.hmtx()?;
-    let relevant_glyphs = cjk_glyphs(f);
+    let measurer = BoundsMeasurer::new(f);
+    let pool_size = relevant_glyphs.len();
+    let sampled = sample_pool(relevant_glyphs, options);
+    let relevant_glyphs = sampled.as_deref().unwrap_or(relevant_glyphs);
+    let bounds = measurer.bounds(relevant_glyphs);
+    // Every glyph was empty, so there is no character face to average
+    if bounds.is_empty() {
+        return Err(AutobaseError::NoCjkGlyphs);
+    }
+    if relevant_glyphs.len() < pool_size {
+        log_sampling_error(&bounds, pool_size);
+    }
     let average_width = relevant_glyphs
         .iter()
         .map(|&gid| hmtx.advance(gid).map(|x| x as f32).unwrap_or(upem)) // Promote to f32 to avoid overflow
         .sum::<f32>()
         / relevant_glyphs.len() as f32;
-    Ok(CjkMetrics::from_bounds(
-        &relevant_glyphs
+    let mut metrics = CjkMetrics::from_bounds(&bounds, upem, average_width, options.trim_percent);
+    if let Some(descender) = options.descender {
+        metrics = metrics.with_descender(descender as f32, upem);
+    }
+    if options.strategy == CjkStrategy::ReferenceGlyphs {
+        let charmap = f.charmap();
+        let reference_glyphs = options
+            .reference_characters
             .iter()
-            .filter_map(|&gid| glyph_metrics.bounds(gid))
-            .collect::<Vec<_>>(),
-        upem,
-        average_width,
-    ))
+            .filter_map(|&c| {
+                let gid = charmap.map(c);
+                if gid.is_none() {
+                    log::warn!("Reference character {} is not in the font", c);
+                }
+                gid
+            })
+            .collect::<Vec<_>>();
+        let reference_bounds = measurer.bounds(&reference_glyphs);
+        if reference_bounds.is_empty() {
+            log::warn!(
+                "None of the reference characters could be measured; averaging every glyph instead"
+            );
+        } else {
+            // The em-box still comes from every ideograph; only the character face changes
+            let reference = CjkMetrics::from_bounds(
+                &reference_bounds,
+                upem,
+                average_width,
+                options.trim_percent,
+            );
+            metrics.h_icfb = reference.h_icfb;
+            metrics.h_icft = reference.h_icft;
+            metrics.v_icfb = reference.v_icfb;
+            metrics.v_icft = reference.v_icft;
+        }
+    }
+    Ok(metrics)
+}
+
+/// The sample of the pool to measure, or `None` if every glyph is measured.
+fn sample_pool(glyphs: &[GlyphId], options: &CjkOptions) -> Option<Vec<GlyphId>> {
+    match options.sample_size {
+        Some(size) if size > 0 && size < glyphs.len() => {
+            Some(sample_glyphs(glyphs, size, options.sample_seed))
+        }
+        _ => None,
+    }
+}
+
+/// A deterministic pseudo-random sample of `size` glyphs, in their original order.
+///
+/// The same pool, size and seed always give the same sample, so results are
+/// reproducible between runs and machines.
+fn sample_glyphs(glyphs: &[GlyphId], size: usize, seed: u64) -> Vec<GlyphId> {
+    // splitmix64: tiny, well-distributed, and needs no extra dependency
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    // A partial Fisher-Yates shuffle picks the first `size` indices
+    let mut indices = (0..glyphs.len()).collect::<Vec<_>>();
+    for i in 0..size {
+        let j = i + (next() % (glyphs.len() - i) as u64) as usize;
+        indices.swap(i, j);
+    }
+    let mut chosen = indices[..size].to_vec();
+    chosen.sort_unstable();
+    chosen.into_iter().map(|i| glyphs[i]).collect()
+}
+
+/// Log how far the averages of a sample of glyph bounds may be from those of the whole pool.
+fn log_sampling_error(sample: &[BoundingBox], pool_size: usize) {
+    let Some(bound) = sampling_error_bound(sample, pool_size) else {
+        return;
+    };
+    log::info!(
+        "Sampled {} of {} CJK glyphs; the character face averages are within ±{:.1} units of exact at 95% confidence",
+        sample.len(),
+        pool_size,
+        bound
+    );
+}
+
+/// How far, at 95% confidence, the average of each edge of a sample of glyph
+/// bounds may be from that of the whole pool, estimated from the sample itself.
+fn sampling_error_bound(sample: &[BoundingBox], pool_size: usize) -> Option<f64> {
+    let n = sample.len();
+    if n < 2 || pool_size < n {
+        return None;
+    }
+    let standard_deviation = |value: fn(&BoundingBox) -> f32| {
+        let values = sample.iter().map(|b| value(b) as f64).collect::<Vec<_>>();
+        mean_and_standard_deviation(&values).map_or(0.0, |(_, sd)| sd)
+    };
+    let largest_deviation = [
+        standard_deviation(|b| b.y_min),
+        standard_deviation(|b| b.y_max),
+        standard_deviation(|b| b.x_min),
+        standard_deviation(|b| b.x_max),
+    ]
+    .into_iter()
+    .fold(0.0, f64::max);
+    // 95% confidence interval of a mean, with the finite population correction
+    let correction = ((pool_size - n) as f64 / (pool_size - 1) as f64).sqrt();
+    Some(1.96 * largest_deviation / (n as f64).sqrt() * correction)
+}
+
+/// Percentiles reported for each edge of the CJK glyph bounds.
+const REPORT_PERCENTILES: [f32; 7] = [0.0, 5.0, 25.0, 50.0, 75.0, 95.0, 100.0];
+/// The number of bins in each histogram of the CJK glyph bounds.
+const REPORT_BINS: usize = 10;
+
+/// The distribution of one edge of the CJK glyph bounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeDistribution {
+    /// Pairs of percentile and value
+    pub percentiles: Vec<(f32, f32)>,
+    /// The lower bound of each bin and the number of glyphs in it; the bins are of equal width
+    pub histogram: Vec<(f32, usize)>,
+    /// The width of each bin
+    pub bin_width: f32,
+}
+
+impl EdgeDistribution {
+    fn new(mut values: Vec<f32>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+        let percentiles = REPORT_PERCENTILES
+            .iter()
+            .map(|&p| {
+                let rank = (p / 100.0 * (values.len() - 1) as f32).round() as usize;
+                (p, values[rank])
+            })
+            .collect();
+        let (min, max) = (values[0], values[values.len() - 1]);
+        let bin_width = ((max - min) / REPORT_BINS as f32).max(1.0);
+        let mut counts = vec![0; REPORT_BINS];
+        for value in values.iter() {
+            let bin = (((value - min) / bin_width) as usize).min(REPORT_BINS - 1);
+            counts[bin] += 1;
+        }
+        let histogram = counts
+            .into_iter()
+            .enumerate()
+            .map(|(bin, count)| (min + bin as f32 * bin_width, count))
+            .collect();
+        Some(EdgeDistribution {
+            percentiles,
+            histogram,
+            bin_width,
+        })
+    }
+}
+
+impl std::fmt::Display for EdgeDistribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percentiles = self
+            .percentiles
+            .iter()
+            .map(|(p, value)| format!("p{} {:.0}", p, value))
+            .collect::<Vec<_>>();
+        writeln!(f, "    {}", percentiles.join(" / "))?;
+        let largest = self.histogram.iter().map(|(_, c)| *c).max().unwrap_or(0);
+        for (start, count) in self.histogram.iter() {
+            // Bars are scaled so the fullest bin is 40 characters wide
+            let bar = (*count * 40).checked_div(largest).unwrap_or(0);
+            writeln!(
+                f,
+                "    {:>6.0} .. {:>6.0} | {:<40} {}",
+                start,
+                start + self.bin_width,
+                "#".repeat(bar),
+                count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The distribution of the bounds of the CJK glyphs, alongside the baselines chosen from them.
+///
+/// This lets designers check whether the averaged baselines represent the design.
+#[derive(Debug, Clone)]
+pub struct CjkReport {
+    /// The number of glyphs in the pool
+    pub pool_size: usize,
+    /// The number of non-empty glyphs measured (after sampling)
+    pub measured: usize,
+    pub tops: Option<EdgeDistribution>,
+    pub bottoms: Option<EdgeDistribution>,
+    pub lefts: Option<EdgeDistribution>,
+    pub rights: Option<EdgeDistribution>,
+    /// The baselines chosen
+    pub metrics: CjkMetrics,
+}
+
+impl CjkReport {
+    /// Measure the font's CJK glyphs as [`compute_bounds`] does, and report them
+    /// alongside the given metrics.
+    pub fn new(
+        f: &skrifa::FontRef,
+        options: &CjkOptions,
+        metrics: CjkMetrics,
+    ) -> Result<Self, AutobaseError> {
+        let (pool, options) = cjk_glyphs(f, options);
+        let sampled = sample_pool(&pool, &options);
+        let bounds = BoundsMeasurer::new(f).bounds(sampled.as_deref().unwrap_or(&pool));
+        let edge = |value: fn(&BoundingBox) -> f32| {
+            EdgeDistribution::new(bounds.iter().map(value).collect())
+        };
+        Ok(CjkReport {
+            pool_size: pool.len(),
+            measured: bounds.len(),
+            tops: edge(|b| b.y_max),
+            bottoms: edge(|b| b.y_min),
+            lefts: edge(|b| b.x_min),
+            rights: edge(|b| b.x_max),
+            metrics,
+        })
+    }
+}
+
+impl std::fmt::Display for CjkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Measured {} of {} CJK glyphs",
+            self.measured, self.pool_size
+        )?;
+        let value = |v: Option<f32>| v.map_or("-".to_string(), |v| format!("{:.0}", v));
+        let m = &self.metrics;
+        for (name, distribution, chosen) in [
+            (
+                "Tops (horizontal icft)",
+                &self.tops,
+                format!("icft {}, idtp {}", value(m.h_icft), value(m.h_idtp)),
+            ),
+            (
+                "Bottoms (horizontal icfb)",
+                &self.bottoms,
+                format!("icfb {}, ideo {}", value(m.h_icfb), value(m.h_ideo)),
+            ),
+            (
+                "Lefts (vertical icfb)",
+                &self.lefts,
+                format!("icfb {}, ideo {}", value(m.v_icfb), value(m.v_ideo)),
+            ),
+            (
+                "Rights (vertical icft)",
+                &self.rights,
+                format!("icft {}, idtp {}", value(m.v_icft), value(m.v_idtp)),
+            ),
+        ] {
+            writeln!(f, "{}: chosen {}", name, chosen)?;
+            if let Some(distribution) = distribution {
+                write!(f, "{}", distribution)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use skrifa::{instance::Location, raw::types::F2Dot14, FontRef};
+    use write_fonts::{
+        tables::{
+            cmap::Cmap,
+            fvar::{AxisInstanceArrays, Fvar, VariationAxisRecord},
+            head::{Flags, Head, MacStyle},
+            hhea::Hhea,
+            hmtx::{Hmtx, LongMetric},
+            maxp::Maxp,
+            vhea::Vhea,
+            vmtx::Vmtx,
+        },
+        types::{Fixed, LongDateTime, NameId},
+        FontBuilder,
+    };
+
+    use super::*;
+    use crate::config::Config;
+
+    // Charstring operators
+    const RLINETO: u8 = 5;
+    const RRCURVETO: u8 = 8;
+    const ENDCHAR: u8 = 14;
+    const BLEND: u8 = 16;
+    const RMOVETO: u8 = 21;
+
+    fn charstring(ops: &[&[i16]]) -> Vec<u8> {
+        let mut data = vec![];
+        for op in ops {
+            let (operator, operands) = op.split_last().unwrap();
+            for operand in operands {
+                data.push(28);
+                data.extend(operand.to_be_bytes());
+            }
+            data.push(*operator as u8);
+        }
+        data
+    }
+
+    fn dict_int(value: usize) -> Vec<u8> {
+        let mut data = vec![29];
+        data.extend((value as i32).to_be_bytes());
+        data
+    }
+
+    fn index(items: &[&[u8]], cff2: bool) -> Vec<u8> {
+        let mut data = if cff2 {
+            (items.len() as u32).to_be_bytes().to_vec()
+        } else {
+            (items.len() as u16).to_be_bytes().to_vec()
+        };
+        if items.is_empty() {
+            return data;
+        }
+        data.push(1);
+        let mut offset = 1;
+        data.push(offset);
+        for item in items {
+            offset += item.len() as u8;
+            data.push(offset);
+        }
+        for item in items {
+            data.extend(*item);
+        }
+        data
+    }
+
+    /// The outline of 水 in [`ideograph_font`]: a box from (100, -50) to
+    /// (900, 700), topped by a curve whose control points are at 900 units but
+    /// which itself only reaches 850. In CFF2, the control points rise by another
+    /// 200 units at the maximum of the weight axis, and the curve reaches 1000.
+    fn ideograph(cff2: bool) -> Vec<u8> {
+        let mut ops: Vec<&[i16]> = vec![
+            &[100, -50, RMOVETO as i16],
+            &[800, 0, 0, 750, RLINETO as i16],
+        ];
+        if cff2 {
+            ops.extend([
+                &[0, 200, 200, 1, BLEND as i16][..],
+                &[-800, 0, 0, -200, -200, 1, BLEND as i16],
+                &[RRCURVETO as i16],
+                &[0, -750, RLINETO as i16],
+            ]);
+        } else {
+            ops.extend([
+                &[0, 200, -800, 0, 0, -200, RRCURVETO as i16][..],
+                &[0, -750, RLINETO as i16],
+                &[ENDCHAR as i16],
+            ]);
+        }
+        charstring(&ops)
+    }
+
+    fn cff_table() -> Vec<u8> {
+        cff_table_with(&ideograph(false))
+    }
+
+    /// A CFF table whose glyph 1 has the given charstring.
+    fn cff_table_with(glyph: &[u8]) -> Vec<u8> {
+        let charstrings = index(&[&[ENDCHAR], glyph], false);
+        let name = index(&[b"A"], false);
+        let strings = index(&[], false);
+        let global_subrs = index(&[], false);
+        // The top DICT is a fixed size, as its numbers are all five-byte integers
+        let top_dict_index_len = 2 + 1 + 2 + 17;
+        let charstrings_offset =
+            4 + name.len() + top_dict_index_len + strings.len() + global_subrs.len();
+        let mut top_dict = dict_int(charstrings_offset);
+        top_dict.push(17);
+        // An empty Private DICT
+        top_dict.extend(dict_int(0));
+        top_dict.extend(dict_int(0));
+        top_dict.push(18);
+        let mut table = vec![1, 0, 4, 1];
+        table.extend(name);
+        table.extend(index(&[&top_dict], false));
+        table.extend(strings);
+        table.extend(global_subrs);
+        assert_eq!(table.len(), charstrings_offset);
+        table.extend(charstrings);
+        table
+    }
+
+    fn cff2_table() -> Vec<u8> {
+        // An item variation store with one region, peaking at the axis maximum
+        let mut store = vec![0, 1, 0, 0, 0, 12, 0, 1, 0, 0, 0, 22];
+        store.extend([0, 1, 0, 1, 0, 0, 0x40, 0, 0x40, 0]);
+        store.extend([0, 0, 0, 0, 0, 1, 0, 0]);
+        let global_subrs = index(&[], true);
+        let store_offset = 5 + 12 + global_subrs.len();
+        let charstrings_offset = store_offset + 2 + store.len();
+        let mut top_dict = dict_int(charstrings_offset);
+        top_dict.push(17);
+        top_dict.extend(dict_int(store_offset));
+        top_dict.push(24);
+        let mut table = vec![2, 0, 5];
+        table.extend((top_dict.len() as u16).to_be_bytes());
+        table.extend(top_dict);
+        table.extend(global_subrs);
+        table.extend((store.len() as u16).to_be_bytes());
+        table.extend(store);
+        table.extend(index(&[&[], &ideograph(true)], true));
+        table
+    }
+
+    /// A Source Han-style CFF (or, with `cff2`, variable CFF2) font with one
+    /// ideograph, 水, on a 1000-unit em.
+    pub(crate) fn ideograph_font(cff2: bool) -> Vec<u8> {
+        ideograph_font_with_em(cff2, 1000, 1000)
+    }
+
+    /// [`ideograph_font`] with another em and advance for the ideograph.
+    pub(crate) fn ideograph_font_with_em(cff2: bool, upem: u16, advance: u16) -> Vec<u8> {
+        let mut builder = FontBuilder::new();
+        builder
+            .add_table(&Head::new(
+                Fixed::ONE,
+                0,
+                Flags::empty(),
+                upem,
+                LongDateTime::new(0),
+                LongDateTime::new(0),
+                100,
+                -50,
+                900,
+                1000,
+                MacStyle::empty(),
+                0,
+                0,
+            ))
+            .unwrap();
+        builder
+            .add_table(&Hhea {
+                ascender: 880.into(),
+                descender: (-120).into(),
+                number_of_h_metrics: 2,
+                ..Default::default()
+            })
+            .unwrap();
+        builder
+            .add_table(&Hmtx::new(
+                vec![LongMetric::new(1000, 0), LongMetric::new(advance, 100)],
+                vec![],
+            ))
+            .unwrap();
+        builder.add_table(&Maxp::new(2)).unwrap();
+        builder
+            .add_table(&Cmap::from_mappings([('水', GlyphId::new(1))]).unwrap())
+            .unwrap();
+        if cff2 {
+            builder
+                .add_table(&Fvar::new(AxisInstanceArrays::new(
+                    vec![VariationAxisRecord::new(
+                        Tag::new(b"wght"),
+                        Fixed::from_f64(200.0),
+                        Fixed::from_f64(200.0),
+                        Fixed::from_f64(900.0),
+                        0,
+                        NameId::new(256),
+                    )],
+                    vec![],
+                )))
+                .unwrap();
+            builder.add_raw(Tag::new(b"CFF2"), cff2_table());
+        } else {
+            builder.add_raw(Tag::new(b"CFF "), cff_table());
+        }
+        builder.build()
+    }
+
+    fn assert_bounds(bounds: BoundingBox, expected: [f32; 4]) {
+        let actual = [bounds.x_min, bounds.y_min, bounds.x_max, bounds.y_max];
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 0.01,
+                "bounds {:?}, expected {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn empty_ideographs_are_no_cjk_glyphs() {
+        let data = ideograph_font(false);
+        let mut builder = FontBuilder::new();
+        builder.add_raw(Tag::new(b"CFF "), cff_table_with(&[ENDCHAR]));
+        builder.copy_missing_tables(FontRef::new(&data).unwrap());
+        let data = builder.build();
+        let font = FontRef::new(&data).unwrap();
+        assert!(matches!(
+            compute_bounds(&font, &CjkOptions::default()),
+            Err(AutobaseError::NoCjkGlyphs)
+        ));
+    }
+
+    #[test]
+    fn cff_bounds_are_tight() {
+        for cff2 in [false, true] {
+            let data = ideograph_font(cff2);
+            let font = FontRef::new(&data).unwrap();
+            let outlines = font.outline_glyphs();
+            let bounds = outline_bounds(&outlines, LocationRef::default(), GlyphId::new(1));
+            // Not 900, the height of the control points
+            assert_bounds(bounds.unwrap(), [100.0, -50.0, 900.0, 850.0]);
+        }
+    }
+
+    #[test]
+    fn cff2_bounds_follow_the_location() {
+        let data = ideograph_font(true);
+        let font = FontRef::new(&data).unwrap();
+        let outlines = font.outline_glyphs();
+        let coords = [F2Dot14::from_f32(1.0)];
+        let bounds = outline_bounds(&outlines, LocationRef::new(&coords), GlyphId::new(1));
+        assert_bounds(bounds.unwrap(), [100.0, -50.0, 900.0, 1000.0]);
+        let location: Location = font.axes().location([("wght", 550.0)]);
+        let bounds = outline_bounds(&outlines, (&location).into(), GlyphId::new(1));
+        assert_bounds(bounds.unwrap(), [100.0, -50.0, 900.0, 925.0]);
+    }
+
+    #[test]
+    fn cff_character_face_is_the_outline_bounds() {
+        for cff2 in [false, true] {
+            let data = ideograph_font(cff2);
+            let font = FontRef::new(&data).unwrap();
+            let metrics = compute_bounds(&font, &CjkOptions::default()).unwrap();
+            assert_eq!(metrics.h_icfb, Some(-50.0));
+            assert_eq!(metrics.h_icft, Some(850.0));
+            assert_eq!(metrics.v_icfb, Some(100.0));
+            assert_eq!(metrics.v_icft, Some(900.0));
+            // The em-box is centred on the character face
+            assert_eq!(metrics.h_ideo, Some(-100.0));
+            assert_eq!(metrics.h_idtp, Some(900.0));
+        }
+    }
+
+    /// Whether the `idtp` baselines are written for the font's ideographs.
+    fn writes_idtp(upem: u16, advance: u16, tolerance: f32) -> bool {
+        let data = ideograph_font_with_em(false, upem, advance);
+        let font = FontRef::new(&data).unwrap();
+        let metrics = compute_bounds(&font, &CjkOptions::default()).unwrap();
+        let mut base = BaseTable::default();
+        metrics
+            .insert_into_base(upem as f32, tolerance, &[Tag::new(b"hani")], &mut base)
+            .unwrap();
+        let horizontal = base.horizontal[0].baseline(Tag::new(b"idtp")).is_some();
+        let vertical = base.vertical[0].baseline(Tag::new(b"idtp")).is_some();
+        assert_eq!(horizontal, vertical);
+        horizontal
+    }
+
+    #[test]
+    fn squareness_is_relative_to_the_em() {
+        for (upem, advance, square) in [
+            (1000, 1000, true),
+            (1000, 1010, true),
+            (1000, 1020, false),
+            (1024, 1024, true),
+            (1024, 1034, true),
+            (1024, 1000, false),
+            (2048, 2048, true),
+            (2048, 2028, true),
+            // A 2000-unit advance is square at 2000 UPM, but not at 2048
+            (2048, 2000, false),
+        ] {
+            assert_eq!(
+                writes_idtp(upem, advance, DEFAULT_SQUARE_TOLERANCE),
+                !square,
+                "{} units on a {}-unit em",
+                advance,
+                upem
+            );
+        }
+    }
+
+    #[test]
+    fn squareness_tolerance_is_configurable() {
+        assert!(!writes_idtp(2048, 2000, 0.025));
+        assert!(writes_idtp(1000, 1010, 0.0));
+        assert!(!writes_idtp(1000, 1000, 0.0));
+        // A negative tolerance is treated as none
+        assert!(!font_is_square(1010.0, 1000.0, -0.5));
+        assert!(font_is_square(1000.0, 1000.0, -0.5));
+    }
+
+    #[test]
+    fn vertical_baselines_lie_across_the_line() {
+        let data = ideograph_font(false);
+        let mut builder = FontBuilder::new();
+        builder
+            .add_table(&Vhea {
+                number_of_long_ver_metrics: 2,
+                ..Default::default()
+            })
+            .unwrap();
+        builder
+            .add_table(&Vmtx::new(
+                vec![LongMetric::new(1000, 0), LongMetric::new(1200, 30)],
+                vec![],
+            ))
+            .unwrap();
+        builder.copy_missing_tables(FontRef::new(&data).unwrap());
+        let data = builder.build();
+        let font = FontRef::new(&data).unwrap();
+        let metrics = compute_bounds(&font, &CjkOptions::default()).unwrap();
+        // From the horizontal advance and the left and right edges, not from the
+        // vertical advance and top side bearing
+        assert_eq!(metrics.v_ideo, Some(0.0));
+        assert_eq!(metrics.v_idtp, Some(1000.0));
+        assert_eq!(metrics.v_icfb, Some(100.0));
+        assert_eq!(metrics.v_icft, Some(900.0));
+    }
+
+    #[test]
+    fn every_glyph_is_measured_by_default() {
+        let options = Config::default().cjk_options();
+        assert_eq!(options.sample_size, None);
+        assert_eq!(options.advance_tolerance, None);
+        let pool = (0..5000).map(GlyphId::new).collect::<Vec<_>>();
+        assert_eq!(sample_pool(&pool, &options), None);
+    }
+
+    #[test]
+    fn sampled_averages_are_within_the_logged_bound() {
+        // A pool the size of a Source Han font's, whose edges vary by a few
+        // percent of the em, as ideographs' do
+        let mut state = 1u64;
+        let mut noise = move |spread: f32| {
+            let mut total = 0.0;
+            for _ in 0..3 {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                total += (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5;
+            }
+            total * spread
+        };
+        let pool = (0..25000)
+            .map(|_| BoundingBox {
+                x_min: 60.0 + noise(60.0),
+                y_min: -60.0 + noise(60.0),
+                x_max: 940.0 + noise(60.0),
+                y_max: 820.0 + noise(80.0),
+            })
+            .collect::<Vec<_>>();
+        let mean = |bounds: &[BoundingBox], edge: fn(&BoundingBox) -> f32| {
+            bounds.iter().map(|b| edge(b) as f64).sum::<f64>() / bounds.len() as f64
+        };
+        let edges: [fn(&BoundingBox) -> f32; 4] =
+            [|b| b.x_min, |b| b.y_min, |b| b.x_max, |b| b.y_max];
+        let glyphs = (0..pool.len() as u32).map(GlyphId::new).collect::<Vec<_>>();
+        let mut within = 0;
+        for seed in 0..40 {
+            let sample = sample_glyphs(&glyphs, 2000, seed)
+                .into_iter()
+                .map(|gid| pool[gid.to_u32() as usize])
+                .collect::<Vec<_>>();
+            let bound = sampling_error_bound(&sample, pool.len()).unwrap();
+            assert!(bound < 2.0, "bound {}", bound);
+            let error = edges
+                .iter()
+                .map(|edge| (mean(&sample, *edge) - mean(&pool, *edge)).abs())
+                .fold(0.0, f64::max);
+            assert!(
+                error < 2.0 * bound,
+                "error {} beyond twice the bound {}",
+                error,
+                bound
+            );
+            if error <= bound {
+                within += 1;
+            }
+        }
+        // The bound is for the most variable edge, at 95% confidence
+        assert!(
+            within >= 36,
+            "only {} of 40 samples within the bound",
+            within
+        );
+    }
 }
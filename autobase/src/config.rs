@@ -1,8 +1,19 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf, str::FromStr};
 
+use anyhow::Context;
 use serde::Deserialize;
+use skrifa::Tag;
 
-use crate::utils::KNOWN_ISO_SCRIPTS;
+use crate::{
+    base::Tolerance,
+    cjk::{CjkEmBox, CjkExclusion, CjkOptions, CjkStrategy, IdtpPolicy},
+    font_metrics::MetricSource,
+    meta::MetaPolicy,
+    opsz::OpszBand,
+    profile::Profile,
+    utils::{iso15924_to_opentype, iso639_to_opentype, KNOWN_ISO_SCRIPTS},
+    wordlist::WordListSource,
+};
 
 #[derive(Debug, Default, Deserialize, Clone)]
 pub struct Override {
@@ -12,60 +23,1324 @@ pub struct Override {
     pub max: Option<i16>,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+/// A script, and optionally a language written in it.
+///
+/// The OpenType language system is part of its identity, so that `zh-Hans` and
+/// `zh-Hant` are kept apart although both are Chinese in Han.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ScriptLanguage {
     pub script: String,
     pub language: Option<String>,
+    /// The OpenType language system implied by a BCP 47 tag, where it differs from
+    /// that of the bare language code (e.g. `ZHT ` for `zh-Hant`)
+    pub opentype_language: Option<Tag>,
+}
+
+impl ScriptLanguage {
+    /// Create a script-language combination, checking that the script is a known
+    /// ISO 15924 code and that the language code is plausible.
+    pub fn new(script: &str, language: Option<&str>) -> Result<Self, String> {
+        if !KNOWN_ISO_SCRIPTS.contains(&script) {
+            return Err(format!("unknown ISO 15924 script code: {}", script));
+        }
+        if let Some(lang) = language {
+            if lang.len() != 2 && lang.len() != 3 {
+                return Err(format!("language code must be 2 or 3 letters: {}", lang));
+            }
+        }
+        Ok(ScriptLanguage {
+            script: script.to_string(),
+            language: language.map(|l| l.to_string()),
+            opentype_language: None,
+        })
+    }
+
+    /// Parse a BCP 47 language tag, such as `sr-Latn`, `zh-Hant-HK` or `pt-BR`.
+    ///
+    /// The script subtags `Hans` and `Hant` are measured as Han, but select the
+    /// Simplified or Traditional Chinese language systems. A tag without a script
+    /// subtag is in the language's default script (see `default_script`).
+    pub fn from_bcp47(tag: &str) -> Result<Self, String> {
+        let mut subtags = tag.split('-');
+        let language = subtags
+            .next()
+            .filter(|l| !l.is_empty())
+            .ok_or_else(|| format!("missing language in BCP 47 tag: {}", tag))?
+            .to_ascii_lowercase();
+        let mut script = None;
+        let mut region = None;
+        for subtag in subtags {
+            // A singleton such as `x` or `u` starts private use or an extension
+            if subtag.len() == 1 {
+                break;
+            }
+            let alphabetic = subtag.bytes().all(|b| b.is_ascii_alphabetic());
+            if subtag.len() == 4 && alphabetic && script.is_none() && region.is_none() {
+                let mut titlecase = subtag.to_ascii_lowercase();
+                titlecase[..1].make_ascii_uppercase();
+                script = Some(titlecase);
+            } else if region.is_none()
+                && ((subtag.len() == 2 && alphabetic)
+                    || (subtag.len() == 3 && subtag.bytes().all(|b| b.is_ascii_digit())))
+            {
+                region = Some(subtag.to_ascii_uppercase());
+            }
+        }
+        let script = match script {
+            Some(script) => script,
+            None => default_script(&language)
+                .ok_or_else(|| {
+                    format!(
+                        "no default script is known for {}, give one as a script subtag: {}",
+                        language, tag
+                    )
+                })?
+                .to_string(),
+        };
+        let opentype_language = match (language.as_str(), script.as_str(), region.as_deref()) {
+            ("zh", "Hant", Some("HK" | "MO")) => Some(Tag::new(b"ZHH ")),
+            ("zh", "Hant", _) => Some(Tag::new(b"ZHT ")),
+            ("zh", "Hans", _) => Some(Tag::new(b"ZHS ")),
+            _ => None,
+        };
+        let iso_script = match script.as_str() {
+            "Hans" | "Hant" => "Hani",
+            other => other,
+        };
+        let mut script_language = ScriptLanguage::new(iso_script, Some(&language))?;
+        script_language.opentype_language = opentype_language;
+        Ok(script_language)
+    }
+}
+
+/// The script a language is written in when a BCP 47 tag doesn't say: that of
+/// its bundled word lists, passing over romanizations if it has others. `None` if
+/// there are no word lists for the language, or they are in several scripts.
+pub fn default_script(language: &str) -> Option<&'static str> {
+    match language_scripts(language).as_slice() {
+        [script] => Some(script),
+        _ => None,
+    }
+}
+
+/// The scripts of a language's bundled word lists, passing over romanizations if
+/// it has others.
+pub fn language_scripts(language: &str) -> Vec<&'static str> {
+    let mut scripts = static_lang_word_lists::ALL_WORD_LISTS
+        .iter()
+        .filter(|word_list| word_list.language() == Some(language))
+        .filter_map(|word_list| word_list.script())
+        .collect::<Vec<_>>();
+    if scripts.iter().any(|script| *script != "Latn") {
+        scripts.retain(|script| *script != "Latn");
+    }
+    scripts.sort();
+    scripts.dedup();
+    scripts
+}
+
+// "ef_Abcd" -> ("Abcd", Some("ef")); "ef-Abcd" is parsed as a BCP 47 tag
+impl FromStr for ScriptLanguage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains('-') {
+            return ScriptLanguage::from_bcp47(s);
+        }
+        let mut parts = s.split('_').rev();
+
+        let script = parts.next().ok_or_else(|| "missing script".to_string())?;
+        let language = parts.next();
+        if parts.next().is_some() {
+            return Err("too many parts, expected format: [language_]script".to_string());
+        }
+        ScriptLanguage::new(script, language)
+    }
 }
 
-// "ef_Abcd" -> ("Abcd", Some("ef"))
 impl<'de> Deserialize<'de> for ScriptLanguage {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        let mut parts = s.split('_').rev();
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
 
-        let script = parts
-            .next()
-            .ok_or_else(|| serde::de::Error::custom("missing script"))?
-            .to_string();
-        if !KNOWN_ISO_SCRIPTS.contains(&script.as_str()) {
-            return Err(serde::de::Error::custom(format!(
-                "unknown ISO 15924 script code: {}",
-                script
-            )));
-        }
-        let language = parts.next().map(|s| s.to_string());
-        if let Some(lang) = &language {
-            if lang.len() != 2 && lang.len() != 3 {
-                return Err(serde::de::Error::custom(format!(
-                    "language code must be 2 or 3 letters: {}",
-                    lang
-                )));
+/// How the extreme of a set of measured words is chosen.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// The single most extreme word
+    #[default]
+    Absolute,
+    /// The word at a given percentile of extremeness (see `Config::percentile`)
+    Percentile,
+    /// The mean of the most extreme words (see `Config::trimmed_mean_words`)
+    TrimmedMean,
+}
+
+/// How the tables of several fonts are collated into one.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Collation {
+    /// Every script of every font, with the widest extremes
+    #[default]
+    Union,
+    /// Only the scripts which every font has, with the widest extremes
+    Intersection,
+    /// Every script of every font, with the mean of the fonts' values
+    Average,
+    /// Every script of every font, with the median of the fonts' values
+    Median,
+}
+
+/// What happens to the sides of a MinMax which are NULL, because they are no
+/// different from the font's defaults.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NullPolicy {
+    /// Write them as NULL, so that the font's defaults apply
+    #[default]
+    Keep,
+    /// Write the font's default min or max in their place, as FEA output must
+    Replace,
+}
+
+impl FromStr for Collation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "union" => Ok(Collation::Union),
+            "intersection" => Ok(Collation::Intersection),
+            "average" => Ok(Collation::Average),
+            "median" => Ok(Collation::Median),
+            _ => Err(format!(
+                "unknown collation {} (expected union, intersection, average or median)",
+                s
+            )),
+        }
+    }
+}
+
+/// How much the fonts whose file names match a pattern count for when the
+/// tables of several fonts are collated.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct FontWeight {
+    /// A file name pattern, in which `*` matches any run of characters and `?`
+    /// any single character (e.g. `"*Display*"`)
+    pub pattern: String,
+    /// The weight of the fonts' values; defaults to 1. Fonts with a weight of 0
+    /// are left out of the collation.
+    #[serde(default)]
+    pub weight: Option<f32>,
+    /// The weight of the fonts' MinMax values; defaults to `weight`
+    #[serde(default)]
+    pub min_max_weight: Option<f32>,
+}
+
+impl FontWeight {
+    /// Whether `file_name` matches the pattern.
+    pub fn matches(&self, file_name: &str) -> bool {
+        wildcard_match(
+            &self.pattern.chars().collect::<Vec<_>>(),
+            &file_name.chars().collect::<Vec<_>>(),
+        )
+    }
+}
+
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..])),
+        Some((c, rest)) => text
+            .split_first()
+            .is_some_and(|(t, text)| (*c == '?' || c == t) && wildcard_match(rest, text)),
+    }
+}
+
+pub const DEFAULT_PERCENTILE: f64 = 99.5;
+pub const DEFAULT_TRIMMED_MEAN_WORDS: usize = 10;
+
+/// A strategy, with its parameters, for choosing the extremes of one script.
+///
+/// Unset fields fall back to the global settings in the config.
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct ScriptStrategy {
+    #[serde(default)]
+    pub strategy: Option<Strategy>,
+    #[serde(default)]
+    pub percentile: Option<f64>,
+    #[serde(default)]
+    pub trimmed_mean_words: Option<usize>,
+}
+
+/// How much of a script a font must cover for the script to get records.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoverageThreshold {
+    /// At least this many encoded codepoints
+    Codepoints(usize),
+    /// At least this percentage of the script's exemplar characters
+    Percent(f64),
+}
+
+// "12" -> Codepoints(12), "5%" -> Percent(5.0)
+impl FromStr for CoverageThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(percent) = s.trim().strip_suffix('%') {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid coverage percentage: {}", s))?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("coverage percentage must be 0-100: {}", s));
             }
+            Ok(CoverageThreshold::Percent(percent))
+        } else {
+            s.trim()
+                .parse()
+                .map(CoverageThreshold::Codepoints)
+                .map_err(|_| format!("coverage must be a codepoint count or a percentage: {}", s))
         }
-        if parts.next().is_some() {
-            return Err(serde::de::Error::custom(
-                "too many parts, expected format: [language_]script",
-            ));
+    }
+}
+
+impl<'de> Deserialize<'de> for CoverageThreshold {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Count(usize),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Count(count) => Ok(CoverageThreshold::Codepoints(count)),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl std::fmt::Display for CoverageThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoverageThreshold::Codepoints(count) => write!(f, "{} codepoints", count),
+            CoverageThreshold::Percent(percent) => {
+                write!(f, "{}% of exemplar characters", percent)
+            }
         }
-        Ok(ScriptLanguage { script, language })
     }
 }
 
+/// Where the set of scripts supported by a font is taken from.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScriptSource {
+    /// The scripts of the characters in the font's character map
+    #[default]
+    Cmap,
+    /// The scripts in the font's GSUB and GPOS script lists
+    Layout,
+}
+
+impl FromStr for ScriptSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cmap" => Ok(ScriptSource::Cmap),
+            "layout" => Ok(ScriptSource::Layout),
+            _ => Err(format!(
+                "unknown script source {}, expected cmap or layout",
+                s
+            )),
+        }
+    }
+}
+
+/// A fully resolved strategy for choosing extremes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    Absolute,
+    Percentile(f64),
+    TrimmedMean(usize),
+}
+
 #[derive(Debug, Default, Deserialize, Clone)]
 pub struct Config {
     pub r#override: HashMap<ScriptLanguage, Override>,
     pub languages: Vec<ScriptLanguage>,
     pub tolerance: Option<u16>,
+    /// Tolerance for maxima, in place of `tolerance`
+    pub max_tolerance: Option<u16>,
+    /// Tolerance for minima, in place of `tolerance`
+    pub min_tolerance: Option<u16>,
     #[serde(default)]
     pub exclusions: Vec<String>,
+    /// Additional word lists to measure, given as `path:script[:language]`
+    #[serde(default)]
+    pub wordlists: Vec<WordListSource>,
+    /// Plain-text documents to split into words and measure, given as `path:script[:language]`
+    #[serde(default)]
+    pub corpora: Vec<WordListSource>,
+    /// Only measure the additional word lists, not those bundled with autobase
+    #[serde(default)]
+    pub skip_bundled_wordlists: bool,
+    /// A table of word frequencies, as tab-separated `word count` lines
+    #[serde(default)]
+    pub frequencies: Option<PathBuf>,
+    /// The share of word occurrences allowed to lie beyond the chosen extremes
+    ///
+    /// If unset, the single most extreme word determines each extreme.
+    #[serde(default)]
+    pub frequency_cutoff: Option<f64>,
+    /// How the extreme of each word list is chosen
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// The percentile used by the percentile strategy; defaults to 99.5
+    #[serde(default)]
+    pub percentile: Option<f64>,
+    /// The number of words averaged by the trimmed mean strategy; defaults to 10
+    #[serde(default)]
+    pub trimmed_mean_words: Option<usize>,
+    /// Per-script strategies, keyed by ISO 15924 script code
+    #[serde(default)]
+    pub script_strategies: HashMap<String, ScriptStrategy>,
+    /// Discard words whose extremes lie more than this many standard deviations
+    /// from the mean of their word list before choosing an extreme
+    #[serde(default)]
+    pub outlier_stddevs: Option<f64>,
+    /// Automatically split out languages whose extremes differ from the rest of
+    /// their script by more than this many units
+    #[serde(default)]
+    pub auto_split_threshold: Option<u16>,
+    /// Optical size bands whose extremes are measured and reported separately
+    #[serde(default)]
+    pub opsz_bands: Vec<OpszBand>,
+    /// The optical size band whose instances determine the table; if unset, all instances are used
+    #[serde(default)]
+    pub opsz_band: Option<String>,
+    /// How far (as a fraction of the em) the average ideographic advance may be
+    /// from the em for a CJK font to count as square; defaults to 0.01
+    #[serde(default)]
+    pub cjk_square_tolerance: Option<f32>,
+    /// Whether the CJK idtp baselines are written: automatically, always or never
+    #[serde(default)]
+    pub cjk_idtp: IdtpPolicy,
+    /// How the ideographic character face is determined
+    #[serde(default)]
+    pub cjk_strategy: CjkStrategy,
+    /// The reference ideographs used by the reference-glyphs CJK strategy; defaults to 水東国
+    #[serde(default)]
+    pub cjk_reference_characters: Option<String>,
+    /// How the Hangul character face is determined, when Hangul is measured on its own
+    #[serde(default)]
+    pub cjk_hangul_strategy: CjkStrategy,
+    /// The Hangul syllables used by the reference-glyphs Hangul strategy
+    #[serde(default)]
+    pub cjk_hangul_reference_characters: Option<String>,
+    /// Codepoints, codepoint ranges and glyph names left out of the CJK bounds computation
+    #[serde(default)]
+    pub cjk_exclude: Vec<CjkExclusion>,
+    /// The number of CJK glyphs sampled when computing the CJK baselines; if unset,
+    /// every glyph is measured
+    #[serde(default)]
+    pub cjk_sample_size: Option<usize>,
+    /// The seed used to choose the CJK glyph sample
+    #[serde(default)]
+    pub cjk_sample_seed: Option<u64>,
+    /// The percentage of CJK glyphs at each end of each character face edge left out of its average
+    #[serde(default)]
+    pub cjk_trim_percent: Option<f32>,
+    /// How far, as a fraction, a CJK glyph's advance may be from the most common advance
+    /// for it to be measured; if unset, glyphs are measured whatever their advance
+    #[serde(default)]
+    pub cjk_advance_tolerance: Option<f32>,
+    /// Measure every CJK glyph, even if `cjk_sample_size` is set
+    #[serde(default)]
+    pub cjk_exact: bool,
+    /// Measure the baselines of Han, Kana and Hangul separately, from their own glyphs
+    #[serde(default)]
+    pub cjk_per_script: bool,
+    /// A fixed bottom edge for the ideographic em-box; its top edge is one em above
+    #[serde(default)]
+    pub cjk_descender: Option<i16>,
+    /// Fixed values for the CJK baselines, which replace the measured ones
+    #[serde(default)]
+    pub cjk_em_box: CjkEmBox,
+    /// Scripts, in order of priority, from which to seed a DFLT script record
+    ///
+    /// Each may be an OpenType script tag (`hani`) or an ISO 15924 code (`Hani`).
+    #[serde(default)]
+    pub dflt_from: Vec<String>,
+    /// Don't write baseline values for scripts which have no measured baselines
+    #[serde(default)]
+    pub omit_unmeasured_baselines: bool,
+    /// A directory in which to cache tokenized corpora between runs
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    /// A directory in which to cache word list measurements between runs
+    #[serde(default)]
+    pub measurement_cache_dir: Option<PathBuf>,
+    /// Only emit records for these scripts (ISO 15924 codes); if empty, all supported scripts
+    #[serde(default)]
+    pub scripts: Vec<String>,
+    /// Never emit records for these scripts (ISO 15924 codes)
+    #[serde(default)]
+    pub exclude_scripts: Vec<String>,
+    /// Languages (ISO 639 codes) whose word lists are not measured and which never get records
+    #[serde(default)]
+    pub exclude_languages: Vec<String>,
+    /// Glyph names, keyed by ISO 15924 script code, whose bounds must be included
+    /// in that script's MinMax
+    #[serde(default)]
+    pub glyphs: HashMap<String, Vec<String>>,
+    /// OpenType script tags to use for ISO 15924 scripts, overriding the built-in mapping
+    /// (e.g. `Deva = "deva"` to write the old Devanagari tag rather than `dev2`)
+    #[serde(default)]
+    pub script_tags: HashMap<String, String>,
+    /// OpenType language system tags to use for ISO 639 languages, overriding the
+    /// built-in mapping and `language_tag_file` (e.g. `mo = "MOL"`)
+    #[serde(default)]
+    pub language_tags: HashMap<String, String>,
+    /// A file of ISO 639 to OpenType language tag mappings, as whitespace-separated
+    /// `code TAG` lines, which take precedence over the built-in mapping
+    #[serde(default)]
+    pub language_tag_file: Option<PathBuf>,
+    /// Where the font's supported scripts are taken from
+    #[serde(default)]
+    pub scripts_from: ScriptSource,
+    /// How the languages declared in the font's `meta` table are used
+    #[serde(default)]
+    pub meta: MetaPolicy,
+    /// The only languages (ISO 639 codes) which may be measured separately and get
+    /// records; if unset, all languages. Set per font from its `meta` table.
+    #[serde(skip)]
+    pub declared_languages: Option<Vec<String>>,
+    /// Ignore scripts which the font covers less than this, given as a codepoint
+    /// count (`12`) or a percentage of the script's exemplar characters (`"5%"`)
+    #[serde(default)]
+    pub min_script_coverage: Option<CoverageThreshold>,
+    /// Add min-max records (equivalent to `--min-max`)
+    #[serde(default)]
+    pub min_max: bool,
+    /// Use hhea ascent/descent as the font default MinMax (equivalent to `--use-hhea`)
+    #[serde(default)]
+    pub use_hhea: bool,
+    /// Where the font default MinMax comes from (equivalent to `--metric-source`); takes
+    /// precedence over `use_hhea`
+    #[serde(default)]
+    pub metric_source: Option<MetricSource>,
+    /// Clamp generated MinMax values to the OS/2 usWinAscent and usWinDescent
+    #[serde(default)]
+    pub clamp_to_win: bool,
+    /// Clamp generated max values to this ceiling, in place of usWinAscent
+    #[serde(default)]
+    pub clamp_max: Option<i16>,
+    /// Clamp generated min values to this floor, in place of usWinDescent
+    #[serde(default)]
+    pub clamp_min: Option<i16>,
+    /// Write the BASE table into the font binary rather than printing FEA (equivalent to `--binary`)
+    #[serde(default)]
+    pub binary: bool,
+    /// How the tables of several fonts are collated into one (equivalent to `--collation`)
+    #[serde(default)]
+    pub collation: Collation,
+    /// How much fonts count for when their tables are collated, by file name
+    /// pattern; the first matching pattern applies, and other fonts count fully
+    #[serde(default)]
+    pub font_weights: Vec<FontWeight>,
+    /// A specification which the font and its table must meet (equivalent to `--profile`)
+    #[serde(default)]
+    pub profile: Option<Profile>,
+    /// What the binary table holds for NULL MinMax sides; FEA output always replaces them
+    #[serde(default)]
+    pub null_policy: Option<NullPolicy>,
+}
+
+/// A named bundle of settings for a common workflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recipe {
+    /// CJK fonts for Google Fonts: ideographic baselines, seeded into a DFLT record
+    GfCjk,
+    /// Multiscript fonts for Android, which takes its vertical metrics from hhea
+    /// and reads NULL MinMax sides as them, so is best written with `--binary`
+    AndroidMultiscript,
+    /// Latin web fonts with extended (e.g. Vietnamese) coverage
+    WebLatinExt,
+}
+
+impl Recipe {
+    pub const ALL: [Recipe; 3] = [
+        Recipe::GfCjk,
+        Recipe::AndroidMultiscript,
+        Recipe::WebLatinExt,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Recipe::GfCjk => "gf-cjk",
+            Recipe::AndroidMultiscript => "android-multiscript",
+            Recipe::WebLatinExt => "web-latin-ext",
+        }
+    }
+
+    /// Overlay this recipe's settings onto a config.
+    ///
+    /// The recipe only fills in what the config leaves unset, and switches on the
+    /// flags (such as `min_max`) that it needs: anything else the user has set is
+    /// kept, as are settings which the recipe doesn't care about. It never
+    /// chooses the output format, which is left to the caller.
+    pub fn apply(&self, config: &mut Config) {
+        match self {
+            Recipe::GfCjk => {
+                if config.dflt_from.is_empty() {
+                    config.dflt_from = vec!["hani".to_string(), "kana".to_string()];
+                }
+                config.omit_unmeasured_baselines = true;
+            }
+            Recipe::AndroidMultiscript => {
+                config.min_max = true;
+                config.metric_source.get_or_insert(MetricSource::Hhea);
+                // Android reads NULL sides as the hhea metrics, so they are
+                // kept wherever the output can hold them
+                config.null_policy.get_or_insert(NullPolicy::Keep);
+                config.tolerance.get_or_insert(10);
+                config.auto_split_threshold.get_or_insert(50);
+            }
+            Recipe::WebLatinExt => {
+                config.min_max = true;
+                if !config.use_hhea {
+                    config.metric_source.get_or_insert(MetricSource::Typo);
+                }
+                // Browsers differ on NULL sides; spell the defaults out, so the
+                // binary also matches the FEA output
+                config.null_policy.get_or_insert(NullPolicy::Replace);
+                config.tolerance.get_or_insert(20);
+                if config.scripts.is_empty() {
+                    config.scripts = vec!["Latn".to_string()];
+                }
+                if config.languages.is_empty() {
+                    config.languages = vec![ScriptLanguage {
+                        script: "Latn".to_string(),
+                        language: Some("vi".to_string()),
+                        opentype_language: None,
+                    }];
+                }
+                if config.strategy == Strategy::default() {
+                    config.strategy = Strategy::Percentile;
+                }
+            }
+        }
+    }
 }
 
+impl FromStr for Recipe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Recipe::ALL
+            .into_iter()
+            .find(|recipe| recipe.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown recipe {}, expected one of: {}",
+                    s,
+                    Recipe::ALL.map(|r| r.name()).join(", ")
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for Recipe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The keys which may appear at the top level of a configuration file.
+///
+/// This must list every deserialized field of `Config`; it is what `config-check`
+/// uses to report unknown keys.
+pub const CONFIG_KEYS: &[&str] = &[
+    "override",
+    "languages",
+    "tolerance",
+    "max_tolerance",
+    "min_tolerance",
+    "exclusions",
+    "wordlists",
+    "corpora",
+    "skip_bundled_wordlists",
+    "frequencies",
+    "frequency_cutoff",
+    "strategy",
+    "percentile",
+    "trimmed_mean_words",
+    "script_strategies",
+    "outlier_stddevs",
+    "auto_split_threshold",
+    "opsz_bands",
+    "opsz_band",
+    "cjk_square_tolerance",
+    "cjk_idtp",
+    "cjk_em_box",
+    "cjk_descender",
+    "cjk_strategy",
+    "cjk_reference_characters",
+    "cjk_hangul_strategy",
+    "cjk_hangul_reference_characters",
+    "cjk_per_script",
+    "cjk_exclude",
+    "cjk_sample_size",
+    "cjk_sample_seed",
+    "cjk_exact",
+    "cjk_trim_percent",
+    "cjk_advance_tolerance",
+    "dflt_from",
+    "omit_unmeasured_baselines",
+    "cache_dir",
+    "measurement_cache_dir",
+    "scripts",
+    "exclude_scripts",
+    "exclude_languages",
+    "glyphs",
+    "script_tags",
+    "language_tags",
+    "language_tag_file",
+    "scripts_from",
+    "meta",
+    "min_script_coverage",
+    "min_max",
+    "use_hhea",
+    "metric_source",
+    "clamp_to_win",
+    "clamp_max",
+    "clamp_min",
+    "binary",
+    "collation",
+    "font_weights",
+    "profile",
+    "null_policy",
+];
+
+impl Config {
+    /// Whether the language filters allow measuring and emitting records for the given source.
+    pub fn language_allowed(&self, source: &ScriptLanguage) -> bool {
+        source
+            .language
+            .as_ref()
+            .is_none_or(|lang| self.language_code_allowed(lang))
+    }
+
+    /// Whether the language filters allow records for the given ISO 639 language.
+    pub fn language_code_allowed(&self, language: &str) -> bool {
+        !self.exclude_languages.iter().any(|l| l == language)
+            && self
+                .declared_languages
+                .as_ref()
+                .is_none_or(|declared| declared.iter().any(|l| l == language))
+    }
+
+    /// Whether the script filters allow records for the given ISO 15924 script.
+    pub fn script_allowed(&self, script: &str) -> bool {
+        (self.scripts.is_empty() || self.scripts.iter().any(|s| s == script))
+            && !self.exclude_scripts.iter().any(|s| s == script)
+    }
+
+    /// The files the configuration refers to, whose contents the generated
+    /// table depends on: word lists, corpora, frequencies and language tags.
+    pub fn referenced_files(&self) -> Vec<&std::path::Path> {
+        self.wordlists
+            .iter()
+            .chain(&self.corpora)
+            .map(|source| source.path.as_path())
+            .chain(self.frequencies.as_deref())
+            .chain(self.language_tag_file.as_deref())
+            .collect()
+    }
+
+    /// Take the font default MinMax from the hhea metrics if `use_hhea` is set,
+    /// MinMax records are being made and no `metric_source` is given.
+    pub fn resolve_metric_source(&mut self, min_max: bool) {
+        if self.metric_source.is_none() && self.use_hhea && min_max {
+            self.metric_source = Some(MetricSource::Hhea);
+        }
+    }
+
+    /// The tolerance for MinMax maxima and minima: `max_tolerance` and
+    /// `min_tolerance`, each falling back to `tolerance`.
+    pub fn min_max_tolerance(&self) -> Tolerance {
+        let tolerance = self.tolerance.unwrap_or(0);
+        Tolerance {
+            max: self.max_tolerance.unwrap_or(tolerance),
+            min: self.min_tolerance.unwrap_or(tolerance),
+        }
+    }
+
+    /// The weights of a font's values and of its MinMax values when tables are
+    /// collated, from the first of `font_weights` whose pattern matches its file name.
+    pub fn font_weight(&self, file_name: &str) -> (f32, f32) {
+        match self.font_weights.iter().find(|w| w.matches(file_name)) {
+            Some(w) => {
+                let weight = w.weight.unwrap_or(1.0);
+                (weight, w.min_max_weight.unwrap_or(weight))
+            }
+            None => (1.0, 1.0),
+        }
+    }
+
+    /// The OpenType script tag for an ISO 15924 script, from `script_tags` if given there.
+    pub fn opentype_script(&self, script: &str) -> Option<Tag> {
+        match self.script_tags.get(script) {
+            Some(tag) => Tag::new_checked(tag.as_bytes()).ok(),
+            None => iso15924_to_opentype(script),
+        }
+    }
+
+    /// The OpenType language system tag for an ISO 639 language in a script.
+    ///
+    /// A BCP 47 tag in `languages` or `override` which implies a particular language
+    /// system takes precedence, followed by `language_tags` (and the `language_tag_file`).
+    /// If several language systems are declared for the language (say `zh-Hans` and
+    /// `zh-Hant`), none of them is preferred.
+    pub fn opentype_language(&self, script: &str, language: &str) -> Tag {
+        match self
+            .declared_opentype_languages(script, language)
+            .as_slice()
+        {
+            [tag] => *tag,
+            _ => self.undeclared_opentype_language(language),
+        }
+    }
+
+    /// The OpenType language systems a language in a script gets records for: each
+    /// one declared by a BCP 47 tag in `languages` or `override`, or else the one
+    /// given by `opentype_language`.
+    pub fn opentype_languages(&self, script: &str, language: &str) -> Vec<Tag> {
+        let declared = self.declared_opentype_languages(script, language);
+        if declared.is_empty() {
+            vec![self.undeclared_opentype_language(language)]
+        } else {
+            declared
+        }
+    }
+
+    fn declared_opentype_languages(&self, script: &str, language: &str) -> Vec<Tag> {
+        let mut tags = self
+            .languages
+            .iter()
+            .chain(self.r#override.keys())
+            .filter(|sl| sl.script == script && sl.language.as_deref() == Some(language))
+            .filter_map(|sl| sl.opentype_language)
+            .collect::<Vec<_>>();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    fn undeclared_opentype_language(&self, language: &str) -> Tag {
+        self.language_tags
+            .get(language)
+            .and_then(|tag| Tag::new_checked(tag.as_bytes()).ok())
+            .unwrap_or_else(|| iso639_to_opentype(language))
+    }
+
+    /// Check that the `language_tags` are valid OpenType tags.
+    pub fn check_language_tags(&self) -> Result<(), String> {
+        for (language, tag) in self.language_tags.iter() {
+            if tag.is_empty() || tag.len() > 4 || Tag::new_checked(tag.as_bytes()).is_err() {
+                return Err(format!(
+                    "invalid OpenType language tag for {}: {}",
+                    language, tag
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the `script_tags` map known ISO 15924 scripts to valid OpenType tags.
+    pub fn check_script_tags(&self) -> Result<(), String> {
+        for (script, tag) in self.script_tags.iter() {
+            if !KNOWN_ISO_SCRIPTS.contains(&script.as_str()) {
+                return Err(format!(
+                    "unknown ISO 15924 script code in script_tags: {}",
+                    script
+                ));
+            }
+            if tag.is_empty() || tag.len() > 4 || Tag::new_checked(tag.as_bytes()).is_err() {
+                return Err(format!(
+                    "invalid OpenType script tag for {}: {}",
+                    script, tag
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The `dflt_from` scripts as OpenType script tags.
+    pub fn dflt_from_tags(&self) -> Result<Vec<Tag>, String> {
+        self.dflt_from
+            .iter()
+            .map(|script| {
+                if KNOWN_ISO_SCRIPTS.contains(&script.as_str()) {
+                    self.opentype_script(script)
+                        .ok_or_else(|| format!("script {} has no OpenType tag", script))
+                } else {
+                    Tag::new_checked(script.as_bytes())
+                        .map_err(|_| format!("invalid script tag in dflt_from: {}", script))
+                }
+            })
+            .collect()
+    }
+
+    /// The options for computing CJK baselines.
+    pub fn cjk_options(&self) -> CjkOptions {
+        let mut options = CjkOptions {
+            strategy: self.cjk_strategy,
+            per_script: self.cjk_per_script,
+            exclude: self.cjk_exclude.clone(),
+            ..CjkOptions::default()
+        };
+        if self.cjk_exact {
+            options.sample_size = None;
+        } else if self.cjk_sample_size.is_some() {
+            options.sample_size = self.cjk_sample_size;
+        }
+        if let Some(seed) = self.cjk_sample_seed {
+            options.sample_seed = seed;
+        }
+        if let Some(trim) = self.cjk_trim_percent {
+            options.trim_percent = trim;
+        }
+        if self.cjk_advance_tolerance.is_some() {
+            options.advance_tolerance = self.cjk_advance_tolerance;
+        }
+        if let Some(characters) = &self.cjk_reference_characters {
+            options.reference_characters =
+                characters.chars().filter(|c| !c.is_whitespace()).collect();
+        }
+        options.descender = self.cjk_descender;
+        options.hangul_strategy = self.cjk_hangul_strategy;
+        if let Some(characters) = &self.cjk_hangul_reference_characters {
+            options.hangul_reference_characters =
+                characters.chars().filter(|c| !c.is_whitespace()).collect();
+        }
+        options
+    }
+
+    /// The strategy for choosing the extremes of the given script.
+    pub fn aggregation_for(&self, script: &str) -> Aggregation {
+        let script_strategy = self.script_strategies.get(script);
+        let strategy = script_strategy
+            .and_then(|s| s.strategy)
+            .unwrap_or(self.strategy);
+        match strategy {
+            Strategy::Absolute => Aggregation::Absolute,
+            Strategy::Percentile => Aggregation::Percentile(
+                script_strategy
+                    .and_then(|s| s.percentile)
+                    .or(self.percentile)
+                    .unwrap_or(DEFAULT_PERCENTILE),
+            ),
+            Strategy::TrimmedMean => Aggregation::TrimmedMean(
+                script_strategy
+                    .and_then(|s| s.trimmed_mean_words)
+                    .or(self.trimmed_mean_words)
+                    .unwrap_or(DEFAULT_TRIMMED_MEAN_WORDS),
+            ),
+        }
+    }
+}
+
+/// The file format of a configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Guess the format from the file extension, defaulting to TOML.
+    pub fn from_path(path: &std::path::Path) -> Self {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            _ => Err(format!(
+                "unknown config format {}, expected toml or json",
+                s
+            )),
+        }
+    }
+}
+
+/// Load a configuration file, in the format given by its extension.
 pub fn load_config(path: &std::path::Path) -> anyhow::Result<Config> {
+    load_config_as(path, ConfigFormat::from_path(path))
+}
+
+/// Load a configuration file in the given format.
+pub fn load_config_as(path: &std::path::Path, format: ConfigFormat) -> anyhow::Result<Config> {
     let contents = std::fs::read_to_string(path)?;
-    let config: Config = toml::from_str(&contents)?;
+    parse_config(&contents, format, path)
+}
+
+/// Parse a configuration as if it had been read from the file at `path`, which
+/// the paths it gives are relative to.
+pub fn parse_config(
+    contents: &str,
+    format: ConfigFormat,
+    path: &std::path::Path,
+) -> anyhow::Result<Config> {
+    let config: Config = match format {
+        ConfigFormat::Toml => toml::from_str(contents)?,
+        ConfigFormat::Json => serde_json::from_str(contents)?,
+    };
+    resolve_config(config, path)
+}
+
+/// Resolve the paths in a config loaded from the given file, and load the files it refers to.
+fn resolve_config(mut config: Config, path: &std::path::Path) -> anyhow::Result<Config> {
+    // Word list paths in the config file are relative to the config file
+    if let Some(dir) = path.parent() {
+        config.wordlists = config
+            .wordlists
+            .into_iter()
+            .map(|w| w.relative_to(dir))
+            .collect();
+        config.corpora = config
+            .corpora
+            .into_iter()
+            .map(|w| w.relative_to(dir))
+            .collect();
+        config.frequencies = config.frequencies.map(|f| dir.join(f));
+        config.cache_dir = config.cache_dir.map(|d| dir.join(d));
+        config.measurement_cache_dir = config.measurement_cache_dir.map(|d| dir.join(d));
+        config.language_tag_file = config.language_tag_file.map(|f| dir.join(f));
+    }
+    if let Some(file) = config.language_tag_file.as_deref() {
+        // Overrides in the config file itself win over the data file
+        for (language, tag) in load_language_tags(file)? {
+            config.language_tags.entry(language).or_insert(tag);
+        }
+    }
     Ok(config)
 }
+
+/// Standalone configuration files found by `discover_config`, in order of preference.
+pub const DISCOVERED_CONFIG_NAMES: [&str; 2] = ["autobase.toml", "autobase.json"];
+
+/// Search upward from a directory for a configuration file, as rustfmt and clippy do.
+///
+/// In each directory, a standalone `autobase.toml` (or `autobase.json`) is
+/// preferred, followed by a `[tool.autobase]` section in a `pyproject.toml`;
+/// a `pyproject.toml` which cannot be parsed is skipped with a warning.
+/// Returns the path of the file found along with the loaded config, or `None` if
+/// no configuration was found.
+pub fn discover_config(start: &std::path::Path) -> anyhow::Result<Option<(PathBuf, Config)>> {
+    for dir in start.ancestors() {
+        for name in DISCOVERED_CONFIG_NAMES {
+            let path = dir.join(name);
+            if path.is_file() {
+                let config = load_config(&path)
+                    .with_context(|| format!("failed to load {}", path.display()))?;
+                return Ok(Some((path, config)));
+            }
+        }
+        let pyproject = dir.join("pyproject.toml");
+        if pyproject.is_file() {
+            // A pyproject.toml belongs to another tool first, so one autobase
+            // can't read is passed over rather than stopping the search
+            let table = match std::fs::read_to_string(&pyproject)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| Ok(toml::from_str::<toml::Table>(&contents)?))
+            {
+                Ok(table) => table,
+                Err(e) => {
+                    log::warn!(
+                        "Skipping {}, which could not be read: {}",
+                        pyproject.display(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if let Some(section) = table
+                .get("tool")
+                .and_then(|tool| tool.get("autobase"))
+                .cloned()
+            {
+                let config: Config = section.try_into().with_context(|| {
+                    format!("invalid [tool.autobase] section in {}", pyproject.display())
+                })?;
+                let config = resolve_config(config, &pyproject)?;
+                return Ok(Some((pyproject, config)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Load ISO 639 to OpenType language tag mappings from a file of `code TAG` lines.
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub fn load_language_tags(path: &std::path::Path) -> anyhow::Result<HashMap<String, String>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read language tags from {}", path.display()))?;
+    let mut tags = HashMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(language), Some(tag), None) => {
+                tags.insert(language.to_string(), tag.to_string());
+            }
+            _ => anyhow::bail!(
+                "{}:{}: expected an ISO 639 code and an OpenType tag",
+                path.display(),
+                number + 1
+            ),
+        }
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recipes_keep_the_users_settings() {
+        let mut config = parse_config(
+            r#"
+                languages = ["fi_Latn"]
+                scripts = ["Latn", "Grek"]
+                tolerance = 5
+                strategy = "trimmed-mean"
+                null_policy = "keep"
+                [override]
+            "#,
+            ConfigFormat::Toml,
+            std::path::Path::new("autobase.toml"),
+        )
+        .unwrap();
+        Recipe::WebLatinExt.apply(&mut config);
+        assert_eq!(config.languages, ["fi_Latn".parse().unwrap()]);
+        assert_eq!(config.scripts, ["Latn", "Grek"]);
+        assert_eq!(config.tolerance, Some(5));
+        assert_eq!(config.strategy, Strategy::TrimmedMean);
+        assert_eq!(config.null_policy, Some(NullPolicy::Keep));
+        assert!(config.min_max);
+
+        let mut config = Config::default();
+        Recipe::WebLatinExt.apply(&mut config);
+        assert_eq!(config.languages, ["vi_Latn".parse().unwrap()]);
+        assert_eq!(config.scripts, ["Latn"]);
+        assert_eq!(config.tolerance, Some(20));
+        assert_eq!(config.strategy, Strategy::Percentile);
+        assert_eq!(config.metric_source, Some(MetricSource::Typo));
+        assert_eq!(config.null_policy, Some(NullPolicy::Replace));
+
+        let mut config = Config {
+            use_hhea: true,
+            max_tolerance: Some(3),
+            ..Default::default()
+        };
+        Recipe::WebLatinExt.apply(&mut config);
+        assert_eq!(config.metric_source, None);
+        assert_eq!(config.min_max_tolerance(), Tolerance { max: 3, min: 20 });
+
+        let mut config = Config::default();
+        Recipe::AndroidMultiscript.apply(&mut config);
+        assert_eq!(config.metric_source, Some(MetricSource::Hhea));
+        assert_eq!(config.null_policy, Some(NullPolicy::Keep));
+        assert!(!config.binary);
+    }
+
+    #[test]
+    fn config_formats_agree() {
+        let toml = r#"
+            languages = ["en_Latn", "zh-Hant"]
+            tolerance = 5
+            cjk_exclude = ["U+3007", "U+2E80-U+2EFF", "uni6C34.alt"]
+            [override]
+            Latn = { max = 900 }
+            [glyphs]
+            Deva = ["rupee", "rupee.alt"]
+        "#;
+        let json = r#"{
+            "languages": ["en_Latn", "zh-Hant"],
+            "tolerance": 5,
+            "cjk_exclude": ["U+3007", "U+2E80-U+2EFF", "uni6C34.alt"],
+            "override": {"Latn": {"max": 900}},
+            "glyphs": {"Deva": ["rupee", "rupee.alt"]}
+        }"#;
+        let path = std::path::Path::new("autobase.conf");
+        for (format, contents) in [(ConfigFormat::Toml, toml), (ConfigFormat::Json, json)] {
+            let config = parse_config(contents, format, path).unwrap();
+            assert_eq!(
+                config.languages,
+                ["en_Latn".parse().unwrap(), "zh-Hant".parse().unwrap()],
+                "{:?}",
+                format
+            );
+            assert_eq!(config.tolerance, Some(5));
+            assert_eq!(
+                config.cjk_exclude,
+                [
+                    CjkExclusion::Codepoints(0x3007, 0x3007),
+                    CjkExclusion::Codepoints(0x2E80, 0x2EFF),
+                    CjkExclusion::Glyph("uni6C34.alt".to_string()),
+                ]
+            );
+            assert_eq!(config.r#override[&"Latn".parse().unwrap()].max, Some(900));
+            assert_eq!(config.glyphs["Deva"], ["rupee", "rupee.alt"]);
+        }
+        assert!(parse_config(
+            r#"{"languages": ["xx-Qqqq-Latn-Cyrl"]}"#,
+            ConfigFormat::Json,
+            path
+        )
+        .is_err());
+        assert!(parse_config(
+            r#"{"cjk_exclude": ["U+30-U+20"]}"#,
+            ConfigFormat::Json,
+            path
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn unparseable_pyproject_is_skipped() {
+        let root = std::env::temp_dir().join(format!("autobase-discover-{}", std::process::id()));
+        let inner = root.join("fonts");
+        std::fs::create_dir_all(&inner).unwrap();
+        std::fs::write(root.join("autobase.toml"), "languages = []\n[override]\n").unwrap();
+        std::fs::write(inner.join("pyproject.toml"), "[tool.autobase\n").unwrap();
+        let found = discover_config(&inner).map(|found| found.map(|(path, _)| path));
+        std::fs::write(
+            inner.join("pyproject.toml"),
+            "[tool.autobase]\ntolerance = \"x\"\n",
+        )
+        .unwrap();
+        let invalid = discover_config(&inner);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(found.unwrap(), Some(root.join("autobase.toml")));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn bcp47_language_systems_are_distinct() {
+        let hans: ScriptLanguage = "zh-Hans".parse().unwrap();
+        let hant: ScriptLanguage = "zh-Hant".parse().unwrap();
+        let hk: ScriptLanguage = "zh-Hant-HK".parse().unwrap();
+        for sl in [&hans, &hant, &hk] {
+            assert_eq!(sl.script, "Hani");
+            assert_eq!(sl.language.as_deref(), Some("zh"));
+        }
+        assert_eq!(hans.opentype_language, Some(Tag::new(b"ZHS ")));
+        assert_eq!(hant.opentype_language, Some(Tag::new(b"ZHT ")));
+        assert_eq!(hk.opentype_language, Some(Tag::new(b"ZHH ")));
+        let overrides = HashMap::from([(hans, Override::default()), (hant, Override::default())]);
+        assert_eq!(overrides.len(), 2);
+    }
+
+    #[test]
+    fn bcp47_skips_variants_and_extensions() {
+        let de = ScriptLanguage::from_bcp47("de-Latn-1996").unwrap();
+        assert_eq!(
+            (de.script.as_str(), de.language.as_deref()),
+            ("Latn", Some("de"))
+        );
+        let hk = ScriptLanguage::from_bcp47("zh-Hant-HK-x-abcd").unwrap();
+        assert_eq!(hk.opentype_language, Some(Tag::new(b"ZHH ")));
+        let tw = ScriptLanguage::from_bcp47("zh-Hant-u-rg-hkzzzz").unwrap();
+        assert_eq!(tw.opentype_language, Some(Tag::new(b"ZHT ")));
+    }
+
+    #[test]
+    fn bcp47_non_ascii_subtag_is_not_a_script() {
+        let zh: ScriptLanguage = "zh-éab-Hant".parse().unwrap();
+        assert_eq!(zh.script, "Hani");
+        assert_eq!(zh.opentype_language, Some(Tag::new(b"ZHT ")));
+        // Four bytes, but not four letters: no panic slicing it
+        assert!(ScriptLanguage::from_bcp47("xx-éab").is_err());
+    }
+
+    #[test]
+    fn bcp47_without_script_uses_default_script() {
+        let pt: ScriptLanguage = "pt-BR".parse().unwrap();
+        assert_eq!(pt.script, "Latn");
+        assert_eq!(pt.language.as_deref(), Some("pt"));
+        assert_eq!(pt.opentype_language, None);
+        // Serbian's Latin word lists are a romanization
+        let sr = ScriptLanguage::from_bcp47("sr-RS").unwrap();
+        assert_eq!(sr.script, "Cyrl");
+        // A numeric variant is not a script subtag
+        let de = ScriptLanguage::from_bcp47("de-1996").unwrap();
+        assert_eq!(de.script, "Latn");
+        let sr_latn: ScriptLanguage = "sr-Latn".parse().unwrap();
+        assert_eq!(sr_latn.script, "Latn");
+        // Japanese is written in several scripts, so one must be given
+        assert!(ScriptLanguage::from_bcp47("ja-JP").is_err());
+        assert!(ScriptLanguage::from_bcp47("-Latn").is_err());
+    }
+
+    #[test]
+    fn several_language_systems_are_not_preferred() {
+        let mut config = Config {
+            languages: vec!["zh-Hant".parse().unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(config.opentype_language("Hani", "zh"), Tag::new(b"ZHT "));
+        config.languages.push("zh-Hans".parse().unwrap());
+        assert_eq!(
+            config.opentype_languages("Hani", "zh"),
+            vec![Tag::new(b"ZHS "), Tag::new(b"ZHT ")]
+        );
+        assert_eq!(
+            config.opentype_language("Hani", "zh"),
+            iso639_to_opentype("zh")
+        );
+    }
+
+    #[test]
+    fn use_hhea_applies_only_to_min_max_without_a_metric_source() {
+        let mut config = Config {
+            use_hhea: true,
+            ..Default::default()
+        };
+        config.resolve_metric_source(false);
+        assert_eq!(config.metric_source, None);
+        config.resolve_metric_source(true);
+        assert_eq!(config.metric_source, Some(MetricSource::Hhea));
+        config.metric_source = Some(MetricSource::Typo);
+        config.resolve_metric_source(true);
+        assert_eq!(config.metric_source, Some(MetricSource::Typo));
+    }
+}
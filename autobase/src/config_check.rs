@@ -0,0 +1,426 @@
+//! Validate a configuration file, reporting every problem found.
+//!
+//! Loading a config stops at the first serde error, and a config which loads may
+//! still name scripts which don't exist or set contradictory options. This module
+//! checks the whole file and reports each problem, with its position in the file
+//! where the format allows (positions are only available for TOML). Unknown keys
+//! are reported in every format.
+use std::{collections::HashMap, ops::Range, path::Path};
+
+use toml::de::{DeTable, DeValue};
+
+use crate::{
+    config::{Config, ConfigFormat, ScriptLanguage, CONFIG_KEYS},
+    utils::KNOWN_ISO_SCRIPTS,
+};
+
+/// A problem found in a configuration file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigProblem {
+    pub message: String,
+    /// The byte range in the file the problem refers to, if known
+    pub span: Option<Range<usize>>,
+}
+
+impl ConfigProblem {
+    fn new(message: impl Into<String>, span: Option<Range<usize>>) -> Self {
+        ConfigProblem {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// The one-based line and column at which the problem starts, if known.
+    pub fn line_column(&self, text: &str) -> Option<(usize, usize)> {
+        let start = self.span.as_ref()?.start.min(text.len());
+        let before = &text[..start];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rfind('\n')
+            .map_or(before, |newline| &before[newline + 1..])
+            .chars()
+            .count()
+            + 1;
+        Some((line, column))
+    }
+}
+
+/// Check a configuration file's contents, returning every problem found.
+///
+/// Paths in the config are taken relative to `base_dir`, the directory of the file.
+pub fn check_config(text: &str, format: ConfigFormat, base_dir: &Path) -> Vec<ConfigProblem> {
+    let mut problems = vec![];
+    if format == ConfigFormat::Toml {
+        let (table, errors) = DeTable::parse_recoverable(text);
+        for error in errors {
+            problems.push(ConfigProblem::new(error.message(), error.span()));
+        }
+        check_table(table.get_ref(), &mut problems);
+    } else if let Ok(serde_json::Value::Object(object)) = serde_json::from_str(text) {
+        check_keys(&object, &mut problems);
+    }
+    // Type errors and the like are found by loading the config itself
+    let loaded: Result<Config, ConfigProblem> = match format {
+        ConfigFormat::Toml => {
+            toml::from_str(text).map_err(|e| ConfigProblem::new(e.message(), e.span()))
+        }
+        ConfigFormat::Json => {
+            serde_json::from_str(text).map_err(|e| ConfigProblem::new(e.to_string(), None))
+        }
+    };
+    match loaded {
+        Ok(config) => check_loaded(&config, format, base_dir, &mut problems),
+        Err(problem) => {
+            // Don't report the same problem twice
+            let overlaps = problem.span.as_ref().is_some_and(|span| {
+                problems.iter().any(|p| {
+                    p.span
+                        .as_ref()
+                        .is_some_and(|s| s.start < span.end && span.start < s.end)
+                })
+            });
+            if !overlaps {
+                problems.push(problem);
+            }
+        }
+    }
+    // In file order, followed by the problems without a position
+    problems.sort_by_key(|p| p.span.as_ref().map_or(usize::MAX, |span| span.start));
+    problems
+}
+
+fn check_script(script: &str, span: Range<usize>, key: &str, problems: &mut Vec<ConfigProblem>) {
+    if !KNOWN_ISO_SCRIPTS.contains(&script) {
+        problems.push(ConfigProblem::new(
+            format!("unknown ISO 15924 script code in {}: {}", key, script),
+            Some(span),
+        ));
+    }
+}
+
+fn check_language(
+    language: &str,
+    span: Range<usize>,
+    key: &str,
+    problems: &mut Vec<ConfigProblem>,
+) {
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_lowercase()) {
+        problems.push(ConfigProblem::new(
+            format!("invalid ISO 639 language code in {}: {}", key, language),
+            Some(span),
+        ));
+    }
+}
+
+/// Check the keys and values of a TOML config, where their positions are known.
+fn check_table(table: &DeTable, problems: &mut Vec<ConfigProblem>) {
+    for (key, value) in table.iter() {
+        let name = key.get_ref().as_ref();
+        if !CONFIG_KEYS.contains(&name) {
+            problems.push(ConfigProblem::new(
+                format!("unknown key: {}", name),
+                Some(key.span()),
+            ));
+            continue;
+        }
+        match (name, value.get_ref()) {
+            ("scripts" | "exclude_scripts", DeValue::Array(items)) => {
+                for item in items.iter() {
+                    if let DeValue::String(script) = item.get_ref() {
+                        check_script(script, item.span(), name, problems);
+                    }
+                }
+            }
+            ("exclude_languages", DeValue::Array(items)) => {
+                for item in items.iter() {
+                    if let DeValue::String(language) = item.get_ref() {
+                        check_language(language, item.span(), name, problems);
+                    }
+                }
+            }
+            ("languages", DeValue::Array(items)) => {
+                let mut seen: HashMap<ScriptLanguage, Range<usize>> = HashMap::new();
+                for item in items.iter() {
+                    let DeValue::String(text) = item.get_ref() else {
+                        continue;
+                    };
+                    match text.parse::<ScriptLanguage>() {
+                        Ok(sl) => {
+                            if seen.insert(sl, item.span()).is_some() {
+                                problems.push(ConfigProblem::new(
+                                    format!("{} is listed more than once in languages", text),
+                                    Some(item.span()),
+                                ));
+                            }
+                        }
+                        Err(e) => problems.push(ConfigProblem::new(
+                            format!("invalid entry in languages: {}", e),
+                            Some(item.span()),
+                        )),
+                    }
+                }
+            }
+            ("override", DeValue::Table(overrides)) => check_overrides(overrides, problems),
+            ("script_strategies" | "glyphs" | "script_tags", DeValue::Table(scripts)) => {
+                for (script, _) in scripts.iter() {
+                    check_script(script.get_ref(), script.span(), name, problems);
+                }
+            }
+            ("language_tags", DeValue::Table(languages)) => {
+                for (language, _) in languages.iter() {
+                    check_language(language.get_ref(), language.span(), name, problems);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Check the keys of a JSON config, whose positions aren't known.
+fn check_keys(
+    object: &serde_json::Map<String, serde_json::Value>,
+    problems: &mut Vec<ConfigProblem>,
+) {
+    for (name, value) in object.iter() {
+        if !CONFIG_KEYS.contains(&name.as_str()) {
+            problems.push(ConfigProblem::new(format!("unknown key: {}", name), None));
+            continue;
+        }
+        let (true, serde_json::Value::Object(overrides)) = (name == "override", value) else {
+            continue;
+        };
+        for (text, minmax) in overrides.iter() {
+            let serde_json::Value::Object(minmax) = minmax else {
+                continue;
+            };
+            for side in minmax.keys() {
+                if !["min", "max"].contains(&side.as_str()) {
+                    problems.push(ConfigProblem::new(
+                        format!("unknown key in override {}: {}", text, side),
+                        None,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn check_overrides(overrides: &DeTable, problems: &mut Vec<ConfigProblem>) {
+    let mut seen: HashMap<ScriptLanguage, String> = HashMap::new();
+    for (key, value) in overrides.iter() {
+        let text = key.get_ref().as_ref();
+        match text.parse::<ScriptLanguage>() {
+            Ok(sl) => {
+                if let Some(previous) = seen.insert(sl, text.to_string()) {
+                    problems.push(ConfigProblem::new(
+                        format!("override {} conflicts with override {}", text, previous),
+                        Some(key.span()),
+                    ));
+                }
+            }
+            Err(e) => problems.push(ConfigProblem::new(
+                format!("invalid override key: {}", e),
+                Some(key.span()),
+            )),
+        }
+        if let DeValue::Table(minmax) = value.get_ref() {
+            let integer = |side: &str| match minmax.get(side).map(|v| v.get_ref()) {
+                Some(DeValue::Integer(i)) => i64::from_str_radix(i.as_str(), i.radix()).ok(),
+                _ => None,
+            };
+            if let (Some(min), Some(max)) = (integer("min"), integer("max")) {
+                if min > max {
+                    problems.push(ConfigProblem::new(
+                        format!("override {} has min {} above max {}", text, min, max),
+                        Some(value.span()),
+                    ));
+                }
+            }
+            for (side, _) in minmax.iter() {
+                if !["min", "max"].contains(&side.get_ref().as_ref()) {
+                    problems.push(ConfigProblem::new(
+                        format!("unknown key in override {}: {}", text, side.get_ref()),
+                        Some(side.span()),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Check the options of a loaded config against each other.
+fn check_loaded(
+    config: &Config,
+    format: ConfigFormat,
+    base_dir: &Path,
+    problems: &mut Vec<ConfigProblem>,
+) {
+    // The TOML checks have already covered these, with positions
+    if format != ConfigFormat::Toml {
+        for (key, scripts) in [
+            ("scripts", &config.scripts),
+            ("exclude_scripts", &config.exclude_scripts),
+        ] {
+            for script in scripts {
+                if !KNOWN_ISO_SCRIPTS.contains(&script.as_str()) {
+                    problems.push(ConfigProblem::new(
+                        format!("unknown ISO 15924 script code in {}: {}", key, script),
+                        None,
+                    ));
+                }
+            }
+        }
+    }
+    for script in config.scripts.iter() {
+        if config.exclude_scripts.contains(script) {
+            problems.push(ConfigProblem::new(
+                format!("script {} is in both scripts and exclude_scripts", script),
+                None,
+            ));
+        }
+    }
+    for sl in config.languages.iter().chain(config.r#override.keys()) {
+        if let Some(language) = &sl.language {
+            if config.exclude_languages.contains(language) {
+                problems.push(ConfigProblem::new(
+                    format!(
+                        "language {} is excluded, but is split out or overridden as {}_{}",
+                        language, language, sl.script
+                    ),
+                    None,
+                ));
+            }
+        }
+        if !config.script_allowed(&sl.script) {
+            problems.push(ConfigProblem::new(
+                format!(
+                    "script {} is filtered out, but has a language or override entry",
+                    sl.script
+                ),
+                None,
+            ));
+        }
+    }
+    if let Some(band) = &config.opsz_band {
+        if !config.opsz_bands.iter().any(|b| &b.name == band) {
+            problems.push(ConfigProblem::new(
+                format!("opsz_band {} is not one of the configured opsz_bands", band),
+                None,
+            ));
+        }
+    }
+    if let Some(trim) = config.cjk_trim_percent {
+        if !(0.0..50.0).contains(&trim) {
+            problems.push(ConfigProblem::new(
+                format!(
+                    "cjk_trim_percent must be at least 0 and less than 50, not {}",
+                    trim
+                ),
+                None,
+            ));
+        }
+    }
+    if let Some(cutoff) = config.frequency_cutoff {
+        if !(0.0..1.0).contains(&cutoff) {
+            problems.push(ConfigProblem::new(
+                format!(
+                    "frequency_cutoff must be at least 0 and less than 1, not {}",
+                    cutoff
+                ),
+                None,
+            ));
+        }
+    }
+    for (message, result) in [
+        ("dflt_from", config.dflt_from_tags().map(|_| ())),
+        ("script_tags", config.check_script_tags()),
+        ("language_tags", config.check_language_tags()),
+    ] {
+        if let Err(e) = result {
+            problems.push(ConfigProblem::new(format!("{}: {}", message, e), None));
+        }
+    }
+    for (key, path) in [
+        ("frequencies", &config.frequencies),
+        ("language_tag_file", &config.language_tag_file),
+    ] {
+        if let Some(path) = path {
+            if !base_dir.join(path).exists() {
+                problems.push(ConfigProblem::new(
+                    format!("{} file does not exist: {}", key, path.display()),
+                    None,
+                ));
+            }
+        }
+    }
+    for source in config.wordlists.iter().chain(config.corpora.iter()) {
+        if !base_dir.join(&source.path).exists() {
+            problems.push(ConfigProblem::new(
+                format!("word list file does not exist: {}", source.path.display()),
+                None,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(text: &str, format: ConfigFormat) -> Vec<String> {
+        check_config(text, format, Path::new("."))
+            .into_iter()
+            .map(|problem| problem.message)
+            .collect()
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_in_every_format() {
+        let toml =
+            "languages = []\nmin_maxx = true\n[override]\nen_Latn = { max = 900, mni = 1 }\n";
+        let json = r#"{"languages": [], "min_maxx": true, "override": {"en_Latn": {"max": 900, "mni": 1}}}"#;
+        for (text, format) in [(toml, ConfigFormat::Toml), (json, ConfigFormat::Json)] {
+            let messages = messages(text, format);
+            assert!(
+                messages.contains(&"unknown key: min_maxx".to_string()),
+                "{:?}: {:?}",
+                format,
+                messages
+            );
+            assert!(
+                messages.contains(&"unknown key in override en_Latn: mni".to_string()),
+                "{:?}: {:?}",
+                format,
+                messages
+            );
+        }
+    }
+
+    #[test]
+    fn toml_problems_have_positions() {
+        let text = "languages = [\"en_Latn\", \"en_Latn\"]\nscripts = [\"Latx\"]\n[override]\n";
+        let problems = check_config(text, ConfigFormat::Toml, Path::new("."));
+        let positions = problems
+            .iter()
+            .map(|problem| problem.line_column(text))
+            .collect::<Vec<_>>();
+        // Problems found in the loaded config come last, without positions
+        assert_eq!(positions[..2], [Some((1, 25)), Some((2, 12))]);
+        assert!(positions[2..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn frequency_cutoff_must_be_a_share() {
+        let problem = "frequency_cutoff must be at least 0 and less than 1, not 5";
+        let problems = messages(
+            "languages = []\nfrequency_cutoff = 5.0\n[override]\n",
+            ConfigFormat::Toml,
+        );
+        assert!(problems.contains(&problem.to_string()), "{:?}", problems);
+        let problems = messages(
+            "languages = []\nfrequency_cutoff = 0.001\n[override]\n",
+            ConfigFormat::Toml,
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+}
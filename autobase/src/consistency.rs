@@ -0,0 +1,81 @@
+//! Check that the FEA and binary outputs agree.
+//!
+//! FEA can't express NULL MinMax values, so the FEA output has them replaced with
+//! the font defaults, and the table is then compiled by somebody else's compiler.
+//! The table which ends up in a font built from our FEA may therefore not be the
+//! table we would have written into the binary. Here we compile our FEA with
+//! fea-rs and compare the result with the table we write directly.
+use fea_rs::{
+    compile::{self, NopFeatureProvider, NopVariationInfo, Opts},
+    GlyphMap, GlyphName,
+};
+use skrifa::raw::{tables::base::Base, FontData, FontRead};
+
+use crate::{base::BaseTable, error::AutobaseError};
+
+/// Compile FEA source containing a BASE table, returning the binary table.
+fn compile_fea(fea: &str) -> Result<Vec<u8>, AutobaseError> {
+    // The BASE table doesn't refer to any glyphs
+    let glyph_map: GlyphMap = std::iter::once(GlyphName::new(".notdef")).collect();
+    let (tree, diagnostics) = fea_rs::parse::parse_string(fea);
+    if diagnostics.has_errors() {
+        return Err(AutobaseError::FeaCompile(format!("{:?}", diagnostics)));
+    }
+    let (compilation, _warnings) = compile::compile::<NopVariationInfo, NopFeatureProvider>(
+        &tree,
+        &glyph_map,
+        None,
+        None,
+        Opts::new(),
+    )
+    .map_err(|diagnostics| AutobaseError::FeaCompile(format!("{:?}", diagnostics)))?;
+    let base = compilation
+        .base
+        .ok_or_else(|| AutobaseError::FeaCompile("no BASE table was compiled".to_string()))?;
+    Ok(write_fonts::dump_table(&base)?)
+}
+
+/// Describe a binary BASE table line by line, in feature syntax.
+fn describe(bytes: &[u8]) -> Result<Vec<String>, AutobaseError> {
+    let base = Base::read(FontData::new(bytes))?;
+    Ok(BaseTable::from_skrifa(&base)?
+        .to_fea()
+        .lines()
+        .map(|line| line.trim().to_string())
+        .collect())
+}
+
+/// Compare the table we would write into a binary with the table produced by
+/// compiling the FEA output.
+///
+/// `binary` is the table as written into a binary font, and `fea` is the table
+/// as exported to feature syntax (i.e. after NULL replacement). Returns a list of
+/// human-readable differences, which is empty if the two compile identically.
+pub fn fea_divergences(binary: &BaseTable, fea: &BaseTable) -> Result<Vec<String>, AutobaseError> {
+    let from_binary = write_fonts::dump_table(&binary.to_skrifa()?)?;
+    let from_fea = compile_fea(&fea.to_fea())?;
+    if from_binary == from_fea {
+        return Ok(vec![]);
+    }
+    let binary_lines = describe(&from_binary)?;
+    let fea_lines = describe(&from_fea)?;
+    let mut divergences = binary_lines
+        .iter()
+        .filter(|line| !fea_lines.contains(line))
+        .map(|line| format!("binary only: {}", line))
+        .chain(
+            fea_lines
+                .iter()
+                .filter(|line| !binary_lines.contains(line))
+                .map(|line| format!("FEA only: {}", line)),
+        )
+        .collect::<Vec<_>>();
+    if divergences.is_empty() {
+        divergences.push(format!(
+            "tables have the same values but different encodings ({} bytes from binary, {} bytes from FEA)",
+            from_binary.len(),
+            from_fea.len()
+        ));
+    }
+    Ok(divergences)
+}
@@ -13,4 +13,18 @@ pub enum AutobaseError {
     BaseScriptNotFound { script: Tag },
     #[error("Error building binary font: {0}")]
     FontBuild(#[from] write_fonts::BuilderError),
+    #[error("Error writing binary table: {0}")]
+    TableWrite(#[from] write_fonts::error::Error),
+    #[error("Error compiling feature code: {0}")]
+    FeaCompile(String),
+    #[error("{what} is {value}, which is out of the range of a 16-bit font unit")]
+    ValueOutOfRange { what: String, value: f64 },
+    #[error("The font has no CJK ideographs, kana or hangul to measure")]
+    NoCjkGlyphs,
+    #[error("Error rendering proof: {0}")]
+    Proof(String),
+    #[error("Error decoding WOFF2 font: {0}")]
+    Woff2(String),
+    #[error("Invalid location {setting:?}: {reason}")]
+    InvalidLocation { setting: String, reason: String },
 }
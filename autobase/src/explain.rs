@@ -0,0 +1,453 @@
+//! Narrate how each value of the generated records was arrived at.
+//!
+//! A surprising MinMax can come from many places: a word in an unexpected list,
+//! an override in the config, the tolerance pruning a value close to the font's
+//! own metrics, clamping, or the font default standing in for a NULL in the FEA
+//! output. Rather than leaving users to work this out from the source, the
+//! explanation goes through each value of each record and says which of these
+//! happened, working back from the record to the measurements which fed it.
+use std::{collections::BTreeSet, fmt::Write};
+
+use crate::{
+    base::MinMax,
+    config::Config,
+    fea::{FeaDowngrade, Side},
+    report::{Extreme, Extremes, MeasurementReport, ScriptReport},
+};
+
+/// The measurements whose words could have produced a record's values.
+struct Group<'a> {
+    /// "dflt", or the OpenType language tag
+    name: String,
+    measurements: Vec<&'a MeasurementReport>,
+}
+
+/// Split a script's measurements between its default record and its language
+/// records, as the records were built.
+fn groups<'a>(script: &'a ScriptReport, config: &Config) -> Vec<Group<'a>> {
+    let languages = script
+        .record
+        .as_ref()
+        .map(|record| record.languages.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut groups = vec![Group {
+        name: "dflt".to_string(),
+        measurements: vec![],
+    }];
+    groups.extend(languages.iter().map(|language| Group {
+        name: language.clone(),
+        measurements: vec![],
+    }));
+    for measurement in script.measurements.iter() {
+        // A language with several language systems (zh-Hans and zh-Hant) fed
+        // the records of each of them
+        let tags = measurement
+            .language
+            .as_deref()
+            .map(|language| config.opentype_languages(&script.script, language))
+            .unwrap_or_default();
+        let mut positions = tags
+            .iter()
+            .filter_map(|tag| {
+                let tag = tag.to_string();
+                groups.iter().position(|g| g.name == tag.trim_end())
+            })
+            .collect::<Vec<_>>();
+        if positions.is_empty() {
+            positions.push(0);
+        }
+        for group in positions {
+            groups[group].measurements.push(measurement);
+        }
+    }
+    groups
+}
+
+/// The most extreme word measured on one side, and the measurement it was in.
+fn most_extreme<'a>(
+    measurements: &[&'a MeasurementReport],
+    side: Side,
+) -> Option<(&'a Extreme, &'a MeasurementReport)> {
+    let candidates = measurements.iter().filter_map(|m| {
+        let extreme = match side {
+            Side::Max => m.extremes.max.as_ref(),
+            Side::Min => m.extremes.min.as_ref(),
+        }?;
+        Some((extreme, *m))
+    });
+    match side {
+        Side::Max => candidates.max_by_key(|(e, _)| e.value),
+        Side::Min => candidates.min_by_key(|(e, _)| e.value),
+    }
+}
+
+fn where_measured(measurement: &MeasurementReport) -> String {
+    format!(
+        "word list {} at {}",
+        measurement.word_list, measurement.location
+    )
+}
+
+/// Explain one side of a MinMax.
+fn explain_side(
+    out: &mut String,
+    group: &Group,
+    extremes: Option<&Extremes>,
+    side: Side,
+    font_default: &MinMax,
+    config: &Config,
+    downgrade: Option<i16>,
+) {
+    let tolerance = config.min_max_tolerance();
+    let (label, chosen, default, tolerance) = match side {
+        Side::Max => (
+            "max",
+            extremes.and_then(|e| e.max.as_ref()),
+            font_default.highest,
+            tolerance.max,
+        ),
+        Side::Min => (
+            "min",
+            extremes.and_then(|e| e.min.as_ref()),
+            font_default.lowest,
+            tolerance.min,
+        ),
+    };
+    let measured = most_extreme(&group.measurements, side);
+    let _ = write!(out, "  {} {}", group.name, label);
+    match chosen {
+        Some(chosen) => {
+            let _ = write!(out, " {}: ", chosen.value);
+            let word = chosen.word.as_str();
+            if word == "<override>" {
+                out.push_str("set by an override in the config");
+            } else if let Some(clamped) = word.strip_prefix("<clamped from ") {
+                let _ = write!(
+                    out,
+                    "clamped to the configured limit; the measured word was {}",
+                    clamped.strip_suffix('>').unwrap_or(clamped)
+                );
+            } else if word.starts_with("<mean of") {
+                let _ = write!(out, "the trimmed mean aggregation chose {}", word);
+            } else if word.starts_with('<') {
+                out.push_str("the font default");
+            } else {
+                let _ = write!(out, "reached by \"{}\"", word);
+                let source = group.measurements.iter().find(|m| {
+                    let candidate = match side {
+                        Side::Max => m.extremes.max.as_ref(),
+                        Side::Min => m.extremes.min.as_ref(),
+                    };
+                    candidate.is_some_and(|c| c.word == word && c.value == chosen.value)
+                });
+                if let Some(source) = source {
+                    let _ = write!(out, " in {}", where_measured(source));
+                }
+            }
+            if let Some((extreme, source)) = measured {
+                if extreme.value != chosen.value && !word.starts_with('<') {
+                    let _ = write!(
+                        out,
+                        "; the most extreme word measured, \"{}\" ({} in {}), was passed over by the exclusions, outlier removal, aggregation or frequency cutoff",
+                        extreme.word,
+                        extreme.value,
+                        where_measured(source)
+                    );
+                }
+            }
+        }
+        None => {
+            out.push_str(": omitted");
+            match (measured, default) {
+                (Some((extreme, source)), Some(default)) => {
+                    let _ = write!(
+                        out,
+                        ", as the most extreme value measured, {} (\"{}\" in {}), doesn't go beyond the font default {} by more than the tolerance of {}",
+                        extreme.value,
+                        extreme.word,
+                        where_measured(source),
+                        default,
+                        tolerance
+                    );
+                }
+                (None, _) => out.push_str(", as no words were measured"),
+                _ => {}
+            }
+        }
+    }
+    if let Some(value) = downgrade {
+        let _ = write!(
+            out,
+            "; in the FEA output, the NULL is replaced by the font default {}",
+            value
+        );
+    }
+    out.push('\n');
+}
+
+/// Explain every value of a script's horizontal record.
+///
+/// `downgrades` are the changes made to write the table as FEA, if it is being
+/// written as FEA.
+pub fn explain_script(
+    script: &ScriptReport,
+    config: &Config,
+    font_default: &MinMax,
+    downgrades: &[FeaDowngrade],
+) -> String {
+    let mut out = String::new();
+    let lists = script
+        .measurements
+        .iter()
+        .map(|m| m.word_list.as_str())
+        .collect::<BTreeSet<_>>();
+    let locations = script
+        .measurements
+        .iter()
+        .map(|m| m.location.as_str())
+        .collect::<BTreeSet<_>>();
+    let _ = writeln!(
+        out,
+        "{}: {} measurements of {} word lists at {} locations",
+        script.script,
+        script.measurements.len(),
+        lists.len(),
+        locations.len()
+    );
+    let Some(record) = &script.record else {
+        out.push_str("  no record written\n");
+        let group = Group {
+            name: "dflt".to_string(),
+            measurements: script.measurements.iter().collect(),
+        };
+        for side in [Side::Max, Side::Min] {
+            explain_side(&mut out, &group, None, side, font_default, config, None);
+        }
+        return out;
+    };
+    let ot_script = config.opentype_script(&script.script);
+    let default_added = downgrades.iter().any(|d| {
+        matches!(d, FeaDowngrade::DefaultMinMaxAdded { axis: "HorizAxis", script } if Some(*script) == ot_script)
+    });
+    for group in groups(script, config) {
+        let lists = group
+            .measurements
+            .iter()
+            .map(|m| m.word_list.as_str())
+            .collect::<BTreeSet<_>>();
+        let _ = writeln!(
+            out,
+            "  {} is from: {}",
+            group.name,
+            if lists.is_empty() {
+                "no word lists".to_string()
+            } else {
+                lists.into_iter().collect::<Vec<_>>().join(", ")
+            }
+        );
+        let extremes = if group.name == "dflt" {
+            record.default.as_ref()
+        } else {
+            record.languages.get(&group.name)
+        };
+        if group.name == "dflt" && default_added {
+            out.push_str("  dflt: none measured, so the FEA output uses the font default, as FEA can't express language records without one\n");
+            continue;
+        }
+        for side in [Side::Max, Side::Min] {
+            let downgrade = downgrades.iter().find_map(|d| match d {
+                FeaDowngrade::NullReplaced {
+                    axis: "HorizAxis",
+                    script,
+                    language,
+                    side: s,
+                    value,
+                } if Some(*script) == ot_script
+                    && *s == side
+                    && language
+                        .map_or("dflt".to_string(), |l| l.to_string().trim_end().to_string())
+                        == group.name =>
+                {
+                    Some(*value)
+                }
+                _ => None,
+            });
+            explain_side(
+                &mut out,
+                &group,
+                extremes,
+                side,
+                font_default,
+                config,
+                downgrade,
+            );
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use skrifa::Tag;
+
+    use super::*;
+    use crate::report::RecordReport;
+
+    fn extreme(value: i16, word: &str) -> Option<Extreme> {
+        Some(Extreme {
+            value,
+            word: word.to_string(),
+        })
+    }
+
+    fn measurement(
+        word_list: &str,
+        language: Option<&str>,
+        min: i16,
+        max: i16,
+    ) -> MeasurementReport {
+        MeasurementReport {
+            word_list: word_list.to_string(),
+            language: language.map(str::to_string),
+            location: "wght=400".to_string(),
+            band: None,
+            extremes: Extremes {
+                min: extreme(min, "gjp"),
+                max: extreme(max, "Ålborg"),
+            },
+        }
+    }
+
+    fn script(measurements: Vec<MeasurementReport>, default: Option<Extremes>) -> ScriptReport {
+        ScriptReport {
+            script: "Latn".to_string(),
+            measurements,
+            record: Some(RecordReport {
+                default,
+                languages: BTreeMap::new(),
+                baselines: BTreeMap::new(),
+            }),
+        }
+    }
+
+    fn explain(script: &ScriptReport, downgrades: &[FeaDowngrade]) -> String {
+        let config = Config {
+            min_tolerance: Some(20),
+            ..Default::default()
+        };
+        explain_script(script, &config, &MinMax::new_min_max(-250, 900), downgrades)
+    }
+
+    #[test]
+    fn values_are_traced_to_their_words() {
+        let script = script(
+            vec![measurement("Latin", None, -260, 950)],
+            Some(Extremes {
+                min: None,
+                max: extreme(950, "Ålborg"),
+            }),
+        );
+        assert_eq!(
+            explain(&script, &[]),
+            "Latn: 1 measurements of 1 word lists at 1 locations\n  dflt is from: Latin\n  dflt max 950: reached by \"Ålborg\" in word list Latin at wght=400\n  dflt min: omitted, as the most extreme value measured, -260 (\"gjp\" in word list Latin at wght=400), doesn't go beyond the font default -250 by more than the tolerance of 20\n"
+        );
+    }
+
+    #[test]
+    fn words_passed_over_are_mentioned() {
+        let script = script(
+            vec![
+                measurement("Latin", None, -260, 950),
+                measurement("Vietnamese", None, -260, 1010),
+            ],
+            Some(Extremes {
+                min: None,
+                max: extreme(950, "Ålborg"),
+            }),
+        );
+        let explanation = explain(&script, &[]);
+        assert!(explanation.contains(
+            "dflt max 950: reached by \"Ålborg\" in word list Latin at wght=400; the most extreme word measured, \"Ålborg\" (1010 in word list Vietnamese at wght=400), was passed over"
+        ), "{}", explanation);
+    }
+
+    #[test]
+    fn overrides_and_clamping_are_named() {
+        let script = script(
+            vec![measurement("Latin", None, -400, 950)],
+            Some(Extremes {
+                min: extreme(-300, "<clamped from gjp>"),
+                max: extreme(1000, "<override>"),
+            }),
+        );
+        let explanation = explain(&script, &[]);
+        assert!(explanation.contains("dflt max 1000: set by an override in the config\n"));
+        assert!(explanation.contains(
+            "dflt min -300: clamped to the configured limit; the measured word was gjp\n"
+        ));
+    }
+
+    #[test]
+    fn nulls_replaced_in_the_fea_are_noted() {
+        let script = script(
+            vec![measurement("Latin", None, -260, 950)],
+            Some(Extremes {
+                min: None,
+                max: extreme(950, "Ålborg"),
+            }),
+        );
+        let downgrade = FeaDowngrade::NullReplaced {
+            axis: "HorizAxis",
+            script: Tag::new(b"latn"),
+            language: None,
+            side: Side::Min,
+            value: -250,
+        };
+        let explanation = explain(&script, &[downgrade]);
+        assert!(explanation.ends_with(
+            "by more than the tolerance of 20; in the FEA output, the NULL is replaced by the font default -250\n"
+        ), "{}", explanation);
+    }
+
+    #[test]
+    fn language_records_are_explained_from_their_own_words() {
+        let mut script = script(
+            vec![
+                measurement("Latin", None, -260, 950),
+                measurement("Turkish", Some("tr"), -260, 1020),
+            ],
+            None,
+        );
+        script.record.as_mut().unwrap().languages.insert(
+            "TRK".to_string(),
+            Extremes {
+                min: None,
+                max: extreme(1020, "Ålborg"),
+            },
+        );
+        let downgrade = FeaDowngrade::DefaultMinMaxAdded {
+            axis: "HorizAxis",
+            script: Tag::new(b"latn"),
+        };
+        let explanation = explain(&script, &[downgrade]);
+        assert!(explanation.contains("  dflt is from: Latin\n  dflt: none measured, so the FEA output uses the font default"), "{}", explanation);
+        assert!(explanation.contains(
+            "  TRK is from: Turkish\n  TRK max 1020: reached by \"Ålborg\" in word list Turkish at wght=400\n"
+        ), "{}", explanation);
+    }
+
+    #[test]
+    fn scripts_without_a_record_say_so() {
+        let script = ScriptReport {
+            script: "Latn".to_string(),
+            measurements: vec![],
+            record: None,
+        };
+        assert_eq!(
+            explain(&script, &[]),
+            "Latn: 0 measurements of 0 word lists at 0 locations\n  no record written\n  dflt max: omitted, as no words were measured\n  dflt min: omitted, as no words were measured\n"
+        );
+    }
+}
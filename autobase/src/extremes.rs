@@ -0,0 +1,234 @@
+//! Where the vertical extremes of words come from.
+//!
+//! By default, words are measured with fontheight, one instance of the font at
+//! a time. Integrators may already have a shaper of their own, such as
+//! HarfBuzz, or extremes measured ahead of time; an [`ExtremesProvider`] lets
+//! them hand those to the pipeline, which deduplicates, caches and aggregates
+//! the results exactly as it does fontheight's.
+use std::collections::HashMap;
+
+use fontheight::{InstanceReporter, Location, Reporter, WordList};
+
+use crate::{config::ScriptLanguage, utils::without_kashida, wordlist};
+
+/// The highest and lowest points of a word, in that order, in font units.
+pub type Extremes = (f64, f64);
+
+/// Measures the vertical extremes of words in one font.
+pub trait ExtremesProvider: Sync {
+    /// A name for the measuring method, which keeps measurements cached by
+    /// different providers apart.
+    fn method(&self) -> &str;
+
+    /// Prepare to measure words at one of the font's locations.
+    fn instance<'a>(
+        &'a self,
+        location: &'a Location,
+    ) -> anyhow::Result<Box<dyn InstanceExtremes + 'a>>;
+}
+
+/// Measures the vertical extremes of words at one location of a font.
+pub trait InstanceExtremes: Send + Sync {
+    /// Measure each of `words`, from the word list named `word_list` for
+    /// `source`, returning `None` for words which the font can't render.
+    ///
+    /// Words with a language are shaped in the OpenType language system
+    /// `source.opentype_language`, with `locl` applied.
+    fn measure(
+        &self,
+        word_list: &str,
+        source: &ScriptLanguage,
+        words: &[String],
+    ) -> anyhow::Result<Vec<Option<Extremes>>>;
+}
+
+/// Measures words with fontheight, which shapes them in the script and language
+/// of their word list, in its OpenType language system. Words in joining scripts such as Arabic are shaped whole,
+/// so their joined forms are measured, but without kashida.
+///
+/// Each instance extracts the outline of every glyph once, when it is created,
+/// and composes a word's extremes from those extents offset by the glyphs'
+/// positioning. The pipeline creates one instance per location and measures
+/// every word list with it, so no outline is extracted more than once per
+/// location.
+pub struct FontheightProvider<'a> {
+    reporter: &'a Reporter<'a>,
+}
+
+impl<'a> FontheightProvider<'a> {
+    pub fn new(reporter: &'a Reporter<'a>) -> Self {
+        FontheightProvider { reporter }
+    }
+}
+
+impl ExtremesProvider for FontheightProvider<'_> {
+    fn method(&self) -> &str {
+        "fontheight"
+    }
+
+    fn instance<'a>(
+        &'a self,
+        location: &'a Location,
+    ) -> anyhow::Result<Box<dyn InstanceExtremes + 'a>> {
+        Ok(Box::new(FontheightInstance(
+            self.reporter.instance(location)?,
+        )))
+    }
+}
+
+struct FontheightInstance<'a>(InstanceReporter<'a>);
+
+impl InstanceExtremes for FontheightInstance<'_> {
+    fn measure(
+        &self,
+        word_list: &str,
+        source: &ScriptLanguage,
+        words: &[String],
+    ) -> anyhow::Result<Vec<Option<Extremes>>> {
+        let shaped = words
+            .iter()
+            .map(|word| without_kashida(word, &source.script))
+            .collect::<Vec<_>>();
+        let count = shaped.len();
+        let list = WordList::define(
+            wordlist::shaping_metadata(word_list, source),
+            shaped
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<_>>(),
+        );
+        // Asking for as many exemplars as there are words reports every word the
+        // font can render
+        let report = self.0.par_check(&list, None, count)?;
+        let measured = report
+            .exemplars
+            .highest()
+            .iter()
+            .map(|word| (word.word, (word.extremes.highest(), word.extremes.lowest())))
+            .collect::<HashMap<_, _>>();
+        Ok(shaped
+            .iter()
+            .map(|word| measured.get(word.as_ref()).copied())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{baselines::tests::font_with_advances, cjk::tests::ideograph_font};
+
+    #[test]
+    fn cff_words_are_measured_to_their_curves() {
+        let source = ScriptLanguage {
+            script: "Hani".to_string(),
+            language: None,
+            opentype_language: None,
+        };
+        let words = ["水".to_string()];
+        for (cff2, location, highest) in [
+            (false, Location::new(), 850.0),
+            (true, Location::new(), 850.0),
+            (
+                true,
+                Location::new().axis("wght", 900.0).unwrap().clone(),
+                1000.0,
+            ),
+        ] {
+            let data = ideograph_font(cff2);
+            let reporter = Reporter::new(&data).unwrap();
+            let provider = FontheightProvider::new(&reporter);
+            let instance = provider.instance(&location).unwrap();
+            let measured = instance.measure("test", &source, &words).unwrap();
+            let (top, bottom) = measured[0].unwrap();
+            assert!(
+                (top - highest).abs() < 0.01,
+                "top {}, expected {}",
+                top,
+                highest
+            );
+            assert!((bottom + 50.0).abs() < 0.01, "bottom {}", bottom);
+        }
+    }
+
+    #[test]
+    fn words_in_joining_scripts_are_measured_without_kashida() {
+        let source = ScriptLanguage {
+            script: "Arab".to_string(),
+            language: None,
+            opentype_language: None,
+        };
+        // A tatweel reaching higher than the letters it stretches
+        let data = font_with_advances(&[('ب', -200, 500, 500), ('\u{0640}', 0, 900, 300)]);
+        let reporter = Reporter::new(&data).unwrap();
+        let provider = FontheightProvider::new(&reporter);
+        let location = Location::new();
+        let instance = provider.instance(&location).unwrap();
+        let words = ["ب\u{0640}ب".to_string(), "بب".to_string()];
+        let measured = instance.measure("test", &source, &words).unwrap();
+        assert_eq!(measured[0], Some((500.0, -200.0)));
+        assert_eq!(measured[0], measured[1]);
+    }
+
+    #[test]
+    fn words_are_shaped_in_their_language_system() {
+        use write_fonts::{
+            tables::{
+                gsub::{Gsub, SingleSubst, SubstitutionLookup, SubstitutionLookupList},
+                layout::{
+                    Feature, FeatureList, FeatureRecord, LangSys, LangSysRecord, Lookup,
+                    LookupFlag, Script, ScriptList, ScriptRecord,
+                },
+            },
+            types::{GlyphId16, Tag},
+            FontBuilder,
+        };
+
+        // The Turkish form of i, glyph 2, reaches higher than glyph 1
+        let data = font_with_advances(&[('i', 0, 500, 500), ('\u{E000}', 0, 800, 500)]);
+        let gsub = Gsub::new(
+            ScriptList::new(vec![ScriptRecord::new(
+                Tag::new(b"latn"),
+                Script::new(
+                    Some(LangSys::default()),
+                    vec![LangSysRecord::new(Tag::new(b"TRK "), LangSys::new(vec![0]))],
+                ),
+            )]),
+            FeatureList::new(vec![FeatureRecord::new(
+                Tag::new(b"locl"),
+                Feature::new(None, vec![0]),
+            )]),
+            SubstitutionLookupList::new(vec![SubstitutionLookup::Single(Lookup::new(
+                LookupFlag::empty(),
+                vec![SingleSubst::format_1(
+                    [GlyphId16::new(1)].into_iter().collect(),
+                    1,
+                )],
+            ))]),
+        );
+        let mut builder = FontBuilder::new();
+        builder.add_table(&gsub).unwrap();
+        builder.copy_missing_tables(skrifa::FontRef::new(&data).unwrap());
+        let data = builder.build();
+        let reporter = Reporter::new(&data).unwrap();
+        let provider = FontheightProvider::new(&reporter);
+        let location = Location::new();
+        let instance = provider.instance(&location).unwrap();
+        let words = ["i".to_string()];
+        let highest = |language: Option<&str>, opentype_language: Option<&[u8; 4]>| {
+            let source = ScriptLanguage {
+                script: "Latn".to_string(),
+                language: language.map(str::to_string),
+                opentype_language: opentype_language.map(Tag::new),
+            };
+            instance.measure("test", &source, &words).unwrap()[0]
+                .unwrap()
+                .0
+        };
+        assert_eq!(highest(None, None), 500.0);
+        assert_eq!(highest(Some("tr"), Some(b"TRK ")), 800.0);
+        // The language system given wins over the one for the language code
+        assert_eq!(highest(Some("tr"), Some(b"AZE ")), 500.0);
+        assert_eq!(highest(Some("az"), Some(b"TRK ")), 800.0);
+    }
+}
@@ -0,0 +1,256 @@
+//! Compare the tables generated for the styles of a family.
+//!
+//! Each style is measured on its own, so a heavy weight's extremes usually lie
+//! further out than a light one's. When the difference is large, switching
+//! weights makes lines jump, even though each font's table is right for that
+//! font. Here we line up the values of every style and report those which
+//! spread further than the tolerance.
+use std::collections::BTreeSet;
+
+use skrifa::Tag;
+
+use crate::{
+    base::{BaseScript, MinMax, Tolerance},
+    pipeline::FontResult,
+};
+
+/// A value which differs between the styles of a family by more than the tolerance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub axis: &'static str,
+    pub script: Tag,
+    /// The language of a MinMax record, or `None` for the script's default
+    pub language: Option<Tag>,
+    /// What differs: `min`, `max`, or a baseline tag
+    pub value: String,
+    /// The value in each font, in the order the fonts were given, or `None` if
+    /// the font has no record for the script
+    pub values: Vec<Option<i16>>,
+}
+
+impl Divergence {
+    /// The difference between the highest and lowest values.
+    pub fn spread(&self) -> u16 {
+        let present = self.values.iter().flatten();
+        match (present.clone().max(), present.min()) {
+            (Some(max), Some(min)) => max.abs_diff(*min),
+            _ => 0,
+        }
+    }
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} differs by {} units",
+            self.axis,
+            self.script,
+            self.language.unwrap_or(Tag::new(b"dflt")),
+            self.value,
+            self.spread()
+        )
+    }
+}
+
+/// Compare the tables generated for each font of a family, returning the values
+/// which differ between them by more than `tolerance`.
+///
+/// Maxima are compared with `tolerance.max`, minima with `tolerance.min`, and
+/// baselines with the larger of the two.
+///
+/// MinMax values are compared as the extents each font ends up with: a NULL or
+/// omitted side stands for the font's own default, so it is compared as that
+/// default. Baselines are only compared between the fonts which have them.
+pub fn divergences(fonts: &[FontResult], tolerance: Tolerance) -> Vec<Divergence> {
+    let mut divergences = vec![];
+    for (axis, horizontal) in [("horizontal", true), ("vertical", false)] {
+        let scripts = fonts
+            .iter()
+            .flat_map(|font| {
+                records(font, horizontal)
+                    .0
+                    .iter()
+                    .map(|record| record.script)
+            })
+            .collect::<BTreeSet<_>>();
+        for script in scripts {
+            let found = fonts
+                .iter()
+                .map(|font| {
+                    let (records, defaults) = records(font, horizontal);
+                    records
+                        .iter()
+                        .find(|record| record.script == script)
+                        .map(|record| (record, defaults))
+                })
+                .collect::<Vec<_>>();
+            let mut compare =
+                |language: Option<Tag>, value: String, values: Vec<Option<i16>>, tolerance: u16| {
+                    let divergence = Divergence {
+                        axis,
+                        script,
+                        language,
+                        value,
+                        values,
+                    };
+                    if divergence.spread() > tolerance {
+                        divergences.push(divergence);
+                    }
+                };
+
+            let baselines = found
+                .iter()
+                .flatten()
+                .flat_map(|(record, _)| record.baselines.keys().copied())
+                .collect::<BTreeSet<_>>();
+            for baseline in baselines {
+                compare(
+                    None,
+                    baseline.to_string(),
+                    found
+                        .iter()
+                        .map(|record| record.and_then(|(record, _)| record.baseline(baseline)))
+                        .collect(),
+                    tolerance.loosest(),
+                );
+            }
+
+            let has_min_max = found
+                .iter()
+                .flatten()
+                .any(|(record, _)| record.default_minmax.is_some() || !record.languages.is_empty());
+            if !has_min_max {
+                continue;
+            }
+            let languages = std::iter::once(None)
+                .chain(
+                    found
+                        .iter()
+                        .flatten()
+                        .flat_map(|(record, _)| record.languages.keys().copied().map(Some))
+                        .collect::<BTreeSet<_>>(),
+                )
+                .collect::<Vec<_>>();
+            for language in languages {
+                let extents = found
+                    .iter()
+                    .map(|record| {
+                        record.map(|(record, defaults)| {
+                            record
+                                .min_max(language)
+                                .map_or(defaults.clone(), |mm| mm.with_nulls_replaced(defaults))
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                for (side, value, side_tolerance) in [
+                    (
+                        "min",
+                        (|mm: &MinMax| mm.lowest) as fn(&MinMax) -> Option<i16>,
+                        tolerance.min,
+                    ),
+                    ("max", |mm: &MinMax| mm.highest, tolerance.max),
+                ] {
+                    compare(
+                        language,
+                        side.to_string(),
+                        extents
+                            .iter()
+                            .map(|mm| mm.as_ref().and_then(value))
+                            .collect(),
+                        side_tolerance,
+                    );
+                }
+            }
+        }
+    }
+    divergences
+}
+
+/// The script records on one axis of a font's table, with the font's default
+/// extents on that axis.
+fn records(font: &FontResult, horizontal: bool) -> (&[BaseScript], &MinMax) {
+    if horizontal {
+        (&font.base.horizontal, &font.font_defaults.horizontal)
+    } else {
+        (&font.base.vertical, &font.font_defaults.vertical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{base::BaseTable, font_metrics::FontDefaults};
+
+    /// A font whose Latin record has a `romn` baseline and the given MinMax
+    /// sides, and whose default extents run from -250 to 900.
+    fn font(romn: i16, lowest: Option<i16>, highest: Option<i16>) -> FontResult {
+        let mut latn = BaseScript::new(Tag::new(b"latn"));
+        latn.default_baseline = Some(Tag::new(b"romn"));
+        latn.baselines.insert(Tag::new(b"romn"), romn);
+        latn.default_minmax = Some(MinMax {
+            lowest,
+            highest,
+            ..MinMax::new_min_max(0, 0)
+        });
+        FontResult {
+            base: BaseTable::new(vec![latn], vec![]),
+            font_defaults: FontDefaults {
+                horizontal: MinMax::new_min_max(-250, 900),
+                vertical: MinMax::new_min_max(0, 1000),
+            },
+            scripts: vec![],
+            primary: false,
+            weight: 1.0,
+            min_max_weight: 1.0,
+        }
+    }
+
+    fn diverging(fonts: &[FontResult], max: u16, min: u16) -> Vec<String> {
+        divergences(fonts, Tolerance { max, min })
+            .iter()
+            .map(|divergence| divergence.value.clone())
+            .collect()
+    }
+
+    #[test]
+    fn each_side_has_its_own_tolerance() {
+        let fonts = [
+            font(0, Some(-200), Some(800)),
+            font(0, Some(-230), Some(812)),
+        ];
+        assert_eq!(diverging(&fonts, 10, 40), ["max"]);
+        assert_eq!(diverging(&fonts, 12, 29), ["min"]);
+        assert!(diverging(&fonts, 12, 30).is_empty());
+        let divergence = &divergences(&fonts, Tolerance::uniform(0))[0];
+        assert_eq!(divergence.values, [Some(-200), Some(-230)]);
+        assert_eq!(divergence.spread(), 30);
+    }
+
+    #[test]
+    fn null_sides_stand_for_the_font_default() {
+        // The regular's NULL min is its default of -250, as far out as the bold's
+        let fonts = [font(0, None, Some(800)), font(0, Some(-250), Some(800))];
+        assert!(diverging(&fonts, 0, 0).is_empty());
+        let fonts = [font(0, None, Some(800)), font(0, Some(-200), Some(800))];
+        assert_eq!(diverging(&fonts, 0, 0), ["min"]);
+    }
+
+    #[test]
+    fn baselines_use_the_looser_tolerance() {
+        let fonts = [
+            font(0, Some(-200), Some(800)),
+            font(15, Some(-200), Some(800)),
+        ];
+        assert!(diverging(&fonts, 10, 20).is_empty());
+        assert_eq!(diverging(&fonts, 10, 10), ["romn"]);
+    }
+
+    #[test]
+    fn fonts_without_the_script_are_left_out() {
+        let mut without = font(0, None, None);
+        without.base = BaseTable::default();
+        let fonts = [font(0, Some(-200), Some(800)), without];
+        assert!(diverging(&fonts, 0, 0).is_empty());
+    }
+}
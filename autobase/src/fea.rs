@@ -0,0 +1,147 @@
+//! Downgrade a BASE table to what AFDKO feature syntax can express.
+//!
+//! The binary BASE table can express some things that feature syntax cannot:
+//! MinMax records with one side missing (NULL), and language MinMax records in a
+//! script without a default MinMax. Before writing FEA we replace these with the
+//! nearest representable values, and record each replacement so that users can see
+//! exactly how the FEA output differs from what would be written into the binary.
+use skrifa::Tag;
+
+use crate::{
+    base::{BaseTable, MinMax},
+    font_metrics::FontDefaults,
+};
+
+/// Which side of a MinMax record was changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Min,
+    Max,
+}
+
+/// A lossy transformation made so that a BASE table can be written as FEA.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeaDowngrade {
+    /// A NULL side of a MinMax record was replaced by the font default
+    ///
+    /// A language of `None` refers to the script's default MinMax.
+    NullReplaced {
+        axis: &'static str,
+        script: Tag,
+        language: Option<Tag>,
+        side: Side,
+        value: i16,
+    },
+    /// A script with language MinMax records but no default MinMax was given the
+    /// font default, as FEA can only express language records alongside a default
+    DefaultMinMaxAdded { axis: &'static str, script: Tag },
+}
+
+impl std::fmt::Display for FeaDowngrade {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeaDowngrade::NullReplaced {
+                axis,
+                script,
+                language,
+                side,
+                value,
+            } => write!(
+                f,
+                "{} {} {}: NULL {} replaced by font default {}",
+                axis,
+                script,
+                language
+                    .map(|l| l.to_string())
+                    .unwrap_or_else(|| "dflt".to_string()),
+                match side {
+                    Side::Min => "min",
+                    Side::Max => "max",
+                },
+                value
+            ),
+            FeaDowngrade::DefaultMinMaxAdded { axis, script } => write!(
+                f,
+                "{} {}: font default MinMax added as script default so that language records can be expressed",
+                axis, script
+            ),
+        }
+    }
+}
+
+/// Replace the NULL sides of a MinMax with the font defaults, recording each replacement.
+fn replace_nulls(
+    minmax: &MinMax,
+    font_minmax: &MinMax,
+    axis: &'static str,
+    script: Tag,
+    language: Option<Tag>,
+    downgrades: &mut Vec<FeaDowngrade>,
+) -> MinMax {
+    let replaced = minmax.with_nulls_replaced(font_minmax);
+    for (side, before, after) in [
+        (Side::Min, minmax.lowest, replaced.lowest),
+        (Side::Max, minmax.highest, replaced.highest),
+    ] {
+        if let (None, Some(value)) = (before, after) {
+            downgrades.push(FeaDowngrade::NullReplaced {
+                axis,
+                script,
+                language,
+                side,
+                value,
+            });
+        }
+    }
+    replaced
+}
+
+/// Convert a BASE table into one which FEA can express, using the font's default
+/// MinMax for each axis where values are missing.
+///
+/// Returns the converted table, along with every lossy transformation made.
+pub fn downgrade_for_fea(
+    base: &BaseTable,
+    defaults: &FontDefaults,
+) -> (BaseTable, Vec<FeaDowngrade>) {
+    let mut base = base.clone();
+    let mut downgrades = vec![];
+    for (axis, scripts, font_minmax) in [
+        ("HorizAxis", &mut base.horizontal, &defaults.horizontal),
+        ("VertAxis", &mut base.vertical, &defaults.vertical),
+    ] {
+        for script in scripts.iter_mut() {
+            if script.default_minmax.is_none() && !script.languages.is_empty() {
+                script.default_minmax = Some(font_minmax.clone());
+                downgrades.push(FeaDowngrade::DefaultMinMaxAdded {
+                    axis,
+                    script: script.script,
+                });
+            }
+            if let Some(script_minmax) = &script.default_minmax {
+                // An empty script default is left alone
+                if !script_minmax.is_empty() {
+                    script.default_minmax = Some(replace_nulls(
+                        script_minmax,
+                        font_minmax,
+                        axis,
+                        script.script,
+                        None,
+                        &mut downgrades,
+                    ));
+                }
+            }
+            for (lang, minmax) in script.languages.iter_mut() {
+                *minmax = replace_nulls(
+                    minmax,
+                    font_minmax,
+                    axis,
+                    script.script,
+                    Some(*lang),
+                    &mut downgrades,
+                );
+            }
+        }
+    }
+    (base, downgrades)
+}
@@ -0,0 +1,298 @@
+//! Check a font's existing BASE table for common CJK problems.
+//!
+//! Unlike `--check`, which compares a font's table with the one autobase would
+//! generate, this looks only at the table already in the font, and reports what
+//! is wrong with it in a form which font QA tools such as fontspector can wrap
+//! as a check: a status, a stable code and a message.
+use skrifa::{raw::TableProvider, FontRef, Tag};
+
+use crate::{
+    base::BaseTable,
+    cjk::{self, CjkOptions},
+    config::Config,
+    error::AutobaseError,
+    utils::supported_scripts,
+};
+
+/// How serious a finding is, in the terms QA tools use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warn,
+    Fail,
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Fail => "FAIL",
+        })
+    }
+}
+
+/// A problem found in a font's BASE table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub level: Level,
+    /// A stable identifier for the kind of problem, e.g. `missing-idtp`
+    pub code: &'static str,
+    /// The script record concerned, if the problem is with one record
+    pub script: Option<Tag>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}] {}", self.level, self.code, self.message)
+    }
+}
+
+/// The baselines every CJK script record needs, whether or not its em-box is square.
+const CJK_BASELINES: [&[u8; 4]; 4] = [b"icfb", b"icft", b"ideo", b"romn"];
+
+/// Check the BASE table of the font in `font_bytes`, returning what is wrong
+/// with it; an empty list means no problems were found.
+///
+/// Fonts without CJK ideographs, kana or hangul have nothing to check.
+pub fn check_font(font_bytes: &[u8]) -> Result<Vec<Finding>, AutobaseError> {
+    let font = FontRef::new(font_bytes)?;
+    let metrics = match cjk::compute_bounds(&font, &CjkOptions::default()) {
+        Ok(metrics) => metrics,
+        Err(AutobaseError::NoCjkGlyphs) => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let finding = |level, code, script, message| Finding {
+        level,
+        code,
+        script,
+        message,
+    };
+    let base = match BaseTable::from_font(&font) {
+        Ok(Some(base)) => base,
+        Err(e) => {
+            return Ok(vec![finding(
+                Level::Fail,
+                "unreadable-base-table",
+                None,
+                format!("The BASE table could not be read: {}", e),
+            )])
+        }
+        Ok(None) => {
+            return Ok(vec![finding(
+                Level::Fail,
+                "missing-base-table",
+                None,
+                "The font has CJK glyphs but no BASE table".to_string(),
+            )])
+        }
+    };
+
+    let mut findings = vec![];
+    let upem = font.head()?.units_per_em() as f32;
+    let square = cjk::font_is_square(metrics.average_width(), upem, cjk::DEFAULT_SQUARE_TOLERANCE);
+    let descender = font.os2().map(|os2| os2.s_typo_descender()).ok();
+    let config = Config::default();
+    let mut scripts = supported_scripts(&font)
+        .into_iter()
+        .filter(|script| cjk::is_cjk_script(script))
+        .filter_map(|script| config.opentype_script(script))
+        .collect::<Vec<_>>();
+    scripts.sort();
+    scripts.dedup();
+    for script in scripts {
+        let Some(record) = base.script(script) else {
+            findings.push(finding(
+                Level::Fail,
+                "missing-cjk-script",
+                Some(script),
+                format!(
+                    "The font supports {} but has no horizontal record for it",
+                    script
+                ),
+            ));
+            continue;
+        };
+        let missing = CJK_BASELINES
+            .map(Tag::new)
+            .into_iter()
+            .filter(|tag| record.baseline(*tag).is_none())
+            .map(|tag| tag.to_string())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            findings.push(finding(
+                Level::Fail,
+                "missing-cjk-baselines",
+                Some(script),
+                format!(
+                    "The {} record has no {} baseline",
+                    script,
+                    missing.join(", ")
+                ),
+            ));
+        }
+        if record.default_baseline != Some(Tag::new(b"ideo")) {
+            findings.push(finding(
+                Level::Warn,
+                "cjk-default-baseline",
+                Some(script),
+                format!(
+                    "The default baseline of the {} record is {}, not ideo",
+                    script,
+                    record
+                        .default_baseline
+                        .map_or("missing".to_string(), |tag| tag.to_string())
+                ),
+            ));
+        }
+        if let (Some(ideo), Some(descender)) = (record.baseline(Tag::new(b"ideo")), descender) {
+            if ideo != descender {
+                findings.push(finding(
+                    Level::Warn,
+                    "ideo-descender-mismatch",
+                    Some(script),
+                    format!(
+                        "The {} ideo baseline is {}, but the OS/2 typo descender, the bottom of the ideographic em-box, is {}",
+                        script, ideo, descender
+                    ),
+                ));
+            }
+        }
+        if !square && record.baseline(Tag::new(b"idtp")).is_none() {
+            findings.push(finding(
+                Level::Fail,
+                "missing-idtp",
+                Some(script),
+                format!(
+                    "The average ideographic advance is {:.0} at {} units per em, so the em-box isn't square and the {} record needs an idtp baseline",
+                    metrics.average_width(),
+                    upem,
+                    script
+                ),
+            ));
+        }
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{tables::os2::Os2, FontBuilder};
+
+    use super::*;
+    use crate::{
+        base::BaseScript,
+        baselines::tests::font_with_advances,
+        cjk::tests::{ideograph_font, ideograph_font_with_em},
+    };
+
+    /// A Han record with every baseline a square CJK font needs.
+    fn hani() -> BaseScript {
+        let mut hani = BaseScript::new(Tag::new(b"hani"));
+        hani.default_baseline = Some(Tag::new(b"ideo"));
+        for (tag, value) in [
+            (b"icfb", -80),
+            (b"icft", 840),
+            (b"ideo", -120),
+            (b"romn", 0),
+        ] {
+            hani.baselines.insert(Tag::new(tag), value);
+        }
+        hani
+    }
+
+    fn with_base(data: &[u8], scripts: Vec<BaseScript>) -> Vec<u8> {
+        let font = FontRef::new(data).unwrap();
+        BaseTable::new(scripts, vec![])
+            .add_to_binary(&font)
+            .unwrap()
+    }
+
+    fn codes(data: &[u8]) -> Vec<&'static str> {
+        check_font(data)
+            .unwrap()
+            .iter()
+            .map(|finding| finding.code)
+            .collect()
+    }
+
+    #[test]
+    fn fonts_without_cjk_glyphs_have_nothing_to_check() {
+        assert!(codes(&font_with_advances(&[('x', 0, 500, 500)])).is_empty());
+    }
+
+    #[test]
+    fn cjk_fonts_need_a_readable_base_table() {
+        assert_eq!(codes(&ideograph_font(false)), ["missing-base-table"]);
+
+        let data = ideograph_font(false);
+        let font = FontRef::new(&data).unwrap();
+        let mut builder = FontBuilder::new();
+        builder.add_raw(Tag::new(b"BASE"), vec![0, 1]);
+        builder.copy_missing_tables(font);
+        assert_eq!(codes(&builder.build()), ["unreadable-base-table"]);
+    }
+
+    #[test]
+    fn a_complete_record_has_no_findings() {
+        let data = with_base(&ideograph_font(false), vec![hani()]);
+        assert!(codes(&data).is_empty());
+    }
+
+    #[test]
+    fn every_supported_cjk_script_needs_a_record() {
+        let data = with_base(
+            &ideograph_font(false),
+            vec![BaseScript::new(Tag::new(b"latn"))],
+        );
+        let findings = check_font(&data).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, "missing-cjk-script");
+        assert_eq!(findings[0].script, Some(Tag::new(b"hani")));
+        assert_eq!(findings[0].level, Level::Fail);
+    }
+
+    #[test]
+    fn records_need_the_cjk_baselines_and_ideo_as_default() {
+        let mut record = hani();
+        record.baselines.remove(&Tag::new(b"icft"));
+        record.default_baseline = Some(Tag::new(b"romn"));
+        let data = with_base(&ideograph_font(false), vec![record]);
+        let findings = check_font(&data).unwrap();
+        assert_eq!(
+            findings.iter().map(|f| f.to_string()).collect::<Vec<_>>(),
+            [
+                "FAIL [missing-cjk-baselines] The hani record has no icft baseline",
+                "WARN [cjk-default-baseline] The default baseline of the hani record is romn, not ideo",
+            ]
+        );
+    }
+
+    #[test]
+    fn ideo_should_sit_on_the_typo_descender() {
+        let data = ideograph_font(false);
+        let font = FontRef::new(&data).unwrap();
+        let mut builder = FontBuilder::new();
+        builder
+            .add_table(&Os2 {
+                s_typo_descender: -100,
+                ..Default::default()
+            })
+            .unwrap();
+        builder.copy_missing_tables(font);
+        let data = with_base(&builder.build(), vec![hani()]);
+        assert_eq!(codes(&data), ["ideo-descender-mismatch"]);
+    }
+
+    #[test]
+    fn only_oblong_em_boxes_need_idtp() {
+        let data = with_base(&ideograph_font_with_em(false, 1000, 1100), vec![hani()]);
+        assert_eq!(codes(&data), ["missing-idtp"]);
+
+        let mut record = hani();
+        record.baselines.insert(Tag::new(b"idtp"), 880);
+        let data = with_base(&ideograph_font_with_em(false, 1000, 1100), vec![record]);
+        assert!(codes(&data).is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+//! Fingerprints of the glyphs behind each script's records, for incremental updates.
+//!
+//! Re-measuring every script each time a font is exported is wasteful while a
+//! designer is only working on one of them. A fingerprint holds a hash of the
+//! outlines of each script's glyphs, and a hash of everything which affects
+//! every script: the settings, the layout tables, the vertical metrics, and the
+//! glyphs which don't belong to a single script. Comparing the fingerprint of
+//! the font now with the one stored when its table was last generated tells us
+//! which scripts need to be measured again.
+//!
+//! Glyphs reached only through GSUB, such as Indic conjuncts, can't be tied to
+//! a script without a closure over the layout rules, so they count towards
+//! every script: editing one makes every script stale.
+//!
+//! In a CJK font, every script record carries the ideographic em-box measured
+//! from the CJK glyphs, so editing those glyphs makes every script stale too.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use skrifa::{
+    instance::{LocationRef, NormalizedCoord, Size},
+    outline::{DrawSettings, OutlineGlyphCollection, OutlinePen},
+    raw::TableProvider,
+    FontRef, GlyphId, MetadataProvider, Tag,
+};
+
+use crate::{cjk::is_cjk_script, error::AutobaseError, utils::char_scripts};
+
+/// Tables whose contents affect the records of every script.
+const SHARED_TABLES: [&[u8; 4]; 10] = [
+    b"GSUB", b"GPOS", b"GDEF", b"OS/2", b"hhea", b"vhea", b"vmtx", b"fvar", b"avar", b"meta",
+];
+
+/// The glyphs behind each script's records, as last measured.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// A hash of the settings, and of everything in the font which affects
+    /// every script
+    pub shared: String,
+    /// A hash of the outlines of each script's glyphs, keyed by ISO 15924 code
+    pub scripts: BTreeMap<String, String>,
+}
+
+impl Fingerprint {
+    /// Take the fingerprint of a font.
+    ///
+    /// `settings` is anything else which the generated table depends on, such
+    /// as the text of the configuration file; if it changes, every script is stale.
+    pub fn of_font(font: &FontRef, settings: &[u8]) -> Result<Self, AutobaseError> {
+        let mut shared = Sha256::new();
+        shared.update(env!("CARGO_PKG_VERSION").as_bytes());
+        shared.update(settings);
+        shared.update(font.head()?.units_per_em().to_be_bytes());
+        for tag in SHARED_TABLES.map(Tag::new) {
+            shared.update(tag.to_be_bytes());
+            if let Some(data) = font.table_data(tag) {
+                shared.update(data.as_bytes());
+            }
+        }
+
+        let mut script_glyphs: HashMap<&str, BTreeSet<GlyphId>> = HashMap::new();
+        for (codepoint, gid) in font.charmap().mappings() {
+            let Some(c) = char::from_u32(codepoint) else {
+                continue;
+            };
+            let scripts = char_scripts(c)
+                .filter(|script| !script.starts_with('Z'))
+                .collect::<Vec<_>>();
+            if let [script] = scripts[..] {
+                script_glyphs.entry(script).or_default().insert(gid);
+            }
+        }
+        // Everything which isn't one script's alone is shared: common
+        // characters such as punctuation, and glyphs with no codepoint
+        let exclusive = script_glyphs
+            .values()
+            .flatten()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let glyph_count = font.maxp()?.num_glyphs();
+        let outlines = font.outline_glyphs();
+        let locations = outline_locations(font);
+        let hash_glyph = |hasher: &mut Sha256, gid: GlyphId| {
+            hasher.update(gid.to_u32().to_be_bytes());
+            hash_outline(&outlines, gid, &locations, hasher);
+        };
+        for gid in (0..glyph_count as u32).map(GlyphId::new) {
+            if !exclusive.contains(&gid) {
+                hash_glyph(&mut shared, gid);
+            }
+        }
+
+        let scripts = script_glyphs
+            .into_iter()
+            .map(|(script, glyphs)| {
+                let mut hasher = Sha256::new();
+                for gid in glyphs {
+                    hash_glyph(&mut hasher, gid);
+                }
+                (script.to_string(), format!("{:x}", hasher.finalize()))
+            })
+            .collect();
+        Ok(Fingerprint {
+            shared: format!("{:x}", shared.finalize()),
+            scripts,
+        })
+    }
+
+    /// The scripts, as ISO 15924 codes, which must be measured again because
+    /// their glyphs differ from those in `previous`.
+    ///
+    /// Every script is stale if there is no previous fingerprint, or if anything
+    /// shared by every script has changed. So is every script if the CJK glyphs
+    /// behind the ideographic em-box have changed, as each script's record
+    /// carries it: all the CJK glyphs, or with `cjk_per_script`, the ideographs
+    /// (which also decide whether the font is square), if the font has any.
+    pub fn stale_scripts(
+        &self,
+        previous: Option<&Fingerprint>,
+        cjk_per_script: bool,
+    ) -> Vec<String> {
+        let stale = self
+            .scripts
+            .iter()
+            .filter(|(script, hash)| {
+                previous.is_none_or(|previous| {
+                    previous.shared != self.shared || previous.scripts.get(*script) != Some(hash)
+                })
+            })
+            .map(|(script, _)| script.clone())
+            .collect::<Vec<_>>();
+        let em_box_scripts = |script: &str| {
+            if cjk_per_script && self.scripts.contains_key("Hani") {
+                script == "Hani"
+            } else {
+                is_cjk_script(script)
+            }
+        };
+        if stale.iter().any(|script| em_box_scripts(script)) {
+            return self.scripts.keys().cloned().collect();
+        }
+        stale
+    }
+
+    /// The scripts, as ISO 15924 codes, which were in `previous` but which the
+    /// font no longer has.
+    pub fn removed_scripts(&self, previous: &Fingerprint) -> Vec<String> {
+        previous
+            .scripts
+            .keys()
+            .filter(|script| !self.scripts.contains_key(*script))
+            .cloned()
+            .collect()
+    }
+
+    /// Where the fingerprint of the font at `font_path` is stored unless
+    /// another path is given: beside it, as `<font file>.autobase.json`.
+    pub fn sidecar_path(font_path: &Path) -> PathBuf {
+        let mut file_name = font_path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".autobase.json");
+        font_path.with_file_name(file_name)
+    }
+
+    /// Read a stored fingerprint, or `None` if there isn't one.
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(path).context("failed to read fingerprint")?;
+        Ok(Some(
+            serde_json::from_str(&text).context("failed to parse fingerprint")?,
+        ))
+    }
+
+    /// Store the fingerprint at `path`.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, text).context("failed to write fingerprint")
+    }
+}
+
+/// The locations at which outlines are hashed: the default, and each end of
+/// each axis, so that a change to any master is noticed.
+fn outline_locations(font: &FontRef) -> Vec<Vec<NormalizedCoord>> {
+    let axis_count = font.axes().len();
+    let mut locations = vec![vec![NormalizedCoord::default(); axis_count]];
+    for axis in 0..axis_count {
+        for end in [-1.0, 1.0] {
+            let mut coords = vec![NormalizedCoord::default(); axis_count];
+            coords[axis] = NormalizedCoord::from_f32(end);
+            locations.push(coords);
+        }
+    }
+    locations
+}
+
+fn hash_outline(
+    outlines: &OutlineGlyphCollection,
+    gid: GlyphId,
+    locations: &[Vec<NormalizedCoord>],
+    hasher: &mut Sha256,
+) {
+    let Some(outline) = outlines.get(gid) else {
+        return;
+    };
+    let mut pen = HashPen(hasher);
+    for coords in locations {
+        let settings = DrawSettings::unhinted(Size::unscaled(), LocationRef::new(coords));
+        if outline.draw(settings, &mut pen).is_err() {
+            pen.0.update(b"!");
+        }
+    }
+}
+
+/// Feeds the commands of an outline into a hash.
+struct HashPen<'a>(&'a mut Sha256);
+
+impl HashPen<'_> {
+    fn command(&mut self, op: u8, values: &[f32]) {
+        self.0.update([op]);
+        for value in values {
+            self.0.update(value.to_bits().to_be_bytes());
+        }
+    }
+}
+
+impl OutlinePen for HashPen<'_> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.command(b'M', &[x, y]);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.command(b'L', &[x, y]);
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        self.command(b'Q', &[cx0, cy0, x, y]);
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        self.command(b'C', &[cx0, cy0, cx1, cy1, x, y]);
+    }
+
+    fn close(&mut self) {
+        self.command(b'Z', &[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use skrifa::FontRef;
+
+    use super::Fingerprint;
+    use crate::baselines::tests::font_with_advances;
+
+    fn fingerprint(glyphs: &[(char, i16, i16, u16)], settings: &[u8]) -> Fingerprint {
+        let bytes = font_with_advances(glyphs);
+        Fingerprint::of_font(&FontRef::new(&bytes).unwrap(), settings).unwrap()
+    }
+
+    #[test]
+    fn glyph_edit_makes_its_script_stale() {
+        let before = fingerprint(&[('A', 0, 700, 500), ('α', 0, 500, 500)], b"");
+        let after = fingerprint(&[('A', 0, 710, 500), ('α', 0, 500, 500)], b"");
+        assert_eq!(after.stale_scripts(Some(&before), false), vec!["Latn"]);
+        assert!(before.stale_scripts(Some(&before), false).is_empty());
+    }
+
+    #[test]
+    fn settings_edit_makes_every_script_stale() {
+        let glyphs = [('A', 0, 700, 500), ('α', 0, 500, 500)];
+        let before = fingerprint(&glyphs, b"tolerance = 5");
+        let after = fingerprint(&glyphs, b"tolerance = 10");
+        assert_eq!(
+            after.stale_scripts(Some(&before), false),
+            vec!["Grek", "Latn"]
+        );
+    }
+
+    #[test]
+    fn cjk_edits_make_every_script_stale() {
+        let before = fingerprint(
+            &[
+                ('A', 0, 700, 500),
+                ('水', -120, 880, 1000),
+                ('ア', -100, 800, 1000),
+            ],
+            b"",
+        );
+        let after = fingerprint(
+            &[
+                ('A', 0, 700, 500),
+                ('水', -120, 880, 1000),
+                ('ア', -90, 800, 1000),
+            ],
+            b"",
+        );
+        let mut every = before.scripts.keys().cloned().collect::<Vec<_>>();
+        every.sort();
+        let mut stale = after.stale_scripts(Some(&before), false);
+        stale.sort();
+        assert_eq!(stale, every);
+        // With per-script metrics only the ideographs are shared
+        assert_eq!(after.stale_scripts(Some(&before), true), vec!["Kana"]);
+    }
+}
@@ -0,0 +1,196 @@
+//! Read a font's default vertical extents.
+//!
+//! MinMax values which don't differ from the font's own ascender and descender
+//! are omitted, so we need to know what those are. Not every font has every
+//! table which records them, so if the preferred source is missing we fall back
+//! through the others, warning about which was used. The vertical axis has
+//! defaults of its own, from `vhea` or the em-box.
+use serde::Deserialize;
+use skrifa::{raw::TableProvider, FontRef};
+
+use crate::{base::MinMax, error::AutobaseError, utils::to_font_unit};
+
+/// Where a font's default ascender and descender come from.
+///
+/// Platforms clip text at different values, so the MinMax records should be
+/// compared against the metrics of the platform they are for.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetricSource {
+    /// The OS/2 typo ascender and descender
+    #[default]
+    Typo,
+    /// The OS/2 usWinAscent and usWinDescent
+    Win,
+    /// The hhea ascender and descender
+    Hhea,
+    /// The head table's global bounding box
+    Bbox,
+}
+
+impl MetricSource {
+    /// The order in which sources are tried when the preferred one is missing.
+    const FALLBACK: [MetricSource; 3] =
+        [MetricSource::Typo, MetricSource::Hhea, MetricSource::Bbox];
+
+    /// The (descender, ascender) from this source, or an error if the font lacks its table.
+    pub fn extents(self, font: &FontRef) -> Result<(i16, i16), AutobaseError> {
+        Ok(match self {
+            MetricSource::Typo => {
+                let os2 = font.os2()?;
+                (os2.s_typo_descender(), os2.s_typo_ascender())
+            }
+            MetricSource::Win => {
+                let os2 = font.os2()?;
+                // usWinDescent is positive below the baseline
+                (
+                    to_font_unit(-f64::from(os2.us_win_descent()), "usWinDescent")?,
+                    to_font_unit(f64::from(os2.us_win_ascent()), "usWinAscent")?,
+                )
+            }
+            MetricSource::Hhea => {
+                let hhea = font.hhea()?;
+                (hhea.descender().to_i16(), hhea.ascender().to_i16())
+            }
+            MetricSource::Bbox => {
+                let head = font.head()?;
+                (head.y_min(), head.y_max())
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for MetricSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MetricSource::Typo => "OS/2 typo ascender and descender",
+            MetricSource::Win => "OS/2 usWinAscent and usWinDescent",
+            MetricSource::Hhea => "hhea ascender and descender",
+            MetricSource::Bbox => "head bounding box",
+        })
+    }
+}
+
+impl std::str::FromStr for MetricSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "typo" => Ok(MetricSource::Typo),
+            "win" => Ok(MetricSource::Win),
+            "hhea" => Ok(MetricSource::Hhea),
+            "bbox" => Ok(MetricSource::Bbox),
+            _ => Err(format!(
+                "unknown metric source {}, expected typo, hhea, win or bbox",
+                s
+            )),
+        }
+    }
+}
+
+/// The font's default MinMax, from the preferred source if the font has it.
+///
+/// Otherwise the sources are tried in the order OS/2 (typo, then win), hhea, head, and a warning
+/// says which was used. Only a font without any of them is an error.
+pub fn font_minmax(font: &FontRef, preferred: MetricSource) -> Result<MinMax, AutobaseError> {
+    let mut error = match preferred.extents(font) {
+        Ok((descender, ascender)) => return Ok(MinMax::new_min_max(descender, ascender)),
+        Err(e) => e,
+    };
+    for source in MetricSource::FALLBACK {
+        if source == preferred {
+            continue;
+        }
+        match source.extents(font) {
+            Ok((descender, ascender)) => {
+                log::warn!(
+                    "Could not read the {} ({}); using the {} for the font default instead",
+                    preferred,
+                    error,
+                    source
+                );
+                return Ok(MinMax::new_min_max(descender, ascender));
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// The font's default MinMax for the vertical axis.
+///
+/// Vertical MinMax values are horizontal positions, across the vertical line. The
+/// vhea ascender and descender are measured from the vertical centerline, which
+/// is taken to be at half the em, so that they are in the same frame as the
+/// ideographic em-box. Without a vhea table, the em-box itself, from 0 to the em,
+/// is used.
+pub fn vertical_font_minmax(font: &FontRef) -> Result<MinMax, AutobaseError> {
+    let upem = font.head()?.units_per_em() as i32;
+    let Ok(vhea) = font.vhea() else {
+        log::debug!("No vhea table; using the em-box as the vertical font default");
+        return Ok(MinMax::new_min_max(0, upem.min(i16::MAX as i32) as i16));
+    };
+    let centerline = upem / 2;
+    let clamp = |value: i32| value.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+    Ok(MinMax::new_min_max(
+        clamp(centerline + vhea.descender().to_i16() as i32),
+        clamp(centerline + vhea.ascender().to_i16() as i32),
+    ))
+}
+
+/// The font's default MinMax on each axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontDefaults {
+    pub horizontal: MinMax,
+    pub vertical: MinMax,
+}
+
+impl FontDefaults {
+    /// Read the defaults, taking the horizontal one from the preferred source if possible.
+    pub fn new(font: &FontRef, preferred: MetricSource) -> Result<Self, AutobaseError> {
+        Ok(FontDefaults {
+            horizontal: font_minmax(font, preferred)?,
+            vertical: vertical_font_minmax(font)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{tables::os2::Os2, FontBuilder};
+
+    use super::*;
+
+    fn font_with_os2(os2: Os2) -> Vec<u8> {
+        let mut builder = FontBuilder::new();
+        builder.add_table(&os2).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn win_metrics_beyond_a_font_unit_are_an_error() {
+        let data = font_with_os2(Os2 {
+            us_win_ascent: 1900,
+            us_win_descent: 32768,
+            ..Default::default()
+        });
+        let font = FontRef::new(&data).unwrap();
+        assert_eq!(MetricSource::Win.extents(&font).unwrap(), (-32768, 1900));
+
+        let data = font_with_os2(Os2 {
+            us_win_ascent: 40000,
+            us_win_descent: 500,
+            s_typo_ascender: 800,
+            s_typo_descender: -200,
+            ..Default::default()
+        });
+        let font = FontRef::new(&data).unwrap();
+        assert!(matches!(
+            MetricSource::Win.extents(&font),
+            Err(AutobaseError::ValueOutOfRange { .. })
+        ));
+        // The typo metrics stand in for the win metrics
+        let minmax = font_minmax(&font, MetricSource::Win).unwrap();
+        assert_eq!((minmax.lowest, minmax.highest), (Some(-200), Some(800)));
+    }
+}
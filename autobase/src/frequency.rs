@@ -0,0 +1,109 @@
+//! Word frequencies, used to weight the choice of extremes.
+//!
+//! Without weighting, the single most extreme word in a word list sets the MinMax,
+//! however obscure that word is. With a frequency cutoff configured, the extreme is
+//! instead the most extreme value such that the words lying beyond it account for
+//! no more than the cutoff's share of all word occurrences. Words without a known
+//! frequency count as occurring once.
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Context;
+
+use crate::measurement::WordExtremes;
+
+#[derive(Debug, Default, Clone)]
+pub struct FrequencyTable(HashMap<String, f64>);
+
+impl FrequencyTable {
+    /// Split a `word count` line into its word and (optional) count.
+    ///
+    /// The word and count are separated by a tab, so that words may contain spaces.
+    pub fn parse_line(line: &str) -> Option<(&str, Option<f64>)> {
+        let mut parts = line.splitn(2, '\t');
+        let word = parts.next()?.trim();
+        if word.is_empty() {
+            return None;
+        }
+        let count = parts.next().and_then(|c| c.trim().parse::<f64>().ok());
+        Some((word, count))
+    }
+
+    /// Load a table of tab-separated `word count` lines.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read frequency table {}", path.display()))?;
+        let mut table = Self::default();
+        for line in contents.lines() {
+            if let Some((word, Some(count))) = Self::parse_line(line) {
+                table.insert(word, count);
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn insert(&mut self, word: &str, count: f64) {
+        self.0.insert(word.to_string(), count);
+    }
+
+    /// Count an occurrence of a word.
+    pub fn increment(&mut self, word: &str) {
+        *self.0.entry(word.to_string()).or_default() += 1.0;
+    }
+
+    pub fn get(&self, word: &str) -> Option<f64> {
+        self.0.get(word).copied()
+    }
+
+    /// Add the counts of another table to this one's, so that a word counted in
+    /// several sources gets the sum of its counts.
+    pub fn extend(&mut self, other: FrequencyTable) {
+        for (word, count) in other.0 {
+            *self.0.entry(word).or_default() += count;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Choose the exemplar which sets an extreme, weighting by frequency.
+///
+/// The exemplars must be ordered from most to least extreme. Returns the first
+/// exemplar at which the words seen so far make up more than `cutoff` of the total
+/// frequency.
+pub fn weighted_extreme<'a>(
+    exemplars: impl Iterator<Item = &'a WordExtremes> + Clone,
+    cutoff: f64,
+) -> Option<&'a WordExtremes> {
+    let weight = |w: &WordExtremes| w.frequency.unwrap_or(1.0);
+    let total: f64 = exemplars.clone().map(weight).sum();
+    let mut cumulative = 0.0;
+    for exemplar in exemplars {
+        cumulative += weight(exemplar);
+        if cumulative > cutoff * total {
+            return Some(exemplar);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extending_adds_counts() {
+        let mut table = FrequencyTable::default();
+        table.insert("the", 10.0);
+        table.insert("of", 4.0);
+        let mut corpus = FrequencyTable::default();
+        corpus.increment("the");
+        corpus.increment("the");
+        corpus.increment("and");
+        table.extend(corpus);
+        assert_eq!(table.get("the"), Some(12.0));
+        assert_eq!(table.get("of"), Some(4.0));
+        assert_eq!(table.get("and"), Some(1.0));
+    }
+}
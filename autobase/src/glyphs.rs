@@ -0,0 +1,115 @@
+//! Measure named glyphs as though they were an extra word list.
+//!
+//! Some glyphs set a script's vertical extent without appearing in any word list:
+//! currency symbols, rupee variants, or icons reached only by glyph name. The
+//! config can list glyph names for a script, and their bounds are then always
+//! included in the script's MinMax.
+use skrifa::{instance::Location, FontRef, GlyphNames, MetadataProvider};
+
+use crate::{
+    cjk::outline_bounds,
+    config::ScriptLanguage,
+    measurement::{Measurement, WordExtremes},
+};
+
+/// Measure the named glyphs at each location, as the word lists are measured at
+/// each of the font's interesting locations, giving one measurement per location.
+///
+/// Glyphs are measured to their curves, as words are. Names which are not found
+/// in the font, and glyphs without an outline, are skipped with a warning.
+pub fn measure_glyphs(
+    font: &FontRef,
+    locations: &[Location],
+    script: &str,
+    names: &[String],
+) -> Vec<Measurement> {
+    let glyph_names = GlyphNames::new(font);
+    let outlines = font.outline_glyphs();
+    let glyphs = names
+        .iter()
+        .filter_map(|name| {
+            let found = glyph_names.iter().find(|(_, n)| n.as_str() == name);
+            if found.is_none() {
+                log::warn!(
+                    "Glyph {} listed for script {} is not in the font",
+                    name,
+                    script
+                );
+            }
+            Some((name, found?.0))
+        })
+        .filter(|(name, gid)| {
+            let drawn = outlines.get(*gid).is_some();
+            if !drawn {
+                log::warn!("Glyph {} listed for script {} has no outline", name, script);
+            }
+            drawn
+        })
+        .collect::<Vec<_>>();
+    locations
+        .iter()
+        .map(|location| {
+            let measured = glyphs
+                .iter()
+                .filter_map(|(name, gid)| {
+                    let bounds = outline_bounds(&outlines, location.into(), *gid)?;
+                    Some(WordExtremes {
+                        word: format!("/{}", name),
+                        highest: bounds.y_max as f64,
+                        lowest: bounds.y_min as f64,
+                        frequency: None,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut highest = measured.clone();
+            highest.sort_by(|a, b| b.highest.total_cmp(&a.highest));
+            let mut lowest = measured;
+            lowest.sort_by(|a, b| a.lowest.total_cmp(&b.lowest));
+            Measurement {
+                name: format!("{} glyphs", script),
+                source: ScriptLanguage {
+                    script: script.to_string(),
+                    language: None,
+                    opentype_language: None,
+                },
+                highest,
+                lowest,
+                exact: true,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{tables::post::Post, FontBuilder};
+
+    use super::*;
+    use crate::cjk::tests::ideograph_font;
+
+    #[test]
+    fn named_glyphs_are_measured_at_each_location() {
+        let data = ideograph_font(true);
+        let mut builder = FontBuilder::new();
+        builder
+            .add_table(&Post::new_v2([".notdef", "uni6C34"]))
+            .unwrap();
+        builder.copy_missing_tables(FontRef::new(&data).unwrap());
+        let data = builder.build();
+        let font = FontRef::new(&data).unwrap();
+        let names = ["uni6C34".to_string(), "uni6C34.alt".to_string()];
+        let locations = [Location::default(), font.axes().location([("wght", 900.0)])];
+        let measurements = measure_glyphs(&font, &locations, "Hani", &names);
+        let extremes = measurements
+            .iter()
+            .map(|measurement| {
+                assert_eq!(measurement.highest.len(), 1);
+                let measured = &measurement.highest[0];
+                assert_eq!(measured.word, "/uni6C34");
+                (measured.lowest, measured.highest)
+            })
+            .collect::<Vec<_>>();
+        // The curves, not the control points, at the default; taller when bolder
+        assert_eq!(extremes, [(-50.0, 850.0), (-50.0, 1000.0)]);
+    }
+}
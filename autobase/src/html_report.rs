@@ -0,0 +1,269 @@
+//! A standalone HTML version of the report, with the extreme words drawn.
+//!
+//! Numbers alone rarely convince a designer that a MinMax is right. This page
+//! draws each word which set a script's or language's extremes, in the font
+//! itself, against the record's values, its baselines, and the font's hhea and
+//! typo lines, so that it is obvious at a glance what reaches how far and what
+//! would be clipped. The outlines are inlined as SVG paths, so the page needs
+//! neither the font nor anything else alongside it.
+use std::fmt::Write;
+
+use skrifa::{instance::Location, raw::TableProvider, FontRef};
+
+use crate::{
+    render::{escape, parse_location, render_text},
+    report::{Extreme, Extremes, FontReport, Report, ScriptReport},
+};
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+.pair { display: flex; gap: 2em; flex-wrap: wrap; }
+figure { margin: 0 0 1em 0; }
+figcaption { font-size: 0.9em; color: #444; }
+svg { height: 220px; border: 1px solid #ddd; }
+svg .word { fill: #000; }
+svg line { stroke-width: 4; }
+svg text { font-size: 48px; font-family: sans-serif; }
+.baseline { stroke: #999; }
+.record { stroke: #d00; fill: #d00; }
+.hhea { stroke: #06c; fill: #06c; stroke-dasharray: 20 10; }
+.typo { stroke: #090; fill: #090; stroke-dasharray: 5 10; }
+.base { stroke: #909; fill: #909; stroke-dasharray: 30 10 5 10; }
+";
+
+/// A horizontal line drawn behind each word.
+struct Line {
+    label: String,
+    value: i16,
+    class: &'static str,
+}
+
+/// Lines which are the same for every word of a font.
+fn font_lines(font: &FontRef) -> Vec<Line> {
+    let mut lines = vec![Line {
+        label: "baseline".to_string(),
+        value: 0,
+        class: "baseline",
+    }];
+    if let Ok(hhea) = font.hhea() {
+        for (label, value) in [
+            ("hhea ascender", hhea.ascender().to_i16()),
+            ("hhea descender", hhea.descender().to_i16()),
+        ] {
+            lines.push(Line {
+                label: label.to_string(),
+                value,
+                class: "hhea",
+            });
+        }
+    }
+    if let Ok(os2) = font.os2() {
+        for (label, value) in [
+            ("typo ascender", os2.s_typo_ascender()),
+            ("typo descender", os2.s_typo_descender()),
+        ] {
+            lines.push(Line {
+                label: label.to_string(),
+                value,
+                class: "typo",
+            });
+        }
+    }
+    lines
+}
+
+/// The location a word was measured at, found from the measurement which
+/// produced the extreme; the default location if there isn't one.
+fn location_of<'a>(script: &'a ScriptReport, extreme: &Extreme, highest: bool) -> &'a str {
+    script
+        .measurements
+        .iter()
+        .find(|measurement| {
+            let candidate = if highest {
+                &measurement.extremes.max
+            } else {
+                &measurement.extremes.min
+            };
+            candidate
+                .as_ref()
+                .is_some_and(|c| c.value == extreme.value && c.word == extreme.word)
+        })
+        .map_or("", |measurement| measurement.location.as_str())
+}
+
+/// The word to draw for an extreme. Values which didn't come from a word are
+/// annotated in angle brackets, such as "<from font>", and have nothing to draw;
+/// clamped values draw the word which was clamped.
+fn drawable_word(word: &str) -> &str {
+    match word.strip_prefix("<clamped from ") {
+        Some(clamped) => clamped.strip_suffix('>').unwrap_or(clamped),
+        None if word.starts_with('<') => "",
+        None => word,
+    }
+}
+
+/// Draw one word with the record's value and the font's lines behind it.
+fn figure(
+    html: &mut String,
+    font: &FontRef,
+    location: &str,
+    extreme: &Extreme,
+    what: &str,
+    mut lines: Vec<Line>,
+) {
+    // A report read back for a different font may name axes this one lacks
+    let skrifa_location = parse_location(font, location).unwrap_or_else(|e| {
+        log::warn!("Drawing {} at the default location: {}", extreme.word, e);
+        Location::default()
+    });
+    let rendered = render_text(font, drawable_word(&extreme.word), &skrifa_location);
+    lines.push(Line {
+        label: what.to_string(),
+        value: extreme.value,
+        class: "record",
+    });
+    let margin = 100.0;
+    let label_width = 700.0;
+    let top = lines.iter().map(|l| l.value).max().unwrap_or_default() as f32 + margin;
+    let bottom = lines.iter().map(|l| l.value).min().unwrap_or_default() as f32 - margin;
+    let width = rendered.advance + label_width + 2.0 * margin;
+    let _ = write!(
+        html,
+        "<figure><svg viewBox=\"{} {} {} {}\">",
+        -margin,
+        -top,
+        width,
+        top - bottom
+    );
+    for line in lines.iter() {
+        let y = -(line.value as f32);
+        let _ = write!(
+            html,
+            "<line class=\"{class}\" x1=\"{x1}\" x2=\"{x2}\" y1=\"{y}\" y2=\"{y}\"/>\
+             <text class=\"{class}\" x=\"{x}\" y=\"{ty}\">{label} {value}</text>",
+            class = line.class,
+            x1 = -margin,
+            x2 = rendered.advance + margin,
+            y = y,
+            x = rendered.advance + 1.5 * margin,
+            ty = y + 16.0,
+            label = escape(&line.label),
+            value = line.value,
+        );
+    }
+    let _ = write!(
+        html,
+        "<path class=\"word\" d=\"{}\"/></svg><figcaption>{} {}: {} at {}</figcaption></figure>",
+        rendered.path,
+        escape(what),
+        extreme.value,
+        escape(&extreme.word),
+        if location.is_empty() {
+            "the default location"
+        } else {
+            location
+        }
+    );
+}
+
+/// Draw the words which set one MinMax.
+fn extremes(
+    html: &mut String,
+    font: &FontRef,
+    script: &ScriptReport,
+    heading: &str,
+    extremes: &Extremes,
+    lines: &dyn Fn() -> Vec<Line>,
+) {
+    let _ = write!(html, "<h4>{}</h4><div class=\"pair\">", escape(heading));
+    if let Some(max) = &extremes.max {
+        figure(
+            html,
+            font,
+            location_of(script, max, true),
+            max,
+            "max",
+            lines(),
+        );
+    }
+    if let Some(min) = &extremes.min {
+        figure(
+            html,
+            font,
+            location_of(script, min, false),
+            min,
+            "min",
+            lines(),
+        );
+    }
+    html.push_str("</div>");
+}
+
+/// The most extreme words measured for a script, for scripts whose record has
+/// no MinMax of its own.
+fn measured_extremes(script: &ScriptReport) -> Extremes {
+    let max = script
+        .measurements
+        .iter()
+        .filter_map(|m| m.extremes.max.as_ref())
+        .max_by_key(|e| e.value)
+        .cloned();
+    let min = script
+        .measurements
+        .iter()
+        .filter_map(|m| m.extremes.min.as_ref())
+        .min_by_key(|e| e.value)
+        .cloned();
+    Extremes { min, max }
+}
+
+fn font_section(html: &mut String, report: &FontReport, font: &FontRef) {
+    let _ = write!(html, "<h2>{}</h2>", escape(&report.font));
+    for script in report.scripts.iter() {
+        let _ = write!(html, "<h3>{}</h3>", escape(&script.script));
+        let lines = || {
+            let mut lines = font_lines(font);
+            if let Some(record) = &script.record {
+                lines.extend(record.baselines.iter().map(|(tag, value)| Line {
+                    label: tag.clone(),
+                    value: *value,
+                    class: "base",
+                }));
+            }
+            lines
+        };
+        match script.record.as_ref().and_then(|r| r.default.as_ref()) {
+            Some(default) => extremes(html, font, script, "dflt", default, &lines),
+            None => extremes(
+                html,
+                font,
+                script,
+                "Most extreme words measured (no MinMax written)",
+                &measured_extremes(script),
+                &lines,
+            ),
+        }
+        for (language, language_extremes) in script
+            .record
+            .iter()
+            .flat_map(|record| record.languages.iter())
+        {
+            extremes(html, font, script, language, language_extremes, &lines);
+        }
+    }
+}
+
+/// Write the report as a standalone HTML page, drawing the words of each report
+/// font with the corresponding font in `fonts`.
+pub fn to_html(report: &Report, fonts: &[FontRef]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>autobase report</title>\
+         <style>{}</style></head><body><h1>autobase report</h1>",
+        STYLE
+    );
+    for (font_report, font) in report.fonts.iter().zip(fonts.iter()) {
+        font_section(&mut html, font_report, font);
+    }
+    html.push_str("</body></html>\n");
+    html
+}
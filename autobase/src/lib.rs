@@ -1,6 +1,42 @@
+pub mod accumulator;
 pub mod base;
+pub mod base_coords;
 pub mod base_script;
+pub mod baselines;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod changelog;
 pub mod cjk;
 pub mod config;
+pub mod config_check;
+#[cfg(feature = "fea-rs")]
+pub mod consistency;
 pub mod error;
+pub mod explain;
+pub mod extremes;
+pub mod family;
+pub mod fea;
+pub mod findings;
+pub mod fingerprint;
+pub mod font_metrics;
+pub mod frequency;
+pub mod glyphs;
+pub mod html_report;
+pub mod measurement;
+pub mod measurement_cache;
+pub mod meta;
+pub mod names;
+pub mod opsz;
+pub mod pipeline;
+pub mod profile;
+#[cfg(feature = "png")]
+pub mod proof;
+pub mod render;
+pub mod report;
+pub mod stats;
+pub mod ufo;
 pub mod utils;
+pub mod visualize;
+pub mod woff2;
+pub mod word_csv;
+pub mod wordlist;
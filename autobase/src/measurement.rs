@@ -0,0 +1,162 @@
+//! Owned measurements of word extents.
+//!
+//! fontheight's `Report`s borrow the word list they were produced from, and the
+//! script and language of a report come from that word list's metadata. We
+//! convert them into `Measurement`s which own their data and carry an explicit
+//! script and language, so that word lists from other sources can be attributed
+//! to a script too.
+use std::collections::HashMap;
+
+use fontheight::{Report, WordList};
+use serde::{Deserialize, Serialize};
+use skrifa::{raw::TableProvider, FontRef};
+
+use crate::{config::ScriptLanguage, frequency::FrequencyTable};
+
+/// The vertical extremes of a single word.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WordExtremes {
+    pub word: String,
+    pub highest: f64,
+    pub lowest: f64,
+    /// How often the word occurs, if known
+    pub frequency: Option<f64>,
+}
+
+/// The exemplar words found when measuring a word list at one location.
+#[derive(Clone, Debug)]
+pub struct Measurement {
+    /// The name of the word list which was measured
+    pub name: String,
+    /// The script and language the results are attributed to
+    pub source: ScriptLanguage,
+    /// Exemplar words, tallest first
+    pub highest: Vec<WordExtremes>,
+    /// Exemplar words, deepest first
+    pub lowest: Vec<WordExtremes>,
+    /// Whether every exemplar must fit within the extremes, so that the most
+    /// extreme is always chosen, whatever the configured strategy
+    pub exact: bool,
+}
+
+impl Measurement {
+    pub fn from_report(report: &Report, source: ScriptLanguage) -> Self {
+        let convert = |exemplars: &[fontheight::WordExtremes]| {
+            exemplars
+                .iter()
+                .map(|w| WordExtremes {
+                    word: w.word.to_string(),
+                    highest: w.extremes.highest(),
+                    lowest: w.extremes.lowest(),
+                    frequency: None,
+                })
+                .collect::<Vec<_>>()
+        };
+        Self {
+            name: report.word_list.name().to_string(),
+            source,
+            highest: convert(report.exemplars.highest()),
+            lowest: convert(report.exemplars.lowest()),
+            exact: false,
+        }
+    }
+
+    /// Collect measured words, keeping the `exemplars` most extreme in each direction.
+    pub fn from_words(
+        name: &str,
+        source: ScriptLanguage,
+        mut words: Vec<WordExtremes>,
+        exemplars: usize,
+    ) -> Self {
+        words.sort_by(|a, b| b.highest.total_cmp(&a.highest));
+        let highest = words.iter().take(exemplars).cloned().collect();
+        words.sort_by(|a, b| a.lowest.total_cmp(&b.lowest));
+        let lowest = words.into_iter().take(exemplars).collect();
+        Self {
+            name: name.to_string(),
+            source,
+            highest,
+            lowest,
+            exact: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.highest.is_empty() && self.lowest.is_empty()
+    }
+
+    /// The highest and lowest points of the most extreme words.
+    pub fn extremes(&self) -> Option<(f64, f64)> {
+        Some((self.highest.first()?.highest, self.lowest.first()?.lowest))
+    }
+
+    /// Annotate each exemplar with its frequency from the table.
+    pub fn apply_frequencies(&mut self, frequencies: &FrequencyTable) {
+        for word in self.highest.iter_mut().chain(self.lowest.iter_mut()) {
+            word.frequency = frequencies.get(&word.word);
+        }
+    }
+}
+
+/// The extremes which no word can exceed: the font's bounding box.
+///
+/// The head table's bounding box only covers the default instance, so these
+/// bounds only hold when measuring there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TheoreticalBounds {
+    pub highest: f64,
+    pub lowest: f64,
+}
+
+impl TheoreticalBounds {
+    pub fn from_font(font: &FontRef) -> Option<Self> {
+        let head = font.head().ok()?;
+        Some(Self {
+            highest: head.y_max() as f64,
+            lowest: head.y_min() as f64,
+        })
+    }
+
+    /// Whether extremes have reached the bounds on both sides, so that no
+    /// further word can change them.
+    pub fn reached_by(&self, highest: f64, lowest: f64) -> bool {
+        highest >= self.highest && lowest <= self.lowest
+    }
+}
+
+/// The running extremes of groups of word lists measured at one location, so
+/// that the remaining lists of a group can be skipped once its extremes reach
+/// the theoretical bounds.
+#[derive(Debug, Default)]
+pub struct RunningExtremes {
+    extremes: HashMap<String, (f64, f64)>,
+}
+
+impl RunningExtremes {
+    pub fn add(&mut self, group: &str, measurement: &Measurement) {
+        let Some((highest, lowest)) = measurement.extremes() else {
+            return;
+        };
+        let running = self
+            .extremes
+            .entry(group.to_string())
+            .or_insert((highest, lowest));
+        running.0 = running.0.max(highest);
+        running.1 = running.1.min(lowest);
+    }
+
+    pub fn reached(&self, group: &str, bounds: &TheoreticalBounds) -> bool {
+        self.extremes
+            .get(group)
+            .is_some_and(|(highest, lowest)| bounds.reached_by(*highest, *lowest))
+    }
+}
+
+/// Determine the script and language of a bundled word list from its metadata.
+pub fn wordlist_script_and_language(w: &WordList) -> ScriptLanguage {
+    ScriptLanguage {
+        script: w.script().unwrap_or("Zzzz").to_string(),
+        language: w.language().map(|lang| lang.to_string()),
+        opentype_language: None,
+    }
+}
@@ -0,0 +1,115 @@
+//! An on-disk cache of word list measurements.
+//!
+//! Measuring every word list at every location of a variable font is by far the
+//! slowest part of a run, but its results only depend on the font, the words
+//! measured and how they were measured; tweaks to the strategy, tolerances or
+//! overrides in the config don't change them. When a measurement cache directory
+//! is configured, each measurement is stored there, keyed by a hash of the font
+//! file, the word list, the location and the number of words measured, and
+//! reused by later runs.
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use skrifa::instance::NormalizedCoord;
+
+use crate::{
+    config::ScriptLanguage,
+    measurement::{Measurement, WordExtremes},
+    wordlist::WordSource,
+};
+
+/// The stored form of a measurement; the source is supplied by the caller.
+#[derive(Serialize, Deserialize)]
+struct CachedMeasurement {
+    name: String,
+    highest: Vec<WordExtremes>,
+    lowest: Vec<WordExtremes>,
+    exact: bool,
+}
+
+/// A directory of cached measurements for one font.
+pub struct MeasurementCache {
+    dir: PathBuf,
+    font_checksum: String,
+}
+
+impl MeasurementCache {
+    /// Open the cache in `dir` for the font with the given binary.
+    pub fn new(dir: &Path, font_bytes: &[u8]) -> Self {
+        MeasurementCache {
+            dir: dir.to_path_buf(),
+            font_checksum: format!("{:x}", Sha256::digest(font_bytes)),
+        }
+    }
+
+    /// The key of a measurement of `word_list` at the normalized `coords`.
+    ///
+    /// `method` distinguishes different ways of measuring the same words, such as
+    /// shaping them with a particular OpenType language.
+    pub fn key(
+        &self,
+        word_list: &dyn WordSource,
+        coords: &[NormalizedCoord],
+        words: usize,
+        method: &str,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update(self.font_checksum.as_bytes());
+        hasher.update(word_list.name().as_bytes());
+        for word in word_list.words() {
+            hasher.update(word.as_bytes());
+            hasher.update(b"\n");
+        }
+        for coord in coords {
+            hasher.update(coord.to_bits().to_be_bytes());
+        }
+        hasher.update((words as u64).to_be_bytes());
+        hasher.update(method.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Look up a measurement, attributing it to `source`.
+    pub fn get(&self, key: &str, source: ScriptLanguage) -> Option<Measurement> {
+        let contents = std::fs::read_to_string(self.path(key)).ok()?;
+        let cached: CachedMeasurement = match serde_json::from_str(&contents) {
+            Ok(cached) => cached,
+            Err(e) => {
+                log::debug!("Ignoring unreadable cached measurement {}: {}", key, e);
+                return None;
+            }
+        };
+        log::debug!("Using cached measurement of {}", cached.name);
+        Some(Measurement {
+            name: cached.name,
+            source,
+            highest: cached.highest,
+            lowest: cached.lowest,
+            exact: cached.exact,
+        })
+    }
+
+    /// Store a measurement, warning if it can't be written.
+    pub fn put(&self, key: &str, measurement: &Measurement) {
+        let cached = CachedMeasurement {
+            name: measurement.name.clone(),
+            highest: measurement.highest.clone(),
+            lowest: measurement.lowest.clone(),
+            exact: measurement.exact,
+        };
+        let result = serde_json::to_string(&cached)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                std::fs::create_dir_all(&self.dir)?;
+                std::fs::write(self.path(key), json)
+            });
+        if let Err(e) = result {
+            log::warn!("Could not cache measurement of {}: {}", measurement.name, e);
+        }
+    }
+}
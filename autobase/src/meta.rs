@@ -0,0 +1,142 @@
+//! Read the languages a font declares support for in its `meta` table.
+//!
+//! The `dlng` (design languages) and `slng` (supported languages) entries of the
+//! `meta` table are comma-separated lists of BCP 47-style tags such as `en`,
+//! `Latn`, `sr-Cyrl` or `und-Arab`. A font which carries them is telling us
+//! what it is for, which is a better guide than character coverage to the
+//! scripts and languages which deserve BASE records.
+use std::collections::HashSet;
+
+use serde::Deserialize;
+use skrifa::{raw::TableProvider, FontRef, Tag};
+
+use crate::{config::language_scripts, utils::KNOWN_ISO_SCRIPTS};
+
+/// How the languages declared in a font's `meta` table are used.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetaPolicy {
+    /// Don't look at the `meta` table
+    #[default]
+    Ignore,
+    /// Always keep declared scripts, even if they would be dropped for low coverage
+    Prioritize,
+    /// Only generate records for declared scripts and languages
+    Restrict,
+}
+
+impl std::str::FromStr for MetaPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(MetaPolicy::Ignore),
+            "prioritize" => Ok(MetaPolicy::Prioritize),
+            "restrict" => Ok(MetaPolicy::Restrict),
+            _ => Err(format!(
+                "unknown meta policy {}, expected ignore, prioritize or restrict",
+                s
+            )),
+        }
+    }
+}
+
+/// The scripts and languages declared in a `meta` table.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DeclaredLanguages {
+    /// ISO 15924 script codes, including those implied by languages declared
+    /// without a script
+    pub scripts: HashSet<String>,
+    /// ISO 639 language codes
+    pub languages: HashSet<String>,
+}
+
+impl DeclaredLanguages {
+    fn add_tag(&mut self, tag: &str) {
+        let mut subtags = tag.split('-');
+        let mut language = None;
+        if let Some(first) = subtags.next() {
+            if let Some(script) = as_script(first) {
+                self.scripts.insert(script);
+            } else if first != "und" && (first.len() == 2 || first.len() == 3) {
+                language = Some(first.to_ascii_lowercase());
+            }
+        }
+        if let Some(script) = subtags.find_map(as_script) {
+            self.scripts.insert(script);
+        } else if let Some(language) = &language {
+            // "en" declares English in its usual script, so that declaring "en,
+            // Arab" keeps Latin as well as Arabic
+            self.scripts
+                .extend(language_scripts(language).into_iter().map(str::to_string));
+        }
+        self.languages.extend(language);
+    }
+}
+
+/// Interpret a subtag as an ISO 15924 script code, if it is one.
+fn as_script(subtag: &str) -> Option<String> {
+    if subtag.len() != 4 {
+        return None;
+    }
+    let mut titlecase = subtag.to_ascii_lowercase();
+    titlecase[..1].make_ascii_uppercase();
+    KNOWN_ISO_SCRIPTS
+        .contains(&titlecase.as_str())
+        .then_some(titlecase)
+}
+
+/// Read the languages declared in the font's `meta` table.
+///
+/// The supported languages (`slng`) are used if present, and the design languages
+/// (`dlng`) otherwise. Returns `None` if the font declares neither.
+pub fn declared_languages(font: &FontRef) -> Option<DeclaredLanguages> {
+    let meta = font.meta().ok()?;
+    let table = font.table_data(Tag::new(b"meta"))?;
+    let entry = |tag: &[u8; 4]| {
+        let record = meta
+            .data_maps()
+            .iter()
+            .find(|record| record.tag() == Tag::new(tag))?;
+        let start = record.data_offset().to_u32() as usize;
+        let bytes = table
+            .as_bytes()
+            .get(start..start + record.data_length() as usize)?;
+        std::str::from_utf8(bytes).ok()
+    };
+    let text = entry(b"slng").or_else(|| entry(b"dlng"))?;
+    let mut declared = DeclaredLanguages::default();
+    for tag in text.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        declared.add_tag(tag);
+    }
+    Some(declared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declared(text: &str) -> DeclaredLanguages {
+        let mut declared = DeclaredLanguages::default();
+        for tag in text.split(',').map(str::trim) {
+            declared.add_tag(tag);
+        }
+        declared
+    }
+
+    fn sorted(set: &HashSet<String>) -> Vec<&str> {
+        let mut items = set.iter().map(String::as_str).collect::<Vec<_>>();
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn languages_imply_their_default_script() {
+        let en_arab = declared("en, Arab");
+        assert_eq!(sorted(&en_arab.scripts), ["Arab", "Latn"]);
+        assert_eq!(sorted(&en_arab.languages), ["en"]);
+        // An explicit script wins over the language's default
+        assert_eq!(sorted(&declared("sr-Latn").scripts), ["Latn"]);
+        assert_eq!(sorted(&declared("sr, und-Arab").scripts), ["Arab", "Cyrl"]);
+    }
+}
@@ -0,0 +1,176 @@
+//! Human-readable names for scripts and languages.
+//!
+//! Reports full of OpenType tags are hard for reviewers who don't know the tag
+//! registries by heart. These functions turn tags into English display names,
+//! keeping the tag alongside so that nothing is lost, e.g. "Devanagari (dev2)"
+//! or "Hindi (HIN)".
+use skrifa::Tag;
+
+use crate::utils::{iso15924_to_opentype, UNICODE_SCRIPT_NAMES};
+
+/// English names of OpenType language system tags.
+const LANGUAGE_NAMES: &[(&[u8; 4], &str)] = &[
+    (b"AFK ", "Afrikaans"),
+    (b"AMH ", "Amharic"),
+    (b"ARA ", "Arabic"),
+    (b"ASM ", "Assamese"),
+    (b"AZE ", "Azerbaijani"),
+    (b"BEL ", "Belarusian"),
+    (b"BEN ", "Bangla"),
+    (b"BGR ", "Bulgarian"),
+    (b"BOS ", "Bosnian"),
+    (b"BRE ", "Breton"),
+    (b"BRM ", "Burmese"),
+    (b"CAT ", "Catalan"),
+    (b"CHE ", "Chechen"),
+    (b"CHR ", "Cherokee"),
+    (b"CSY ", "Czech"),
+    (b"DAN ", "Danish"),
+    (b"DEU ", "German"),
+    (b"DIV ", "Divehi"),
+    (b"DZN ", "Dzongkha"),
+    (b"ELL ", "Greek"),
+    (b"ENG ", "English"),
+    (b"ESP ", "Spanish"),
+    (b"ETI ", "Estonian"),
+    (b"EUQ ", "Basque"),
+    (b"FAR ", "Persian"),
+    (b"FIN ", "Finnish"),
+    (b"FOS ", "Faroese"),
+    (b"FRA ", "French"),
+    (b"FRI ", "Frisian"),
+    (b"FUL ", "Fulah"),
+    (b"GAE ", "Scottish Gaelic"),
+    (b"GAL ", "Galician"),
+    (b"GRN ", "Greenlandic"),
+    (b"GUA ", "Guarani"),
+    (b"GUJ ", "Gujarati"),
+    (b"HAU ", "Hausa"),
+    (b"HAW ", "Hawaiian"),
+    (b"HIN ", "Hindi"),
+    (b"HRV ", "Croatian"),
+    (b"HUN ", "Hungarian"),
+    (b"HYE ", "Armenian"),
+    (b"IBO ", "Igbo"),
+    (b"IND ", "Indonesian"),
+    (b"IPPH", "Phonetic transcription—IPA conventions"),
+    (b"IRI ", "Irish"),
+    (b"ISL ", "Icelandic"),
+    (b"ITA ", "Italian"),
+    (b"IWR ", "Hebrew"),
+    (b"JAN ", "Japanese"),
+    (b"JAV ", "Javanese"),
+    (b"KAN ", "Kannada"),
+    (b"KAT ", "Georgian"),
+    (b"KAZ ", "Kazakh"),
+    (b"KHM ", "Khmer"),
+    (b"KIR ", "Kyrgyz"),
+    (b"KOR ", "Korean"),
+    (b"KSH ", "Kashmiri"),
+    (b"KUR ", "Kurdish"),
+    (b"LAO ", "Lao"),
+    (b"LAT ", "Latin"),
+    (b"LTH ", "Lithuanian"),
+    (b"LTZ ", "Luxembourgish"),
+    (b"LVI ", "Latvian"),
+    (b"MAL ", "Malayalam"),
+    (b"MAR ", "Marathi"),
+    (b"MKD ", "Macedonian"),
+    (b"MLG ", "Malagasy"),
+    (b"MLY ", "Malay"),
+    (b"MNG ", "Mongolian"),
+    (b"MOL ", "Moldavian"),
+    (b"MRI ", "Maori"),
+    (b"MTS ", "Maltese"),
+    (b"NEP ", "Nepali"),
+    (b"NLD ", "Dutch"),
+    (b"NOR ", "Norwegian"),
+    (b"NYN ", "Norwegian Nynorsk"),
+    (b"ORI ", "Odia"),
+    (b"PAN ", "Punjabi"),
+    (b"PAS ", "Pashto"),
+    (b"PLK ", "Polish"),
+    (b"PTG ", "Portuguese"),
+    (b"ROM ", "Romanian"),
+    (b"RUS ", "Russian"),
+    (b"SAN ", "Sanskrit"),
+    (b"SKY ", "Slovak"),
+    (b"SLV ", "Slovenian"),
+    (b"SML ", "Somali"),
+    (b"SND ", "Sindhi"),
+    (b"SNH ", "Sinhala"),
+    (b"SQI ", "Albanian"),
+    (b"SRB ", "Serbian"),
+    (b"SUN ", "Sundanese"),
+    (b"SVE ", "Swedish"),
+    (b"SWK ", "Swahili"),
+    (b"SYR ", "Syriac"),
+    (b"TAJ ", "Tajik"),
+    (b"TAM ", "Tamil"),
+    (b"TAT ", "Tatar"),
+    (b"TEL ", "Telugu"),
+    (b"TGL ", "Tagalog"),
+    (b"TGY ", "Tigrinya"),
+    (b"THA ", "Thai"),
+    (b"TIB ", "Tibetan"),
+    (b"TKM ", "Turkmen"),
+    (b"TRK ", "Turkish"),
+    (b"UKR ", "Ukrainian"),
+    (b"URD ", "Urdu"),
+    (b"UYG ", "Uyghur"),
+    (b"UZB ", "Uzbek"),
+    (b"VIT ", "Vietnamese"),
+    (b"WEL ", "Welsh"),
+    (b"WLF ", "Wolof"),
+    (b"XHS ", "Xhosa"),
+    (b"YBA ", "Yoruba"),
+    (b"YCR ", "Y-Cree"),
+    (b"YIM ", "Yi Modern"),
+    (b"ZHH ", "Chinese, Traditional, Hong Kong SAR"),
+    (b"ZHS ", "Chinese, Simplified"),
+    (b"ZHT ", "Chinese, Traditional"),
+];
+
+/// The English name of a script given as an ISO 15924 code, e.g. "Devanagari" for "Deva".
+pub fn script_name(iso_script: &str) -> Option<String> {
+    UNICODE_SCRIPT_NAMES
+        .iter()
+        .find(|(_, iso)| *iso == iso_script)
+        .map(|(name, _)| name.replace('_', " "))
+}
+
+/// The English name of a script given as an OpenType script tag, e.g. "Devanagari" for `dev2`.
+pub fn opentype_script_name(script: Tag) -> Option<String> {
+    if script == Tag::new(b"DFLT") {
+        return Some("Default".to_string());
+    }
+    UNICODE_SCRIPT_NAMES
+        .iter()
+        .find(|(_, iso)| iso15924_to_opentype(iso) == Some(script))
+        .map(|(name, _)| name.replace('_', " "))
+}
+
+/// The English name of an OpenType language system tag, e.g. "Hindi" for `HIN `.
+pub fn language_name(language: Tag) -> Option<&'static str> {
+    LANGUAGE_NAMES
+        .iter()
+        .find(|(tag, _)| Tag::new(tag) == language)
+        .map(|(_, name)| *name)
+}
+
+/// Describe an OpenType script tag for display, e.g. "Devanagari (dev2)".
+pub fn describe_script(script: Tag) -> String {
+    match opentype_script_name(script) {
+        Some(name) => format!("{} ({})", name, script),
+        None => script.to_string(),
+    }
+}
+
+/// Describe an OpenType language tag for display, e.g. "Hindi (HIN)".
+pub fn describe_language(language: Tag) -> String {
+    let tag = language.to_string();
+    match language_name(language) {
+        Some(name) => format!("{} ({})", name, tag.trim_end()),
+        None => tag.trim_end().to_string(),
+    }
+}
@@ -0,0 +1,49 @@
+//! Partition the instances of a variable font by optical size.
+//!
+//! Fonts with an `opsz` axis may have quite different extremes at text and display
+//! sizes. Until BASE tables can carry variable coordinates, we can at least
+//! measure each optical size band separately, report the differences, and choose
+//! which band the static coordinates should come from.
+use serde::Deserialize;
+use skrifa::{instance::Location, FontRef, MetadataProvider, Tag};
+
+/// A named range of optical sizes, e.g. `{ name = "text", min = 6, max = 18 }`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct OpszBand {
+    pub name: String,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl OpszBand {
+    /// Whether the location's optical size lies within this band (inclusive).
+    ///
+    /// Locations in fonts without an `opsz` axis are not in any band.
+    pub fn contains(&self, font: &FontRef, location: &Location) -> bool {
+        let axes = font.axes();
+        let Some(axis) = axes.iter().find(|axis| axis.tag() == Tag::new(b"opsz")) else {
+            return false;
+        };
+        let index = axis.index();
+        let coord = location.coords().get(index).copied().unwrap_or_default();
+        // Compare in normalized space, so that avar mappings are respected
+        let min = axes.location([("opsz", self.min)]).coords()[index];
+        let max = axes.location([("opsz", self.max)]).coords()[index];
+        min <= coord && coord <= max
+    }
+}
+
+impl std::fmt::Display for OpszBand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (opsz {}–{})", self.name, self.min, self.max)
+    }
+}
+
+/// Find the first band containing the location, if any.
+pub fn band_for<'a>(
+    bands: &'a [OpszBand],
+    font: &FontRef,
+    location: &Location,
+) -> Option<&'a OpszBand> {
+    bands.iter().find(|band| band.contains(font, location))
+}
@@ -0,0 +1,1430 @@
+//! The generation pipeline, from fonts and a configuration to a BASE table.
+//!
+//! A [`Generator`] does everything the command line tool does between reading
+//! its options and writing its output: it chooses each font's scripts, measures
+//! the word lists at each of its interesting locations, builds the MinMax
+//! records and CJK baselines, and collates the tables of a family into one,
+//! optionally downgraded to what FEA can express. Build tools and other front
+//! ends can drive it directly rather than shelling out to the CLI.
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use fontheight::Reporter;
+use itertools::Itertools;
+use rayon::prelude::*;
+use skrifa::{raw::TableProvider, FontRef, MetadataProvider, Tag};
+use write_fonts::{tables::base::Base, FontBuilder};
+
+use crate::{
+    accumulator::Accumulators,
+    base::{BaseTable, MinMax, Tolerance},
+    base_script, baselines, cjk,
+    config::{self, Collation, Config, NullPolicy, ScriptLanguage},
+    extremes::{ExtremesProvider, FontheightProvider, InstanceExtremes},
+    fea::{self, FeaDowngrade},
+    font_metrics::{self, FontDefaults, MetricSource},
+    frequency::FrequencyTable,
+    glyphs,
+    measurement::{Measurement, RunningExtremes, TheoreticalBounds, WordExtremes},
+    measurement_cache::MeasurementCache,
+    meta::{self, MetaPolicy},
+    opsz,
+    report::ScriptReport,
+    stats::{weighted_mean, weighted_median, ScriptStatistics},
+    utils::{self, supported_scripts},
+    word_csv::FontWordCsv,
+    wordlist::{BundledWordLists, CustomWordLists, WordSource},
+};
+
+/// The options of a run which aren't part of the configuration file.
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    /// Add MinMax records, rather than only CJK baselines
+    pub min_max: bool,
+    /// Only compute baselines (CJK, hang, math and romn), skipping the word list
+    /// analysis entirely
+    pub baseline_only: bool,
+    /// Only analyze scripts missing from each font's existing BASE table, and add
+    /// the results to that table
+    pub only_missing: bool,
+    /// The number of words from each list to measure
+    pub words_per_list: usize,
+    /// Stop starting new measurements this long after the generator is created
+    pub max_duration: Option<Duration>,
+    /// Keep each script's measurements, for [`FontResult::scripts`]
+    pub reports: bool,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            min_max: false,
+            baseline_only: false,
+            only_missing: false,
+            words_per_list: 1000,
+            max_duration: None,
+            reports: false,
+        }
+    }
+}
+
+/// Follows the measurement of a font's word lists, such as to draw a progress bar.
+pub trait MeasureProgress: Sync {
+    /// Add word list measurements still to be made.
+    fn add_measurements(&self, _count: usize) {}
+
+    /// Note that a word list is being measured, or skipped.
+    fn measuring(&self, _source: &ScriptLanguage, _word_list: &str) {}
+}
+
+/// Ignores progress.
+impl MeasureProgress for () {}
+
+/// The table generated for a single font.
+#[derive(Debug, Clone)]
+pub struct FontResult {
+    pub base: BaseTable,
+    /// The font's own metrics, which stand in for NULL MinMax values in FEA
+    pub font_defaults: FontDefaults,
+    /// The measurements behind each script's record, if
+    /// [`GeneratorOptions::reports`] was set
+    pub scripts: Vec<ScriptReport>,
+    /// Whether this font's values win when the tables of a family are collated
+    pub primary: bool,
+    /// How much this font's baselines count for when the tables of a family are
+    /// collated; a font with a weight of 0 contributes none
+    pub weight: f32,
+    /// How much this font's MinMax values count for when the tables of a family
+    /// are collated; a font with a weight of 0 contributes none
+    pub min_max_weight: f32,
+}
+
+/// A family's table collated for FEA output, with what FEA couldn't express.
+#[derive(Debug, Clone)]
+pub struct FeaResult {
+    pub base: BaseTable,
+    /// The changes made to each font's table, in the order the fonts were given
+    pub downgrades: Vec<Vec<FeaDowngrade>>,
+}
+
+/// Generates BASE tables from a configuration and options.
+pub struct Generator {
+    config: Config,
+    options: GeneratorOptions,
+    word_sources: Vec<Box<dyn WordSource>>,
+    bundled: BundledWordLists,
+    frequencies: FrequencyTable,
+    dflt_from: Vec<Tag>,
+    deadline: Option<Instant>,
+}
+
+impl Generator {
+    /// Check the configuration and load its word lists, corpora and frequencies,
+    /// which are shared by every font.
+    pub fn new(mut config: Config, options: GeneratorOptions) -> anyhow::Result<Self> {
+        if let Some(profile) = config.profile {
+            profile.apply(&mut config);
+        }
+        for script in config.scripts.iter().chain(config.exclude_scripts.iter()) {
+            if !utils::KNOWN_ISO_SCRIPTS.contains(&script.as_str()) {
+                anyhow::bail!(
+                    "Unknown ISO 15924 script code in script filters: {}",
+                    script
+                );
+            }
+        }
+        if let Some(band) = &config.opsz_band {
+            if !config.opsz_bands.iter().any(|b| &b.name == band) {
+                anyhow::bail!("opsz_band {} is not one of the configured opsz_bands", band);
+            }
+        }
+        if let Some(cutoff) = config.frequency_cutoff {
+            if !(0.0..1.0).contains(&cutoff) {
+                anyhow::bail!(
+                    "frequency_cutoff must be at least 0 and less than 1, not {}",
+                    cutoff
+                );
+            }
+        }
+        for weight in config.font_weights.iter() {
+            if [weight.weight, weight.min_max_weight]
+                .into_iter()
+                .flatten()
+                .any(|w| !(w.is_finite() && w >= 0.0))
+            {
+                anyhow::bail!(
+                    "The font weights for {} must not be negative",
+                    weight.pattern
+                );
+            }
+        }
+        config.check_script_tags().map_err(anyhow::Error::msg)?;
+        config.check_language_tags().map_err(anyhow::Error::msg)?;
+        let dflt_from = config.dflt_from_tags().map_err(anyhow::Error::msg)?;
+        // Load and tokenize the custom word lists once, rather than for every font
+        let custom = CustomWordLists::load(&config).context("failed to load word lists")?;
+        let word_sources = custom
+            .lists
+            .into_iter()
+            .map(|list| Box::new(list) as Box<dyn WordSource>)
+            .collect();
+        // Likewise cut down the bundled word lists to the words measured
+        let bundled = BundledWordLists::load(
+            Some(options.words_per_list)
+                .filter(|_| !config.skip_bundled_wordlists && !options.baseline_only),
+            matches!(
+                config.min_script_coverage,
+                Some(config::CoverageThreshold::Percent(_))
+            ),
+        );
+        let deadline = options
+            .max_duration
+            .map(|duration| Instant::now() + duration);
+        Ok(Generator {
+            config,
+            options,
+            word_sources,
+            bundled,
+            frequencies: custom.frequencies,
+            dflt_from,
+            deadline,
+        })
+    }
+
+    /// Measure the words of `source` for its script, alongside the word lists and
+    /// corpora of the configuration.
+    pub fn with_word_source(mut self, source: impl WordSource + 'static) -> Self {
+        self.word_sources.push(Box::new(source));
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn options(&self) -> &GeneratorOptions {
+        &self.options
+    }
+
+    /// Generate the table of each font and collate them into one.
+    pub fn generate(&self, fonts: &[&[u8]]) -> anyhow::Result<BaseTable> {
+        let results = fonts
+            .par_iter()
+            .map(|font_bytes| self.generate_font(font_bytes, &(), None))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(self.collate(&results))
+    }
+
+    /// Generate the BASE table of a font being compiled, ready to be added to it.
+    ///
+    /// This is for font compilers such as fontc, which can call it once the
+    /// tables needed for measuring (`cmap`, `glyf` or `CFF`, `fvar` and the
+    /// vertical metrics) are built, rather than adding the table to the
+    /// finished binary afterwards. Any BASE table already in `font` is used as
+    /// it is by [`GeneratorOptions::only_missing`].
+    pub fn build_base(&self, font: &FontRef) -> anyhow::Result<Base> {
+        // fontheight reads a font from its own bytes, so a member of a
+        // collection, or a font assembled in memory, is copied out first
+        let mut builder = FontBuilder::new();
+        builder.copy_missing_tables(font.clone());
+        let font_bytes = builder.build();
+        let result = self.generate_font(&font_bytes, &(), None)?;
+        Ok(self.collate(&[result]).to_skrifa()?)
+    }
+
+    /// Generate the table of a single font, reporting progress to `progress` and
+    /// exporting every word measured to `words_csv`, if given.
+    pub fn generate_font(
+        &self,
+        font_bytes: &[u8],
+        progress: &dyn MeasureProgress,
+        words_csv: Option<&FontWordCsv>,
+    ) -> anyhow::Result<FontResult> {
+        generate_base_for_font(self, font_bytes, None, progress, words_csv)
+    }
+
+    /// Generate the table of a single font, measuring words with `provider`
+    /// rather than fontheight.
+    pub fn generate_font_with(
+        &self,
+        font_bytes: &[u8],
+        provider: &dyn ExtremesProvider,
+        progress: &dyn MeasureProgress,
+        words_csv: Option<&FontWordCsv>,
+    ) -> anyhow::Result<FontResult> {
+        generate_base_for_font(self, font_bytes, Some(provider), progress, words_csv)
+    }
+
+    /// Collate the tables of a family into one table for the font binaries.
+    ///
+    /// One font is primary: the one marked [`FontResult::primary`], or else the
+    /// first. Its baselines win, and its MinMax values are kept unless another
+    /// font's lie further out by more than the tolerance. Baselines and records
+    /// which the primary font lacks are taken from the other fonts in order.
+    /// Fonts with a [`FontResult::weight`] or [`FontResult::min_max_weight`]
+    /// of 0 contribute no baselines or no MinMax values respectively, and the
+    /// weights scale each font's values when they are averaged. Baselines which
+    /// a script has no value for, and which will be written as 0, are warned
+    /// about here. Under [`NullPolicy::Replace`], NULL MinMax sides are replaced
+    /// with the font defaults, as for [`collate_for_fea`](Self::collate_for_fea).
+    pub fn collate(&self, fonts: &[FontResult]) -> BaseTable {
+        let base = match self.config.null_policy.unwrap_or_default() {
+            NullPolicy::Keep => {
+                self.finish(fonts.iter().map(|font| font.base.clone()).collect(), fonts)
+            }
+            NullPolicy::Replace => self.collate_for_fea(fonts).base,
+        };
+        base.warn_filled_baselines();
+        base
+    }
+
+    /// Collate the tables of a family into one table which FEA can express.
+    ///
+    /// FEA doesn't support NULL MinMax values, so each font's are replaced with
+    /// that font's default min and max before the tables are collated as
+    /// [`collate`](Self::collate) does.
+    pub fn collate_for_fea(&self, fonts: &[FontResult]) -> FeaResult {
+        let (bases, downgrades) = fonts
+            .iter()
+            .map(|font| fea::downgrade_for_fea(&font.base, &font.font_defaults))
+            .unzip();
+        FeaResult {
+            base: self.finish(bases, fonts),
+            downgrades,
+        }
+    }
+
+    fn finish(&self, bases: Vec<BaseTable>, fonts: &[FontResult]) -> BaseTable {
+        let weighted = bases
+            .into_iter()
+            .zip(fonts)
+            .map(|(base, font)| Weighted {
+                base,
+                primary: font.primary,
+                weight: font.weight as f64,
+                min_max_weight: font.min_max_weight as f64,
+            })
+            .collect();
+        let mut base = collate_bases(
+            weighted,
+            self.config.min_max_tolerance(),
+            self.config.collation,
+        );
+        if !self.dflt_from.is_empty() {
+            base.add_dflt(&self.dflt_from);
+        }
+        if self.config.omit_unmeasured_baselines {
+            base.omit_unmeasured_baselines();
+        }
+        base
+    }
+}
+
+/// Generate the BASE table of a font being compiled; see [`Generator::build_base`].
+///
+/// Compilers building many fonts with the same configuration should create a
+/// [`Generator`] once instead, so that its word lists are only loaded once.
+pub fn build_base(
+    font: &FontRef,
+    config: Config,
+    options: GeneratorOptions,
+) -> anyhow::Result<Base> {
+    Generator::new(config, options)?.build_base(font)
+}
+
+/// Whether the font covers enough of a script for it to get records.
+///
+/// Percentage thresholds are measured against the characters of the script's
+/// bundled word lists; scripts without any are always kept.
+fn script_meets_coverage(
+    font: &skrifa::FontRef,
+    script: &str,
+    coverage: Option<&utils::ScriptCoverage>,
+    threshold: config::CoverageThreshold,
+    bundled: &BundledWordLists,
+) -> bool {
+    match threshold {
+        config::CoverageThreshold::Codepoints(count) => {
+            coverage.map_or(0, |c| c.codepoints) >= count
+        }
+        config::CoverageThreshold::Percent(percent) => {
+            let Some(exemplars) = bundled.exemplar_characters(script) else {
+                log::debug!(
+                    "No exemplar characters for script {}, so its coverage is not checked",
+                    script
+                );
+                return true;
+            };
+            let charmap = font.charmap();
+            let covered = exemplars
+                .iter()
+                .filter(|c| charmap.map(**c).is_some())
+                .count();
+            covered as f64 * 100.0 / exemplars.len() as f64 >= percent
+        }
+    }
+}
+
+fn generate_base_for_font(
+    generator: &Generator,
+    font_bytes: &[u8],
+    provider: Option<&dyn ExtremesProvider>,
+    progress: &dyn MeasureProgress,
+    words_csv: Option<&FontWordCsv>,
+) -> anyhow::Result<FontResult> {
+    let options = &generator.options;
+    let mut config = generator.config.clone();
+    let reporter = Reporter::new(font_bytes)?;
+    let font = reporter.fontref();
+    let locations = reporter.interesting_locations();
+    let fontheight = FontheightProvider::new(&reporter);
+    let provider = provider.unwrap_or(&fontheight);
+    let instance_bands = locations
+        .iter()
+        .map(|location| {
+            opsz::band_for(
+                &config.opsz_bands,
+                font,
+                &utils::skrifa_location(font, location),
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut supported = match config.scripts_from {
+        config::ScriptSource::Cmap => supported_scripts(font),
+        config::ScriptSource::Layout => {
+            let scripts = utils::layout_scripts(font);
+            if scripts.is_empty() {
+                log::warn!(
+                    "Font has no scripts in its GSUB or GPOS script lists; using the cmap instead"
+                );
+                supported_scripts(font)
+            } else {
+                scripts
+            }
+        }
+    };
+    log::info!(
+        "Supported scripts: {}",
+        supported.iter().cloned().collect::<Vec<_>>().join(", ")
+    );
+    let declared = if config.meta == MetaPolicy::Ignore {
+        None
+    } else {
+        let declared = meta::declared_languages(font);
+        if declared.is_none() {
+            log::info!("Font declares no languages in a meta table");
+        }
+        declared
+    };
+    if let (MetaPolicy::Restrict, Some(declared)) = (config.meta, &declared) {
+        supported.retain(|script| {
+            // Languages whose script isn't known declare no scripts, and so
+            // restrict none
+            let allowed = declared.scripts.is_empty() || declared.scripts.contains(*script);
+            if !allowed {
+                log::info!(
+                    "Skipping script {}, which is not declared in the meta table",
+                    script
+                );
+            }
+            allowed
+        });
+        if !declared.languages.is_empty() {
+            config.declared_languages = Some(declared.languages.iter().cloned().collect());
+        }
+    }
+    if let Some(threshold) = config.min_script_coverage {
+        let coverage = utils::script_coverage(font);
+        supported.retain(|script| {
+            if config.meta == MetaPolicy::Prioritize
+                && declared
+                    .as_ref()
+                    .is_some_and(|d| d.scripts.contains(*script))
+            {
+                return true;
+            }
+            let covered = script_meets_coverage(
+                font,
+                script,
+                coverage.get(script),
+                threshold,
+                &generator.bundled,
+            );
+            if !covered {
+                log::info!(
+                    "Skipping script {}, which has less than {} coverage",
+                    script,
+                    threshold
+                );
+            }
+            covered
+        });
+    }
+    supported.retain(|script| {
+        let allowed = config.script_allowed(script);
+        if !allowed {
+            log::info!("Skipping script {}, which is filtered out", script);
+        }
+        allowed
+    });
+    let existing_base = if options.only_missing {
+        let existing = BaseTable::from_font(font)
+            .context("failed to read existing BASE table")?
+            .unwrap_or_default();
+        supported.retain(|script| {
+            config
+                .opentype_script(script)
+                .map(|tag| !existing.has_script(tag, options.min_max))
+                .unwrap_or(true)
+        });
+        log::info!(
+            "Scripts missing from the existing BASE table: {}",
+            supported.iter().cloned().collect::<Vec<_>>().join(", ")
+        );
+        Some(existing)
+    } else {
+        None
+    };
+    if options.baseline_only {
+        log::info!("Only computing baselines, skipping word list analysis");
+        let mut base = BaseTable::default();
+        add_cjk_baselines(font, &config, &supported, &mut base)?;
+        add_registered_baselines(font, &config, &supported, &mut base)?;
+        if let Some(mut existing) = existing_base {
+            existing.fill_missing(&base);
+            base = existing;
+        }
+        return Ok(FontResult {
+            base,
+            font_defaults: get_font_defaults(font, &config)?,
+            scripts: vec![],
+            primary: false,
+            weight: 1.0,
+            min_max_weight: 1.0,
+        });
+    }
+    let frequencies = &generator.frequencies;
+    let custom_wordlists = generator
+        .word_sources
+        .iter()
+        .filter(|w| {
+            let supported = supported.contains(w.script());
+            if !supported {
+                log::warn!(
+                    "Skipping {}: script {} is not supported by the font",
+                    w.name(),
+                    w.script()
+                );
+            }
+            supported
+        })
+        .map(|w| (w.as_ref(), w.source()));
+    let bundled_wordlists = generator
+        .bundled
+        .lists
+        .iter()
+        // Filter out word lists that don't have a script in the font
+        .filter(|word_list| supported.contains(word_list.script()))
+        .map(|word_list| {
+            let word_list: &dyn WordSource = word_list;
+            (word_list, word_list.source())
+        });
+    let wordlists = custom_wordlists
+        .chain(bundled_wordlists)
+        .filter(|(word_list, source)| {
+            let allowed = config.language_allowed(source);
+            if !allowed {
+                log::debug!(
+                    "Skipping word list {}: language is excluded",
+                    word_list.name()
+                );
+            }
+            allowed
+        })
+        .collect::<Vec<_>>();
+    // We want to filter out any words which are in the exclusions. But:
+    // - We can't clone or modify a wordlist
+    // - We can create a wordlist from an iterator but we then lose the metadata
+    // - We can't create new metadata objects or change the metadata on an existing wordlist
+    // - We can't add a filter function into par_check after par_iter because the function can't go across threads
+    // - We can't add a filter function into par_check before par_iter because we need Wordlist.par_iter to produce a ParWordListIter
+    // So there's not much we can do except get a large number of exemplars and hope for the best.
+    let cache = config
+        .measurement_cache_dir
+        .as_deref()
+        .map(|dir| MeasurementCache::new(dir, font_bytes));
+    let context = MeasureContext {
+        config: &config,
+        frequencies,
+        words_per_list: options.words_per_list,
+        cache: cache.as_ref(),
+        deadline: generator.deadline,
+        progress,
+        words_csv,
+    };
+    let instance_coords = locations
+        .iter()
+        .map(|location| utils::skrifa_location(font, location).coords().to_vec())
+        .collect::<Vec<_>>();
+    // Each instance extracts every glyph's outline up front, which is wasted if
+    // there are no word lists to measure
+    let instances = if wordlists.is_empty() {
+        vec![]
+    } else {
+        locations
+            .par_iter()
+            .map(|location| provider.instance(location))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("failed to initialise instances for testing")?
+    };
+    let bounds = TheoreticalBounds::from_font(font);
+    // Each location's word lists are measured in turn, so that a script whose
+    // extremes have reached the font's bounds can skip the rest of its lists.
+    // Measurements are folded into accumulators as they are made, rather than
+    // all being kept until the end.
+    progress.add_measurements(wordlists.len() * instances.len());
+    let (mut accumulators, unmeasured) = instances
+        .par_iter()
+        .zip(instance_bands.par_iter())
+        .zip(instance_coords.par_iter())
+        .map(|((instance, band), coords)| -> anyhow::Result<Measured> {
+            let bounds = bounds.filter(|_| is_default_location(coords));
+            let location = utils::describe_location(font, coords);
+            let mut running = RunningExtremes::default();
+            let mut known = HashMap::new();
+            let mut accumulators = Accumulators::default();
+            let mut unmeasured = vec![];
+            for (word_list, source) in wordlists.iter() {
+                context.progress.measuring(source, word_list.name());
+                if out_of_time(context.deadline) {
+                    unmeasured.push(source.script.clone());
+                    continue;
+                }
+                let group = early_exit_group(context.config, source);
+                if let Some((group, bounds)) = group.as_deref().zip(bounds.as_ref()) {
+                    if running.reached(group, bounds) {
+                        log::debug!(
+                            "Skipping {}, as {} has reached the font's bounds",
+                            word_list.name(),
+                            group
+                        );
+                        continue;
+                    }
+                }
+                let shaped_as = shaping_source(context.config, source);
+                let key = context.cache.map(|cache| {
+                    cache.key(
+                        *word_list,
+                        coords,
+                        context.words_per_list,
+                        &format!("{} {:?}", provider.method(), shaped_as.opentype_language),
+                    )
+                });
+                let measurement = match context
+                    .cache
+                    .zip(key.as_deref())
+                    .and_then(|(cache, key)| cache.get(key, source.clone()))
+                {
+                    Some(measurement) => measurement,
+                    None => {
+                        let measurement = measure_deduplicated(
+                            instance.as_ref(),
+                            *word_list,
+                            source,
+                            &shaped_as,
+                            context.words_per_list,
+                            known.entry(shaped_as.clone()).or_default(),
+                        )?;
+                        if let (Some(cache), Some(key)) = (context.cache, &key) {
+                            cache.put(key, &measurement);
+                        }
+                        measurement
+                    }
+                };
+                if let Some(group) = &group {
+                    running.add(group, &measurement);
+                }
+                accumulate(&mut accumulators, &context, &location, *band, measurement)?;
+            }
+            Ok((accumulators, unmeasured))
+        })
+        .try_reduce(
+            || (Accumulators::default(), vec![]),
+            |(a, mut a_unmeasured), (b, b_unmeasured)| {
+                a_unmeasured.extend(b_unmeasured);
+                Ok((a.merge(b), a_unmeasured))
+            },
+        )?;
+    // Named glyphs are measured at the same locations as the word lists
+    let skrifa_locations = locations
+        .iter()
+        .map(|location| utils::skrifa_location(font, location))
+        .collect::<Vec<_>>();
+    for (script, glyph_names) in config.glyphs.iter() {
+        if !supported.contains(script.as_str()) {
+            continue;
+        }
+        let measurements = glyphs::measure_glyphs(font, &skrifa_locations, script, glyph_names);
+        for ((measurement, location), band) in measurements
+            .iter()
+            .zip(skrifa_locations.iter())
+            .zip(instance_bands.iter())
+        {
+            accumulators.add(
+                &utils::describe_location(font, location.coords()),
+                band.map(|b| b.name.as_str()),
+                measurement,
+                &config,
+            )?;
+        }
+    }
+    if !unmeasured.is_empty() {
+        report_unmeasured(&unmeasured);
+    }
+    if !config.opsz_bands.is_empty() {
+        report_opsz_bands(&accumulators, &config);
+    }
+    // Only restrict to one band if the font has instances in it
+    let wanted_band = config.opsz_band.as_ref().filter(|wanted| {
+        let found = accumulators.in_band(wanted).next().is_some();
+        if !found {
+            log::warn!(
+                "No instances fall within optical size band {}, using all instances",
+                wanted
+            );
+        }
+        found
+    });
+    let accumulators_by_script = accumulators.into_scripts(wanted_band.map(String::as_str));
+    let coverage = utils::script_coverage(font);
+    for (script, accumulator) in accumulators_by_script.iter() {
+        log::info!(
+            "{}",
+            ScriptStatistics::new(
+                font,
+                script,
+                coverage.get(script.as_str()),
+                &accumulator.words
+            )
+        );
+    }
+    let font_defaults = get_font_defaults(font, &config)?;
+    let font_minmax = &font_defaults.horizontal;
+    log::info!(
+        "Font default min {} max {}",
+        font_minmax.lowest.unwrap_or_default(),
+        font_minmax.highest.unwrap_or_default(),
+    );
+    let clamp_limits = get_clamp_limits(font, &config)?;
+    let mut base_script_records = vec![];
+    if options.min_max {
+        for (script, accumulator) in accumulators_by_script.iter() {
+            let Some(mut record) = base_script::base_script_record(
+                script,
+                &accumulator.minmaxes,
+                &config,
+                font_minmax,
+            ) else {
+                continue;
+            };
+            if let Some(limits) = &clamp_limits {
+                base_script::clamp_record(&mut record, &accumulator.words, limits);
+            }
+            base_script_records.push(record);
+        }
+    }
+
+    let mut base = BaseTable::new(
+        base_script_records,
+        vec![], // No vertical today
+    );
+    add_cjk_baselines(font, &config, &supported, &mut base)?;
+    add_registered_baselines(font, &config, &supported, &mut base)?;
+    if let Some(mut existing) = existing_base {
+        existing.fill_missing(&base);
+        base = existing;
+    }
+    let reports = if options.reports {
+        accumulators_by_script
+            .iter()
+            .map(|(script, accumulator)| {
+                let tag = config.opentype_script(script);
+                let record = tag.and_then(|tag| base.script(tag));
+                ScriptReport::new(script, accumulator, record)
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+    Ok(FontResult {
+        base,
+        font_defaults,
+        scripts: reports,
+        primary: false,
+        weight: 1.0,
+        min_max_weight: 1.0,
+    })
+}
+
+/// Add the `hang`, `math` and `romn` baselines of every supported script, as
+/// both the full pipeline and `--baseline-only` write them.
+fn add_registered_baselines(
+    font: &skrifa::FontRef,
+    config: &Config,
+    supported: &HashSet<&str>,
+    base: &mut BaseTable,
+) -> anyhow::Result<()> {
+    let scripts = supported
+        .iter()
+        .sorted()
+        .filter_map(|script| Some((*script, config.opentype_script(script)?)))
+        .collect::<Vec<_>>();
+    baselines::insert_into_base(font, &scripts, base)?;
+    Ok(())
+}
+
+/// Add CJK baselines to the table if any of the supported scripts are CJK scripts,
+/// returning whether they were.
+pub fn add_cjk_baselines(
+    font: &skrifa::FontRef,
+    config: &Config,
+    supported: &HashSet<&str>,
+    base: &mut BaseTable,
+) -> anyhow::Result<bool> {
+    let needs_cjk = supported.iter().any(|s| cjk::is_cjk_script(s));
+    if needs_cjk {
+        log::info!("CJK scripts detected, adding CJK BASE records");
+        let options = config.cjk_options();
+        // A profile's fixed baselines win over the configured ones
+        let profile_em_box = match config.profile {
+            Some(profile) => profile.em_box(font)?,
+            None => cjk::CjkEmBox::default(),
+        };
+        let cjk_bounds = match cjk::compute_bounds(font, &options) {
+            // A font can cover CJK scripts through punctuation and symbols alone
+            Err(crate::error::AutobaseError::NoCjkGlyphs) => {
+                log::warn!("CJK scripts are supported, but the font has no CJK ideographs, kana or hangul to measure; not adding CJK baselines");
+                return Ok(false);
+            }
+            result => result?,
+        }
+        .with_em_box(&config.cjk_em_box)
+        .with_em_box(&profile_em_box)
+        .with_idtp_policy(config.cjk_idtp);
+        let upem = font.head()?.units_per_em() as f32;
+        let square_tolerance = config
+            .cjk_square_tolerance
+            .unwrap_or(cjk::DEFAULT_SQUARE_TOLERANCE);
+        let mut ot_scripts = supported
+            .iter()
+            .flat_map(|script| config.opentype_script(script))
+            .collect::<Vec<_>>();
+        if options.per_script {
+            for group in cjk::CjkGroup::ALL {
+                let group_scripts = ot_scripts
+                    .iter()
+                    .copied()
+                    .filter(|script| cjk::CjkGroup::for_script(*script) == Some(group))
+                    .collect::<Vec<_>>();
+                if group_scripts.is_empty() {
+                    continue;
+                }
+                let Some(group_bounds) = cjk::compute_group_bounds(font, group, &options)? else {
+                    continue;
+                };
+                log::info!("Measured {} baselines from {} glyphs alone", group, group);
+                // Whether the font is square is decided once, so that half-width
+                // kana don't give the kana records an idtp which the rest lack
+                group_bounds
+                    .with_average_width(cjk_bounds.average_width())
+                    .with_em_box(&config.cjk_em_box)
+                    .with_em_box(&profile_em_box)
+                    .with_idtp_policy(config.cjk_idtp)
+                    .insert_into_base(upem, square_tolerance, &group_scripts, base)?;
+                ot_scripts.retain(|script| !group_scripts.contains(script));
+            }
+        }
+        // Everything else, including non-CJK scripts, gets the ideographic baselines
+        cjk_bounds.insert_into_base(upem, square_tolerance, &ot_scripts, base)?;
+    }
+    Ok(needs_cjk)
+}
+
+/// The script and language a word list's words are shaped in: for a language,
+/// the OpenType language system its records are written for, so that `locl`
+/// selects the forms the font shows in that language.
+fn shaping_source(config: &Config, source: &ScriptLanguage) -> ScriptLanguage {
+    let mut shaped_as = source.clone();
+    if let Some(language) = source.language.as_deref() {
+        shaped_as
+            .opentype_language
+            .get_or_insert_with(|| config.opentype_language(&source.script, language));
+    }
+    shaped_as
+}
+
+/// Measure the first `words` words of a word list at one instance, shaping
+/// them as `shaped_as`.
+///
+/// Word lists for the same language share much of their vocabulary, so only words
+/// which haven't been measured at this instance already are measured; `known`
+/// holds the extremes of each word measured so far shaped the same way, or
+/// `None` if the font can't render it. The results are still attributed to
+/// this word list's `source`.
+fn measure_deduplicated(
+    instance: &dyn InstanceExtremes,
+    word_list: &dyn WordSource,
+    source: &ScriptLanguage,
+    shaped_as: &ScriptLanguage,
+    words: usize,
+    known: &mut HashMap<String, Option<(f64, f64)>>,
+) -> anyhow::Result<Measurement> {
+    let mut seen = HashSet::new();
+    let list_words = word_list
+        .words()
+        .take(words)
+        .filter(|word| seen.insert(*word))
+        .collect::<Vec<_>>();
+    let new_words = list_words
+        .iter()
+        .filter(|word| !known.contains_key(**word))
+        .map(|word| word.to_string())
+        .collect::<Vec<_>>();
+    if !new_words.is_empty() {
+        let measured = instance.measure(word_list.name(), shaped_as, &new_words)?;
+        if measured.len() != new_words.len() {
+            anyhow::bail!(
+                "measuring {} words of {} returned {} results",
+                new_words.len(),
+                word_list.name(),
+                measured.len()
+            );
+        }
+        known.extend(new_words.into_iter().zip(measured));
+    }
+    let extremes = list_words
+        .into_iter()
+        .filter_map(|word| {
+            let (highest, lowest) = known.get(word).copied().flatten()?;
+            Some(WordExtremes {
+                word: word.to_string(),
+                highest,
+                lowest,
+                frequency: None,
+            })
+        })
+        .collect();
+    Ok(Measurement::from_words(
+        word_list.name(),
+        source.clone(),
+        extremes,
+        10000,
+    ))
+}
+
+/// Whether the time budget has run out, so no new measurements should be started.
+fn out_of_time(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// Warn about the scripts whose word lists weren't all measured before the time
+/// budget ran out, given the script of each word list and location skipped.
+fn report_unmeasured(unmeasured: &[String]) {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for script in unmeasured {
+        *counts.entry(script).or_default() += 1;
+    }
+    log::warn!(
+        "The time budget ran out, so these scripts were only partially sampled: {}",
+        counts
+            .iter()
+            .map(|(script, count)| format!("{} ({} word list measurements skipped)", script, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+}
+
+/// Whether normalized coordinates are those of the font's default instance.
+fn is_default_location(coords: &[skrifa::instance::NormalizedCoord]) -> bool {
+    coords.iter().all(|coord| coord.to_bits() == 0)
+}
+
+/// The group of word lists whose running extremes decide when measuring can stop
+/// early: the script, or the script and language if the language gets its own
+/// record. Returns `None` if every word is needed, because the extremes aren't
+/// simply the most extreme word, words may yet be excluded or dropped as
+/// outliers, or the languages must all be compared.
+fn early_exit_group(config: &Config, source: &ScriptLanguage) -> Option<String> {
+    if config.frequency_cutoff.is_some()
+        || !config.exclusions.is_empty()
+        || config.outlier_stddevs.is_some()
+        || config.auto_split_threshold.is_some()
+        || config.aggregation_for(&source.script) != config::Aggregation::Absolute
+    {
+        return None;
+    }
+    let same_source =
+        |sl: &ScriptLanguage| sl.script == source.script && sl.language == source.language;
+    let separate =
+        config.languages.iter().any(same_source) || config.r#override.keys().any(same_source);
+    match &source.language {
+        Some(language) if separate => Some(format!("{}_{}", language, source.script)),
+        _ => Some(source.script.clone()),
+    }
+}
+
+/// The options and state shared by every measurement of a font.
+#[derive(Clone, Copy)]
+struct MeasureContext<'a> {
+    config: &'a Config,
+    frequencies: &'a FrequencyTable,
+    words_per_list: usize,
+    cache: Option<&'a MeasurementCache>,
+    deadline: Option<Instant>,
+    progress: &'a dyn MeasureProgress,
+    words_csv: Option<&'a FontWordCsv<'a>>,
+}
+
+/// Accumulated measurements, and the script of each word list skipped because
+/// the time budget ran out.
+type Measured = (Accumulators, Vec<String>);
+
+/// Fold a measurement made in an optical size band into the accumulators, once
+/// its words' frequencies are known, exporting its words if asked to.
+fn accumulate(
+    accumulators: &mut Accumulators,
+    context: &MeasureContext,
+    location: &str,
+    band: Option<&opsz::OpszBand>,
+    mut measurement: Measurement,
+) -> anyhow::Result<()> {
+    if !context.frequencies.is_empty() {
+        measurement.apply_frequencies(context.frequencies);
+    }
+    if let Some(csv) = context.words_csv {
+        csv.write(location, &measurement)
+            .context("failed to write word CSV")?;
+    }
+    accumulators.add(
+        location,
+        band.map(|b| b.name.as_str()),
+        &measurement,
+        context.config,
+    )?;
+    Ok(())
+}
+
+/// Log the extremes of each script within each optical size band.
+fn report_opsz_bands(accumulators: &Accumulators, config: &Config) {
+    for band in config.opsz_bands.iter() {
+        let mut found = false;
+        for (script, accumulator) in accumulators.in_band(&band.name) {
+            found = true;
+            if let Some(extremes) = base_script::script_extremes(&accumulator.minmaxes, config) {
+                log::info!(
+                    "Optical size band {}, script {}: {}",
+                    band,
+                    script,
+                    extremes
+                );
+            }
+        }
+        if !found {
+            log::warn!("No instances fall within optical size band {}", band);
+        }
+    }
+}
+
+/// A font's table with how much it counts for in the collation.
+struct Weighted {
+    base: BaseTable,
+    primary: bool,
+    weight: f64,
+    min_max_weight: f64,
+}
+
+/// Combine the tables of several fonts into one.
+///
+/// The primary font's table (the first font marked as primary, or else the
+/// first font) is the starting point, and the others are merged into it. Under
+/// `union` and `intersection`, weights only decide whether a font's values count
+/// at all (a weight of zero drops them); fractional weights only matter to the
+/// `average` and `median` collations. A primary font whose weights are both zero
+/// is dropped before the primary is chosen, so another font takes its place,
+/// with a warning.
+fn collate_bases(fonts: Vec<Weighted>, tolerance: Tolerance, collation: Collation) -> BaseTable {
+    let had_primary = fonts.iter().any(|font| font.primary);
+    // Fonts whose values don't count lose them before anything is merged
+    let fonts = fonts
+        .into_iter()
+        .filter(|font| font.weight > 0.0 || font.min_max_weight > 0.0)
+        .map(|mut font| {
+            for record in font
+                .base
+                .horizontal
+                .iter_mut()
+                .chain(font.base.vertical.iter_mut())
+            {
+                if font.weight <= 0.0 {
+                    record.default_baseline = None;
+                    record.baselines.clear();
+                }
+                if font.min_max_weight <= 0.0 {
+                    record.default_minmax = None;
+                    record.languages.clear();
+                }
+            }
+            font
+        })
+        .collect::<Vec<_>>();
+    let primary = fonts
+        .iter()
+        .position(|font| font.primary)
+        .unwrap_or_else(|| {
+            if had_primary && !fonts.is_empty() {
+                log::warn!(
+                "The primary font has a weight of 0, so another font is treated as primary instead"
+            );
+            }
+            0
+        });
+    let Some(mut collated) = fonts.get(primary).map(|font| font.base.clone()) else {
+        return BaseTable::new(vec![], vec![]);
+    };
+    for (index, font) in fonts.iter().enumerate() {
+        if index != primary {
+            collated.merge(&font.base, tolerance);
+        }
+    }
+    match collation {
+        Collation::Union => {}
+        Collation::Intersection => {
+            collated.horizontal.retain(|record| {
+                fonts
+                    .iter()
+                    .all(|font| font.base.script(record.script).is_some())
+            });
+            collated.vertical.retain(|record| {
+                fonts
+                    .iter()
+                    .all(|font| font.base.vertical_script(record.script).is_some())
+            });
+        }
+        Collation::Average | Collation::Median => {
+            aggregate_values(&mut collated, &fonts, collation)
+        }
+    }
+    // Simplify the BASE table to remove redundant entries
+    collated.simplify(tolerance);
+    collated
+}
+
+/// Replace each value of the collated table with the mean or median of the
+/// fonts' values.
+///
+/// Each value is aggregated over the fonts which have it, with a font's
+/// language records falling back to its script default; a NULL MinMax side
+/// stays NULL only if it is NULL in every font.
+fn aggregate_values(collated: &mut BaseTable, fonts: &[Weighted], collation: Collation) {
+    let aggregate = |values: Vec<(i16, f64)>| -> Option<i16> {
+        let values = values
+            .into_iter()
+            .map(|(value, weight)| (f64::from(value), weight))
+            .collect::<Vec<_>>();
+        let value = if collation == Collation::Median {
+            weighted_median(values)?
+        } else {
+            weighted_mean(&values)?
+        };
+        Some(value.round() as i16)
+    };
+    let word = |count: usize| {
+        let name = if collation == Collation::Median {
+            "median"
+        } else {
+            "average"
+        };
+        format!("<{} of {} fonts>", name, count)
+    };
+    let aggregate_min_max = |min_max: &mut MinMax, others: Vec<(&MinMax, f64)>| {
+        let highests = others
+            .iter()
+            .filter_map(|(mm, weight)| Some((mm.highest?, *weight)))
+            .collect::<Vec<_>>();
+        if let Some(highest) = aggregate(highests.clone()) {
+            min_max.highest = Some(highest);
+            min_max.highest_word = word(highests.len());
+        }
+        let lowests = others
+            .iter()
+            .filter_map(|(mm, weight)| Some((mm.lowest?, *weight)))
+            .collect::<Vec<_>>();
+        if let Some(lowest) = aggregate(lowests.clone()) {
+            min_max.lowest = Some(lowest);
+            min_max.lowest_word = word(lowests.len());
+        }
+    };
+    for horizontal in [true, false] {
+        let records = if horizontal {
+            &mut collated.horizontal
+        } else {
+            &mut collated.vertical
+        };
+        for record in records.iter_mut() {
+            let others = fonts
+                .iter()
+                .filter_map(|font| {
+                    if horizontal {
+                        font.base.script(record.script)
+                    } else {
+                        font.base.vertical_script(record.script)
+                    }
+                    .map(|other| (other, font))
+                })
+                .collect::<Vec<_>>();
+            for (tag, value) in record.baselines.iter_mut() {
+                if let Some(aggregated) = aggregate(
+                    others
+                        .iter()
+                        .filter_map(|(r, font)| Some((r.baseline(*tag)?, font.weight)))
+                        .collect(),
+                ) {
+                    *value = aggregated;
+                }
+            }
+            let min_maxes = |language: Option<Tag>| {
+                others
+                    .iter()
+                    .filter_map(|(r, font)| Some((r.min_max(language)?, font.min_max_weight)))
+                    .collect::<Vec<_>>()
+            };
+            if let Some(min_max) = record.default_minmax.as_mut() {
+                aggregate_min_max(min_max, min_maxes(None));
+            }
+            for (language, min_max) in record.languages.iter_mut() {
+                aggregate_min_max(min_max, min_maxes(Some(*language)));
+            }
+        }
+    }
+}
+
+/// The limits to clamp MinMax values to, if any are configured.
+fn get_clamp_limits(font: &skrifa::FontRef, config: &Config) -> anyhow::Result<Option<MinMax>> {
+    let mut limits = MinMax {
+        highest: config.clamp_max,
+        highest_word: "<clamp_max>".to_string(),
+        lowest: config.clamp_min,
+        lowest_word: "<clamp_min>".to_string(),
+    };
+    if config.clamp_to_win {
+        let win = font_metrics::font_minmax(font, MetricSource::Win)
+            .context("failed to read the font's win metrics")?;
+        // Explicit limits take precedence over the win metrics
+        limits.highest = limits.highest.or(win.highest);
+        limits.lowest = limits.lowest.or(win.lowest);
+    }
+    Ok((limits.highest.is_some() || limits.lowest.is_some()).then_some(limits))
+}
+
+fn get_font_defaults(font: &skrifa::FontRef, config: &Config) -> anyhow::Result<FontDefaults> {
+    FontDefaults::new(font, config.metric_source.unwrap_or_default())
+        .context("failed to read the font's vertical metrics")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn early_exit_needs_every_word_when_words_may_be_dropped() {
+        let latin = ScriptLanguage::new("Latn", Some("en")).unwrap();
+        let config = Config::default();
+        assert_eq!(early_exit_group(&config, &latin).as_deref(), Some("Latn"));
+        let config = Config {
+            exclusions: vec!["Ǻ".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(early_exit_group(&config, &latin), None);
+        let config = Config {
+            outlier_stddevs: Some(3.0),
+            ..Default::default()
+        };
+        assert_eq!(early_exit_group(&config, &latin), None);
+    }
+
+    #[test]
+    fn null_policy_decides_the_binary_table() {
+        let mut script = crate::base::BaseScript::new(Tag::new(b"latn"));
+        script.default_minmax = Some(MinMax {
+            highest: None,
+            lowest: Some(-300),
+            ..MinMax::new_min_max(-300, 0)
+        });
+        let font = FontResult {
+            base: BaseTable::new(vec![script], vec![]),
+            font_defaults: FontDefaults {
+                horizontal: MinMax::new_min_max(-250, 900),
+                vertical: MinMax::new_min_max(-500, 500),
+            },
+            scripts: vec![],
+            primary: true,
+            weight: 1.0,
+            min_max_weight: 1.0,
+        };
+        let highest = |null_policy| {
+            let config = Config {
+                null_policy,
+                ..Default::default()
+            };
+            let generator = Generator::new(config, GeneratorOptions::default()).unwrap();
+            let base = generator.collate(std::slice::from_ref(&font));
+            base.horizontal[0].default_minmax.as_ref().unwrap().highest
+        };
+        assert_eq!(highest(None), None);
+        assert_eq!(highest(Some(NullPolicy::Keep)), None);
+        assert_eq!(highest(Some(NullPolicy::Replace)), Some(900));
+    }
+
+    #[test]
+    fn fea_collation_leaves_the_binary_table_alone() {
+        let mut script = crate::base::BaseScript::new(Tag::new(b"latn"));
+        script.languages.insert(
+            Tag::new(b"TRK "),
+            MinMax {
+                highest: None,
+                ..MinMax::new_min_max(-300, 0)
+            },
+        );
+        let font = FontResult {
+            base: BaseTable::new(vec![script], vec![]),
+            font_defaults: FontDefaults {
+                horizontal: MinMax::new_min_max(-250, 900),
+                vertical: MinMax::new_min_max(-500, 500),
+            },
+            scripts: vec![],
+            primary: true,
+            weight: 1.0,
+            min_max_weight: 1.0,
+        };
+        let generator = Generator::new(Config::default(), GeneratorOptions::default()).unwrap();
+        let fonts = std::slice::from_ref(&font);
+        let before = generator.collate(fonts);
+        let fea = generator.collate_for_fea(fonts);
+        let after = generator.collate(fonts);
+        assert_eq!(before.to_fea(), after.to_fea());
+        let binary = &after.horizontal[0];
+        assert!(binary.default_minmax.is_none());
+        assert_eq!(binary.languages[&Tag::new(b"TRK ")].highest, None);
+        let fea = &fea.base.horizontal[0];
+        assert!(fea.default_minmax.is_some());
+        assert_eq!(fea.languages[&Tag::new(b"TRK ")].highest, Some(900));
+    }
+
+    fn weighted(records: Vec<(&[u8; 4], i16, i16, i16)>, primary: bool, weight: f64) -> Weighted {
+        let records = records
+            .into_iter()
+            .map(|(script, romn, low, high)| {
+                let mut record = crate::base::BaseScript::new(Tag::new(script));
+                record.baselines.insert(Tag::new(b"romn"), romn);
+                record.default_minmax = Some(MinMax::new_min_max(low, high));
+                record
+            })
+            .collect();
+        Weighted {
+            base: BaseTable::new(records, vec![]),
+            primary,
+            weight,
+            min_max_weight: weight,
+        }
+    }
+
+    /// A bold with an extra script, then the primary regular, then a black which
+    /// doesn't count.
+    fn family(bold_weight: f64, regular_weight: f64) -> Vec<Weighted> {
+        vec![
+            weighted(
+                vec![(b"latn", 10, -300, 900), (b"grek", 20, -250, 850)],
+                false,
+                bold_weight,
+            ),
+            weighted(vec![(b"latn", 0, -200, 800)], true, regular_weight),
+            weighted(vec![(b"latn", 50, -1000, 2000)], false, 0.0),
+        ]
+    }
+
+    /// The `romn` baseline, min and max of a script in a collated table.
+    fn values(base: &BaseTable, script: &[u8; 4]) -> Option<(i16, i16, i16)> {
+        let record = base.script(Tag::new(script))?;
+        let min_max = record.default_minmax.as_ref()?;
+        Some((
+            record.baseline(Tag::new(b"romn"))?,
+            min_max.lowest?,
+            min_max.highest?,
+        ))
+    }
+
+    #[test]
+    fn collations_combine_the_family() {
+        let collate = |fonts, collation| collate_bases(fonts, Tolerance::uniform(10), collation);
+
+        // The primary's baseline wins, and the extents are widened to the bold's
+        let union = collate(family(1.0, 1.0), Collation::Union);
+        assert_eq!(values(&union, b"latn"), Some((0, -300, 900)));
+        assert_eq!(values(&union, b"grek"), Some((20, -250, 850)));
+        // Fractional weights make no difference to the union
+        let fractional = collate(family(0.5, 1.0), Collation::Union);
+        assert_eq!(values(&fractional, b"latn"), values(&union, b"latn"));
+
+        let intersection = collate(family(1.0, 1.0), Collation::Intersection);
+        assert_eq!(values(&intersection, b"latn"), Some((0, -300, 900)));
+        assert_eq!(values(&intersection, b"grek"), None);
+
+        let average = collate(family(1.0, 1.0), Collation::Average);
+        assert_eq!(values(&average, b"latn"), Some((5, -250, 850)));
+        assert_eq!(values(&average, b"grek"), Some((20, -250, 850)));
+        let average = collate(family(3.0, 1.0), Collation::Average);
+        assert_eq!(values(&average, b"latn"), Some((8, -275, 875)));
+
+        let median = collate(family(1.0, 1.0), Collation::Median);
+        assert_eq!(values(&median, b"latn"), Some((5, -250, 850)));
+        let median = collate(family(3.0, 1.0), Collation::Median);
+        assert_eq!(values(&median, b"latn"), Some((10, -300, 900)));
+    }
+
+    #[test]
+    fn zero_weight_primary_hands_over_primacy() {
+        let union = collate_bases(family(1.0, 0.0), Tolerance::uniform(10), Collation::Union);
+        // The bold is now primary, and its baseline wins
+        assert_eq!(values(&union, b"latn"), Some((10, -300, 900)));
+        let nobody = collate_bases(family(0.0, 0.0), Tolerance::uniform(10), Collation::Union);
+        assert!(nobody.horizontal.is_empty());
+    }
+
+    #[test]
+    fn squareness_is_decided_once_per_font() {
+        // Full-width ideographs, but half of the kana are half-width
+        let data = crate::baselines::tests::font_with_advances(&[
+            ('水', -50, 800, 1000),
+            ('東', -50, 800, 1000),
+            ('ア', -40, 700, 1000),
+            ('ｱ', -40, 700, 500),
+        ]);
+        let font = FontRef::new(&data).unwrap();
+        let config = Config {
+            cjk_per_script: true,
+            ..Default::default()
+        };
+        let supported = HashSet::from(["Hani", "Kana"]);
+        let mut base = BaseTable::default();
+        add_cjk_baselines(&font, &config, &supported, &mut base).unwrap();
+        let idtp = Tag::new(b"idtp");
+        for script in [b"hani", b"kana"] {
+            let script = Tag::new(script);
+            assert!(base.script(script).is_some());
+            assert_eq!(base.baseline(script, idtp), None, "{}", script);
+        }
+    }
+}
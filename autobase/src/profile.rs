@@ -0,0 +1,402 @@
+//! Compliance profiles, which hold a font and its BASE table to a published
+//! specification.
+//!
+//! A [`Recipe`](crate::config::Recipe) only suggests settings; a profile also
+//! pins the baselines the specification fixes, and afterwards checks the font's
+//! own metrics and the generated table against it. Some fonts can't comply
+//! whatever table they are given, for example because their typo metrics don't
+//! describe the ideographic em-box, and these are reported rather than papered
+//! over.
+use std::str::FromStr;
+
+use serde::Deserialize;
+use skrifa::{raw::TableProvider, FontRef, Tag};
+
+use crate::{
+    base::{BaseScript, BaseTable},
+    cjk::{self, CjkEmBox, IdtpPolicy},
+    config::{Config, Recipe},
+    error::AutobaseError,
+};
+
+/// A specification which the generated table, and the font, must meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    /// The CJK vertical metrics of the Google Fonts guide
+    ///
+    /// See https://googlefonts.github.io/gf-guide/metrics.html#cjk-vertical-metrics
+    GfCjk,
+}
+
+/// A way in which a font or its BASE table fails a profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// What is wrong, e.g. "OS/2 sTypoDescender" or "hani horizontal ideo baseline"
+    pub subject: String,
+    /// What the profile requires
+    pub expected: String,
+    /// What was found instead
+    pub found: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is {}, but must be {}",
+            self.subject, self.found, self.expected
+        )
+    }
+}
+
+/// The baselines which every CJK script record must have under [`Profile::GfCjk`].
+const GF_CJK_BASELINES: [&[u8; 4]; 5] = [b"icfb", b"icft", b"ideo", b"idtp", b"romn"];
+
+impl Profile {
+    pub const ALL: [Profile; 1] = [Profile::GfCjk];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::GfCjk => "gf-cjk",
+        }
+    }
+
+    /// Overlay the settings this profile needs onto a config.
+    ///
+    /// Unlike a recipe's, these replace whatever the config or command line
+    /// asked for.
+    pub fn apply(&self, config: &mut Config) {
+        match self {
+            Profile::GfCjk => {
+                Recipe::GfCjk.apply(config);
+                // The guide's em-box is explicit, so idtp is written even for square fonts
+                config.cjk_idtp = IdtpPolicy::Always;
+            }
+        }
+    }
+
+    /// The baselines this profile fixes for a font, which replace those measured
+    /// from its glyphs.
+    pub fn em_box(&self, font: &FontRef) -> Result<CjkEmBox, AutobaseError> {
+        match self {
+            Profile::GfCjk => {
+                // The em-box is one em tall, with its bottom at the typo descender
+                let upem = font.head()?.units_per_em() as i32;
+                let descender = font.os2()?.s_typo_descender();
+                Ok(CjkEmBox {
+                    h_ideo: Some(descender),
+                    h_idtp: i16::try_from(descender as i32 + upem).ok(),
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
+    /// Check a font's metrics, and the BASE table to be written into it, against
+    /// this profile.
+    pub fn check(&self, font: &FontRef, base: &BaseTable) -> Result<Vec<Violation>, AutobaseError> {
+        match self {
+            Profile::GfCjk => check_gf_cjk(font, base),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Profile::ALL
+            .into_iter()
+            .find(|profile| profile.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown profile {}, expected one of: {}",
+                    s,
+                    Profile::ALL.map(|p| p.name()).join(", ")
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn check_gf_cjk(font: &FontRef, base: &BaseTable) -> Result<Vec<Violation>, AutobaseError> {
+    let upem = font.head()?.units_per_em() as f32;
+    let os2 = font.os2()?;
+    let hhea = font.hhea()?;
+    let mut violations = vec![];
+    let mut require = |complies: bool, subject: String, expected: String, found: String| {
+        if !complies {
+            violations.push(Violation {
+                subject,
+                expected,
+                found,
+            });
+        }
+    };
+
+    // The typo ascender and descender describe the ideographic em-box
+    let ascender = os2.s_typo_ascender();
+    let descender = os2.s_typo_descender();
+    for (name, value, share) in [
+        ("OS/2 sTypoAscender", ascender, 0.88),
+        ("OS/2 sTypoDescender", descender, -0.12),
+    ] {
+        let expected = (upem * share).round() as i16;
+        require(
+            value == expected,
+            name.to_string(),
+            format!("{} ({} em)", expected, share),
+            value.to_string(),
+        );
+    }
+    for (name, value) in [
+        ("OS/2 sTypoLineGap", os2.s_typo_line_gap()),
+        ("hhea lineGap", hhea.line_gap().to_i16()),
+    ] {
+        require(
+            value == 0,
+            name.to_string(),
+            "0".to_string(),
+            value.to_string(),
+        );
+    }
+
+    let cjk_scripts = |scripts: &[BaseScript]| {
+        scripts
+            .iter()
+            .filter(|script| cjk::is_cjk_script(&script.script.to_string()))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let horizontal = cjk_scripts(&base.horizontal);
+    let vertical = cjk_scripts(&base.vertical);
+    require(
+        !horizontal.is_empty(),
+        "The number of CJK script records".to_string(),
+        "at least one".to_string(),
+        "none".to_string(),
+    );
+    for (axis, scripts) in [("horizontal", &horizontal), ("vertical", &vertical)] {
+        for script in scripts {
+            require(
+                script.default_baseline == Some(Tag::new(b"ideo")),
+                format!("The {} {} default baseline", script.script, axis),
+                "ideo".to_string(),
+                script
+                    .default_baseline
+                    .map_or("missing".to_string(), |tag| tag.to_string()),
+            );
+            for tag in GF_CJK_BASELINES.map(Tag::new) {
+                require(
+                    script.baseline(tag).is_some(),
+                    format!("The {} {} {} baseline", script.script, axis, tag),
+                    "present".to_string(),
+                    "missing".to_string(),
+                );
+            }
+        }
+    }
+    // The em-box is one em tall with its bottom at the typo descender, whatever
+    // the typo ascender says
+    let em_box_top = descender as i32 + upem as i32;
+    for script in horizontal.iter() {
+        let subject = |tag: &str| format!("The {} horizontal {} baseline", script.script, tag);
+        let baseline = |tag: &[u8; 4]| script.baseline(Tag::new(tag));
+        if let Some(romn) = baseline(b"romn") {
+            require(
+                romn == 0,
+                subject("romn"),
+                "0".to_string(),
+                romn.to_string(),
+            );
+        }
+        if let Some(ideo) = baseline(b"ideo") {
+            require(
+                ideo == descender,
+                subject("ideo"),
+                format!("{} (the em-box bottom)", descender),
+                ideo.to_string(),
+            );
+        }
+        if let Some(idtp) = baseline(b"idtp") {
+            require(
+                idtp as i32 == em_box_top,
+                subject("idtp"),
+                format!("{} (the em-box top)", em_box_top),
+                idtp.to_string(),
+            );
+        }
+        // The character face lies within the em-box
+        if let (Some(ideo), Some(icfb)) = (baseline(b"ideo"), baseline(b"icfb")) {
+            require(
+                icfb >= ideo,
+                subject("icfb"),
+                format!("at or above ideo ({})", ideo),
+                icfb.to_string(),
+            );
+        }
+        if let (Some(idtp), Some(icft)) = (baseline(b"idtp"), baseline(b"icft")) {
+            require(
+                icft <= idtp,
+                subject("icft"),
+                format!("at or below idtp ({})", idtp),
+                icft.to_string(),
+            );
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        tables::{
+            head::{Flags, Head, MacStyle},
+            hhea::Hhea,
+            os2::Os2,
+        },
+        types::{Fixed, LongDateTime},
+        FontBuilder,
+    };
+
+    use super::*;
+
+    /// A font with the given typo ascender and descender, and no line gaps.
+    fn font_with_typo(upem: u16, ascender: i16, descender: i16) -> Vec<u8> {
+        let mut builder = FontBuilder::new();
+        builder
+            .add_table(&Head::new(
+                Fixed::ONE,
+                0,
+                Flags::empty(),
+                upem,
+                LongDateTime::new(0),
+                LongDateTime::new(0),
+                0,
+                descender,
+                upem as i16,
+                ascender,
+                MacStyle::empty(),
+                0,
+                0,
+            ))
+            .unwrap();
+        builder.add_table(&Hhea::default()).unwrap();
+        builder
+            .add_table(&Os2 {
+                s_typo_ascender: ascender,
+                s_typo_descender: descender,
+                ..Default::default()
+            })
+            .unwrap();
+        builder.build()
+    }
+
+    /// A Han record with the em-box of a 1000 upem font whose typo descender is -120.
+    fn hani(baselines: &[(&[u8; 4], i16)]) -> BaseScript {
+        let mut hani = BaseScript::new(Tag::new(b"hani"));
+        hani.default_baseline = Some(Tag::new(b"ideo"));
+        for (tag, value) in [
+            (b"icfb", -80),
+            (b"icft", 840),
+            (b"ideo", -120),
+            (b"idtp", 880),
+            (b"romn", 0),
+        ]
+        .into_iter()
+        .chain(baselines.iter().copied())
+        {
+            hani.baselines.insert(Tag::new(tag), value);
+        }
+        hani
+    }
+
+    fn subjects(violations: &[Violation]) -> Vec<&str> {
+        violations
+            .iter()
+            .map(|violation| violation.subject.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn the_gf_cjk_em_box_is_one_em_from_the_typo_descender() {
+        let data = font_with_typo(1000, 800, -150);
+        let font = FontRef::new(&data).unwrap();
+        let em_box = Profile::GfCjk.em_box(&font).unwrap();
+        assert_eq!(em_box.h_ideo, Some(-150));
+        assert_eq!(em_box.h_idtp, Some(850));
+    }
+
+    #[test]
+    fn a_conforming_font_has_no_violations() {
+        let data = font_with_typo(1000, 880, -120);
+        let font = FontRef::new(&data).unwrap();
+        let base = BaseTable::new(vec![hani(&[])], vec![]);
+        assert_eq!(Profile::GfCjk.check(&font, &base).unwrap(), []);
+    }
+
+    #[test]
+    fn typo_metrics_off_the_em_box_are_violations() {
+        let data = font_with_typo(1000, 800, -120);
+        let font = FontRef::new(&data).unwrap();
+        let base = BaseTable::new(vec![hani(&[])], vec![]);
+        let violations = Profile::GfCjk.check(&font, &base).unwrap();
+        assert_eq!(subjects(&violations), ["OS/2 sTypoAscender"]);
+        assert_eq!(
+            violations[0].to_string(),
+            "OS/2 sTypoAscender is 800, but must be 880 (0.88 em)"
+        );
+    }
+
+    #[test]
+    fn baselines_off_the_em_box_are_violations() {
+        let data = font_with_typo(1000, 880, -120);
+        let font = FontRef::new(&data).unwrap();
+        let base = BaseTable::new(vec![hani(&[(b"icft", 900), (b"romn", 10)])], vec![]);
+        let violations = Profile::GfCjk.check(&font, &base).unwrap();
+        assert_eq!(
+            subjects(&violations),
+            [
+                "The hani horizontal romn baseline",
+                "The hani horizontal icft baseline"
+            ]
+        );
+    }
+
+    #[test]
+    fn cjk_records_need_every_baseline() {
+        let data = font_with_typo(1000, 880, -120);
+        let font = FontRef::new(&data).unwrap();
+        let mut vertical = hani(&[]);
+        vertical.baselines.remove(&Tag::new(b"icfb"));
+        let base = BaseTable::new(vec![hani(&[])], vec![vertical]);
+        let violations = Profile::GfCjk.check(&font, &base).unwrap();
+        assert_eq!(subjects(&violations), ["The hani vertical icfb baseline"]);
+
+        let base = BaseTable::new(vec![BaseScript::new(Tag::new(b"latn"))], vec![]);
+        let violations = Profile::GfCjk.check(&font, &base).unwrap();
+        assert_eq!(subjects(&violations), ["The number of CJK script records"]);
+    }
+
+    #[test]
+    fn applying_gf_cjk_always_writes_idtp() {
+        let mut config = Config::default();
+        Profile::GfCjk.apply(&mut config);
+        assert_eq!(config.cjk_idtp, IdtpPolicy::Always);
+    }
+
+    #[test]
+    fn profiles_are_named() {
+        assert_eq!("gf-cjk".parse::<Profile>(), Ok(Profile::GfCjk));
+        assert_eq!(
+            "gf-kr".parse::<Profile>(),
+            Err("unknown profile gf-kr, expected one of: gf-cjk".to_string())
+        );
+    }
+}
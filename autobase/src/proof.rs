@@ -0,0 +1,94 @@
+//! Rasterized proofs of the extreme words.
+//!
+//! The HTML report needs a browser; pipelines which archive their results, and
+//! reviewers without any font tooling, are better served by plain images. A proof
+//! draws words side by side in the font, with horizontal rules at the values
+//! being checked, and encodes the result as a PNG.
+use skrifa::{instance::Location, outline::OutlinePen, FontRef};
+use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Rect, Transform};
+
+use crate::{error::AutobaseError, render::draw_text};
+
+/// The margin around the words, in font units.
+const MARGIN: f32 = 100.0;
+
+/// Builds a tiny-skia path from an outline.
+struct SkiaPen(PathBuilder);
+
+impl OutlinePen for SkiaPen {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        self.0.quad_to(cx0, cy0, x, y);
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        self.0.cubic_to(cx0, cy0, cx1, cy1, x, y);
+    }
+
+    fn close(&mut self) {
+        self.0.close();
+    }
+}
+
+/// A horizontal rule across a proof.
+#[derive(Debug, Clone, Copy)]
+pub struct Rule {
+    /// The height of the rule, in font units
+    pub value: i16,
+    pub color: [u8; 3],
+}
+
+/// Draw each word at its location, side by side, over the rules, as a PNG image
+/// `height` pixels high.
+pub fn proof_png(
+    font: &FontRef,
+    words: &[(&str, &Location)],
+    rules: &[Rule],
+    height: u32,
+) -> Result<Vec<u8>, AutobaseError> {
+    let mut paths = vec![];
+    let mut x = 0.0;
+    for (word, location) in words {
+        let mut pen = SkiaPen(PathBuilder::new());
+        let advance = draw_text(font, word, location, &mut pen);
+        paths.push((pen.0.finish(), x));
+        x += advance + MARGIN;
+    }
+    let top = rules.iter().map(|r| r.value).max().unwrap_or(0).max(0) as f32 + MARGIN;
+    let bottom = rules.iter().map(|r| r.value).min().unwrap_or(0).min(0) as f32 - MARGIN;
+    let scale = height as f32 / (top - bottom);
+    let width = ((x + MARGIN) * scale).ceil().max(1.0) as u32;
+    let mut pixmap = Pixmap::new(width, height.max(1))
+        .ok_or_else(|| AutobaseError::Proof(format!("cannot make a {}x{} image", width, height)))?;
+    pixmap.fill(Color::WHITE);
+
+    let mut paint = Paint::default();
+    for rule in rules {
+        let [r, g, b] = rule.color;
+        paint.set_color_rgba8(r, g, b, 255);
+        let y = (top - rule.value as f32) * scale;
+        if let Some(rect) = Rect::from_xywh(0.0, y - 1.0, width as f32, 2.0) {
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+    paint.set_color_rgba8(0, 0, 0, 255);
+    for (path, x) in paths {
+        // A word with no outlines at all leaves no path to fill
+        let Some(path) = path else {
+            continue;
+        };
+        let transform =
+            Transform::from_row(scale, 0.0, 0.0, scale, (x + MARGIN) * scale, top * scale);
+        pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+    }
+    pixmap
+        .encode_png()
+        .map_err(|e| AutobaseError::Proof(e.to_string()))
+}
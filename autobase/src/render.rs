@@ -0,0 +1,240 @@
+//! Render text as SVG paths, for reports and proofs.
+//!
+//! Words are laid out glyph by glyph from the cmap and horizontal advances,
+//! without shaping; this is enough to show which word reached an extreme and
+//! how it sits against the font's lines, but joining scripts will appear in
+//! their isolated forms. Coordinates are in font units with the y axis flipped,
+//! so that a path can be placed directly in an SVG whose `y = -value` is the
+//! font's `value`.
+use std::fmt::Write;
+
+use skrifa::{
+    instance::Location,
+    outline::{DrawSettings, OutlinePen},
+    prelude::Size,
+    FontRef, GlyphId, GlyphNames, MetadataProvider, Tag,
+};
+
+use crate::error::AutobaseError;
+
+/// Writes an outline as SVG path data.
+#[derive(Default)]
+struct SvgPathPen(String);
+
+impl SvgPathPen {
+    fn point(&mut self, x: f32, y: f32) {
+        let _ = write!(self.0, "{} {} ", x, y);
+    }
+}
+
+impl OutlinePen for SvgPathPen {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.push('M');
+        self.point(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.push('L');
+        self.point(x, y);
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        self.0.push('Q');
+        self.point(cx0, cy0);
+        self.point(x, y);
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        self.0.push('C');
+        self.point(cx0, cy0);
+        self.point(cx1, cy1);
+        self.point(x, y);
+    }
+
+    fn close(&mut self) {
+        self.0.push('Z');
+    }
+}
+
+/// Passes a glyph's outline on to another pen, moved to the glyph's position
+/// and flipped vertically.
+struct PlacedPen<'a, P> {
+    pen: &'a mut P,
+    x_offset: f32,
+}
+
+impl<P: OutlinePen> OutlinePen for PlacedPen<'_, P> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.pen.move_to(x + self.x_offset, -y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.pen.line_to(x + self.x_offset, -y);
+    }
+
+    fn quad_to(&mut self, cx0: f32, cy0: f32, x: f32, y: f32) {
+        self.pen
+            .quad_to(cx0 + self.x_offset, -cy0, x + self.x_offset, -y);
+    }
+
+    fn curve_to(&mut self, cx0: f32, cy0: f32, cx1: f32, cy1: f32, x: f32, y: f32) {
+        self.pen.curve_to(
+            cx0 + self.x_offset,
+            -cy0,
+            cx1 + self.x_offset,
+            -cy1,
+            x + self.x_offset,
+            -y,
+        );
+    }
+
+    fn close(&mut self) {
+        self.pen.close();
+    }
+}
+
+/// Text drawn as a single SVG path.
+#[derive(Debug, Clone, Default)]
+pub struct RenderedText {
+    /// The path data, in font units with y pointing down
+    pub path: String,
+    /// The total advance width of the text
+    pub advance: f32,
+}
+
+/// The glyphs of `text`, or the single glyph named by text of the form `/name`,
+/// as named glyphs are reported by [`crate::glyphs::measure_glyphs`].
+fn glyphs(font: &FontRef, text: &str) -> Vec<GlyphId> {
+    if let Some(name) = text.strip_prefix('/') {
+        if let Some((gid, _)) = GlyphNames::new(font)
+            .iter()
+            .find(|(_, n)| n.as_str() == name)
+        {
+            return vec![gid];
+        }
+    }
+    let charmap = font.charmap();
+    text.chars()
+        .map(|c| charmap.map(c).unwrap_or(GlyphId::NOTDEF))
+        .collect()
+}
+
+/// Draw `text` at `location` into `pen`, starting at the origin, in font units
+/// with y pointing down. Returns the advance width of the text.
+pub fn draw_text(
+    font: &FontRef,
+    text: &str,
+    location: &Location,
+    pen: &mut impl OutlinePen,
+) -> f32 {
+    let metrics = font.glyph_metrics(Size::unscaled(), location);
+    let outlines = font.outline_glyphs();
+    let mut advance = 0.0;
+    for gid in glyphs(font, text) {
+        if let Some(outline) = outlines.get(gid) {
+            let mut placed = PlacedPen {
+                pen: &mut *pen,
+                x_offset: advance,
+            };
+            if let Err(e) = outline.draw(
+                DrawSettings::unhinted(Size::unscaled(), location),
+                &mut placed,
+            ) {
+                log::debug!("Could not draw glyph {} of {:?}: {}", gid, text, e);
+            }
+        }
+        advance += metrics.advance_width(gid).unwrap_or_default();
+    }
+    advance
+}
+
+/// Draw `text` at `location` as an SVG path, starting at the origin.
+pub fn render_text(font: &FontRef, text: &str, location: &Location) -> RenderedText {
+    let mut pen = SvgPathPen::default();
+    let advance = draw_text(font, text, location, &mut pen);
+    RenderedText {
+        path: pen.0,
+        advance,
+    }
+}
+
+/// The location described by a string of user-space axis values such as
+/// "wght=700 wdth=75", as produced by [`crate::utils::describe_location`].
+///
+/// Axes which aren't mentioned are left at their defaults, and `default` is
+/// the default location. A setting which isn't `tag=value`, or which names an
+/// axis the font doesn't have, is an error.
+pub fn parse_location(font: &FontRef, description: &str) -> Result<Location, AutobaseError> {
+    let axes = font.axes();
+    let mut settings = vec![];
+    for setting in description.split_whitespace() {
+        if setting == "default" {
+            continue;
+        }
+        let invalid = |reason: &str| AutobaseError::InvalidLocation {
+            setting: setting.to_string(),
+            reason: reason.to_string(),
+        };
+        let (tag, value) = setting
+            .split_once('=')
+            .ok_or_else(|| invalid("expected an axis tag and value, such as wght=700"))?;
+        let tag = Tag::new_checked(tag.as_bytes()).map_err(|_| invalid("not an axis tag"))?;
+        let value = value
+            .parse::<f32>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| invalid("not a number"))?;
+        if !axes.iter().any(|axis| axis.tag() == tag) {
+            return Err(invalid("the font has no such axis"));
+        }
+        settings.push((tag, value));
+    }
+    Ok(axes.location(settings))
+}
+
+/// Escape text for inclusion in SVG or HTML.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cjk::tests::ideograph_font;
+
+    #[test]
+    fn malformed_locations_are_reported() {
+        let data = ideograph_font(true);
+        let font = FontRef::new(&data).unwrap();
+        let wght = font.axes().location([("wght", 900.0)]);
+        assert_eq!(parse_location(&font, "wght=900").unwrap(), wght);
+        let default = font.axes().location(Vec::<(&str, f32)>::new());
+        assert_eq!(parse_location(&font, "default").unwrap(), default);
+        for description in [
+            "wght",
+            "wght=heavy",
+            "wght=NaN",
+            "wdth=75",
+            "wght=900 toolong=1",
+        ] {
+            assert!(
+                matches!(
+                    parse_location(&font, description),
+                    Err(AutobaseError::InvalidLocation { .. })
+                ),
+                "{} was accepted",
+                description
+            );
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! A report of the words which produced each extreme.
+//!
+//! The BASE table only records how high and low each script and language reaches.
+//! For a linguist or designer reviewing the results, what matters is which words
+//! reached those values: a single misspelt or foreign word in a list can push a
+//! record far beyond the rest of the text. The report lists, for every word list
+//! measured at every location, the words which reached its extremes, alongside the
+//! values finally written for each script and language.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    accumulator::{MeasuredMinMax, ScriptAccumulator},
+    base::{BaseScript, MinMax},
+};
+
+/// A value and the word which reached it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Extreme {
+    pub value: i16,
+    pub word: String,
+}
+
+/// The lowest and highest points of a MinMax, with the words which reached them.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Extremes {
+    pub min: Option<Extreme>,
+    pub max: Option<Extreme>,
+}
+
+impl From<&MinMax> for Extremes {
+    fn from(minmax: &MinMax) -> Self {
+        Extremes {
+            min: minmax.lowest.map(|value| Extreme {
+                value,
+                word: minmax.lowest_word.clone(),
+            }),
+            max: minmax.highest.map(|value| Extreme {
+                value,
+                word: minmax.highest_word.clone(),
+            }),
+        }
+    }
+}
+
+/// The extremes of one word list at one location.
+#[derive(Debug, Clone, Serialize)]
+pub struct MeasurementReport {
+    pub word_list: String,
+    /// The ISO 639 language the word list is attributed to, if any
+    pub language: Option<String>,
+    /// The location measured, as user-space axis values
+    pub location: String,
+    /// The optical size band the location falls in, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub band: Option<String>,
+    #[serde(flatten)]
+    pub extremes: Extremes,
+}
+
+impl From<&MeasuredMinMax> for MeasurementReport {
+    fn from(measured: &MeasuredMinMax) -> Self {
+        MeasurementReport {
+            word_list: measured.name.clone(),
+            language: measured.source.language.clone(),
+            location: measured.location.clone(),
+            band: measured.band.clone(),
+            extremes: Extremes::from(&measured.minmax),
+        }
+    }
+}
+
+/// The extremes written to a script's record, for the script as a whole and for
+/// each language with its own MinMax, and the record's baselines.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordReport {
+    pub default: Option<Extremes>,
+    /// Keyed by OpenType language tag
+    pub languages: BTreeMap<String, Extremes>,
+    /// Keyed by baseline tag
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub baselines: BTreeMap<String, i16>,
+}
+
+impl From<&BaseScript> for RecordReport {
+    fn from(record: &BaseScript) -> Self {
+        RecordReport {
+            default: record.default_minmax.as_ref().map(Extremes::from),
+            languages: record
+                .languages
+                .iter()
+                .map(|(tag, minmax)| (tag.to_string().trim_end().to_string(), minmax.into()))
+                .collect(),
+            baselines: record
+                .baselines
+                .iter()
+                .map(|(tag, value)| (tag.to_string(), *value))
+                .collect(),
+        }
+    }
+}
+
+/// Everything measured for one script, and the record chosen from it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptReport {
+    /// The ISO 15924 script code
+    pub script: String,
+    pub measurements: Vec<MeasurementReport>,
+    /// The script's horizontal record, if one was written
+    pub record: Option<RecordReport>,
+}
+
+impl ScriptReport {
+    pub fn new(script: &str, accumulator: &ScriptAccumulator, record: Option<&BaseScript>) -> Self {
+        ScriptReport {
+            script: script.to_string(),
+            measurements: accumulator.minmaxes.iter().map(Into::into).collect(),
+            record: record.map(Into::into),
+        }
+    }
+}
+
+/// The report for one font.
+#[derive(Debug, Clone, Serialize)]
+pub struct FontReport {
+    pub font: String,
+    pub scripts: Vec<ScriptReport>,
+}
+
+/// The report for a whole run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub fonts: Vec<FontReport>,
+}
+
+impl Report {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
@@ -0,0 +1,173 @@
+//! Statistics describing how representative a script's measurements are.
+//!
+//! A MinMax is only as good as the words it was measured from. These statistics
+//! tell reviewers how much of the script the font covers, how many of the
+//! measured words the font could actually render, and how the extremes of those
+//! words are distributed, so that an extreme value can be recognised as typical
+//! or as a fluke.
+use std::collections::HashMap;
+
+use skrifa::{FontRef, MetadataProvider};
+
+use crate::{names::script_name, utils::ScriptCoverage};
+
+/// The minimum, median and maximum of a set of values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distribution {
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    pub fn from_values(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+        let mid = values.len() / 2;
+        let median = if values.len().is_multiple_of(2) {
+            (values[mid - 1] + values[mid]) / 2.0
+        } else {
+            values[mid]
+        };
+        Some(Self {
+            min: values[0],
+            median,
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+impl std::fmt::Display for Distribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "min {:.0} / median {:.0} / max {:.0}",
+            self.min, self.median, self.max
+        )
+    }
+}
+
+/// Sampling statistics for one script.
+#[derive(Debug, Clone)]
+pub struct ScriptStatistics {
+    /// The ISO 15924 script code
+    pub script: String,
+    /// The number of codepoints in the script which the font encodes
+    pub codepoints: usize,
+    /// The number of distinct glyphs those codepoints map to
+    pub glyphs: usize,
+    /// The number of distinct words measured
+    pub words: usize,
+    /// The number of distinct words for which the font encodes every character
+    pub shapeable_words: usize,
+    /// The distribution of the highest point of each measured word (at whichever
+    /// location it is highest)
+    pub highest: Option<Distribution>,
+    /// The distribution of the lowest point of each measured word
+    pub lowest: Option<Distribution>,
+}
+
+impl ScriptStatistics {
+    pub fn new(
+        font: &FontRef,
+        script: &str,
+        coverage: Option<&ScriptCoverage>,
+        words: &HashMap<String, (f64, f64)>,
+    ) -> Self {
+        let charmap = font.charmap();
+        let shapeable_words = words
+            .keys()
+            .filter(|word| {
+                word.chars()
+                    .filter(|c| !c.is_whitespace())
+                    .all(|c| charmap.map(c).is_some())
+            })
+            .count();
+        Self {
+            script: script.to_string(),
+            codepoints: coverage.map(|c| c.codepoints).unwrap_or(0),
+            glyphs: coverage.map(|c| c.glyphs.len()).unwrap_or(0),
+            words: words.len(),
+            shapeable_words,
+            highest: Distribution::from_values(words.values().map(|(h, _)| *h).collect()),
+            lowest: Distribution::from_values(words.values().map(|(_, l)| *l).collect()),
+        }
+    }
+}
+
+impl std::fmt::Display for ScriptStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} codepoints, {} glyphs; {} of {} words shapeable",
+            script_name(&self.script)
+                .map(|name| format!("{} ({})", name, self.script))
+                .unwrap_or_else(|| self.script.clone()),
+            self.codepoints,
+            self.glyphs,
+            self.shapeable_words,
+            self.words
+        )?;
+        if let Some(highest) = &self.highest {
+            write!(f, "; highest points {}", highest)?;
+        }
+        if let Some(lowest) = &self.lowest {
+            write!(f, "; lowest points {}", lowest)?;
+        }
+        Ok(())
+    }
+}
+
+/// The mean and (population) standard deviation of a set of values.
+pub fn mean_and_standard_deviation(values: &[f64]) -> Option<(f64, f64)> {
+    if values.is_empty() {
+        return None;
+    }
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+    Some((mean, variance.sqrt()))
+}
+
+/// The mean of a set of values, each paired with its weight.
+pub fn weighted_mean(values: &[(f64, f64)]) -> Option<f64> {
+    let total = values.iter().map(|(_, weight)| weight).sum::<f64>();
+    if total <= 0.0 {
+        return None;
+    }
+    Some(
+        values
+            .iter()
+            .map(|(value, weight)| value * weight)
+            .sum::<f64>()
+            / total,
+    )
+}
+
+/// The median of a set of values, each paired with its weight: the value at
+/// which half of the total weight lies on either side.
+///
+/// As with an unweighted median, when the halfway point falls between two
+/// values, the median is their mean.
+pub fn weighted_median(mut values: Vec<(f64, f64)>) -> Option<f64> {
+    values.retain(|(_, weight)| *weight > 0.0);
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let half = values.iter().map(|(_, weight)| weight).sum::<f64>() / 2.0;
+    let mut cumulative = 0.0;
+    for (index, (value, weight)) in values.iter().enumerate() {
+        cumulative += weight;
+        if cumulative > half {
+            return Some(*value);
+        }
+        if cumulative == half {
+            return Some(
+                values
+                    .get(index + 1)
+                    .map_or(*value, |(next, _)| (value + next) / 2.0),
+            );
+        }
+    }
+    None
+}
@@ -0,0 +1,170 @@
+//! Write a BASE table into UFO sources, for fontmake and other ufo2ft builds.
+//!
+//! ufo2ft compiles whatever is in a UFO's `features.fea`, so the table is
+//! written there as FEA, between marker comments which let later runs replace
+//! it. The table itself, with the NULL MinMax values that FEA can't express and
+//! the word behind each extreme, is also stored in `lib.plist` under
+//! [`LIB_KEY`], for tools which read BASE data from the source.
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::base::BaseTable;
+
+/// The `lib.plist` key under which the table is stored.
+pub const LIB_KEY: &str = "com.github.simoncozens.autobase.BASE";
+
+const BEGIN_MARKER: &str = "# BEGIN autobase BASE table";
+const END_MARKER: &str = "# END autobase BASE table";
+
+/// Write `base` into the UFO at `ufo`: `fea_base`, its FEA-expressible
+/// collation, goes into `features.fea` and `base` itself into `lib.plist`.
+pub fn write_to_ufo(base: &BaseTable, fea_base: &BaseTable, ufo: &Path) -> anyhow::Result<()> {
+    if !ufo.join("metainfo.plist").is_file() {
+        anyhow::bail!("{} is not a UFO", ufo.display());
+    }
+    // Both files are prepared before either is written, so that a UFO which
+    // can't take the table is left as it was
+    let lib_path = ufo.join("lib.plist");
+    let lib = updated_lib(base, &lib_path)?;
+    let features_path = ufo.join("features.fea");
+    let features = if features_path.exists() {
+        std::fs::read_to_string(&features_path).context("failed to read features.fea")?
+    } else {
+        String::new()
+    };
+    let features = replace_fea_block(&features, fea_base)?;
+    lib.to_file_xml(&lib_path)
+        .context("failed to write lib.plist")?;
+    std::fs::write(&features_path, features).context("failed to write features.fea")?;
+    Ok(())
+}
+
+/// The contents of the `lib.plist` at `path`, with `base` stored in it.
+fn updated_lib(base: &BaseTable, path: &Path) -> anyhow::Result<plist::Value> {
+    let mut lib = if path.exists() {
+        plist::Value::from_file(path)
+            .context("failed to read lib.plist")?
+            .into_dictionary()
+            .context("lib.plist is not a dictionary")?
+    } else {
+        plist::Dictionary::new()
+    };
+    lib.insert(
+        LIB_KEY.to_string(),
+        plist::to_value(base).context("failed to convert the BASE table to a plist")?,
+    );
+    Ok(plist::Value::Dictionary(lib))
+}
+
+/// Replace the table autobase wrote into `features` on an earlier run with
+/// `base`, or add it if there isn't one.
+///
+/// An empty table removes the earlier one. Feature files with a BASE table
+/// which autobase didn't write are left to the user to resolve.
+pub fn replace_fea_block(features: &str, base: &BaseTable) -> anyhow::Result<String> {
+    let block = if base.horizontal.is_empty() && base.vertical.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "{}\n{}\n{}\n",
+            BEGIN_MARKER,
+            base.to_fea().trim_end(),
+            END_MARKER
+        )
+    };
+    if let Some(start) = features.find(BEGIN_MARKER) {
+        let end = features[start..]
+            .find(END_MARKER)
+            .map(|end| start + end + END_MARKER.len())
+            .with_context(|| format!("features.fea has no \"{}\" line", END_MARKER))?;
+        let end = if features[end..].starts_with('\n') {
+            end + 1
+        } else {
+            end
+        };
+        return Ok(format!(
+            "{}{}{}",
+            &features[..start],
+            block,
+            &features[end..]
+        ));
+    }
+    if features
+        .lines()
+        .any(|line| line.trim_start().starts_with("table BASE"))
+    {
+        anyhow::bail!(
+            "features.fea already has a BASE table which autobase didn't write; remove it first"
+        );
+    }
+    if block.is_empty() {
+        return Ok(features.to_string());
+    }
+    let mut features = features.to_string();
+    if !features.is_empty() {
+        if !features.ends_with('\n') {
+            features.push('\n');
+        }
+        features.push('\n');
+    }
+    features.push_str(&block);
+    Ok(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use skrifa::Tag;
+
+    use super::*;
+    use crate::base::{BaseScript, MinMax};
+
+    fn table(max: i16) -> BaseTable {
+        let mut latn = BaseScript::new(Tag::new(b"latn"));
+        latn.default_minmax = Some(MinMax::new_min_max(-200, max));
+        BaseTable::new(vec![latn], vec![])
+    }
+
+    #[test]
+    fn earlier_table_is_replaced() {
+        let features = "languagesystem DFLT dflt;\n\nfeature kern {\n} kern;\n";
+        let first = replace_fea_block(features, &table(800)).unwrap();
+        assert!(first.starts_with(features));
+        assert!(first.contains(" 800;"));
+        let second = replace_fea_block(&first, &table(900)).unwrap();
+        assert_eq!(second, first.replace(" 800;", " 900;"));
+        assert_eq!(second.matches(BEGIN_MARKER).count(), 1);
+    }
+
+    #[test]
+    fn empty_table_removes_the_earlier_one() {
+        let features = "feature kern {\n} kern;\n";
+        let written = replace_fea_block(features, &table(800)).unwrap();
+        let removed = replace_fea_block(&written, &BaseTable::default()).unwrap();
+        assert_eq!(removed.trim_end(), features.trim_end());
+        assert!(!removed.contains("BASE"));
+    }
+
+    #[test]
+    fn foreign_base_table_is_left_alone() {
+        let features = "table BASE {\n  HorizAxis.BaseTagList romn;\n} BASE;\n";
+        assert!(replace_fea_block(features, &table(800)).is_err());
+        // Nor is a block whose end marker has been lost
+        let written = replace_fea_block("", &table(800)).unwrap();
+        let truncated = written.replace(END_MARKER, "");
+        assert!(replace_fea_block(&truncated, &table(900)).is_err());
+    }
+
+    #[test]
+    fn nothing_is_written_when_the_table_cannot_go_in() {
+        let ufo = std::env::temp_dir().join(format!("autobase-ufo-{}.ufo", std::process::id()));
+        std::fs::create_dir_all(&ufo).unwrap();
+        std::fs::write(ufo.join("metainfo.plist"), "").unwrap();
+        std::fs::write(ufo.join("features.fea"), "table BASE {\n} BASE;\n").unwrap();
+        let result = write_to_ufo(&table(800), &table(800), &ufo);
+        let lib_written = ufo.join("lib.plist").exists();
+        std::fs::remove_dir_all(&ufo).unwrap();
+        assert!(result.is_err());
+        assert!(!lib_written);
+    }
+}
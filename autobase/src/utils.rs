@@ -1,29 +1,110 @@
-use std::collections::HashSet;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
 
-use skrifa::{FontRef, MetadataProvider, Tag};
+use skrifa::{
+    instance::{Location, NormalizedCoord},
+    raw::TableProvider,
+    FontRef, GlyphId, MetadataProvider, Tag,
+};
 use ucd::Codepoint;
+use unicode_script::UnicodeScript;
+
+use crate::error::AutobaseError;
+
+/// Return the ISO 15924 code of the script of a character, if it has one.
+pub fn char_script(c: char) -> Option<&'static str> {
+    let script = c.script()?;
+    // Would you believe, no Display, no .to_string(), we just have to
+    // grub around with Debug.
+    let script_name = format!("{:?}", script);
+    let iso_script = unicode_to_iso(&script_name);
+    if iso_script.is_none() {
+        log::warn!("No ISO 15924 code for script: {}", script_name);
+    }
+    iso_script
+}
+
+/// Return the ISO 15924 codes of every script a character is used in, according
+/// to its Script_Extensions property.
+///
+/// Characters shared between scripts, such as the Devanagari danda (used by many
+/// Indic scripts) or CJK punctuation, belong to each of them rather than to Common.
+/// Characters which are used with any script are reported as `Zyyy` or `Zinh`.
+pub fn char_scripts(c: char) -> impl Iterator<Item = &'static str> {
+    c.script_extension().iter().filter_map(|script| {
+        let short_name = script.short_name();
+        KNOWN_ISO_SCRIPTS
+            .iter()
+            .chain(["Zinh", "Zyyy"].iter())
+            .find(|iso| **iso == short_name)
+            .copied()
+    })
+}
 
 /// Return the set of scripts supported by the font, as ISO 15924 codes.
+///
+/// A script is supported if the font encodes characters of its own, by their
+/// Script property. Characters shared between scripts, such as the Devanagari
+/// danda or the middle dot, count towards each of their Script_Extensions
+/// scripts in [`script_coverage`], but don't make the font support them: a
+/// Latin font with a middle dot doesn't support Han or Georgian.
 pub fn supported_scripts(font: &FontRef) -> HashSet<&'static str> {
     let cmap = font.charmap();
     let mut strings = HashSet::new();
     for (codepoint, _glyphid) in cmap.mappings() {
-        if let Some(script) = char::from_u32(codepoint).and_then(|c| c.script()) {
-            // Would you believe, no Display, no .to_string(), we just have to
-            // grub around with Debug.
-            let script_name = format!("{:?}", script);
-            if let Some(iso_script) = unicode_to_iso(&script_name) {
-                if !iso_script.starts_with("Z") {
-                    strings.insert(iso_script);
-                }
-            } else {
-                log::warn!("No ISO 15924 code for script: {}", script_name);
+        let Some(c) = char::from_u32(codepoint) else {
+            continue;
+        };
+        if let Some(iso_script) = char_script(c) {
+            if !iso_script.starts_with("Z") {
+                strings.insert(iso_script);
             }
         }
     }
     strings
 }
 
+/// Return the set of scripts in the font's GSUB and GPOS script lists, as ISO 15924 codes.
+///
+/// The `DFLT` script is ignored, as are script tags with no ISO 15924 equivalent.
+pub fn layout_scripts(font: &FontRef) -> HashSet<&'static str> {
+    let mut tags = vec![];
+    if let Ok(script_list) = font.gsub().and_then(|gsub| gsub.script_list()) {
+        tags.extend(script_list.script_records().iter().map(|r| r.script_tag()));
+    }
+    if let Ok(script_list) = font.gpos().and_then(|gpos| gpos.script_list()) {
+        tags.extend(script_list.script_records().iter().map(|r| r.script_tag()));
+    }
+    tags.into_iter().flat_map(opentype_to_iso15924).collect()
+}
+
+/// How much of a script the font's character map covers.
+#[derive(Debug, Default, Clone)]
+pub struct ScriptCoverage {
+    /// The number of encoded codepoints in the script
+    pub codepoints: usize,
+    /// The distinct glyphs those codepoints map to
+    pub glyphs: HashSet<GlyphId>,
+}
+
+/// Return the coverage of each script in the font's character map, keyed by ISO 15924 code.
+pub fn script_coverage(font: &FontRef) -> HashMap<&'static str, ScriptCoverage> {
+    let mut coverage: HashMap<&'static str, ScriptCoverage> = HashMap::new();
+    for (codepoint, glyphid) in font.charmap().mappings() {
+        let Some(c) = char::from_u32(codepoint) else {
+            continue;
+        };
+        for iso_script in char_scripts(c) {
+            let entry = coverage.entry(iso_script).or_default();
+            entry.codepoints += 1;
+            entry.glyphs.insert(glyphid);
+        }
+    }
+    coverage
+}
+
 pub const KNOWN_ISO_SCRIPTS: [&str; 172] = [
     "Adlm", "Aghb", "Ahom", "Arab", "Armi", "Armn", "Avst", "Bali", "Bamu", "Bass", "Batk", "Beng",
     "Bhks", "Bopo", "Brah", "Brai", "Bugi", "Buhd", "Cakm", "Cans", "Cari", "Cham", "Cher", "Chrs",
@@ -42,182 +123,187 @@ pub const KNOWN_ISO_SCRIPTS: [&str; 172] = [
     "Zanb", "Zinh", "Zyyy", "Zzzz",
 ];
 
+/// Unicode script property values and their ISO 15924 codes.
+pub const UNICODE_SCRIPT_NAMES: [(&str, &str); 172] = [
+    ("Adlam", "Adlm"),
+    ("Caucasian_Albanian", "Aghb"),
+    ("Ahom", "Ahom"),
+    ("Arabic", "Arab"),
+    ("Imperial_Aramaic", "Armi"),
+    ("Armenian", "Armn"),
+    ("Avestan", "Avst"),
+    ("Balinese", "Bali"),
+    ("Bamum", "Bamu"),
+    ("Bassa_Vah", "Bass"),
+    ("Batak", "Batk"),
+    ("Bengali", "Beng"),
+    ("Bhaiksuki", "Bhks"),
+    ("Bopomofo", "Bopo"),
+    ("Brahmi", "Brah"),
+    ("Braille", "Brai"),
+    ("Buginese", "Bugi"),
+    ("Buhid", "Buhd"),
+    ("Chakma", "Cakm"),
+    ("Canadian_Aboriginal", "Cans"),
+    ("Carian", "Cari"),
+    ("Cham", "Cham"),
+    ("Cherokee", "Cher"),
+    ("Chorasmian", "Chrs"),
+    ("Coptic", "Copt"),
+    ("Cypro_Minoan", "Cpmn"),
+    ("Cypriot", "Cprt"),
+    ("Cyrillic", "Cyrl"),
+    ("Devanagari", "Deva"),
+    ("Dives_Akuru", "Diak"),
+    ("Dogra", "Dogr"),
+    ("Deseret", "Dsrt"),
+    ("Duployan", "Dupl"),
+    ("Egyptian_Hieroglyphs", "Egyp"),
+    ("Elbasan", "Elba"),
+    ("Elymaic", "Elym"),
+    ("Ethiopic", "Ethi"),
+    ("Garay", "Gara"),
+    ("Georgian", "Geor"),
+    ("Glagolitic", "Glag"),
+    ("Gunjala_Gondi", "Gong"),
+    ("Masaram_Gondi", "Gonm"),
+    ("Gothic", "Goth"),
+    ("Grantha", "Gran"),
+    ("Greek", "Grek"),
+    ("Gujarati", "Gujr"),
+    ("Gurung_Khema", "Gukh"),
+    ("Gurmukhi", "Guru"),
+    ("Hangul", "Hang"),
+    ("Han", "Hani"),
+    ("Hanunoo", "Hano"),
+    ("Hatran", "Hatr"),
+    ("Hebrew", "Hebr"),
+    ("Hiragana", "Hira"),
+    ("Anatolian_Hieroglyphs", "Hluw"),
+    ("Pahawh_Hmong", "Hmng"),
+    ("Nyiakeng_Puachue_Hmong", "Hmnp"),
+    ("Katakana_Or_Hiragana", "Hrkt"),
+    ("Old_Hungarian", "Hung"),
+    ("Old_Italic", "Ital"),
+    ("Javanese", "Java"),
+    ("Kayah_Li", "Kali"),
+    ("Katakana", "Kana"),
+    ("Kawi", "Kawi"),
+    ("Kharoshthi", "Khar"),
+    ("Khmer", "Khmr"),
+    ("Khojki", "Khoj"),
+    ("Khitan_Small_Script", "Kits"),
+    ("Kannada", "Knda"),
+    ("Kirat_Rai", "Krai"),
+    ("Kaithi", "Kthi"),
+    ("Tai_Tham", "Lana"),
+    ("Lao", "Laoo"),
+    ("Latin", "Latn"),
+    ("Lepcha", "Lepc"),
+    ("Limbu", "Limb"),
+    ("Linear_A", "Lina"),
+    ("Linear_B", "Linb"),
+    ("Lisu", "Lisu"),
+    ("Lycian", "Lyci"),
+    ("Lydian", "Lydi"),
+    ("Mahajani", "Mahj"),
+    ("Makasar", "Maka"),
+    ("Mandaic", "Mand"),
+    ("Manichaean", "Mani"),
+    ("Marchen", "Marc"),
+    ("Medefaidrin", "Medf"),
+    ("Mende_Kikakui", "Mend"),
+    ("Meroitic_Cursive", "Merc"),
+    ("Meroitic_Hieroglyphs", "Mero"),
+    ("Malayalam", "Mlym"),
+    ("Modi", "Modi"),
+    ("Mongolian", "Mong"),
+    ("Mro", "Mroo"),
+    ("Meetei_Mayek", "Mtei"),
+    ("Multani", "Mult"),
+    ("Myanmar", "Mymr"),
+    ("Nag_Mundari", "Nagm"),
+    ("Nandinagari", "Nand"),
+    ("Old_North_Arabian", "Narb"),
+    ("Nabataean", "Nbat"),
+    ("Newa", "Newa"),
+    ("Nko", "Nkoo"),
+    ("Nushu", "Nshu"),
+    ("Ogham", "Ogam"),
+    ("Ol_Chiki", "Olck"),
+    ("Ol_Onal", "Onao"),
+    ("Old_Turkic", "Orkh"),
+    ("Oriya", "Orya"),
+    ("Osage", "Osge"),
+    ("Osmanya", "Osma"),
+    ("Old_Uyghur", "Ougr"),
+    ("Palmyrene", "Palm"),
+    ("Pau_Cin_Hau", "Pauc"),
+    ("Old_Permic", "Perm"),
+    ("Phags_Pa", "Phag"),
+    ("Inscriptional_Pahlavi", "Phli"),
+    ("Psalter_Pahlavi", "Phlp"),
+    ("Phoenician", "Phnx"),
+    ("Miao", "Plrd"),
+    ("Inscriptional_Parthian", "Prti"),
+    ("Rejang", "Rjng"),
+    ("Hanifi_Rohingya", "Rohg"),
+    ("Runic", "Runr"),
+    ("Samaritan", "Samr"),
+    ("Old_South_Arabian", "Sarb"),
+    ("Saurashtra", "Saur"),
+    ("SignWriting", "Sgnw"),
+    ("Shavian", "Shaw"),
+    ("Sharada", "Shrd"),
+    ("Siddham", "Sidd"),
+    ("Khudawadi", "Sind"),
+    ("Sinhala", "Sinh"),
+    ("Sogdian", "Sogd"),
+    ("Old_Sogdian", "Sogo"),
+    ("Sora_Sompeng", "Sora"),
+    ("Soyombo", "Soyo"),
+    ("Sundanese", "Sund"),
+    ("Sunuwar", "Sunu"),
+    ("Syloti_Nagri", "Sylo"),
+    ("Syriac", "Syrc"),
+    ("Tagbanwa", "Tagb"),
+    ("Takri", "Takr"),
+    ("Tai_Le", "Tale"),
+    ("New_Tai_Lue", "Talu"),
+    ("Tamil", "Taml"),
+    ("Tangut", "Tang"),
+    ("Tai_Viet", "Tavt"),
+    ("Telugu", "Telu"),
+    ("Tifinagh", "Tfng"),
+    ("Tagalog", "Tglg"),
+    ("Thaana", "Thaa"),
+    ("Thai", "Thai"),
+    ("Tibetan", "Tibt"),
+    ("Tirhuta", "Tirh"),
+    ("Tangsa", "Tnsa"),
+    ("Todhri", "Todr"),
+    ("Toto", "Toto"),
+    ("Tulu_Tigalari", "Tutg"),
+    ("Ugaritic", "Ugar"),
+    ("Vai", "Vaii"),
+    ("Vithkuqi", "Vith"),
+    ("Warang_Citi", "Wara"),
+    ("Wancho", "Wcho"),
+    ("Old_Persian", "Xpeo"),
+    ("Cuneiform", "Xsux"),
+    ("Yezidi", "Yezi"),
+    ("Yi", "Yiii"),
+    ("Zanabazar_Square", "Zanb"),
+    ("Inherited", "Zinh"),
+    ("Common", "Zyyy"),
+    ("Unknown", "Zzzz"),
+];
+
 fn unicode_to_iso(script: &str) -> Option<&'static str> {
-    match script {
-        "Adlam" => Some("Adlm"),
-        "Caucasian_Albanian" => Some("Aghb"),
-        "Ahom" => Some("Ahom"),
-        "Arabic" => Some("Arab"),
-        "Imperial_Aramaic" => Some("Armi"),
-        "Armenian" => Some("Armn"),
-        "Avestan" => Some("Avst"),
-        "Balinese" => Some("Bali"),
-        "Bamum" => Some("Bamu"),
-        "Bassa_Vah" => Some("Bass"),
-        "Batak" => Some("Batk"),
-        "Bengali" => Some("Beng"),
-        "Bhaiksuki" => Some("Bhks"),
-        "Bopomofo" => Some("Bopo"),
-        "Brahmi" => Some("Brah"),
-        "Braille" => Some("Brai"),
-        "Buginese" => Some("Bugi"),
-        "Buhid" => Some("Buhd"),
-        "Chakma" => Some("Cakm"),
-        "Canadian_Aboriginal" => Some("Cans"),
-        "Carian" => Some("Cari"),
-        "Cham" => Some("Cham"),
-        "Cherokee" => Some("Cher"),
-        "Chorasmian" => Some("Chrs"),
-        "Coptic" => Some("Copt"),
-        "Cypro_Minoan" => Some("Cpmn"),
-        "Cypriot" => Some("Cprt"),
-        "Cyrillic" => Some("Cyrl"),
-        "Devanagari" => Some("Deva"),
-        "Dives_Akuru" => Some("Diak"),
-        "Dogra" => Some("Dogr"),
-        "Deseret" => Some("Dsrt"),
-        "Duployan" => Some("Dupl"),
-        "Egyptian_Hieroglyphs" => Some("Egyp"),
-        "Elbasan" => Some("Elba"),
-        "Elymaic" => Some("Elym"),
-        "Ethiopic" => Some("Ethi"),
-        "Garay" => Some("Gara"),
-        "Georgian" => Some("Geor"),
-        "Glagolitic" => Some("Glag"),
-        "Gunjala_Gondi" => Some("Gong"),
-        "Masaram_Gondi" => Some("Gonm"),
-        "Gothic" => Some("Goth"),
-        "Grantha" => Some("Gran"),
-        "Greek" => Some("Grek"),
-        "Gujarati" => Some("Gujr"),
-        "Gurung_Khema" => Some("Gukh"),
-        "Gurmukhi" => Some("Guru"),
-        "Hangul" => Some("Hang"),
-        "Han" => Some("Hani"),
-        "Hanunoo" => Some("Hano"),
-        "Hatran" => Some("Hatr"),
-        "Hebrew" => Some("Hebr"),
-        "Hiragana" => Some("Hira"),
-        "Anatolian_Hieroglyphs" => Some("Hluw"),
-        "Pahawh_Hmong" => Some("Hmng"),
-        "Nyiakeng_Puachue_Hmong" => Some("Hmnp"),
-        "Katakana_Or_Hiragana" => Some("Hrkt"),
-        "Old_Hungarian" => Some("Hung"),
-        "Old_Italic" => Some("Ital"),
-        "Javanese" => Some("Java"),
-        "Kayah_Li" => Some("Kali"),
-        "Katakana" => Some("Kana"),
-        "Kawi" => Some("Kawi"),
-        "Kharoshthi" => Some("Khar"),
-        "Khmer" => Some("Khmr"),
-        "Khojki" => Some("Khoj"),
-        "Khitan_Small_Script" => Some("Kits"),
-        "Kannada" => Some("Knda"),
-        "Kirat_Rai" => Some("Krai"),
-        "Kaithi" => Some("Kthi"),
-        "Tai_Tham" => Some("Lana"),
-        "Lao" => Some("Laoo"),
-        "Latin" => Some("Latn"),
-        "Lepcha" => Some("Lepc"),
-        "Limbu" => Some("Limb"),
-        "Linear_A" => Some("Lina"),
-        "Linear_B" => Some("Linb"),
-        "Lisu" => Some("Lisu"),
-        "Lycian" => Some("Lyci"),
-        "Lydian" => Some("Lydi"),
-        "Mahajani" => Some("Mahj"),
-        "Makasar" => Some("Maka"),
-        "Mandaic" => Some("Mand"),
-        "Manichaean" => Some("Mani"),
-        "Marchen" => Some("Marc"),
-        "Medefaidrin" => Some("Medf"),
-        "Mende_Kikakui" => Some("Mend"),
-        "Meroitic_Cursive" => Some("Merc"),
-        "Meroitic_Hieroglyphs" => Some("Mero"),
-        "Malayalam" => Some("Mlym"),
-        "Modi" => Some("Modi"),
-        "Mongolian" => Some("Mong"),
-        "Mro" => Some("Mroo"),
-        "Meetei_Mayek" => Some("Mtei"),
-        "Multani" => Some("Mult"),
-        "Myanmar" => Some("Mymr"),
-        "Nag_Mundari" => Some("Nagm"),
-        "Nandinagari" => Some("Nand"),
-        "Old_North_Arabian" => Some("Narb"),
-        "Nabataean" => Some("Nbat"),
-        "Newa" => Some("Newa"),
-        "Nko" => Some("Nkoo"),
-        "Nushu" => Some("Nshu"),
-        "Ogham" => Some("Ogam"),
-        "Ol_Chiki" => Some("Olck"),
-        "Ol_Onal" => Some("Onao"),
-        "Old_Turkic" => Some("Orkh"),
-        "Oriya" => Some("Orya"),
-        "Osage" => Some("Osge"),
-        "Osmanya" => Some("Osma"),
-        "Old_Uyghur" => Some("Ougr"),
-        "Palmyrene" => Some("Palm"),
-        "Pau_Cin_Hau" => Some("Pauc"),
-        "Old_Permic" => Some("Perm"),
-        "Phags_Pa" => Some("Phag"),
-        "Inscriptional_Pahlavi" => Some("Phli"),
-        "Psalter_Pahlavi" => Some("Phlp"),
-        "Phoenician" => Some("Phnx"),
-        "Miao" => Some("Plrd"),
-        "Inscriptional_Parthian" => Some("Prti"),
-        "Rejang" => Some("Rjng"),
-        "Hanifi_Rohingya" => Some("Rohg"),
-        "Runic" => Some("Runr"),
-        "Samaritan" => Some("Samr"),
-        "Old_South_Arabian" => Some("Sarb"),
-        "Saurashtra" => Some("Saur"),
-        "SignWriting" => Some("Sgnw"),
-        "Shavian" => Some("Shaw"),
-        "Sharada" => Some("Shrd"),
-        "Siddham" => Some("Sidd"),
-        "Khudawadi" => Some("Sind"),
-        "Sinhala" => Some("Sinh"),
-        "Sogdian" => Some("Sogd"),
-        "Old_Sogdian" => Some("Sogo"),
-        "Sora_Sompeng" => Some("Sora"),
-        "Soyombo" => Some("Soyo"),
-        "Sundanese" => Some("Sund"),
-        "Sunuwar" => Some("Sunu"),
-        "Syloti_Nagri" => Some("Sylo"),
-        "Syriac" => Some("Syrc"),
-        "Tagbanwa" => Some("Tagb"),
-        "Takri" => Some("Takr"),
-        "Tai_Le" => Some("Tale"),
-        "New_Tai_Lue" => Some("Talu"),
-        "Tamil" => Some("Taml"),
-        "Tangut" => Some("Tang"),
-        "Tai_Viet" => Some("Tavt"),
-        "Telugu" => Some("Telu"),
-        "Tifinagh" => Some("Tfng"),
-        "Tagalog" => Some("Tglg"),
-        "Thaana" => Some("Thaa"),
-        "Thai" => Some("Thai"),
-        "Tibetan" => Some("Tibt"),
-        "Tirhuta" => Some("Tirh"),
-        "Tangsa" => Some("Tnsa"),
-        "Todhri" => Some("Todr"),
-        "Toto" => Some("Toto"),
-        "Tulu_Tigalari" => Some("Tutg"),
-        "Ugaritic" => Some("Ugar"),
-        "Vai" => Some("Vaii"),
-        "Vithkuqi" => Some("Vith"),
-        "Warang_Citi" => Some("Wara"),
-        "Wancho" => Some("Wcho"),
-        "Old_Persian" => Some("Xpeo"),
-        "Cuneiform" => Some("Xsux"),
-        "Yezidi" => Some("Yezi"),
-        "Yi" => Some("Yiii"),
-        "Zanabazar_Square" => Some("Zanb"),
-        "Inherited" => Some("Zinh"),
-        "Common" => Some("Zyyy"),
-        "Unknown" => Some("Zzzz"),
-        _ => None,
-    }
+    UNICODE_SCRIPT_NAMES
+        .iter()
+        .find(|(name, _)| *name == script)
+        .map(|(_, iso)| *iso)
 }
 
 pub fn iso15924_to_opentype(script: &str) -> Option<Tag> {
@@ -247,6 +333,47 @@ pub fn iso15924_to_opentype(script: &str) -> Option<Tag> {
     }
 }
 
+/// Return the ISO 15924 codes of the scripts an OpenType script tag may refer to.
+///
+/// Both the old and new Indic tags (`deva` and `dev2`) are recognised, and `kana`
+/// refers to both Hiragana and Katakana.
+pub fn opentype_to_iso15924(tag: Tag) -> Vec<&'static str> {
+    KNOWN_ISO_SCRIPTS
+        .iter()
+        // Hrkt is not a script of any character, so would never be measured
+        .filter(|iso| **iso != "Hrkt")
+        .filter(|iso| {
+            iso15924_to_opentype(iso) == Some(tag)
+                || Tag::new_checked(iso.to_lowercase().as_bytes()).ok() == Some(tag)
+        })
+        .copied()
+        .collect()
+}
+
+/// Scripts (as ISO 15924 codes) whose letters change form when joined to their neighbours.
+pub const JOINING_SCRIPTS: [&str; 10] = [
+    "Adlm", "Arab", "Mand", "Mani", "Mong", "Nkoo", "Phlp", "Rohg", "Sogd", "Syrc",
+];
+
+/// Whether a script joins its letters, so that kashida may stretch its words.
+pub fn is_joining_script(script: &str) -> bool {
+    JOINING_SCRIPTS.contains(&script)
+}
+
+/// ARABIC TATWEEL, used for kashida justification
+const TATWEEL: char = '\u{0640}';
+
+/// A word as it is measured: in joining scripts, without kashida (tatweel), so
+/// that what is measured is the joined forms themselves rather than stretched
+/// connections.
+pub fn without_kashida<'a>(word: &'a str, script: &str) -> Cow<'a, str> {
+    if is_joining_script(script) && word.contains(TATWEEL) {
+        Cow::Owned(word.replace(TATWEEL, ""))
+    } else {
+        Cow::Borrowed(word)
+    }
+}
+
 pub fn is_cjk_codepoint(c: char) -> bool {
     c.script().is_some_and(|s| {
         matches!(
@@ -1922,3 +2049,107 @@ pub fn iso639_to_opentype(language: &str) -> Tag {
         _ => Tag::new(&[0; 4]),     // Unknown
     }
 }
+
+/// Convert a measured value to a font unit, truncating any fraction.
+///
+/// Values which don't fit in an `i16` (possible for fonts with a large em and
+/// tall stacked marks) are an error, rather than being silently clamped.
+pub fn to_font_unit(value: f64, what: impl std::fmt::Display) -> Result<i16, AutobaseError> {
+    let truncated = value.trunc();
+    if truncated.is_finite() && (i16::MIN as f64..=i16::MAX as f64).contains(&truncated) {
+        Ok(truncated as i16)
+    } else {
+        Err(AutobaseError::ValueOutOfRange {
+            what: what.to_string(),
+            value,
+        })
+    }
+}
+
+/// Describe a location by its user-space axis values, e.g. "wght=700 wdth=75".
+///
+/// The values are found by undoing the default normalization, ignoring any avar
+/// mapping; this is exact at each axis's minimum, default and maximum, which are
+/// the locations autobase measures at. Static fonts are described as "default".
+pub fn describe_location(font: &FontRef, coords: &[NormalizedCoord]) -> String {
+    let axes = font.axes();
+    if axes.is_empty() {
+        return "default".to_string();
+    }
+    axes.iter()
+        .map(|axis| {
+            let coord = coords
+                .get(axis.index())
+                .map(|coord| coord.to_f32())
+                .unwrap_or_default();
+            let value = if coord < 0.0 {
+                axis.default_value() + coord * (axis.default_value() - axis.min_value())
+            } else {
+                axis.default_value() + coord * (axis.max_value() - axis.default_value())
+            };
+            format!("{}={}", axis.tag(), value)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The normalized location of one of fontheight's locations, which hold
+/// user-space axis values.
+pub fn skrifa_location(font: &FontRef, location: &fontheight::Location) -> Location {
+    font.axes().location(
+        location
+            .to_simple()
+            .into_iter()
+            .filter_map(|(tag, value)| Some((Tag::new_checked(tag.as_bytes()).ok()?, value))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use skrifa::GlyphId;
+    use write_fonts::{tables::cmap::Cmap, FontBuilder};
+
+    use super::*;
+
+    fn font_with_chars(chars: &str) -> Vec<u8> {
+        let cmap = Cmap::from_mappings(
+            chars
+                .chars()
+                .enumerate()
+                .map(|(i, c)| (c, GlyphId::new(i as u32 + 1))),
+        )
+        .unwrap();
+        let mut builder = FontBuilder::new();
+        builder.add_table(&cmap).unwrap();
+        builder.build()
+    }
+
+    fn scripts(chars: &str) -> Vec<&'static str> {
+        let data = font_with_chars(chars);
+        let mut scripts = supported_scripts(&FontRef::new(&data).unwrap())
+            .into_iter()
+            .collect::<Vec<_>>();
+        scripts.sort();
+        scripts
+    }
+
+    #[test]
+    fn shared_characters_dont_add_scripts() {
+        // U+00B7 MIDDLE DOT has a dozen scripts in its Script_Extensions
+        assert_eq!(scripts("abc\u{b7}"), ["Latn"]);
+        // U+0640 ARABIC TATWEEL is shared with Syriac, Mandaic, Adlam and more
+        assert_eq!(scripts("\u{628}\u{640}"), ["Arab"]);
+        // U+0964 DEVANAGARI DANDA is used by many Indic scripts
+        assert_eq!(scripts("\u{915}\u{964}"), ["Deva"]);
+        // CJK punctuation alone doesn't make a font support CJK
+        assert_eq!(scripts("a\u{3001}\u{300c}"), ["Latn"]);
+    }
+
+    #[test]
+    fn shared_characters_count_towards_coverage() {
+        let data = font_with_chars("\u{915}\u{964}\u{995}");
+        let coverage = script_coverage(&FontRef::new(&data).unwrap());
+        assert_eq!(coverage["Deva"].codepoints, 2);
+        assert_eq!(coverage["Beng"].codepoints, 2);
+    }
+}
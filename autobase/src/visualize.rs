@@ -0,0 +1,119 @@
+//! Draw a BASE table's baselines under sample text.
+//!
+//! Baseline values are hard to judge as numbers: whether `icfb` sits where the
+//! ideographs' ink begins, or `hang` where Devanagari's headline runs, is only
+//! clear when the rules are drawn through real text. The SVG has a row for each
+//! horizontal script record, with sample text in the font and a labelled rule
+//! for each of the script's baselines. Vertical baselines are positions across
+//! the line rather than heights, and aren't drawn.
+use std::fmt::Write;
+
+use skrifa::{instance::Location, raw::TableProvider, FontRef, Tag};
+
+use crate::{
+    base::BaseTable,
+    render::{escape, render_text},
+};
+
+const MARGIN: f32 = 100.0;
+const LABEL_WIDTH: f32 = 900.0;
+const STYLE: &str = "
+text { font-family: sans-serif; font-size: 60px; }
+.script { font-weight: bold; }
+.word { fill: #000; }
+line { stroke-width: 4; stroke: #c06; }
+line.default { stroke: #06c; stroke-width: 8; }
+text.baseline { fill: #c06; }
+text.baseline.default { fill: #06c; }
+line.zero { stroke: #ccc; stroke-dasharray: 10 10; }
+";
+
+/// Draw the horizontal records of `base` under sample text, with `sample`
+/// giving the text for each OpenType script tag. Scripts without sample text
+/// are drawn with their rules alone.
+pub fn baselines_svg(
+    font: &FontRef,
+    base: &BaseTable,
+    sample: impl Fn(Tag) -> Option<String>,
+) -> String {
+    let (descender, ascender) = font
+        .hhea()
+        .map(|hhea| (hhea.descender().to_i16(), hhea.ascender().to_i16()))
+        .unwrap_or((-250, 1000));
+    let location = Location::default();
+    let rows = base
+        .horizontal
+        .iter()
+        .map(|script| {
+            let text = sample(script.script).unwrap_or_default();
+            let rendered = render_text(font, &text, &location);
+            let top = script.baselines.values().copied().fold(ascender, i16::max) as f32 + MARGIN;
+            let bottom =
+                script.baselines.values().copied().fold(descender, i16::min) as f32 - MARGIN;
+            (script, rendered, top, bottom)
+        })
+        .collect::<Vec<_>>();
+    let text_width = rows
+        .iter()
+        .map(|(_, rendered, _, _)| rendered.advance)
+        .fold(0.0, f32::max);
+    let width = LABEL_WIDTH + text_width + LABEL_WIDTH + 2.0 * MARGIN;
+    let height = rows
+        .iter()
+        .map(|(_, _, top, bottom)| top - bottom)
+        .sum::<f32>();
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\"><style>{}</style>",
+        width, height, STYLE
+    );
+    let mut y_offset = 0.0;
+    for (script, rendered, top, bottom) in rows.iter() {
+        // Within each row, y = -value is the font's value
+        let _ = write!(svg, "<g transform=\"translate(0 {})\">", y_offset + top);
+        let _ = write!(
+            svg,
+            "<text class=\"script\" x=\"{}\" y=\"0\">{}</text>",
+            MARGIN,
+            escape(&script.script.to_string())
+        );
+        let rule_start = LABEL_WIDTH;
+        let rule_end = LABEL_WIDTH + text_width + MARGIN;
+        let _ = write!(
+            svg,
+            "<line class=\"zero\" x1=\"{}\" x2=\"{}\" y1=\"0\" y2=\"0\"/>",
+            rule_start, rule_end
+        );
+        for (tag, value) in script.baselines.iter() {
+            let class = if script.default_baseline == Some(*tag) {
+                "default"
+            } else {
+                ""
+            };
+            let y = -(*value as f32);
+            let _ = write!(
+                svg,
+                "<line class=\"{class}\" x1=\"{x1}\" x2=\"{x2}\" y1=\"{y}\" y2=\"{y}\"/>\
+                 <text class=\"baseline {class}\" x=\"{x}\" y=\"{ty}\">{tag}{star} {value}</text>",
+                class = class,
+                x1 = rule_start,
+                x2 = rule_end,
+                y = y,
+                x = rule_end + MARGIN / 2.0,
+                ty = y + 20.0,
+                tag = escape(&tag.to_string()),
+                star = if class.is_empty() { "" } else { "*" },
+                value = value,
+            );
+        }
+        let _ = write!(
+            svg,
+            "<path class=\"word\" transform=\"translate({} 0)\" d=\"{}\"/></g>",
+            LABEL_WIDTH + MARGIN / 2.0,
+            rendered.path
+        );
+        y_offset += top - bottom;
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
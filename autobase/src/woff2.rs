@@ -0,0 +1,302 @@
+//! Read the tables of WOFF2-compressed fonts.
+//!
+//! Webfonts are where shipped BASE tables most often need inspecting, but
+//! skrifa only reads uncompressed fonts. A WOFF2 file is a table directory
+//! followed by a single Brotli stream holding every table; most tables are
+//! stored as they are, but `glyf`, `loca` and usually `hmtx` are transformed
+//! and can only be rebuilt together. Nothing here needs the outlines, so the
+//! font is rebuilt from the tables stored as they are, leaving out the
+//! transformed ones. That is enough to read the BASE table and the font's
+//! metrics, but not to measure glyphs.
+use std::io::Read;
+
+use skrifa::Tag;
+use write_fonts::FontBuilder;
+
+use crate::error::AutobaseError;
+
+const SIGNATURE: &[u8; 4] = b"wOF2";
+const HEADER_LENGTH: usize = 48;
+
+/// The tags which a table directory entry can give by index, from the WOFF2 specification.
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+/// Whether `data` is a WOFF2 file.
+pub fn is_woff2(data: &[u8]) -> bool {
+    data.starts_with(SIGNATURE)
+}
+
+/// A table's entry in the directory.
+struct TableEntry {
+    tag: Tag,
+    /// Where the table starts in the decompressed stream
+    offset: usize,
+    length: usize,
+    transformed: bool,
+}
+
+/// Rebuild the font at `index` in a WOFF2 file (0 unless the file is a
+/// collection) as an uncompressed font, without the transformed tables.
+pub fn decode(data: &[u8], index: u32) -> Result<Vec<u8>, AutobaseError> {
+    if !is_woff2(data) {
+        return Err(AutobaseError::Woff2("not a WOFF2 file".to_string()));
+    }
+    let mut reader = Reader { data, position: 4 };
+    let flavor = reader.u32()?;
+    reader.skip(4)?; // length
+    let num_tables = reader.u16()?;
+    reader.skip(6)?; // reserved, totalSfntSize
+    let compressed_length = reader.u32()? as usize;
+    reader.position = HEADER_LENGTH;
+
+    let mut entries = vec![];
+    let mut offset = 0;
+    for _ in 0..num_tables {
+        let flags = reader.u8()?;
+        let tag = match flags & 0x3f {
+            0x3f => Tag::from_be_bytes(reader.bytes(4)?.try_into().unwrap()),
+            known => Tag::new(KNOWN_TAGS[known as usize]),
+        };
+        let original_length = reader.base128()?;
+        // A transform version of 0 means the null transform for every table
+        // but glyf and loca, for which it is 3
+        let version = flags >> 6;
+        let transformed = if tag == Tag::new(b"glyf") || tag == Tag::new(b"loca") {
+            version != 3
+        } else {
+            version != 0
+        };
+        let length = if transformed {
+            reader.base128()?
+        } else {
+            original_length
+        } as usize;
+        entries.push(TableEntry {
+            tag,
+            offset,
+            length,
+            transformed,
+        });
+        offset += length;
+    }
+
+    let tables: Vec<usize> = if flavor == u32::from_be_bytes(*b"ttcf") {
+        reader.skip(4)?; // version
+        let num_fonts = reader.u255()?;
+        let mut fonts = vec![];
+        for _ in 0..num_fonts {
+            let font_tables = reader.u255()?;
+            reader.skip(4)?; // flavor
+            fonts.push(
+                (0..font_tables)
+                    .map(|_| reader.u255().map(usize::from))
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+        }
+        fonts.into_iter().nth(index as usize).ok_or_else(|| {
+            AutobaseError::Woff2(format!(
+                "the collection has {} fonts, so there is no font {}",
+                num_fonts, index
+            ))
+        })?
+    } else if index == 0 {
+        (0..entries.len()).collect()
+    } else {
+        return Err(AutobaseError::Woff2(format!(
+            "the file is a single font, so there is no font {}",
+            index
+        )));
+    };
+
+    // The table directory says how long the stream is, but can't be trusted to:
+    // decompress no more than one byte beyond it, and reject any mismatch
+    let compressed = reader.bytes(compressed_length)?;
+    let mut stream = Vec::new();
+    brotli_decompressor::Decompressor::new(compressed, 4096)
+        .take(offset as u64 + 1)
+        .read_to_end(&mut stream)
+        .map_err(|e| AutobaseError::Woff2(format!("failed to decompress the tables: {}", e)))?;
+    if stream.len() != offset {
+        return Err(AutobaseError::Woff2(format!(
+            "the table directory gives {} bytes of tables, but {} were decompressed",
+            offset,
+            if stream.len() > offset {
+                "more".to_string()
+            } else {
+                stream.len().to_string()
+            }
+        )));
+    }
+
+    let mut builder = FontBuilder::new();
+    for entry in tables.into_iter().map(|table| {
+        entries
+            .get(table)
+            .ok_or_else(|| AutobaseError::Woff2(format!("table index {} is out of range", table)))
+    }) {
+        let entry = entry?;
+        if entry.transformed {
+            log::debug!("Leaving out the transformed {} table", entry.tag);
+            continue;
+        }
+        let table = stream
+            .get(entry.offset..entry.offset + entry.length)
+            .ok_or_else(|| AutobaseError::Woff2(format!("the {} table is truncated", entry.tag)))?;
+        builder.add_raw(entry.tag, table.to_vec());
+    }
+    Ok(builder.build())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], AutobaseError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or_else(|| AutobaseError::Woff2("the file is truncated".to_string()))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), AutobaseError> {
+        self.bytes(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, AutobaseError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, AutobaseError> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, AutobaseError> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    /// A UIntBase128: up to five bytes of seven bits each, most significant first.
+    fn base128(&mut self) -> Result<u32, AutobaseError> {
+        let mut value: u32 = 0;
+        for i in 0..5 {
+            let byte = self.u8()?;
+            if (i == 0 && byte == 0x80) || value & 0xfe00_0000 != 0 {
+                return Err(AutobaseError::Woff2("invalid UIntBase128".to_string()));
+            }
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(AutobaseError::Woff2("invalid UIntBase128".to_string()))
+    }
+
+    /// A 255UInt16, as used by the collection directory.
+    fn u255(&mut self) -> Result<u16, AutobaseError> {
+        Ok(match self.u8()? {
+            253 => self.u16()?,
+            254 => self.u8()? as u16 + 506,
+            255 => self.u8()? as u16 + 253,
+            code => code as u16,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use skrifa::{raw::TableProvider, FontRef};
+    use write_fonts::tables::{base::Base, maxp::Maxp};
+
+    use super::*;
+
+    fn base128(mut value: u32) -> Vec<u8> {
+        let mut bytes = vec![(value & 0x7f) as u8];
+        value >>= 7;
+        while value > 0 {
+            bytes.insert(0, 0x80 | (value & 0x7f) as u8);
+            value >>= 7;
+        }
+        bytes
+    }
+
+    /// A WOFF2 file holding the given tables untransformed, with the directory
+    /// giving `extra` more bytes of tables than are compressed.
+    fn woff2(tables: &[(Tag, Vec<u8>)], extra: u32) -> Vec<u8> {
+        let mut stream = vec![];
+        let mut directory = vec![];
+        for (i, (tag, data)) in tables.iter().enumerate() {
+            stream.extend_from_slice(data);
+            directory.push(0x3f);
+            directory.extend_from_slice(&tag.to_be_bytes());
+            let mut length = data.len() as u32;
+            if i == tables.len() - 1 {
+                length += extra;
+            }
+            directory.extend(base128(length));
+        }
+        let mut compressed = vec![];
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+            writer.write_all(&stream).unwrap();
+        }
+        let mut file = SIGNATURE.to_vec();
+        file.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // flavor
+        file.extend_from_slice(&0u32.to_be_bytes()); // length
+        file.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+        file.extend_from_slice(&[0; 6]); // reserved, totalSfntSize
+        file.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+        file.resize(HEADER_LENGTH, 0);
+        file.extend_from_slice(&directory);
+        file.extend_from_slice(&compressed);
+        file
+    }
+
+    fn tables() -> Vec<(Tag, Vec<u8>)> {
+        vec![
+            (
+                Tag::new(b"BASE"),
+                write_fonts::dump_table(&Base::default()).unwrap(),
+            ),
+            (
+                Tag::new(b"maxp"),
+                write_fonts::dump_table(&Maxp {
+                    num_glyphs: 3,
+                    ..Default::default()
+                })
+                .unwrap(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn decodes_untransformed_tables() {
+        let tables = tables();
+        let font = decode(&woff2(&tables, 0), 0).unwrap();
+        let font = FontRef::new(&font).unwrap();
+        for (tag, data) in tables.iter() {
+            assert_eq!(font.data_for_tag(*tag).unwrap().as_bytes(), data.as_slice());
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_lengths() {
+        let tables = tables();
+        assert!(decode(&woff2(&tables, 100_000 - 32), 0).is_err());
+        assert!(decode(&woff2(&tables, 3), 0).is_err());
+        assert!(decode(b"wOF2", 0).is_err());
+        assert!(decode(b"OTTO", 0).is_err());
+    }
+}
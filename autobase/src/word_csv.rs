@@ -0,0 +1,98 @@
+//! Export every measured word as CSV.
+//!
+//! autobase only keeps what it needs to choose each record, but the measurements
+//! themselves are useful for analysis of their own: outliers, percentiles, or
+//! comparisons between fonts. The export has a row for every word of every word
+//! list measured at every location, with its lowest and highest points, so that
+//! such statistics can be run downstream without re-implementing the measuring.
+//! Fonts are measured in parallel, so rows from different fonts may interleave.
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::measurement::Measurement;
+
+const HEADER: &str = "font,script,language,word_list,location,word,ymin,ymax\n";
+
+/// Quote a field if it contains anything which would break the row.
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// A CSV file of measured words, shared by every font of a run.
+pub struct WordCsv {
+    out: Mutex<BufWriter<File>>,
+}
+
+impl WordCsv {
+    /// Create the file and write its header.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(HEADER.as_bytes())?;
+        Ok(WordCsv {
+            out: Mutex::new(out),
+        })
+    }
+
+    /// A writer of the rows of one font.
+    pub fn font(&self, font: &str) -> FontWordCsv<'_> {
+        FontWordCsv {
+            csv: self,
+            font: field(font),
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.lock().flush()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BufWriter<File>> {
+        // A panic elsewhere mid-write can at worst leave a partial row
+        self.out.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Writes the rows of one font to a [`WordCsv`].
+pub struct FontWordCsv<'a> {
+    csv: &'a WordCsv,
+    font: String,
+}
+
+impl FontWordCsv<'_> {
+    /// Write a row for each word of a measurement made at `location`.
+    pub fn write(&self, location: &str, measurement: &Measurement) -> io::Result<()> {
+        let prefix = format!(
+            "{},{},{},{},{}",
+            self.font,
+            field(&measurement.source.script),
+            field(measurement.source.language.as_deref().unwrap_or("")),
+            field(&measurement.name),
+            field(location)
+        );
+        // The highest and lowest exemplars are the same words, unless the list had
+        // more words than exemplars are kept
+        let mut seen = HashSet::new();
+        let mut rows = String::new();
+        for word in measurement.highest.iter().chain(measurement.lowest.iter()) {
+            if !seen.insert(word.word.as_str()) {
+                continue;
+            }
+            rows.push_str(&format!(
+                "{},{},{},{}\n",
+                prefix,
+                field(&word.word),
+                word.lowest,
+                word.highest
+            ));
+        }
+        self.csv.lock().write_all(rows.as_bytes())
+    }
+}
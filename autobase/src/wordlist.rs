@@ -0,0 +1,480 @@
+//! User-supplied word lists and corpora.
+//!
+//! Word lists are plain text files with one word per line; blank lines and lines
+//! starting with `#` are ignored. A word may be followed by a tab and its
+//! frequency. Corpora are plain text documents, which are
+//! split into words at Unicode word boundaries. As neither carries any metadata,
+//! the script (and optionally language) they should be attributed to is given
+//! alongside the path, as `path:script[:language]`.
+//!
+//! Word lists and corpora are loaded and tokenized once per run, however many
+//! fonts and instances they are measured against. As splitting a large corpus
+//! into words is slow, the result can also be cached on disk, keyed by a hash
+//! of the corpus contents. The bundled word lists are likewise cut down to the
+//! words which will be measured once per run, rather than for each font.
+//!
+//! Everything measured, whether bundled, read from a file, or supplied by a
+//! library user from a database or another crate, is a [`WordSource`].
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use fontheight::WordList;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use static_lang_word_lists::WordListMetadata;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    config::{Config, ScriptLanguage},
+    frequency::FrequencyTable,
+    utils::char_scripts,
+};
+
+/// The characters of a script which appear in the given word lists.
+///
+/// This serves as the script's exemplar set when judging how much of it a font
+/// covers. It is empty if none of the word lists is in the script.
+pub fn exemplar_characters<'a>(
+    script: &str,
+    word_lists: impl IntoIterator<Item = &'a WordList>,
+) -> HashSet<char> {
+    word_lists
+        .into_iter()
+        .filter(|word_list| word_list.script() == Some(script))
+        .flat_map(|word_list| word_list.iter())
+        .flat_map(|word| word.chars())
+        .filter(|c| char_scripts(*c).any(|s| s == script))
+        .collect()
+}
+
+/// The metadata of a word list defined from words, so that fontheight shapes
+/// them in their script and language rather than as untagged text.
+pub(crate) fn metadata(name: &str, source: &ScriptLanguage) -> WordListMetadata {
+    WordListMetadata {
+        name: name.to_string().into(),
+        script: Some(source.script.clone().into()),
+        language: source.language.clone().map(Into::into),
+    }
+}
+
+/// The metadata of words to be shaped as `source`: like [`metadata`], but in
+/// the OpenType language system of `source`, if it has one, rather than the one
+/// the shaper would choose for the bare language code.
+pub(crate) fn shaping_metadata(name: &str, source: &ScriptLanguage) -> WordListMetadata {
+    let mut metadata = metadata(name, source);
+    if let (Some(language), Some(tag)) = (&source.language, source.opentype_language) {
+        let language = format!("{}-x-hbot{}", language, tag.to_string().trim_end());
+        metadata.language = Some(language.into());
+    }
+    metadata
+}
+
+/// Somewhere the words measured for a script, or one of its languages, come from.
+pub trait WordSource: Send + Sync {
+    /// A name identifying the words in logs, reports and the measurement cache
+    fn name(&self) -> &str;
+
+    /// The ISO 15924 code of the script the words are in
+    fn script(&self) -> &str;
+
+    /// The ISO 639 code of the language the words belong to, if they belong to one
+    fn language(&self) -> Option<&str>;
+
+    /// The words, most important first, as only the first few may be measured
+    fn words(&self) -> Box<dyn Iterator<Item = &str> + '_>;
+
+    /// The script and language the words' measurements are attributed to.
+    fn source(&self) -> ScriptLanguage {
+        ScriptLanguage {
+            script: self.script().to_string(),
+            language: self.language().map(str::to_string),
+            opentype_language: None,
+        }
+    }
+}
+
+/// The word lists bundled from `static_lang_word_lists`, which carry their own
+/// script and language.
+impl WordSource for WordList {
+    fn name(&self) -> &str {
+        WordList::name(self)
+    }
+
+    fn script(&self) -> &str {
+        WordList::script(self).unwrap_or("Zzzz")
+    }
+
+    fn language(&self) -> Option<&str> {
+        WordList::language(self)
+    }
+
+    fn words(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(self.iter())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordListSource {
+    pub path: PathBuf,
+    pub source: ScriptLanguage,
+}
+
+impl FromStr for WordListSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Split from the right, so that paths may contain colons
+        let parts = s.rsplitn(3, ':').collect::<Vec<_>>();
+        let (path, source) = match parts.as_slice() {
+            [language, script, path] if ScriptLanguage::new(script, None).is_ok() => (
+                path.to_string(),
+                ScriptLanguage::new(script, Some(language))?,
+            ),
+            [script, rest @ ..] if !rest.is_empty() => {
+                // Whatever precedes the script is the path, colons and all
+                let path = s[..s.len() - script.len() - 1].to_string();
+                (path, ScriptLanguage::new(script, None)?)
+            }
+            _ => {
+                return Err(format!(
+                    "word list must be given as path:script[:language], got {}",
+                    s
+                ))
+            }
+        };
+        Ok(WordListSource {
+            path: PathBuf::from(path),
+            source,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for WordListSource {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl WordListSource {
+    /// Resolve a relative path against the given directory.
+    pub fn relative_to(mut self, dir: &Path) -> Self {
+        if self.path.is_relative() {
+            self.path = dir.join(&self.path);
+        }
+        self
+    }
+
+    fn read(&self) -> anyhow::Result<String> {
+        std::fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))
+    }
+
+    fn define(&self, words: Vec<String>) -> WordList {
+        log::debug!(
+            "Loaded {} words from {} for {:?}",
+            words.len(),
+            self.path.display(),
+            self.source
+        );
+        WordList::define(
+            metadata(&self.path.display().to_string(), &self.source),
+            words,
+        )
+    }
+
+    /// Read the word list from disk, along with any frequencies it is annotated with.
+    pub fn load(&self) -> anyhow::Result<(WordList, FrequencyTable)> {
+        let mut frequencies = FrequencyTable::default();
+        let words = self
+            .read()?
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(FrequencyTable::parse_line)
+            .map(|(word, count)| {
+                if let Some(count) = count {
+                    frequencies.insert(word, count);
+                }
+                word.to_string()
+            })
+            .collect::<Vec<_>>();
+        Ok((self.define(words), frequencies))
+    }
+
+    /// Read a plain-text corpus from disk, splitting it into unique words.
+    ///
+    /// Words are kept in the order they first appear, so that limiting the number
+    /// of words measured takes them from the start of the document. The number
+    /// of times each word occurs is returned as its frequency.
+    ///
+    /// If a cache directory is given, the words and their counts are stored there
+    /// and reused until the corpus changes.
+    pub fn load_corpus(
+        &self,
+        cache_dir: Option<&Path>,
+    ) -> anyhow::Result<(WordList, FrequencyTable)> {
+        let contents = self.read()?;
+        let cache_path = cache_dir.map(|dir| dir.join(format!("{}.txt", cache_key(&contents))));
+        if let Some(cached) = cache_path
+            .as_deref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+        {
+            log::debug!("Using cached words for {}", self.path.display());
+            let mut frequencies = FrequencyTable::default();
+            let words = cached
+                .lines()
+                .filter_map(FrequencyTable::parse_line)
+                .map(|(word, count)| {
+                    if let Some(count) = count {
+                        frequencies.insert(word, count);
+                    }
+                    word.to_string()
+                })
+                .collect::<Vec<_>>();
+            return Ok((self.define(words), frequencies));
+        }
+
+        let mut seen = HashSet::new();
+        let mut frequencies = FrequencyTable::default();
+        let words = contents
+            .unicode_words()
+            .inspect(|word| frequencies.increment(word))
+            .filter(|word| seen.insert(*word))
+            .map(|word| word.to_string())
+            .collect::<Vec<_>>();
+        if let Some(cache_path) = cache_path {
+            let cached = words
+                .iter()
+                .map(|word| format!("{}\t{}\n", word, frequencies.get(word).unwrap_or(1.0)))
+                .collect::<String>();
+            if let Err(e) = write_cache(&cache_path, &cached) {
+                log::warn!("Could not cache words for {}: {}", self.path.display(), e);
+            }
+        }
+        Ok((self.define(words), frequencies))
+    }
+}
+
+/// A key identifying a corpus's contents as tokenized by this version of autobase.
+fn cache_key(contents: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update(contents.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn write_cache(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, contents)
+}
+
+/// A word list or corpus loaded from a file.
+pub struct FileWordList {
+    pub words: WordList,
+    pub file: WordListSource,
+}
+
+impl WordSource for FileWordList {
+    fn name(&self) -> &str {
+        self.words.name()
+    }
+
+    fn script(&self) -> &str {
+        &self.file.source.script
+    }
+
+    fn language(&self) -> Option<&str> {
+        self.file.source.language.as_deref()
+    }
+
+    fn words(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(self.words.iter())
+    }
+
+    // Keeps the OpenType language system of a BCP 47 tag
+    fn source(&self) -> ScriptLanguage {
+        self.file.source.clone()
+    }
+}
+
+/// The word lists and corpora given in the config, loaded once for all fonts.
+#[derive(Default)]
+pub struct CustomWordLists {
+    /// Each loaded word list
+    pub lists: Vec<FileWordList>,
+    /// Frequencies from the frequency table, word list annotations and corpus counts
+    pub frequencies: FrequencyTable,
+}
+
+impl CustomWordLists {
+    pub fn load(config: &Config) -> anyhow::Result<Self> {
+        let mut loaded = CustomWordLists {
+            lists: vec![],
+            frequencies: match &config.frequencies {
+                Some(path) => FrequencyTable::load(path)?,
+                None => FrequencyTable::default(),
+            },
+        };
+        for w in config.wordlists.iter() {
+            let (words, frequencies) = w.load()?;
+            loaded.frequencies.extend(frequencies);
+            loaded.lists.push(FileWordList {
+                words,
+                file: w.clone(),
+            });
+        }
+        for w in config.corpora.iter() {
+            let (words, frequencies) = w.load_corpus(config.cache_dir.as_deref())?;
+            loaded.frequencies.extend(frequencies);
+            loaded.lists.push(FileWordList {
+                words,
+                file: w.clone(),
+            });
+        }
+        Ok(loaded)
+    }
+}
+
+/// A bundled word list, cut down to the words which will be measured.
+pub struct BundledWordList {
+    name: String,
+    script: String,
+    language: Option<String>,
+    words: Vec<String>,
+}
+
+impl WordSource for BundledWordList {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn script(&self) -> &str {
+        &self.script
+    }
+
+    fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    fn words(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(self.words.iter().map(String::as_str))
+    }
+}
+
+/// The word lists bundled from `static_lang_word_lists`, prepared once for all fonts.
+#[derive(Default)]
+pub struct BundledWordLists {
+    /// The first words of each list with a script, without repeats
+    pub lists: Vec<BundledWordList>,
+    /// The exemplar characters of each script, if they were asked for
+    exemplars: HashMap<String, HashSet<char>>,
+}
+
+impl BundledWordLists {
+    /// Keep the distinct words among the first `words_per_list` of each bundled
+    /// list (or no lists, if `None`), and if `exemplars` is set, collect each
+    /// script's exemplar characters from the whole of its lists.
+    pub fn load(words_per_list: Option<usize>, exemplars: bool) -> Self {
+        let all = static_lang_word_lists::ALL_WORD_LISTS;
+        let lists = all
+            .iter()
+            .filter(|_| words_per_list.is_some())
+            .filter_map(|word_list| {
+                let mut seen = HashSet::new();
+                Some(BundledWordList {
+                    name: word_list.name().to_string(),
+                    script: word_list.script()?.to_string(),
+                    language: word_list.language().map(str::to_string),
+                    words: word_list
+                        .iter()
+                        .take(words_per_list.unwrap_or_default())
+                        .filter(|word| seen.insert(*word))
+                        .map(str::to_string)
+                        .collect(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let exemplars = if exemplars {
+            all.iter()
+                .filter_map(|word_list| word_list.script())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .map(|script| {
+                    (
+                        script.to_string(),
+                        exemplar_characters(script, all.iter().copied()),
+                    )
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        BundledWordLists { lists, exemplars }
+    }
+
+    /// The characters of a script which appear in its bundled word lists, if
+    /// they were collected and it has any.
+    pub fn exemplar_characters(&self, script: &str) -> Option<&HashSet<char>> {
+        self.exemplars
+            .get(script)
+            .filter(|exemplars| !exemplars.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_lists_are_cut_down_once() {
+        let bundled = BundledWordLists::load(Some(10), false);
+        let all = static_lang_word_lists::ALL_WORD_LISTS;
+        assert_eq!(
+            bundled.lists.len(),
+            all.iter().filter(|list| list.script().is_some()).count()
+        );
+        for (prepared, list) in bundled
+            .lists
+            .iter()
+            .zip(all.iter().filter(|list| list.script().is_some()))
+        {
+            assert_eq!(WordSource::name(prepared), list.name());
+            assert_eq!(Some(WordSource::script(prepared)), list.script());
+            let mut seen = HashSet::new();
+            let expected = list
+                .iter()
+                .take(10)
+                .filter(|word| seen.insert(*word))
+                .collect::<Vec<_>>();
+            assert_eq!(prepared.words().collect::<Vec<_>>(), expected);
+        }
+        assert!(bundled.exemplar_characters("Latn").is_none());
+
+        let bundled = BundledWordLists::load(None, true);
+        assert!(bundled.lists.is_empty());
+        assert!(bundled.exemplar_characters("Latn").unwrap().contains(&'a'));
+    }
+
+    #[test]
+    fn custom_lists_keep_their_script_and_language() {
+        let path = std::env::temp_dir().join(format!("autobase-words-{}.txt", std::process::id()));
+        std::fs::write(&path, "Việt\t3\nNam\n").unwrap();
+        let source: WordListSource = format!("{}:Latn:vi", path.display()).parse().unwrap();
+        let (words, frequencies) = source.load().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // fontheight shapes the words with these
+        assert_eq!(words.script(), Some("Latn"));
+        assert_eq!(words.language(), Some("vi"));
+        assert_eq!(words.iter().collect::<Vec<_>>(), ["Việt", "Nam"]);
+        assert_eq!(frequencies.get("Việt"), Some(3.0));
+    }
+}